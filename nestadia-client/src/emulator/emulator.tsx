@@ -41,6 +41,11 @@ class Emulator extends React.Component<{setAppState: Function, mode: EmulatorMod
 
     wsAddEventListener(ws: WebSocket) {
         ws.addEventListener("message", (event) => {
+            // Text messages are errors and the debugger secret, not frames
+            if (typeof event.data === "string") {
+                return;
+            }
+
             let frameEncoded: Uint8Array = new Uint8Array(event.data);
             let frame = gzip.unzip(frameEncoded);
 
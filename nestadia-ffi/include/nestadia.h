@@ -0,0 +1,154 @@
+#ifndef NESTADIA_H
+#define NESTADIA_H
+
+/* Generated with cbindgen from nestadia-ffi/src/lib.rs, don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Width of the frames, in pixels
+ */
+#define NESTADIA_FRAME_WIDTH 256
+
+/**
+ * Height of the frames, in pixels
+ */
+#define NESTADIA_FRAME_HEIGHT 240
+
+/**
+ * Number of controllers, the last two being plugged in a Four Score
+ */
+#define NESTADIA_CONTROLLER_COUNT 4
+
+typedef enum {
+  NESTADIA_STATUS_OK = 0,
+  NESTADIA_STATUS_NULL_POINTER,
+  NESTADIA_STATUS_INVALID_ROM,
+  NESTADIA_STATUS_INVALID_STATE,
+  NESTADIA_STATUS_INVALID_CONTROLLER,
+} NestadiaStatus;
+
+/**
+ * Emulator running a game, along with the inputs and outputs of its frames
+ */
+typedef struct NestadiaEmulator NestadiaEmulator;
+
+/**
+ * Creates an emulator running an iNES/NES 2.0 ROM or an NSF file. `save_data` is the battery
+ * backed RAM saved from a previous session, and can be null. Returns null if the ROM is invalid.
+ *
+ * # Safety
+ * `rom` must point to `rom_len` bytes, and `save_data`, if not null, to `save_data_len` bytes.
+ */
+NestadiaEmulator *nestadia_create(const uint8_t *rom,
+                                  size_t rom_len,
+                                  const uint8_t *save_data,
+                                  size_t save_data_len);
+
+/**
+ * Frees an emulator. Does nothing if it's null.
+ *
+ * # Safety
+ * `emulator` must have been returned by `nestadia_create`, and not be used after this call.
+ */
+void nestadia_destroy(NestadiaEmulator *emulator);
+
+/**
+ * Replaces the game of an emulator, which is powered on again. On error, the previous game
+ * keeps running.
+ *
+ * # Safety
+ * `emulator` must be valid, `rom` must point to `rom_len` bytes, and `save_data`, if not null,
+ * to `save_data_len` bytes.
+ */
+NestadiaStatus nestadia_load_rom(NestadiaEmulator *emulator,
+                                 const uint8_t *rom,
+                                 size_t rom_len,
+                                 const uint8_t *save_data,
+                                 size_t save_data_len);
+
+/**
+ * Sets the buttons of a controller, from 0 to 3, for the next frames. The buttons are, from the
+ * high bit: A, B, Select, Start, Up, Down, Left, Right.
+ *
+ * # Safety
+ * `emulator` must be valid.
+ */
+NestadiaStatus nestadia_set_input(NestadiaEmulator *emulator, size_t controller, uint8_t buttons);
+
+/**
+ * Runs until the next frame, and returns its number since power-on. Returns 0 if `emulator` is
+ * null.
+ *
+ * # Safety
+ * `emulator` must be valid.
+ */
+uint64_t nestadia_run_frame(NestadiaEmulator *emulator);
+
+/**
+ * Palette indices of the pixels of the last frame, `NESTADIA_FRAME_WIDTH` by
+ * `NESTADIA_FRAME_HEIGHT` bytes from the top left. Returns null if `emulator` is null.
+ *
+ * # Safety
+ * `emulator` must be valid.
+ */
+const uint8_t *nestadia_framebuffer(const NestadiaEmulator *emulator);
+
+/**
+ * Writes the last frame as RGBA pixels in `output`
+ *
+ * # Safety
+ * `emulator` must be valid, and `output` must point to
+ * `NESTADIA_FRAME_WIDTH * NESTADIA_FRAME_HEIGHT * 4` bytes.
+ */
+NestadiaStatus nestadia_framebuffer_rgba(const NestadiaEmulator *emulator, uint8_t *output);
+
+/**
+ * Mono audio samples produced during the last frame, their count being written in `len`.
+ * Returns null if `emulator` is null.
+ *
+ * # Safety
+ * `emulator` must be valid, and `len` must point to a `size_t`.
+ */
+const int16_t *nestadia_audio(const NestadiaEmulator *emulator, size_t *len);
+
+/**
+ * Sets the sample rate of the audio, 44100 Hz by default
+ *
+ * # Safety
+ * `emulator` must be valid.
+ */
+NestadiaStatus nestadia_set_audio_sample_rate(NestadiaEmulator *emulator, uint32_t sample_rate);
+
+/**
+ * Saves the state of the emulator in a new buffer, its size being written in `len`. The buffer
+ * must be freed with `nestadia_free_buffer`. Returns null if `emulator` is null.
+ *
+ * # Safety
+ * `emulator` must be valid, and `len` must point to a `size_t`.
+ */
+uint8_t *nestadia_save_state(const NestadiaEmulator *emulator, size_t *len);
+
+/**
+ * Loads a state saved by `nestadia_save_state` for the same game. On error, the emulator is
+ * left unchanged.
+ *
+ * # Safety
+ * `emulator` must be valid, and `state` must point to `len` bytes.
+ */
+NestadiaStatus nestadia_load_state(NestadiaEmulator *emulator, const uint8_t *state, size_t len);
+
+/**
+ * Frees a buffer returned by the emulator. Does nothing if it's null.
+ *
+ * # Safety
+ * `buffer` and `len` must have been returned by `nestadia_save_state`, and the buffer must not
+ * be used after this call.
+ */
+void nestadia_free_buffer(uint8_t *buffer, size_t len);
+
+#endif /* NESTADIA_H */
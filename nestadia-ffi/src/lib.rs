@@ -0,0 +1,294 @@
+// C bindings of the emulator, to embed it in frontends written in other languages.
+//
+// The header is include/nestadia.h, generated from this file with cbindgen (see cbindgen.toml).
+//
+// An emulator is created from a ROM with `nestadia_create` and freed with `nestadia_destroy`.
+// Every frame, the frontend sets the inputs, calls `nestadia_run_frame`, then reads the
+// framebuffer and the audio samples. The pointers returned by the emulator stay valid until the
+// next call taking it, apart from the buffers of `nestadia_save_state`, which are owned by the
+// caller and freed with `nestadia_free_buffer`.
+
+use std::{ptr, slice};
+
+use nestadia::Emulator;
+
+/// Width of the frames, in pixels
+pub const NESTADIA_FRAME_WIDTH: usize = 256;
+/// Height of the frames, in pixels
+pub const NESTADIA_FRAME_HEIGHT: usize = 240;
+/// Number of controllers, the last two being plugged in a Four Score
+pub const NESTADIA_CONTROLLER_COUNT: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestadiaStatus {
+    Ok = 0,
+    NullPointer,
+    InvalidRom,
+    InvalidState,
+    InvalidController,
+}
+
+/// Emulator running a game, along with the inputs and outputs of its frames
+pub struct NestadiaEmulator {
+    emulator: Emulator,
+    inputs: [u8; NESTADIA_CONTROLLER_COUNT],
+    audio: Vec<i16>,
+}
+
+/// Creates an emulator running an iNES/NES 2.0 ROM or an NSF file. `save_data` is the battery
+/// backed RAM saved from a previous session, and can be null. Returns null if the ROM is invalid.
+///
+/// # Safety
+/// `rom` must point to `rom_len` bytes, and `save_data`, if not null, to `save_data_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_create(
+    rom: *const u8,
+    rom_len: usize,
+    save_data: *const u8,
+    save_data_len: usize,
+) -> *mut NestadiaEmulator {
+    match load(rom, rom_len, save_data, save_data_len) {
+        Ok(emulator) => Box::into_raw(Box::new(NestadiaEmulator {
+            emulator,
+            inputs: [0; NESTADIA_CONTROLLER_COUNT],
+            audio: Vec::new(),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees an emulator. Does nothing if it's null.
+///
+/// # Safety
+/// `emulator` must have been returned by `nestadia_create`, and not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_destroy(emulator: *mut NestadiaEmulator) {
+    if !emulator.is_null() {
+        drop(Box::from_raw(emulator));
+    }
+}
+
+/// Replaces the game of an emulator, which is powered on again. On error, the previous game
+/// keeps running.
+///
+/// # Safety
+/// `emulator` must be valid, `rom` must point to `rom_len` bytes, and `save_data`, if not null,
+/// to `save_data_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_load_rom(
+    emulator: *mut NestadiaEmulator,
+    rom: *const u8,
+    rom_len: usize,
+    save_data: *const u8,
+    save_data_len: usize,
+) -> NestadiaStatus {
+    let emulator = match emulator.as_mut() {
+        Some(emulator) => emulator,
+        None => return NestadiaStatus::NullPointer,
+    };
+
+    match load(rom, rom_len, save_data, save_data_len) {
+        Ok(loaded) => {
+            emulator.emulator = loaded;
+            emulator.audio.clear();
+            NestadiaStatus::Ok
+        }
+        Err(status) => status,
+    }
+}
+
+/// Sets the buttons of a controller, from 0 to 3, for the next frames. The buttons are, from the
+/// high bit: A, B, Select, Start, Up, Down, Left, Right.
+///
+/// # Safety
+/// `emulator` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_set_input(
+    emulator: *mut NestadiaEmulator,
+    controller: usize,
+    buttons: u8,
+) -> NestadiaStatus {
+    let emulator = match emulator.as_mut() {
+        Some(emulator) => emulator,
+        None => return NestadiaStatus::NullPointer,
+    };
+
+    match emulator.inputs.get_mut(controller) {
+        Some(input) => {
+            *input = buttons;
+            NestadiaStatus::Ok
+        }
+        None => NestadiaStatus::InvalidController,
+    }
+}
+
+/// Runs until the next frame, and returns its number since power-on. Returns 0 if `emulator` is
+/// null.
+///
+/// # Safety
+/// `emulator` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_run_frame(emulator: *mut NestadiaEmulator) -> u64 {
+    let emulator = match emulator.as_mut() {
+        Some(emulator) => emulator,
+        None => return 0,
+    };
+
+    let output = emulator.emulator.run_frame(emulator.inputs);
+    emulator.audio.clear();
+    emulator.audio.extend_from_slice(output.audio);
+    output.frame_number
+}
+
+/// Palette indices of the pixels of the last frame, `NESTADIA_FRAME_WIDTH` by
+/// `NESTADIA_FRAME_HEIGHT` bytes from the top left. Returns null if `emulator` is null.
+///
+/// # Safety
+/// `emulator` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_framebuffer(emulator: *const NestadiaEmulator) -> *const u8 {
+    match emulator.as_ref() {
+        Some(emulator) => emulator.emulator.frame().as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Writes the last frame as RGBA pixels in `output`
+///
+/// # Safety
+/// `emulator` must be valid, and `output` must point to
+/// `NESTADIA_FRAME_WIDTH * NESTADIA_FRAME_HEIGHT * 4` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_framebuffer_rgba(
+    emulator: *const NestadiaEmulator,
+    output: *mut u8,
+) -> NestadiaStatus {
+    match (
+        emulator.as_ref(),
+        (output as *mut [u8; NESTADIA_FRAME_WIDTH * NESTADIA_FRAME_HEIGHT * 4]).as_mut(),
+    ) {
+        (Some(emulator), Some(output)) => {
+            nestadia::frame_to_rgba(emulator.emulator.frame(), output);
+            NestadiaStatus::Ok
+        }
+        _ => NestadiaStatus::NullPointer,
+    }
+}
+
+/// Mono audio samples produced during the last frame, their count being written in `len`.
+/// Returns null if `emulator` is null.
+///
+/// # Safety
+/// `emulator` must be valid, and `len` must point to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_audio(
+    emulator: *const NestadiaEmulator,
+    len: *mut usize,
+) -> *const i16 {
+    match (emulator.as_ref(), len.as_mut()) {
+        (Some(emulator), Some(len)) => {
+            *len = emulator.audio.len();
+            emulator.audio.as_ptr()
+        }
+        _ => ptr::null(),
+    }
+}
+
+/// Sets the sample rate of the audio, 44100 Hz by default
+///
+/// # Safety
+/// `emulator` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_set_audio_sample_rate(
+    emulator: *mut NestadiaEmulator,
+    sample_rate: u32,
+) -> NestadiaStatus {
+    match emulator.as_mut() {
+        Some(emulator) => {
+            emulator.emulator.set_audio_sample_rate(sample_rate);
+            NestadiaStatus::Ok
+        }
+        None => NestadiaStatus::NullPointer,
+    }
+}
+
+/// Saves the state of the emulator in a new buffer, its size being written in `len`. The buffer
+/// must be freed with `nestadia_free_buffer`. Returns null if `emulator` is null.
+///
+/// # Safety
+/// `emulator` must be valid, and `len` must point to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_save_state(
+    emulator: *const NestadiaEmulator,
+    len: *mut usize,
+) -> *mut u8 {
+    match (emulator.as_ref(), len.as_mut()) {
+        (Some(emulator), Some(len)) => {
+            let state = emulator.emulator.save_state().into_boxed_slice();
+            *len = state.len();
+            Box::into_raw(state) as *mut u8
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Loads a state saved by `nestadia_save_state` for the same game. On error, the emulator is
+/// left unchanged.
+///
+/// # Safety
+/// `emulator` must be valid, and `state` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_load_state(
+    emulator: *mut NestadiaEmulator,
+    state: *const u8,
+    len: usize,
+) -> NestadiaStatus {
+    let emulator = match emulator.as_mut() {
+        Some(emulator) => emulator,
+        None => return NestadiaStatus::NullPointer,
+    };
+    if state.is_null() {
+        return NestadiaStatus::NullPointer;
+    }
+
+    match emulator
+        .emulator
+        .load_state(slice::from_raw_parts(state, len))
+    {
+        Ok(()) => NestadiaStatus::Ok,
+        Err(_) => NestadiaStatus::InvalidState,
+    }
+}
+
+/// Frees a buffer returned by the emulator. Does nothing if it's null.
+///
+/// # Safety
+/// `buffer` and `len` must have been returned by `nestadia_save_state`, and the buffer must not
+/// be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn nestadia_free_buffer(buffer: *mut u8, len: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
+
+unsafe fn load(
+    rom: *const u8,
+    rom_len: usize,
+    save_data: *const u8,
+    save_data_len: usize,
+) -> Result<Emulator, NestadiaStatus> {
+    if rom.is_null() {
+        return Err(NestadiaStatus::NullPointer);
+    }
+
+    let rom = slice::from_raw_parts(rom, rom_len);
+    let save_data = if save_data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(save_data, save_data_len))
+    };
+
+    Emulator::new(rom, save_data).map_err(|_| NestadiaStatus::InvalidRom)
+}
@@ -14,7 +14,7 @@ use nestadia::Emulator;
 // NES outputs a 256 x 240 pixel image
 const NUM_PIXELS: usize = 256 * 240;
 
-const MOCK_AUDIO: [i16; 1470] = [0i16; 1470];
+const SAMPLE_RATE: u32 = 44100;
 
 bitflags! {
     #[derive(Default)]
@@ -100,7 +100,10 @@ impl Core for State {
             Err(_) => {
                 return LoadGameResult::Failed(game_data);
             }
-            Ok(emulator) => emulator,
+            Ok(mut emulator) => {
+                emulator.set_audio_sample_rate(SAMPLE_RATE);
+                emulator
+            }
         };
 
         self.emulator = Some(emulator);
@@ -110,7 +113,7 @@ impl Core for State {
         // We might need to change it later if need be.
         let av_info = AudioVideoInfo::new()
             .video(256, 240, 60.00, PixelFormat::ARGB8888)
-            .audio(44100.0)
+            .audio(f64::from(SAMPLE_RATE))
             .region(Region::NTSC);
 
         LoadGameResult::Success(av_info)
@@ -140,7 +143,13 @@ impl Core for State {
         nestadia::frame_to_argb(&frame, &mut current_frame);
 
         handle.upload_video_frame(&current_frame);
-        handle.upload_audio_frame(&MOCK_AUDIO);
+
+        // The emulator outputs mono samples, libretro expects interleaved stereo
+        let audio: Vec<i16> = emulator
+            .drain_audio_samples()
+            .flat_map(|sample| [sample, sample])
+            .collect();
+        handle.upload_audio_frame(&audio);
 
         // Reading controller inputs
         macro_rules! update_controllers {
@@ -180,7 +189,7 @@ impl Core for State {
         match &mut self.emulator {
             None => {}
             Some(emu) => {
-                emu.reset();
+                emu.soft_reset();
             }
         }
     }
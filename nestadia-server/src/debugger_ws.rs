@@ -0,0 +1,337 @@
+// Debugger protocol of the server, for a browser debugging UI to attach to a running
+// emulation. It's only served with --enable-debugger. The running sessions are listed by
+// /api/debugger/sessions, and the UI attaches to one with a websocket on
+// /api/debugger/{session}?secret=SECRET. The secret of a session is only sent to its player, as
+// the text message "debugger ID SECRET" when the emulation starts.
+//
+// Commands and replies are JSON text messages, one reply per command:
+//
+// {"command": "add_breakpoint", "address": 32768, "condition": "a == 3"}
+// {"type": "breakpoint_added", "id": 1}
+//
+// The emulation thread runs the commands between two frames. Whenever the emulation breaks, it
+// also sends a "break" message to every debugger attached, with the reason and the registers.
+
+use std::{sync::Weak, time::Instant};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use actix::prelude::*;
+use actix_web_actors::ws;
+
+use nestadia::{Emulator, EmulatorHandle, Event, Expression};
+
+use crate::nestadia_ws::{Debuggers, CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+
+/// Most CPU cycles run by a step over or out, about a second, so stepping over a routine that
+/// never returns doesn't hang the session
+const STEP_MAX_CYCLES: u64 = 1_789_773;
+
+/// Most instructions disassembled by a command, so a huge count doesn't stall the emulation
+const DISASSEMBLE_MAX_COUNT: usize = 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DebugCommand {
+    Registers,
+    /// `count` instructions from `address`, or from the PC without one, up to
+    /// `DISASSEMBLE_MAX_COUNT`
+    Disassemble {
+        address: Option<u16>,
+        count: usize,
+    },
+    AddBreakpoint {
+        address: u16,
+        /// Expression that must be true to break, like "a == 3"
+        condition: Option<String>,
+    },
+    RemoveBreakpoint {
+        id: u32,
+    },
+    Breakpoints,
+    /// Pauses on the next frame boundary
+    Pause,
+    Resume,
+    StepInto,
+    StepOver,
+    StepOut,
+    /// Reads without the side effects of reading the registers
+    ReadMemory {
+        address: u16,
+        length: u16,
+    },
+    WriteMemory {
+        address: u16,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub status: u8,
+    /// CPU cycles run since power-on
+    pub cycles: u64,
+    pub paused: bool,
+}
+
+impl Registers {
+    fn new(emulator: &Emulator) -> Self {
+        let cpu = emulator.cpu();
+        Self {
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            sp: cpu.st,
+            pc: cpu.pc,
+            status: cpu.status_register.bits(),
+            cycles: emulator.debugger().cycles(),
+            paused: emulator.is_paused(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisassemblyLine {
+    /// PRG ROM bank of the instruction, `None` outside of the PRG ROM
+    pub bank: Option<u8>,
+    pub address: u16,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakpointInfo {
+    pub id: u32,
+    pub address: u16,
+    pub enabled: bool,
+    pub condition: Option<String>,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Message)]
+#[rtype(result = "()")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DebugReply {
+    Registers(Registers),
+    Disassembly {
+        lines: Vec<DisassemblyLine>,
+    },
+    BreakpointAdded {
+        id: u32,
+    },
+    Breakpoints {
+        breakpoints: Vec<BreakpointInfo>,
+    },
+    Memory {
+        address: u16,
+        data: Vec<u8>,
+    },
+    /// Sent unprompted when the emulation breaks
+    Break {
+        reason: String,
+        registers: Registers,
+    },
+    /// Sent unprompted when the conditions of an achievement are met
+    AchievementUnlocked {
+        id: u32,
+        points: u32,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+impl DebugReply {
+    pub fn from_event(emulator: &Emulator, event: Event) -> Self {
+        match event {
+            Event::Break(brk) => DebugReply::Break {
+                reason: format!("{:?}", brk.reason),
+                registers: Registers::new(emulator),
+            },
+            Event::AchievementUnlocked { id, points } => {
+                DebugReply::AchievementUnlocked { id, points }
+            }
+        }
+    }
+}
+
+/// Runs a command of a debugger on the emulator of its session
+pub fn run_command(emulator: &mut Emulator, command: DebugCommand) -> DebugReply {
+    match command {
+        DebugCommand::Registers => DebugReply::Registers(Registers::new(emulator)),
+        DebugCommand::Disassemble { address, count } => {
+            let start = address.unwrap_or(emulator.cpu().pc);
+            let lines = emulator
+                .disassemble_instructions(start, count.min(DISASSEMBLE_MAX_COUNT))
+                .into_iter()
+                .map(|(bank, address, text)| DisassemblyLine {
+                    bank,
+                    address,
+                    text,
+                })
+                .collect();
+            DebugReply::Disassembly { lines }
+        }
+        DebugCommand::AddBreakpoint { address, condition } => {
+            let condition = match condition.as_deref().map(Expression::parse) {
+                Some(Ok(condition)) => Some(condition),
+                Some(Err(e)) => {
+                    return DebugReply::Error {
+                        message: format!("Invalid condition: {}", e),
+                    }
+                }
+                None => None,
+            };
+
+            let debugger = emulator.debugger_mut();
+            let id = debugger.add_breakpoint(address);
+            debugger.set_breakpoint_condition(id, condition);
+            DebugReply::BreakpointAdded { id }
+        }
+        DebugCommand::RemoveBreakpoint { id } => {
+            match emulator.debugger_mut().remove_breakpoint(id) {
+                Some(_) => DebugReply::Ok,
+                None => DebugReply::Error {
+                    message: format!("Unknown breakpoint: {}", id),
+                },
+            }
+        }
+        DebugCommand::Breakpoints => {
+            let breakpoints = emulator
+                .debugger()
+                .breakpoints()
+                .iter()
+                .map(|breakpoint| BreakpointInfo {
+                    id: breakpoint.id,
+                    address: breakpoint.address,
+                    enabled: breakpoint.enabled,
+                    condition: breakpoint.condition.as_ref().map(|c| c.to_string()),
+                    hits: breakpoint.hits,
+                })
+                .collect();
+            DebugReply::Breakpoints { breakpoints }
+        }
+        DebugCommand::Pause => {
+            // Already paused on a break, which must not run to the end of the frame
+            if !emulator.is_paused() {
+                emulator.pause();
+            }
+            DebugReply::Registers(Registers::new(emulator))
+        }
+        DebugCommand::Resume => {
+            emulator.resume();
+            DebugReply::Ok
+        }
+        DebugCommand::StepInto => {
+            emulator.step_into();
+            DebugReply::Registers(Registers::new(emulator))
+        }
+        DebugCommand::StepOver => {
+            emulator.step_over(STEP_MAX_CYCLES);
+            DebugReply::Registers(Registers::new(emulator))
+        }
+        DebugCommand::StepOut => {
+            emulator.step_out(STEP_MAX_CYCLES);
+            DebugReply::Registers(Registers::new(emulator))
+        }
+        DebugCommand::ReadMemory { address, length } => {
+            let mut data = vec![0; usize::from(length)];
+            emulator.peek_memory_range(address, &mut data);
+            DebugReply::Memory { address, data }
+        }
+        DebugCommand::WriteMemory { address, data } => {
+            emulator.write_memory_range(address, &data);
+            DebugReply::Ok
+        }
+    }
+}
+
+/// Websocket of a debugger attached to a session
+pub struct DebuggerWs {
+    /// Emulator of the session, gone once the session ended
+    pub emulator: Weak<EmulatorHandle>,
+    pub debuggers: Debuggers,
+    pub heartbeat: Instant,
+}
+
+impl DebuggerWs {
+    fn reply(&self, reply: &DebugReply, ctx: &mut ws::WebsocketContext<Self>) {
+        // The replies are plain data, so this can't fail
+        if let Ok(text) = serde_json::to_string(reply) {
+            ctx.text(text);
+        }
+    }
+}
+
+impl Actor for DebuggerWs {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if self.emulator.upgrade().is_some() {
+            let recipient = ctx.address().recipient();
+            self.debuggers.lock().unwrap().push(recipient);
+        } else {
+            ctx.stop();
+        }
+
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+                info!("Debugger heartbeat failed, disconnecting!");
+                ctx.stop();
+            } else {
+                ctx.ping(b"");
+            }
+        });
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for DebuggerWs {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        self.heartbeat = Instant::now();
+
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Pong(_)) => (),
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<DebugCommand>(&text) {
+                Ok(command) => match self.emulator.upgrade() {
+                    // Through the handle, which paces the frames again from now on
+                    Some(emulator) if matches!(command, DebugCommand::Resume) => {
+                        emulator.resume();
+                        self.reply(&DebugReply::Ok, ctx);
+                    }
+                    Some(emulator) => {
+                        let recipient = ctx.address().recipient();
+                        emulator.run(move |emulator| {
+                            let _ = recipient.do_send(run_command(emulator, command));
+                        });
+                    }
+                    None => {
+                        let message = "The session ended".to_string();
+                        self.reply(&DebugReply::Error { message }, ctx);
+                        ctx.stop();
+                    }
+                },
+                Err(e) => {
+                    let message = format!("Invalid command: {}", e);
+                    self.reply(&DebugReply::Error { message }, ctx);
+                }
+            },
+            Ok(ws::Message::Close(_)) => ctx.stop(),
+            _ => log::warn!("Debugger received msg of unsupported type {:?}", msg),
+        }
+    }
+}
+
+impl Handler<DebugReply> for DebuggerWs {
+    type Result = ();
+
+    fn handle(&mut self, msg: DebugReply, ctx: &mut Self::Context) {
+        self.reply(&msg, ctx);
+    }
+}
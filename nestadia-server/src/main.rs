@@ -1,9 +1,15 @@
+#[cfg(feature = "debugger")]
+mod debugger_ws;
 mod nestadia_ws;
 
 use std::error::Error;
 
 use structopt::StructOpt;
 
+#[cfg(feature = "debugger")]
+use debugger_ws::DebuggerWs;
+#[cfg(feature = "debugger")]
+use nestadia_ws::Sessions;
 use nestadia_ws::{EmulationState, NestadiaWs};
 
 use std::time::Instant;
@@ -20,7 +26,11 @@ struct Credentials {
     password: String,
 }
 
-async fn emulator_start_param(req: HttpRequest, stream: web::Payload) -> impl Responder {
+async fn emulator_start_param(
+    req: HttpRequest,
+    stream: web::Payload,
+    #[cfg(feature = "debugger")] sessions: Option<web::Data<Sessions>>,
+) -> impl Responder {
     let rom_name = req.match_info().get("rom_name").unwrap();
 
     let rom: &[u8] = match rom_name {
@@ -35,37 +45,105 @@ async fn emulator_start_param(req: HttpRequest, stream: web::Payload) -> impl Re
         heartbeat: Instant::now(),
         custom_rom: vec![],
         custom_rom_len: 0,
+        #[cfg(feature = "debugger")]
+        sessions,
+        #[cfg(feature = "debugger")]
+        session_id: None,
     };
 
     ws::start(websocket, &req, stream)
 }
 
-async fn custom_emulator(req: HttpRequest, stream: web::Payload) -> impl Responder {
+async fn custom_emulator(
+    req: HttpRequest,
+    stream: web::Payload,
+    #[cfg(feature = "debugger")] sessions: Option<web::Data<Sessions>>,
+) -> impl Responder {
     let websocket = NestadiaWs {
         state: EmulationState::Waiting,
         heartbeat: Instant::now(),
         custom_rom: vec![],
         custom_rom_len: 0,
+        #[cfg(feature = "debugger")]
+        sessions,
+        #[cfg(feature = "debugger")]
+        session_id: None,
     };
 
     ws::start(websocket, &req, stream)
 }
 
+#[cfg(feature = "debugger")]
+#[derive(Debug, Deserialize)]
+struct DebuggerQuery {
+    /// Secret of the session, sent to its player
+    secret: String,
+}
+
+#[cfg(feature = "debugger")]
+async fn debugger_sessions(sessions: web::Data<Sessions>) -> impl Responder {
+    HttpResponse::Ok().json(sessions.list())
+}
+
+#[cfg(feature = "debugger")]
+async fn attach_debugger(
+    req: HttpRequest,
+    stream: web::Payload,
+    sessions: web::Data<Sessions>,
+    query: web::Query<DebuggerQuery>,
+) -> impl Responder {
+    // Not found either with a wrong secret
+    let session = req
+        .match_info()
+        .get("session")
+        .and_then(|id| id.parse().ok())
+        .and_then(|id| sessions.attach(id, &query.secret));
+
+    match session {
+        Some((emulator, debuggers)) => {
+            let websocket = DebuggerWs {
+                emulator,
+                debuggers,
+                heartbeat: Instant::now(),
+            };
+            ws::start(websocket, &req, stream)
+        }
+        None => Ok(HttpResponse::NotFound().into()),
+    }
+}
+
 async fn rom_list(_req: HttpRequest) -> impl Responder {
     HttpResponse::Ok().json(ROM_LIST)
 }
 
 #[actix_web::main]
-pub async fn actix_main(bind_addr: String, port: u16) -> std::io::Result<()> {
+pub async fn actix_main(
+    bind_addr: String,
+    port: u16,
+    #[cfg(feature = "debugger")] enable_debugger: bool,
+) -> std::io::Result<()> {
+    #[cfg(feature = "debugger")]
+    let sessions = web::Data::new(Sessions::default());
+
     HttpServer::new(move || {
+        #[allow(unused_mut)]
+        let mut api = web::scope("/api")
+            .route("/emulator/custom", web::get().to(custom_emulator))
+            .route("/emulator/{rom_name}", web::get().to(emulator_start_param))
+            .route("/list", web::get().to(rom_list));
+
+        // Without the sessions, the emulations aren't registered for the debuggers
+        #[cfg(feature = "debugger")]
+        if enable_debugger {
+            api = api
+                .app_data(sessions.clone())
+                .route("/debugger/sessions", web::get().to(debugger_sessions))
+                .route("/debugger/{session}", web::get().to(attach_debugger));
+        }
+
         App::new()
             .wrap(actix_web::middleware::Logger::default())
-            .service(
-                web::scope("/api")
-                    .route("/emulator/custom", web::get().to(custom_emulator))
-                    .route("/emulator/{rom_name}", web::get().to(emulator_start_param))
-                    .route("/list", web::get().to(rom_list)),
-            )
+            .service(api)
             .service(
                 actix_files::Files::new("/", "client_build")
                     .index_file("index.html")
@@ -87,6 +165,11 @@ struct Opt {
 
     #[structopt(default_value = "8080", long, short)]
     port: u16,
+
+    /// Serves the debugger protocol, letting the players debug their emulation
+    #[cfg(feature = "debugger")]
+    #[structopt(long)]
+    enable_debugger: bool,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -95,5 +178,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         .start()
         .unwrap();
 
-    Ok(actix_main(opt.bind_addr, opt.port)?)
+    #[cfg(feature = "debugger")]
+    let result = actix_main(opt.bind_addr, opt.port, opt.enable_debugger);
+    #[cfg(not(feature = "debugger"))]
+    let result = actix_main(opt.bind_addr, opt.port);
+
+    Ok(result?)
 }
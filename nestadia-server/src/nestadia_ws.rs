@@ -1,82 +1,232 @@
 use std::convert::TryInto;
 use std::io::Write;
+#[cfg(feature = "debugger")]
+use std::{
+    collections::HashMap,
+    sync::{Mutex, Weak},
+};
 use std::{
     fs::{self, OpenOptions},
     io::Read,
-    pin::Pin,
-    sync::mpsc::{channel, Receiver, Sender},
-    time::{Duration, Instant},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use futures::task::{Poll, Waker};
 use log::info;
 
 use actix::prelude::*;
+#[cfg(feature = "debugger")]
+use actix_web::web;
 use actix_web_actors::ws;
 use flate2::{write::GzEncoder, Compression};
+#[cfg(feature = "debugger")]
+use serde::Serialize;
+
+#[cfg(feature = "debugger")]
+use nestadia::Event;
+use nestadia::{Cheat, Emulator, EmulatorHandle, RomParserError};
 
-use nestadia::{Emulator, RomParserError};
+#[cfg(feature = "debugger")]
+use crate::debugger_ws::DebugReply;
 
 /// How often heartbeat pings are sent
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// How long before lack of client response causes a timeout
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(20);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(20);
+/// Minimum time between two writes of the save file while the game runs
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, Copy)]
 pub struct EmulationError(RomParserError);
 
 impl core::fmt::Display for EmulationError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "{:?}", &self)
+        write!(f, "{}", &self.0)
     }
 }
 
 impl std::error::Error for EmulationError {}
 
 pub enum EmulationState {
-    Waiting,                        // wait for a user-provided ROM
-    Ready { rom: Vec<u8> },         // ready to start immediately
-    Started(Sender<EmulatorInput>), // up and running
+    Waiting,                // wait for a user-provided ROM
+    Ready { rom: Vec<u8> }, // ready to start immediately
+    Started(Emulation),     // up and running
 }
 
+/// Emulation of a player, running on its own thread
+pub struct Emulation {
+    emulator: Arc<EmulatorHandle>,
+    rom_hash: String,
+    #[cfg(feature = "debugger")]
+    debuggers: Debuggers,
+}
+
+/// Debuggers attached to an emulation, which are told when it breaks
+#[cfg(feature = "debugger")]
+pub type Debuggers = Arc<Mutex<Vec<Recipient<DebugReply>>>>;
+
 pub struct NestadiaWs {
     pub state: EmulationState,
     pub heartbeat: Instant,
     pub custom_rom: Vec<u8>,
     pub custom_rom_len: usize,
+    /// Where to register the emulation, when the debugger is enabled
+    #[cfg(feature = "debugger")]
+    pub sessions: Option<web::Data<Sessions>>,
+    /// Id of the emulation in `sessions`, once started
+    #[cfg(feature = "debugger")]
+    pub session_id: Option<u32>,
 }
 
-struct FrameStream {
-    receiver: Receiver<Vec<u8>>,
-    sender: Sender<Waker>,
+/// Running emulations, by id, for the debuggers to attach to
+#[cfg(feature = "debugger")]
+#[derive(Default)]
+pub struct Sessions(Mutex<HashMap<u32, Session>>);
+
+#[cfg(feature = "debugger")]
+struct Session {
+    rom_hash: String,
+    /// Hash of the secret needed to attach a debugger, compared in constant time
+    secret: blake3::Hash,
+    emulator: Weak<EmulatorHandle>,
+    debuggers: Debuggers,
 }
 
-#[derive(Message)]
-#[rtype(result = "()")]
-struct Frame(Vec<u8>);
+#[cfg(feature = "debugger")]
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: u32,
+    /// Hash of the ROM, which names its save file
+    pub rom_hash: String,
+}
 
-pub enum EmulatorInput {
-    Stop,
-    Controller1(u8),
+#[cfg(feature = "debugger")]
+impl Sessions {
+    /// Adds an emulation and returns its id, and the secret to attach a debugger to it which
+    /// only its player gets
+    pub fn add(&self, emulation: &Emulation) -> (u32, String) {
+        let mut sessions = self.0.lock().unwrap();
+        let id = loop {
+            let id = rand::random();
+            if !sessions.contains_key(&id) {
+                break id;
+            }
+        };
+
+        let secret = format!("{:032x}", rand::random::<u128>());
+        let session = Session {
+            rom_hash: emulation.rom_hash.clone(),
+            secret: blake3::hash(secret.as_bytes()),
+            emulator: Arc::downgrade(&emulation.emulator),
+            debuggers: emulation.debuggers.clone(),
+        };
+        sessions.insert(id, session);
+        (id, secret)
+    }
+
+    pub fn remove(&self, id: u32) {
+        self.0.lock().unwrap().remove(&id);
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let sessions = self.0.lock().unwrap();
+        let mut list: Vec<_> = sessions
+            .iter()
+            .map(|(id, session)| SessionInfo {
+                id: *id,
+                rom_hash: session.rom_hash.clone(),
+            })
+            .collect();
+        list.sort_by_key(|session| session.id);
+        list
+    }
+
+    /// Emulator and debuggers of the session, if `secret` is the one of the session
+    pub fn attach(&self, id: u32, secret: &str) -> Option<(Weak<EmulatorHandle>, Debuggers)> {
+        let sessions = self.0.lock().unwrap();
+        sessions
+            .get(&id)
+            .filter(|session| session.secret == blake3::hash(secret.as_bytes()))
+            .map(|session| (session.emulator.clone(), session.debuggers.clone()))
+    }
 }
 
-impl Stream for FrameStream {
-    type Item = Frame;
-
-    fn poll_next(
-        self: Pin<&mut Self>,
-        ctx: &mut futures::task::Context<'_>,
-    ) -> Poll<Option<Self::Item>> {
-        // Check whether a frame is ready
-        match self.receiver.try_recv() {
-            Ok(f) => Poll::Ready(Some(Frame(f))),
-            Err(std::sync::mpsc::TryRecvError::Empty) => {
-                let _ = self.sender.send(ctx.waker().clone());
-                Poll::Pending
+impl NestadiaWs {
+    fn start(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        rom: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let emulation = start_emulation(ctx, rom)?;
+
+        // The player gets the secret of the session as "debugger ID SECRET"
+        #[cfg(feature = "debugger")]
+        if let Some(sessions) = &self.sessions {
+            let (id, secret) = sessions.add(&emulation);
+            self.session_id = Some(id);
+            ctx.text(format!("debugger {} {}", id, secret));
+        }
+
+        self.state = EmulationState::Started(emulation);
+        Ok(())
+    }
+}
+
+impl Emulation {
+    /// Runs a text command of the player
+    fn apply(&self, input: EmulatorInput) {
+        match input {
+            EmulatorInput::AddCheat(cheat) => self.emulator.run(move |emulator| {
+                emulator.add_cheat(cheat);
+            }),
+            EmulatorInput::RemoveCheat(index) => self.emulator.run(move |emulator| {
+                emulator.remove_cheat(index);
+            }),
+            EmulatorInput::ClearCheats => self.emulator.run(|emulator| emulator.clear_cheats()),
+            EmulatorInput::Screenshot => {
+                let rom_hash = self.rom_hash.clone();
+                self.emulator
+                    .run(move |emulator| write_screenshot(emulator, &rom_hash));
             }
-            Err(std::sync::mpsc::TryRecvError::Disconnected) => Poll::Ready(None),
         }
     }
+
+    /// Writes the save file, if the game saved since the last write
+    fn save(&self) {
+        let save_path = save_path(&self.rom_hash);
+        self.emulator.run(move |emulator| {
+            if emulator.is_save_data_dirty() {
+                write_save_file(emulator, &save_path);
+            }
+        });
+    }
+
+    /// Tells the debuggers when the emulation breaks or unlocks an achievement, forgetting the
+    /// disconnected ones
+    #[cfg(feature = "debugger")]
+    fn forward_events(&self) {
+        let debuggers = self.debuggers.clone();
+        self.emulator.run(move |emulator| {
+            let events: Vec<Event> = emulator.drain_events().collect();
+            let mut debuggers = debuggers.lock().unwrap();
+            for event in events {
+                let reply = DebugReply::from_event(emulator, event);
+                debuggers.retain(|debugger| debugger.do_send(reply.clone()).is_ok());
+            }
+        });
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Frame(Vec<u8>);
+
+/// Text command of the player
+pub enum EmulatorInput {
+    AddCheat(Cheat),
+    RemoveCheat(usize),
+    ClearCheats,
+    Screenshot,
 }
 
 impl Actor for NestadiaWs {
@@ -85,8 +235,8 @@ impl Actor for NestadiaWs {
     fn started(&mut self, ctx: &mut Self::Context) {
         if let EmulationState::Ready { rom } = &self.state {
             // At this point, ROMs are hardcoded, so this shouldn't fail
-            let sender = start_emulation(ctx, rom).unwrap();
-            self.state = EmulationState::Started(sender);
+            let rom = rom.clone();
+            self.start(ctx, &rom).unwrap();
         }
 
         ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
@@ -97,12 +247,23 @@ impl Actor for NestadiaWs {
                 ctx.ping(b"");
             }
         });
+
+        ctx.run_interval(SAVE_INTERVAL, |act, _ctx| {
+            if let EmulationState::Started(emulation) = &act.state {
+                emulation.save();
+            }
+        });
     }
 
     fn stopped(&mut self, _ctx: &mut Self::Context) {
-        // Tell the emulation thread to stop
-        if let EmulationState::Started(input_sender) = &self.state {
-            input_sender.send(EmulatorInput::Stop).unwrap()
+        #[cfg(feature = "debugger")]
+        if let (Some(sessions), Some(id)) = (&self.sessions, self.session_id) {
+            sessions.remove(id);
+        }
+
+        // The emulation thread writes the save file, then stops with the last handle
+        if let EmulationState::Started(emulation) = &self.state {
+            emulation.save();
         }
     }
 }
@@ -134,21 +295,36 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for NestadiaWs {
                         }
 
                         if self.custom_rom.len() == self.custom_rom_len {
-                            // If there's an error, just ignore it and wait for a valid ROM
-                            if let Ok(sender) = start_emulation(ctx, &self.custom_rom) {
-                                self.state = EmulationState::Started(sender);
+                            // If there's an error, report it and wait for a valid ROM
+                            let rom = std::mem::take(&mut self.custom_rom);
+                            match self.start(ctx, &rom) {
+                                Ok(()) => (),
+                                Err(e) => {
+                                    log::warn!("Couldn't load the ROM: {}", e);
+                                    ctx.text(e.to_string());
+                                }
                             }
                         }
                     }
-                    EmulationState::Started(input_sender) => {
+                    EmulationState::Started(emulation) => {
                         // Received controller input
                         if !bin.is_empty() {
-                            let _ = input_sender.send(EmulatorInput::Controller1(bin[0]));
+                            emulation.emulator.set_input(0, bin[0]);
                         };
                     }
                     EmulationState::Ready { .. } => (), // Ignore
                 }
             }
+            // Text messages are commands: "screenshot", and those of the cheats panel,
+            // "cheat add AAAA:VV", "cheat remove INDEX" and "cheat clear"
+            Ok(ws::Message::Text(text)) => {
+                if let EmulationState::Started(emulation) = &self.state {
+                    match parse_command(&text) {
+                        Ok(input) => emulation.apply(input),
+                        Err(e) => ctx.text(e),
+                    }
+                }
+            }
             Ok(ws::Message::Close(_)) => ctx.stop(),
             _ => (log::warn!("Websocket received msg of unsupported type {:?}", msg)),
         }
@@ -172,94 +348,117 @@ impl Handler<Frame> for NestadiaWs {
         if encoder.write_all(&msg.0).is_ok() {
             ctx.binary(encoder.finish().unwrap()); // TODO: Send real frame
         }
+
+        #[cfg(feature = "debugger")]
+        if let EmulationState::Started(emulation) = &self.state {
+            emulation.forward_events();
+        }
+    }
+}
+
+fn parse_command(command: &str) -> Result<EmulatorInput, String> {
+    let mut words = command.split_whitespace();
+    match words.next() {
+        Some("screenshot") => Ok(EmulatorInput::Screenshot),
+        Some("cheat") => parse_cheat_command(words.next(), words.next(), command),
+        _ => Err(format!("Unknown command: {}", command)),
     }
 }
 
+fn parse_cheat_command(
+    action: Option<&str>,
+    argument: Option<&str>,
+    command: &str,
+) -> Result<EmulatorInput, String> {
+    match (action, argument) {
+        (Some("add"), Some(code)) => Cheat::from_code(code)
+            .map(EmulatorInput::AddCheat)
+            .map_err(|e| format!("Invalid cheat {}: {}", code, e)),
+        (Some("remove"), Some(index)) => index
+            .parse()
+            .map(EmulatorInput::RemoveCheat)
+            .map_err(|_| format!("Invalid cheat index: {}", index)),
+        (Some("clear"), None) => Ok(EmulatorInput::ClearCheats),
+        _ => Err(format!("Unknown command: {}", command)),
+    }
+}
+
+fn rom_hash(rom: &[u8]) -> String {
+    blake3::hash(rom).to_hex().to_string()
+}
+
+fn write_save_file(emulator: &mut Emulator, save_path: &str) {
+    if let Err(e) = fs::create_dir_all("saves") {
+        log::warn!("Couldn't create save folder: {}", e)
+    };
+
+    if let Some(save_data) = emulator.get_save_data() {
+        if let Ok(mut f) = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(save_path)
+        {
+            if f.write_all(&save_data).is_ok() {
+                emulator.mark_save_data_saved();
+            }
+        }
+    }
+}
+
+/// Saves the last frame in the screenshots folder, named after the ROM and the time
+fn write_screenshot(emulator: &Emulator, rom_hash: &str) {
+    if let Err(e) = fs::create_dir_all("screenshots") {
+        log::warn!("Couldn't create screenshot folder: {}", e)
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+    let path = format!("screenshots/{}-{}.png", rom_hash, timestamp);
+    if let Err(e) = fs::write(&path, emulator.screenshot()) {
+        log::warn!("Couldn't write screenshot {}: {}", path, e)
+    }
+}
+
+fn save_path(rom_hash: &str) -> String {
+    "saves/".to_string() + rom_hash + ".save"
+}
+
 fn start_emulation(
     ctx: &mut ws::WebsocketContext<NestadiaWs>,
     rom: &[u8],
-) -> Result<Sender<EmulatorInput>, Box<dyn std::error::Error>> {
+) -> Result<Emulation, Box<dyn std::error::Error>> {
     // Read save file
-    let rom_hash = blake3::hash(rom).to_hex().to_string();
-    let save_path = "saves/".to_string() + &rom_hash + ".save";
+    let rom_hash = rom_hash(rom);
     let mut buf = Vec::new();
 
-    let save_data = if let Ok(mut f) = std::fs::File::open(&save_path) {
+    let save_data = if let Ok(mut f) = std::fs::File::open(save_path(&rom_hash)) {
         let _ = f.read_to_end(&mut buf);
         Some(buf.as_slice())
     } else {
         None
     };
 
-    let mut emulator = Emulator::new(rom, save_data).map_err(EmulationError)?;
-
-    let (input_sender, input_receiver) = channel();
-    let (frame_sender, frame_receiver) = channel();
-    let (waker_sender, waker_receiver) = channel();
+    let emulator = Emulator::new(rom, save_data).map_err(EmulationError)?;
+    let emulator = EmulatorHandle::spawn(emulator);
 
-    // This thread runs the actual emulator and sync the framerate
+    // This thread passes the frames to the websocket, dropping them when it can't keep up
+    let frames = emulator.subscribe();
+    let address = ctx.address();
     std::thread::spawn(move || {
-        let mut next_frame_time = Instant::now() + Duration::new(0, 1_000_000_000u32 / 60);
-        let mut frame_waker: Option<Waker> = None;
-
-        loop {
-            // Check if we received  an input or if we close the thread
-            if let Ok(emulator_input) = input_receiver.try_recv() {
-                match emulator_input {
-                    EmulatorInput::Stop => break,
-                    EmulatorInput::Controller1(x) => emulator.set_controller1(x),
-                }
-            };
-
-            // Loop until we get a frame
-            let frame = loop {
-                if let Some(frame) = emulator.clock() {
-                    break frame;
-                }
-            }
-            .to_vec();
-
-            if Instant::now() < next_frame_time {
-                std::thread::sleep(next_frame_time.duration_since(Instant::now()));
-            };
-
-            match frame_sender.send(frame) {
-                Ok(_) => {}
-                Err(_) => break, // Stop the thread if there is an error to avoid infinite loop
-            };
-
-            // Wake the FrameStream task
-            if let Ok(waker) = waker_receiver.try_recv() {
-                frame_waker = Some(waker);
-            };
-            if let Some(waker) = frame_waker.take() {
-                waker.wake();
-            }
-
-            next_frame_time = Instant::now() + Duration::new(0, 1_000_000_000u32 / 60);
-        }
-
-        // Save file
-        if let Err(e) = fs::create_dir_all("saves") {
-            log::warn!("Couldn't create save folder: {}", e)
-        };
-
-        if let Some(save_data) = emulator.get_save_data() {
-            if let Ok(mut f) = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(save_path)
-            {
-                let _ = f.write_all(save_data);
+        for frame in frames {
+            match address.try_send(Frame(frame.video.to_vec())) {
+                Ok(()) | Err(SendError::Full(_)) => (),
+                Err(SendError::Closed(_)) => break,
             }
         }
     });
 
-    ctx.add_message_stream(FrameStream {
-        receiver: frame_receiver,
-        sender: waker_sender,
-    });
-
-    Ok(input_sender)
+    Ok(Emulation {
+        emulator: Arc::new(emulator),
+        rom_hash,
+        #[cfg(feature = "debugger")]
+        debuggers: Debuggers::default(),
+    })
 }
@@ -0,0 +1,114 @@
+// wasm-bindgen interface of the emulator, to run it in any web page.
+// https://rustwasm.github.io/docs/wasm-bindgen/
+//
+// The web frontend can use it to run the emulator in the browser instead of streaming the
+// frames from the server. Build it with `wasm-pack build --target web`, then from JavaScript:
+//
+//     const emulator = new NestadiaEmulator(rom);
+//     emulator.run_frame(new Uint8Array([controller1, controller2]));
+//     context.putImageData(new ImageData(emulator.framebuffer(), 256, 240), 0, 0);
+
+use std::convert::TryInto as _;
+
+use nestadia::Emulator;
+use wasm_bindgen::{prelude::*, Clamped};
+
+const FRAME_WIDTH: usize = 256;
+const FRAME_HEIGHT: usize = 240;
+
+#[wasm_bindgen]
+pub struct NestadiaEmulator {
+    emulator: Emulator,
+    audio: Vec<i16>,
+}
+
+#[wasm_bindgen]
+impl NestadiaEmulator {
+    /// Creates an emulator running an iNES/NES 2.0 ROM or an NSF file, with the battery backed
+    /// RAM saved from a previous session if there's one. Throws if the ROM is invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], save_data: Option<Box<[u8]>>) -> Result<NestadiaEmulator, JsValue> {
+        Ok(Self {
+            emulator: load(rom, save_data)?,
+            audio: Vec::new(),
+        })
+    }
+
+    /// Replaces the game, which is powered on again. On error, the previous game keeps running.
+    pub fn load_rom(&mut self, rom: &[u8], save_data: Option<Box<[u8]>>) -> Result<(), JsValue> {
+        self.emulator = load(rom, save_data)?;
+        self.audio.clear();
+        Ok(())
+    }
+
+    /// Sets the buttons of up to 4 controllers, then runs until the next frame. The buttons are,
+    /// from the high bit: A, B, Select, Start, Up, Down, Left, Right.
+    pub fn run_frame(&mut self, inputs: &[u8]) {
+        let mut states = [0; 4];
+        for (state, input) in states.iter_mut().zip(inputs) {
+            *state = *input;
+        }
+
+        let output = self.emulator.run_frame(states);
+        self.audio.clear();
+        self.audio.extend_from_slice(output.audio);
+    }
+
+    /// Number of frames run since power-on
+    pub fn frame_count(&self) -> f64 {
+        self.emulator.frame_count() as f64
+    }
+
+    /// RGBA pixels of the last frame, ready for an `ImageData`
+    pub fn framebuffer(&self) -> Clamped<Vec<u8>> {
+        let mut rgba = vec![0u8; FRAME_WIDTH * FRAME_HEIGHT * 4];
+        nestadia::frame_to_rgba(
+            self.emulator.frame(),
+            rgba.as_mut_slice().try_into().unwrap(),
+        );
+        Clamped(rgba)
+    }
+
+    /// Palette indices of the pixels of the last frame
+    pub fn palette_indices(&self) -> Vec<u8> {
+        self.emulator.frame().to_vec()
+    }
+
+    /// Mono audio samples produced during the last frame
+    pub fn audio(&self) -> Vec<i16> {
+        self.audio.clone()
+    }
+
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.emulator.set_audio_sample_rate(sample_rate);
+    }
+
+    pub fn soft_reset(&mut self) {
+        self.emulator.soft_reset();
+    }
+
+    pub fn power_cycle(&mut self) {
+        self.emulator.power_cycle();
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.emulator.save_state()
+    }
+
+    /// Loads a state saved for the same game. Throws if it's invalid, leaving the emulator
+    /// unchanged.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+        self.emulator
+            .load_state(state)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Battery backed RAM of the game, to store for the next session
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.emulator.get_save_data()
+    }
+}
+
+fn load(rom: &[u8], save_data: Option<Box<[u8]>>) -> Result<Emulator, JsValue> {
+    Emulator::new(rom, save_data.as_deref()).map_err(|e| JsValue::from_str(&e.to_string()))
+}
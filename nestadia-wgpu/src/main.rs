@@ -1,5 +1,5 @@
 use futures::executor::block_on;
-use nestadia::Emulator;
+use nestadia::{Emulator, FastForward, FastForwardAudio};
 use wgpu::util::DeviceExt;
 
 use std::{
@@ -28,9 +28,16 @@ struct Opt {
 
     #[structopt(short = "p", long)]
     start_paused: bool,
+
+    /// Rhai script run along the emulation
+    #[cfg(feature = "scripting")]
+    #[structopt(short = "s", long, parse(from_os_str))]
+    script: Option<PathBuf>,
 }
 
 mod debugger;
+#[cfg(feature = "scripting")]
+mod scripting;
 
 bitflags! {
     #[derive(Default)]
@@ -68,6 +75,12 @@ impl TryFrom<&VirtualKeyCode> for ControllerState {
 // Target for NTSC is ~60 FPS
 const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
 
+// Minimum time between two writes of the save file while the game runs
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Frames emulated without being rendered after each rendered frame while Tab is held
+const FAST_FORWARD_FRAME_SKIP: u32 = 3;
+
 // NES outputs a 256 x 240 pixel image
 const NUM_PIXELS: usize = 256 * 240;
 
@@ -104,10 +117,14 @@ struct State {
     emulator: Emulator,
     controller1: ControllerState,
     last_frame_time: Instant,
+    last_save_time: Instant,
 
     paused: bool,
     breakpoints: Vec<u16>,
 
+    #[cfg(feature = "scripting")]
+    script: Option<scripting::Script>,
+
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -340,10 +357,14 @@ impl State {
             emulator,
             controller1: Default::default(),
             last_frame_time: Instant::now(),
+            last_save_time: Instant::now(),
 
             paused: false,
             breakpoints: Vec::new(),
 
+            #[cfg(feature = "scripting")]
+            script: None,
+
             surface,
             device,
             queue,
@@ -382,6 +403,12 @@ impl State {
 
                         self.emulator.set_controller1(self.controller1.bits());
                         true
+                    } else if *key_code == VirtualKeyCode::Tab {
+                        self.emulator.set_fast_forward(Some(FastForward::new(
+                            FAST_FORWARD_FRAME_SKIP,
+                            FastForwardAudio::Mute,
+                        )));
+                        true
                     } else {
                         false
                     }
@@ -397,6 +424,9 @@ impl State {
 
                         self.emulator.set_controller1(self.controller1.bits());
                         true
+                    } else if *key_code == VirtualKeyCode::Tab {
+                        self.emulator.set_fast_forward(None);
+                        true
                     } else {
                         false
                     }
@@ -439,19 +469,30 @@ impl State {
                 );
             }
         } else {
+            #[cfg(feature = "scripting")]
+            if let Some(script) = &mut self.script {
+                script.before_frame(&mut self.emulator);
+            }
+
             // Clock until a frame is ready
-            let frame = loop {
+            let frame_ready = loop {
                 if self.breakpoints.contains(&self.emulator.cpu().pc) {
                     println!("Reached breakpoint at {:#06x}", self.emulator.cpu().pc);
                     self.paused = true;
-                    break None;
+                    break false;
                 }
-                if let Some(frame) = self.emulator.clock() {
-                    break Some(frame);
+                if self.emulator.clock().is_some() {
+                    break true;
                 }
             };
 
-            if let Some(frame) = frame {
+            if frame_ready {
+                #[cfg(feature = "scripting")]
+                if let Some(script) = &mut self.script {
+                    script.after_frame(&mut self.emulator);
+                }
+
+                let frame = self.emulator.frame();
                 let mut current_frame = [0u8; NUM_PIXELS * 4];
                 nestadia::frame_to_rgba(&frame, &mut current_frame);
 
@@ -521,7 +562,8 @@ impl State {
         Ok(())
     }
 
-    fn save_data(&self, save_path: &Path) {
+    fn save_data(&mut self, save_path: &Path) {
+        self.last_save_time = Instant::now();
         if let Some(save_data) = self.emulator.get_save_data() {
             if let Ok(mut f) = OpenOptions::new()
                 .read(true)
@@ -529,7 +571,9 @@ impl State {
                 .create(true)
                 .open(&save_path)
             {
-                let _ = f.write_all(save_data);
+                if f.write_all(&save_data).is_ok() {
+                    self.emulator.mark_save_data_saved();
+                }
             }
         }
     }
@@ -586,10 +630,24 @@ fn main() {
         state.pause();
     }
 
+    #[cfg(feature = "scripting")]
+    if let Some(script_path) = &opt.script {
+        match scripting::Script::load(script_path) {
+            Ok(script) => state.script = Some(script),
+            Err(e) => eprintln!("Could not load the script: {}", e),
+        }
+    }
+
     // Handle window events
     event_loop.run(move |event, _, control_flow| match event {
         Event::RedrawRequested(_) => {
             state.update();
+            if state.emulator.is_save_data_dirty()
+                && state.last_save_time.elapsed() >= SAVE_INTERVAL
+            {
+                state.save_data(&save_path);
+            }
+
             match state.render() {
                 Ok(_) => {}
                 Err(wgpu::SwapChainError::Lost) => state.resize(state.size),
@@ -0,0 +1,226 @@
+// Rhai scripts run along the emulation, like the Lua scripts of FCEUX.
+// https://rhai.rs/book/
+//
+// A script can define `before_frame()`, called before every frame to read the memory and set
+// the inputs, and `after_frame()`, called once the frame is rendered to draw over it. The top
+// level of the script runs once when it's loaded, to set up its variables.
+//
+// Functions available to the scripts:
+//  - read(addr), write(addr, value): CPU address space. Reads have no side effects.
+//  - input(controller), set_input(controller, buttons): controllers 0 to 3, A in the high bit
+//  - frame_count()
+//  - draw_pixel(x, y, color), draw_line(x0, y0, x1, y1, color), draw_rect(x, y, w, h, color),
+//    fill_rect(x, y, w, h, color), draw_text(x, y, text, color): colors of the NES palette
+//
+// The memory is copied before calling the script, and the writes and inputs are applied after,
+// so the script never holds on to the emulator.
+
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use nestadia::{Emulator, Overlay};
+use rhai::{CallFnOptions, Engine, EvalAltResult, Scope, AST};
+
+enum DrawCommand {
+    Pixel(i32, i32, u8),
+    Line(i32, i32, i32, i32, u8),
+    Rect(i32, i32, i32, i32, u8),
+    FillRect(i32, i32, i32, i32, u8),
+    Text(i32, i32, String, u8),
+}
+
+impl DrawCommand {
+    fn draw(&self, overlay: &mut Overlay) {
+        match *self {
+            Self::Pixel(x, y, color) => overlay.pixel(x, y, color),
+            Self::Line(x0, y0, x1, y1, color) => overlay.line(x0, y0, x1, y1, color),
+            Self::Rect(x, y, w, h, color) => overlay.rect(x, y, w, h, color),
+            Self::FillRect(x, y, w, h, color) => overlay.fill_rect(x, y, w, h, color),
+            Self::Text(x, y, ref text, color) => overlay.text(x, y, text, color),
+        }
+    }
+}
+
+/// State shared between the script functions and the emulator
+#[derive(Default)]
+struct ScriptContext {
+    memory: Vec<u8>,
+    writes: Vec<(u16, u8)>,
+    controllers: [u8; 4],
+    inputs: [Option<u8>; 4],
+    frame_count: u64,
+    draws: Vec<DrawCommand>,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    context: Rc<RefCell<ScriptContext>>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, Box<EvalAltResult>> {
+        let context = Rc::new(RefCell::new(ScriptContext::default()));
+        let mut engine = Engine::new();
+        register_functions(&mut engine, &context);
+
+        let ast = engine.compile_file(path.to_path_buf())?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            context,
+        })
+    }
+
+    pub fn before_frame(&mut self, emulator: &mut Emulator) {
+        self.call(emulator, "before_frame");
+    }
+
+    pub fn after_frame(&mut self, emulator: &mut Emulator) {
+        self.call(emulator, "after_frame");
+    }
+
+    fn call(&mut self, emulator: &mut Emulator, name: &str) {
+        if !self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.is_empty())
+        {
+            return;
+        }
+
+        {
+            let mut context = self.context.borrow_mut();
+            context.memory = (0..=0xFFFF)
+                .map(|addr| emulator.peek_memory(addr))
+                .collect();
+            context.controllers = emulator.controller_states();
+            context.frame_count = emulator.frame_count();
+        }
+
+        // The top level already ran when loading the script
+        let options = CallFnOptions::new().eval_ast(false);
+        if let Err(e) =
+            self.engine
+                .call_fn_with_options::<()>(options, &mut self.scope, &self.ast, name, ())
+        {
+            eprintln!("Script error in {}: {}", name, e);
+        }
+
+        let mut context = self.context.borrow_mut();
+        for (addr, data) in context.writes.drain(..) {
+            emulator.write_memory(addr, data);
+        }
+        for (controller, input) in context.inputs.iter_mut().enumerate() {
+            if let Some(state) = input.take() {
+                match controller {
+                    0 => emulator.set_controller1(state),
+                    1 => emulator.set_controller2(state),
+                    2 => emulator.set_controller3(state),
+                    _ => emulator.set_controller4(state),
+                }
+            }
+        }
+
+        let mut overlay = emulator.overlay();
+        for command in context.draws.drain(..) {
+            command.draw(&mut overlay);
+        }
+    }
+}
+
+fn register_functions(engine: &mut Engine, context: &Rc<RefCell<ScriptContext>>) {
+    let ctx = context.clone();
+    engine.register_fn("read", move |addr: i64| {
+        i64::from(ctx.borrow().memory[usize::from(addr as u16)])
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("write", move |addr: i64, value: i64| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.memory[usize::from(addr as u16)] = value as u8;
+        ctx.writes.push((addr as u16, value as u8));
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("input", move |controller: i64| {
+        i64::from(ctx.borrow().controllers[(controller & 3) as usize])
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("set_input", move |controller: i64, buttons: i64| {
+        let mut ctx = ctx.borrow_mut();
+        let controller = (controller & 3) as usize;
+        ctx.controllers[controller] = buttons as u8;
+        ctx.inputs[controller] = Some(buttons as u8);
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("frame_count", move || ctx.borrow().frame_count as i64);
+
+    let ctx = context.clone();
+    engine.register_fn("draw_pixel", move |x: i64, y: i64, color: i64| {
+        ctx.borrow_mut()
+            .draws
+            .push(DrawCommand::Pixel(x as i32, y as i32, color as u8));
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "draw_line",
+        move |x0: i64, y0: i64, x1: i64, y1: i64, color: i64| {
+            ctx.borrow_mut().draws.push(DrawCommand::Line(
+                x0 as i32,
+                y0 as i32,
+                x1 as i32,
+                y1 as i32,
+                color as u8,
+            ));
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "draw_rect",
+        move |x: i64, y: i64, w: i64, h: i64, color: i64| {
+            ctx.borrow_mut().draws.push(DrawCommand::Rect(
+                x as i32,
+                y as i32,
+                w as i32,
+                h as i32,
+                color as u8,
+            ));
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "fill_rect",
+        move |x: i64, y: i64, w: i64, h: i64, color: i64| {
+            ctx.borrow_mut().draws.push(DrawCommand::FillRect(
+                x as i32,
+                y as i32,
+                w as i32,
+                h as i32,
+                color as u8,
+            ));
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "draw_text",
+        move |x: i64, y: i64, text: &str, color: i64| {
+            ctx.borrow_mut().draws.push(DrawCommand::Text(
+                x as i32,
+                y as i32,
+                text.to_string(),
+                color as u8,
+            ));
+        },
+    );
+}
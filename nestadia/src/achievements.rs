@@ -0,0 +1,498 @@
+// Achievements unlocked by conditions on the memory, like the ones of RetroAchievements.
+// https://docs.retroachievements.org/Condition-Syntax/
+//
+// The conditions of every achievement are evaluated at the end of each frame. A condition
+// compares two operands: a constant, a value in memory, or the value it had at the previous
+// frame (its delta). Once all of its conditions are true together, the achievement is unlocked
+// and an `Event::AchievementUnlocked` is queued.
+//
+// A condition with a hit target must be true on that many frames, not necessarily in a row,
+// before counting as true. A true "reset if" condition clears the hit counts, and a true "pause
+// if" condition freezes the achievement for the frame. Achievements must be seen false once
+// before they can unlock, so loading a state or adding an achievement late doesn't unlock it
+// right away.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AchievementError {
+    /// A condition doesn't follow the RetroAchievements syntax, or uses an unsupported feature
+    InvalidCondition,
+    /// The address isn't in the CPU RAM ($0000-$1FFF) or the PRG RAM ($6000-$7FFF)
+    UnsupportedAddress(u16),
+    /// An achievement needs at least one condition that isn't "reset if" or "pause if"
+    NoCondition,
+}
+
+impl core::fmt::Display for AchievementError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// Part of the memory read by an operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemorySize {
+    /// A bit, from 0 to 7
+    Bit(u8),
+    LowerNibble,
+    UpperNibble,
+    Byte,
+    /// Little endian 16 bits
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Value(u32),
+    Memory(u16, MemorySize),
+    /// Value of the memory at the previous frame
+    Delta(u16, MemorySize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionKind {
+    Standard,
+    /// Clears the hit counts of the achievement while true
+    ResetIf,
+    /// Stops evaluating the achievement while true
+    PauseIf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub kind: ConditionKind,
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+    /// Number of frames the condition must be true on, or 0 to only need it true now
+    pub hit_target: u32,
+}
+
+impl Condition {
+    pub fn new(left: Operand, comparison: Comparison, right: Operand) -> Self {
+        Self {
+            kind: ConditionKind::Standard,
+            left,
+            comparison,
+            right,
+            hit_target: 0,
+        }
+    }
+
+    /// Parses a condition, like "R:0xH0010!=d0xH0010" or "0x 0020>=h100.3."
+    pub fn parse(condition: &str) -> Result<Self, AchievementError> {
+        let (kind, condition) = match condition.get(..2) {
+            Some("R:") => (ConditionKind::ResetIf, &condition[2..]),
+            Some("P:") => (ConditionKind::PauseIf, &condition[2..]),
+            _ => (ConditionKind::Standard, condition),
+        };
+
+        let (condition, hit_target) = match condition.strip_suffix('.') {
+            Some(condition) => {
+                let (condition, hits) = condition
+                    .rsplit_once('.')
+                    .ok_or(AchievementError::InvalidCondition)?;
+                let hits = hits
+                    .parse()
+                    .map_err(|_| AchievementError::InvalidCondition)?;
+                (condition, hits)
+            }
+            None => (condition, 0),
+        };
+
+        // The longest operators first, as "<" starts "<="
+        let operators = [
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("=", Comparison::Equal),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ];
+        let (left, comparison, right) = operators
+            .iter()
+            .find_map(|(operator, comparison)| {
+                let (left, right) = condition.split_once(operator)?;
+                Some((left, *comparison, right))
+            })
+            .ok_or(AchievementError::InvalidCondition)?;
+
+        Ok(Self {
+            kind,
+            left: parse_operand(left)?,
+            comparison,
+            right: parse_operand(right)?,
+            hit_target,
+        })
+    }
+
+    fn is_true(&self, left: u32, right: u32) -> bool {
+        match self.comparison {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+fn parse_operand(operand: &str) -> Result<Operand, AchievementError> {
+    let invalid = |_| AchievementError::InvalidCondition;
+
+    let (delta, memory) = match operand.strip_prefix('d') {
+        Some(memory) => (true, memory),
+        None => (false, operand),
+    };
+    let memory = match memory.strip_prefix("0x") {
+        Some(memory) => memory,
+        None if delta => return Err(AchievementError::InvalidCondition),
+        None => {
+            return match operand.strip_prefix('h') {
+                Some(value) => u32::from_str_radix(value, 16)
+                    .map(Operand::Value)
+                    .map_err(invalid),
+                None => operand.parse().map(Operand::Value).map_err(invalid),
+            };
+        }
+    };
+
+    let (size, address) = match memory.chars().next() {
+        Some(size @ 'M'..='T') => (MemorySize::Bit(size as u8 - b'M'), &memory[1..]),
+        Some('L') => (MemorySize::LowerNibble, &memory[1..]),
+        Some('U') => (MemorySize::UpperNibble, &memory[1..]),
+        Some('H') => (MemorySize::Byte, &memory[1..]),
+        Some(' ') => (MemorySize::Word, &memory[1..]),
+        _ => (MemorySize::Word, memory),
+    };
+    let address = u16::from_str_radix(address, 16).map_err(invalid)?;
+    check_address(address)?;
+
+    Ok(if delta {
+        Operand::Delta(address, size)
+    } else {
+        Operand::Memory(address, size)
+    })
+}
+
+fn check_address(address: u16) -> Result<(), AchievementError> {
+    if matches!(address, 0x0000..=0x1FFF | 0x6000..=0x7FFF) {
+        Ok(())
+    } else {
+        Err(AchievementError::UnsupportedAddress(address))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Achievement {
+    pub id: u32,
+    pub title: String,
+    pub points: u32,
+    pub conditions: Vec<Condition>,
+}
+
+impl Achievement {
+    pub fn new(
+        id: u32,
+        title: String,
+        points: u32,
+        conditions: Vec<Condition>,
+    ) -> Result<Self, AchievementError> {
+        for condition in &conditions {
+            for operand in [condition.left, condition.right] {
+                if let Operand::Memory(address, _) | Operand::Delta(address, _) = operand {
+                    check_address(address)?;
+                }
+            }
+        }
+        if !conditions.iter().any(|c| c.kind == ConditionKind::Standard) {
+            return Err(AchievementError::NoCondition);
+        }
+
+        Ok(Self {
+            id,
+            title,
+            points,
+            conditions,
+        })
+    }
+
+    /// Parses the conditions of an achievement in the RetroAchievements syntax, separated by
+    /// '_', like "0xH0010=5_d0xH0010!=5". Alternative groups and the other condition flags
+    /// aren't supported.
+    pub fn parse(
+        id: u32,
+        title: String,
+        points: u32,
+        conditions: &str,
+    ) -> Result<Self, AchievementError> {
+        let conditions = conditions
+            .split('_')
+            .map(Condition::parse)
+            .collect::<Result<_, _>>()?;
+
+        Self::new(id, title, points, conditions)
+    }
+}
+
+/// Progress of an achievement
+struct Tracker {
+    achievement: Achievement,
+    hits: Vec<u32>,
+    /// Operands of the conditions at the previous frame, for the deltas
+    previous: Vec<(u32, u32)>,
+    /// Seen false since it was added
+    armed: bool,
+    unlocked: bool,
+}
+
+/// Achievements evaluated at the end of every frame
+#[derive(Default)]
+pub struct Achievements {
+    trackers: Vec<Tracker>,
+}
+
+impl Achievements {
+    /// Adds an achievement, replacing the one with the same id
+    pub fn add(&mut self, achievement: Achievement) {
+        self.remove(achievement.id);
+        self.trackers.push(Tracker {
+            hits: alloc::vec![0; achievement.conditions.len()],
+            previous: Vec::new(),
+            armed: false,
+            unlocked: false,
+            achievement,
+        });
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Achievement> {
+        let index = self.trackers.iter().position(|t| t.achievement.id == id)?;
+        Some(self.trackers.remove(index).achievement)
+    }
+
+    pub fn clear(&mut self) {
+        self.trackers.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Achievement> {
+        self.trackers.iter().map(|tracker| &tracker.achievement)
+    }
+
+    pub fn is_unlocked(&self, id: u32) -> bool {
+        self.trackers
+            .iter()
+            .any(|tracker| tracker.achievement.id == id && tracker.unlocked)
+    }
+
+    /// Evaluates the achievements left, with `read` reading the memory, and queues an event for
+    /// each one unlocked
+    pub fn evaluate<F>(&mut self, read: F, events: &mut Vec<Event>)
+    where
+        F: Fn(u16) -> u8,
+    {
+        let read_size = |address: u16, size| {
+            let byte = u32::from(read(address));
+            match size {
+                MemorySize::Bit(bit) => (byte >> bit) & 0x01,
+                MemorySize::LowerNibble => byte & 0x0F,
+                MemorySize::UpperNibble => byte >> 4,
+                MemorySize::Byte => byte,
+                MemorySize::Word => byte | u32::from(read(address.wrapping_add(1))) << 8,
+            }
+        };
+
+        for tracker in self.trackers.iter_mut().filter(|t| !t.unlocked) {
+            let conditions = &tracker.achievement.conditions;
+
+            // Memory operands first, their previous values giving the deltas
+            let current: Vec<(u32, u32)> = conditions
+                .iter()
+                .map(|condition| {
+                    let memory = |operand| match operand {
+                        Operand::Memory(address, size) | Operand::Delta(address, size) => {
+                            read_size(address, size)
+                        }
+                        Operand::Value(value) => value,
+                    };
+                    (memory(condition.left), memory(condition.right))
+                })
+                .collect();
+            if tracker.previous.is_empty() {
+                tracker.previous = current.clone();
+            }
+            let states: Vec<bool> = conditions
+                .iter()
+                .zip(current.iter().zip(&tracker.previous))
+                .map(|(condition, (current, previous))| {
+                    let value = |operand, current, previous| match operand {
+                        Operand::Delta(..) => previous,
+                        _ => current,
+                    };
+                    condition.is_true(
+                        value(condition.left, current.0, previous.0),
+                        value(condition.right, current.1, previous.1),
+                    )
+                })
+                .collect();
+            tracker.previous = current;
+
+            let is_true = |kind| {
+                conditions
+                    .iter()
+                    .zip(&states)
+                    .any(|(condition, state)| condition.kind == kind && *state)
+            };
+            if is_true(ConditionKind::PauseIf) {
+                continue;
+            }
+            if is_true(ConditionKind::ResetIf) {
+                tracker.hits.iter_mut().for_each(|hits| *hits = 0);
+                tracker.armed = true;
+                continue;
+            }
+
+            let mut all_true = true;
+            for ((condition, state), hits) in conditions.iter().zip(&states).zip(&mut tracker.hits)
+            {
+                if condition.kind != ConditionKind::Standard {
+                    continue;
+                }
+
+                let satisfied = if condition.hit_target == 0 {
+                    *state
+                } else {
+                    if *state && *hits < condition.hit_target {
+                        *hits += 1;
+                    }
+                    *hits >= condition.hit_target
+                };
+                all_true &= satisfied;
+            }
+
+            if !all_true {
+                tracker.armed = true;
+            } else if tracker.armed {
+                tracker.unlocked = true;
+                events.push(Event::AchievementUnlocked {
+                    id: tracker.achievement.id,
+                    points: tracker.achievement.points,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+    use core::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn parses_conditions() {
+        assert_eq!(
+            Condition::parse("0xH0010=5"),
+            Ok(Condition::new(
+                Operand::Memory(0x0010, MemorySize::Byte),
+                Comparison::Equal,
+                Operand::Value(5)
+            ))
+        );
+        assert_eq!(
+            Condition::parse("R:0xN6000!=d0xN6000"),
+            Ok(Condition {
+                kind: ConditionKind::ResetIf,
+                left: Operand::Memory(0x6000, MemorySize::Bit(1)),
+                comparison: Comparison::NotEqual,
+                right: Operand::Delta(0x6000, MemorySize::Bit(1)),
+                hit_target: 0,
+            })
+        );
+        assert_eq!(
+            Condition::parse("0x 0020>=h100.3.").map(|c| (c.left, c.right, c.hit_target)),
+            Ok((
+                Operand::Memory(0x0020, MemorySize::Word),
+                Operand::Value(0x100),
+                3
+            ))
+        );
+
+        assert_eq!(
+            Condition::parse("0xH2002=0"),
+            Err(AchievementError::UnsupportedAddress(0x2002))
+        );
+        assert_eq!(
+            Condition::parse("0xH0010"),
+            Err(AchievementError::InvalidCondition)
+        );
+        assert_eq!(
+            Achievement::parse(1, "".to_string(), 5, "P:0xH0010=1"),
+            Err(AchievementError::NoCondition)
+        );
+    }
+
+    #[test]
+    fn unlocks_achievements() {
+        let ram = Cell::new([0u8; 2]);
+        let mut achievements = Achievements::default();
+        let mut events = Vec::new();
+        let mut run_frame = |values, achievements: &mut Achievements| {
+            ram.set(values);
+            achievements.evaluate(|address| ram.get()[usize::from(address)], &mut events);
+        };
+
+        // Lives going from 3 to 2 twice, with the level being 1 while they change
+        achievements.add(
+            Achievement::parse(
+                7,
+                "Clumsy".to_string(),
+                10,
+                "0xH0000<d0xH0000.2._0xH0001=1_R:0xH0001=9",
+            )
+            .unwrap(),
+        );
+        run_frame([3, 1], &mut achievements);
+        run_frame([2, 1], &mut achievements);
+        run_frame([3, 1], &mut achievements);
+        run_frame([3, 9], &mut achievements);
+        run_frame([2, 1], &mut achievements);
+        assert!(!achievements.is_unlocked(7));
+        run_frame([3, 1], &mut achievements);
+        run_frame([2, 1], &mut achievements);
+        assert!(achievements.is_unlocked(7));
+
+        // Already true when added
+        achievements.add(Achievement::parse(8, "Level 1".to_string(), 5, "0xH0001=1").unwrap());
+        run_frame([2, 1], &mut achievements);
+        assert!(!achievements.is_unlocked(8));
+        run_frame([2, 2], &mut achievements);
+        run_frame([2, 1], &mut achievements);
+        run_frame([2, 1], &mut achievements);
+        assert!(achievements.is_unlocked(8));
+
+        assert_eq!(
+            events,
+            vec![
+                Event::AchievementUnlocked { id: 7, points: 10 },
+                Event::AchievementUnlocked { id: 8, points: 5 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::irq::{IrqLine, IrqSource};
+
+/// Number of CPU cycles between each output level change (NTSC)
+/// http://wiki.nesdev.com/w/index.php/APU_DMC
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// Delta modulation channel. Plays 1-bit delta-encoded samples fetched from CPU memory.
+pub struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    rate: u16,
+    timer: u16,
+
+    // Memory reader
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    // Output unit
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+}
+
+impl_stateful!(Dmc {
+    irq_enabled,
+    looping,
+    rate,
+    timer,
+    sample_address,
+    sample_length,
+    current_address,
+    bytes_remaining,
+    sample_buffer,
+    shift_register,
+    bits_remaining,
+    silence,
+    output_level
+});
+
+impl Dmc {
+    pub fn new() -> Self {
+        Self {
+            irq_enabled: false,
+            looping: false,
+            rate: RATE_TABLE[0],
+            timer: 0,
+
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+        }
+    }
+
+    /// Writes one of the 4 registers of the channel. `addr` is the offset of the register (0 to 3).
+    pub fn write(&mut self, addr: u16, data: u8, irq_line: &mut IrqLine) {
+        match addr & 0x03 {
+            0 => {
+                // IL-- RRRR
+                self.irq_enabled = data & 0x80 == 0x80;
+                self.looping = data & 0x40 == 0x40;
+                self.rate = RATE_TABLE[(data & 0x0F) as usize];
+
+                if !self.irq_enabled {
+                    irq_line.acknowledge(IrqSource::APU_DMC);
+                }
+            }
+            1 => {
+                // Direct load
+                self.output_level = data & 0x7F;
+            }
+            2 => {
+                // Sample address = %11AAAAAA.AA000000
+                self.sample_address = 0xC000 | (u16::from(data) << 6);
+            }
+            3 => {
+                // Sample length = %LLLL.LLLL0001
+                self.sample_length = (u16::from(data) << 4) | 1;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Starts or stops the playback of the sample
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.rate - 1;
+            self.clock_output_unit();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Address the memory reader needs to fetch, if the sample buffer is empty
+    pub fn dma_address(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    /// Fills the sample buffer with the byte fetched at `dma_address()`
+    pub fn dma_fill(&mut self, data: u8, irq_line: &mut IrqLine) {
+        self.sample_buffer = Some(data);
+
+        // The address wraps around to $8000
+        self.current_address = if self.current_address == 0xFFFF {
+            0x8000
+        } else {
+            self.current_address + 1
+        };
+
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                irq_line.assert(IrqSource::APU_DMC);
+            }
+        }
+    }
+
+    /// Current output of the channel, between 0 and 127
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    fn clock_output_unit(&mut self) {
+        if !self.silence {
+            if self.shift_register & 0x01 == 0x01 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+
+        // Start a new output cycle
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irq_raised_at_end_of_sample() {
+        let mut irq_line = IrqLine::default();
+        let mut dmc = Dmc::new();
+
+        dmc.write(0, 0x80, &mut irq_line); // IRQ enabled, no loop
+        dmc.write(3, 0x01, &mut irq_line); // 17 bytes
+        dmc.set_enabled(true);
+
+        assert_eq!(dmc.dma_address(), Some(0xC000));
+        for _ in 0..17 {
+            assert!(!irq_line.is_asserted());
+            dmc.dma_fill(0, &mut irq_line);
+
+            // Consume the sample
+            dmc.sample_buffer = None;
+        }
+
+        assert_eq!(dmc.dma_address(), None);
+        assert!(irq_line.is_asserted());
+
+        // Disabling the IRQ acknowledges it
+        dmc.write(0, 0x00, &mut irq_line);
+        assert!(!irq_line.is_asserted());
+    }
+
+    #[test]
+    fn output_level_follows_deltas() {
+        let mut irq_line = IrqLine::default();
+        let mut dmc = Dmc::new();
+        dmc.write(0, 0x0F, &mut irq_line); // Fastest rate, 54 cycles
+        dmc.write(1, 0x40, &mut irq_line);
+        dmc.write(3, 0x00, &mut irq_line); // 1 byte
+        dmc.set_enabled(true);
+        dmc.dma_fill(0xFF, &mut irq_line);
+
+        // Wait for the current (silent) output cycle to end so the sample gets loaded
+        for _ in 0..(8 * 54) {
+            dmc.clock();
+        }
+        assert_eq!(dmc.output(), 0x40);
+
+        for _ in 0..(8 * 54) {
+            dmc.clock();
+        }
+        assert_eq!(dmc.output(), 0x40 + 16);
+    }
+}
@@ -0,0 +1,60 @@
+/// Volume envelope shared by the pulse and noise channels
+/// http://wiki.nesdev.com/w/index.php/APU_Envelope
+#[derive(Default)]
+pub struct Envelope {
+    start: bool,
+    looping: bool,
+    constant_volume: bool,
+    volume: u8, // Also used as the divider period
+    divider: u8,
+    decay_level: u8,
+}
+
+impl_stateful!(Envelope {
+    start,
+    looping,
+    constant_volume,
+    volume,
+    divider,
+    decay_level
+});
+
+impl Envelope {
+    /// Writes the `--LC VVVV` bits of the channel's first register
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0x20 == 0x20;
+        self.constant_volume = data & 0x10 == 0x10;
+        self.volume = data & 0x0F;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    /// Clocked by the quarter frame signal of the frame counter
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay_level = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+
+            if self.decay_level > 0 {
+                self.decay_level -= 1;
+            } else if self.looping {
+                self.decay_level = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay_level
+        }
+    }
+}
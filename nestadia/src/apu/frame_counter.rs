@@ -0,0 +1,169 @@
+use crate::irq::{IrqLine, IrqSource};
+
+// CPU cycles at which the sequencer steps happen (NTSC)
+// http://wiki.nesdev.com/w/index.php/APU_Frame_Counter
+const STEP_1: u32 = 7457;
+const STEP_2: u32 = 14913;
+const STEP_3: u32 = 22371;
+const STEP_4: u32 = 29829;
+const STEP_5: u32 = 37281;
+const FOUR_STEP_LENGTH: u32 = 29830;
+const FIVE_STEP_LENGTH: u32 = 37282;
+
+/// Signal sent by the frame counter to the channels' units
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameSignal {
+    None,
+
+    /// Clocks the envelopes and the triangle's linear counter
+    QuarterFrame,
+
+    /// Clocks the length counters and the sweep units, in addition to the quarter frame units
+    HalfFrame,
+}
+
+pub struct FrameCounter {
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    cycle_count: u32,
+
+    // Writes to $4017 reset the sequencer after a 3 or 4 cycles delay
+    reset_delay: u8,
+}
+
+impl_stateful!(FrameCounter {
+    five_step_mode,
+    irq_inhibit,
+    cycle_count,
+    reset_delay
+});
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        Self {
+            five_step_mode: false,
+            irq_inhibit: false,
+            cycle_count: 0,
+            reset_delay: 0,
+        }
+    }
+
+    /// Writes the `MI-- ----` bits of $4017
+    pub fn write(&mut self, data: u8, irq_line: &mut IrqLine) -> FrameSignal {
+        self.five_step_mode = data & 0x80 == 0x80;
+        self.irq_inhibit = data & 0x40 == 0x40;
+
+        if self.irq_inhibit {
+            irq_line.acknowledge(IrqSource::APU_FRAME_COUNTER);
+        }
+
+        // The delay depends on whether the write happened during an APU cycle or between two
+        self.reset_delay = if self.cycle_count & 0x01 == 0x01 {
+            4
+        } else {
+            3
+        };
+
+        // Entering 5-step mode immediately clocks all the units
+        if self.five_step_mode {
+            FrameSignal::HalfFrame
+        } else {
+            FrameSignal::None
+        }
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self, irq_line: &mut IrqLine) -> FrameSignal {
+        if self.reset_delay > 0 {
+            self.reset_delay -= 1;
+
+            if self.reset_delay == 0 {
+                self.cycle_count = 0;
+                return FrameSignal::None;
+            }
+        }
+
+        self.cycle_count += 1;
+
+        if self.five_step_mode {
+            match self.cycle_count {
+                STEP_1 | STEP_3 => FrameSignal::QuarterFrame,
+                STEP_2 | STEP_5 => FrameSignal::HalfFrame,
+                FIVE_STEP_LENGTH => {
+                    self.cycle_count = 0;
+                    FrameSignal::None
+                }
+                _ => FrameSignal::None,
+            }
+        } else {
+            // The frame IRQ flag is set during the 3 last cycles of the sequence
+            if self.cycle_count >= STEP_4 - 1 && !self.irq_inhibit {
+                irq_line.assert(IrqSource::APU_FRAME_COUNTER);
+            }
+
+            match self.cycle_count {
+                STEP_1 | STEP_3 => FrameSignal::QuarterFrame,
+                STEP_2 | STEP_4 => FrameSignal::HalfFrame,
+                FOUR_STEP_LENGTH => {
+                    self.cycle_count = 0;
+                    FrameSignal::None
+                }
+                _ => FrameSignal::None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_sequence(frame_counter: &mut FrameCounter, irq_line: &mut IrqLine) -> (u32, u32) {
+        let mut quarter_frames = 0;
+        let mut half_frames = 0;
+
+        // Also covers the delay before a write takes effect
+        for _ in 0..(FIVE_STEP_LENGTH + 4) {
+            match frame_counter.clock(irq_line) {
+                FrameSignal::QuarterFrame => quarter_frames += 1,
+                FrameSignal::HalfFrame => half_frames += 1,
+                FrameSignal::None => {}
+            }
+        }
+
+        (quarter_frames, half_frames)
+    }
+
+    #[test]
+    fn four_step_mode_raises_irq() {
+        let mut irq_line = IrqLine::default();
+        let mut frame_counter = FrameCounter::new();
+
+        for _ in 0..(STEP_4 - 2) {
+            frame_counter.clock(&mut irq_line);
+        }
+        assert!(!irq_line.is_asserted());
+
+        frame_counter.clock(&mut irq_line);
+        assert!(irq_line.is_asserted());
+
+        // Setting the inhibit flag clears the IRQ
+        frame_counter.write(0x40, &mut irq_line);
+        assert!(!irq_line.is_asserted());
+    }
+
+    #[test]
+    fn five_step_mode_has_no_irq() {
+        let mut irq_line = IrqLine::default();
+        let mut frame_counter = FrameCounter::new();
+
+        assert_eq!(
+            frame_counter.write(0x80, &mut irq_line),
+            FrameSignal::HalfFrame
+        );
+
+        let (quarter_frames, half_frames) = run_sequence(&mut frame_counter, &mut irq_line);
+        assert_eq!((quarter_frames, half_frames), (2, 2));
+        assert!(!irq_line.is_asserted());
+    }
+}
@@ -0,0 +1,54 @@
+/// Nonlinear mixing of the channels, using the lookup table approximation of the DAC
+/// http://wiki.nesdev.com/w/index.php/APU_Mixer#Lookup_Table
+pub struct Mixer {
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        let mut pulse_table = [0f32; 31];
+        for (n, value) in pulse_table.iter_mut().enumerate().skip(1) {
+            *value = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+
+        let mut tnd_table = [0f32; 203];
+        for (n, value) in tnd_table.iter_mut().enumerate().skip(1) {
+            *value = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+
+        Self {
+            pulse_table,
+            tnd_table,
+        }
+    }
+
+    /// Output level of the APU, between 0.0 and 1.0.
+    /// Pulses, triangle and noise range from 0 to 15, DMC from 0 to 127.
+    pub fn mix(&self, pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+        let pulse_out = self.pulse_table[(pulse1 + pulse2) as usize];
+        let tnd_out = self.tnd_table[(3 * triangle + 2 * noise + dmc) as usize];
+
+        pulse_out + tnd_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_nonlinear() {
+        let mixer = Mixer::new();
+
+        assert_eq!(mixer.mix(0, 0, 0, 0, 0), 0.0);
+
+        // Two pulses at full volume are quieter than twice a single one
+        let single = mixer.mix(15, 0, 0, 0, 0);
+        let both = mixer.mix(15, 15, 0, 0, 0);
+        assert!(both < 2.0 * single);
+
+        let max = mixer.mix(15, 15, 15, 15, 127);
+        assert!(max > 0.99 && max <= 1.0);
+    }
+}
@@ -0,0 +1,357 @@
+mod dmc;
+mod envelope;
+mod frame_counter;
+mod length_counter;
+mod mixer;
+mod pulse;
+mod register_log;
+
+use bitflags::bitflags;
+
+use dmc::Dmc;
+use frame_counter::{FrameCounter, FrameSignal};
+use mixer::Mixer;
+use pulse::{Pulse, PulseChannel};
+
+pub use register_log::{ApuRegisterLog, ApuRegisterWrite};
+
+use crate::irq::{IrqLine, IrqSource};
+
+bitflags! {
+    /// http://wiki.nesdev.com/w/index.php/APU#Status_.28.244015.29
+    struct StatusReg: u8 {
+        /// Pulse 1 length counter enable / is non-zero
+        const PULSE1 = 0b00000001;
+
+        /// Pulse 2 length counter enable / is non-zero
+        const PULSE2 = 0b00000010;
+
+        /// Triangle length counter enable / is non-zero
+        const TRIANGLE = 0b00000100;
+
+        /// Noise length counter enable / is non-zero
+        const NOISE = 0b00001000;
+
+        /// DMC enable / has bytes remaining
+        const DMC = 0b00010000;
+
+        /// Frame interrupt flag (read only)
+        const FRAME_INTERRUPT = 0b01000000;
+
+        /// DMC interrupt flag (read only)
+        const DMC_INTERRUPT = 0b10000000;
+    }
+}
+
+/// Sound channel, for muting
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioChannel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+
+    /// Channel of the expansion audio chip of the cartridge, starting at 0
+    Expansion(u8),
+}
+
+pub struct Apu {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    dmc: Dmc,
+    frame_counter: FrameCounter,
+    mixer: Mixer,
+
+    /// One bit per channel, in the order of the status register
+    muted_channels: u8,
+
+    register_log: Option<ApuRegisterLog>,
+
+    // Emulation-specific internal stuff
+    cycle_count: u32,
+}
+
+impl_stateful!(Apu {
+    pulse1,
+    pulse2,
+    dmc,
+    frame_counter,
+    cycle_count
+});
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Pulse::new(PulseChannel::Pulse1),
+            pulse2: Pulse::new(PulseChannel::Pulse2),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::new(),
+            mixer: Mixer::new(),
+
+            muted_channels: 0,
+
+            register_log: None,
+
+            cycle_count: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        // Muting and logging are settings of the emulator rather than a state of the APU
+        let muted_channels = self.muted_channels;
+        let register_log = self.register_log.take();
+        *self = Default::default();
+        self.muted_channels = muted_channels;
+        self.register_log = register_log;
+    }
+
+    /// Reset signal while running: the channels are silenced as if $4015 was cleared, and the
+    /// rest is kept
+    pub fn soft_reset(&mut self, irq_line: &mut IrqLine) {
+        self.write(0x4015, 0x00, irq_line);
+    }
+
+    /// Starts logging the writes to the audio registers, dropping the ongoing log if any
+    pub fn start_register_log(&mut self) {
+        self.register_log = Some(Default::default());
+    }
+
+    pub fn stop_register_log(&mut self) -> Option<ApuRegisterLog> {
+        self.register_log.take()
+    }
+
+    /// Logs a write to the expansion audio chip of the cartridge
+    pub fn log_expansion_write(&mut self, addr: u16, data: u8) {
+        if let Some(register_log) = &mut self.register_log {
+            register_log.record(addr, data);
+        }
+    }
+
+    /// Must be called when the PPU finishes a frame, to timestamp the logged writes
+    pub fn end_frame(&mut self) {
+        if let Some(register_log) = &mut self.register_log {
+            register_log.end_frame();
+        }
+    }
+
+    /// Mutes or unmutes one of the APU channels. Expansion channels are ignored.
+    pub fn set_channel_muted(&mut self, channel: AudioChannel, muted: bool) {
+        if let Some(mask) = Self::channel_mask(channel) {
+            if muted {
+                self.muted_channels |= mask.bits();
+            } else {
+                self.muted_channels &= !mask.bits();
+            }
+        }
+    }
+
+    pub fn is_channel_muted(&self, channel: AudioChannel) -> bool {
+        match Self::channel_mask(channel) {
+            Some(mask) => self.muted_channels & mask.bits() != 0,
+            None => false,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8, irq_line: &mut IrqLine) {
+        if let Some(register_log) = &mut self.register_log {
+            register_log.record(addr, data);
+        }
+
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write(addr, data),
+            0x4004..=0x4007 => self.pulse2.write(addr, data),
+            0x4010..=0x4013 => self.dmc.write(addr, data, irq_line),
+            0x4015 => {
+                let enabled = StatusReg::from_bits_truncate(data);
+
+                self.pulse1
+                    .length_counter
+                    .set_enabled(enabled.contains(StatusReg::PULSE1));
+                self.pulse2
+                    .length_counter
+                    .set_enabled(enabled.contains(StatusReg::PULSE2));
+                self.dmc.set_enabled(enabled.contains(StatusReg::DMC));
+
+                // Writing to this register clears the DMC interrupt flag
+                irq_line.acknowledge(IrqSource::APU_DMC);
+            }
+            0x4017 => {
+                let signal = self.frame_counter.write(data, irq_line);
+                self.dispatch_frame_signal(signal);
+            }
+            _ => (), // TODO: Other channels
+        }
+    }
+
+    /// Reads the status register at $4015. This clears the frame interrupt flag.
+    pub fn read_status(&mut self, irq_line: &mut IrqLine) -> u8 {
+        let status = self.peek_status(irq_line);
+        irq_line.acknowledge(IrqSource::APU_FRAME_COUNTER);
+        status
+    }
+
+    /// Reads the status like `read_status`, without acknowledging the frame interrupt
+    pub fn peek_status(&self, irq_line: &IrqLine) -> u8 {
+        let mut status = StatusReg::empty();
+
+        status.set(StatusReg::PULSE1, self.pulse1.length_counter.is_active());
+        status.set(StatusReg::PULSE2, self.pulse2.length_counter.is_active());
+        status.set(StatusReg::DMC, self.dmc.is_active());
+        status.set(
+            StatusReg::FRAME_INTERRUPT,
+            irq_line.is_asserted_by(IrqSource::APU_FRAME_COUNTER),
+        );
+        status.set(
+            StatusReg::DMC_INTERRUPT,
+            irq_line.is_asserted_by(IrqSource::APU_DMC),
+        );
+
+        status.bits()
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self, irq_line: &mut IrqLine) {
+        // Pulse timers are clocked every APU cycle, which is every second CPU cycle
+        if self.cycle_count & 0x01 == 1 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        self.dmc.clock();
+
+        let signal = self.frame_counter.clock(irq_line);
+        self.dispatch_frame_signal(signal);
+
+        if let Some(register_log) = &mut self.register_log {
+            register_log.clock();
+        }
+
+        self.cycle_count = self.cycle_count.wrapping_add(1);
+    }
+
+    /// Address the DMC memory reader needs to fetch from CPU memory, if any
+    pub fn dmc_dma_address(&self) -> Option<u16> {
+        self.dmc.dma_address()
+    }
+
+    /// Gives the DMC the byte it requested through `dmc_dma_address()`
+    pub fn dmc_dma_fill(&mut self, data: u8, irq_line: &mut IrqLine) {
+        self.dmc.dma_fill(data, irq_line);
+    }
+
+    /// Current output level of the APU, between 0.0 and 1.0
+    pub fn output(&self) -> f32 {
+        let muted = StatusReg::from_bits_truncate(self.muted_channels);
+        let unless_muted = |channel, output| {
+            if muted.contains(channel) {
+                0
+            } else {
+                output
+            }
+        };
+
+        // TODO: Triangle and noise channels
+        self.mixer.mix(
+            unless_muted(StatusReg::PULSE1, self.pulse1.output()),
+            unless_muted(StatusReg::PULSE2, self.pulse2.output()),
+            0,
+            0,
+            unless_muted(StatusReg::DMC, self.dmc.output()),
+        )
+    }
+
+    fn channel_mask(channel: AudioChannel) -> Option<StatusReg> {
+        match channel {
+            AudioChannel::Pulse1 => Some(StatusReg::PULSE1),
+            AudioChannel::Pulse2 => Some(StatusReg::PULSE2),
+            AudioChannel::Triangle => Some(StatusReg::TRIANGLE),
+            AudioChannel::Noise => Some(StatusReg::NOISE),
+            AudioChannel::Dmc => Some(StatusReg::DMC),
+            AudioChannel::Expansion(_) => None,
+        }
+    }
+
+    fn dispatch_frame_signal(&mut self, signal: FrameSignal) {
+        match signal {
+            FrameSignal::QuarterFrame => self.clock_quarter_frame(),
+            FrameSignal::HalfFrame => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            FrameSignal::None => {}
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_half_frame();
+        self.pulse2.clock_half_frame();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_reports_length_counters() {
+        let mut irq_line = IrqLine::default();
+        let mut apu = Apu::new();
+
+        // Length counters can't be loaded while disabled
+        apu.write(0x4003, 0x08, &mut irq_line);
+        assert_eq!(apu.read_status(&mut irq_line), 0x00);
+
+        apu.write(0x4015, 0x02, &mut irq_line);
+        apu.write(0x4003, 0x08, &mut irq_line);
+        apu.write(0x4007, 0x08, &mut irq_line);
+        assert_eq!(apu.read_status(&mut irq_line), 0x02);
+
+        apu.write(0x4015, 0x00, &mut irq_line);
+        assert_eq!(apu.read_status(&mut irq_line), 0x00);
+    }
+
+    #[test]
+    fn status_read_clears_frame_interrupt() {
+        let mut irq_line = IrqLine::default();
+        let mut apu = Apu::new();
+        irq_line.assert(IrqSource::APU_FRAME_COUNTER | IrqSource::APU_DMC);
+
+        assert_eq!(apu.read_status(&mut irq_line), 0xC0);
+        assert_eq!(apu.read_status(&mut irq_line), 0x80);
+
+        // Writing the register clears the DMC interrupt
+        apu.write(0x4015, 0x00, &mut irq_line);
+        assert_eq!(apu.read_status(&mut irq_line), 0x00);
+    }
+
+    #[test]
+    fn muted_channels_are_silent() {
+        let mut irq_line = IrqLine::default();
+        let mut apu = Apu::new();
+        apu.write(0x4011, 0x40, &mut irq_line); // DMC direct load
+        let output = apu.output();
+        assert!(output > 0.0);
+
+        apu.set_channel_muted(AudioChannel::Dmc, true);
+        assert_eq!(apu.output(), 0.0);
+
+        // Muting survives a reset
+        apu.reset();
+        assert!(apu.is_channel_muted(AudioChannel::Dmc));
+        apu.set_channel_muted(AudioChannel::Dmc, false);
+        apu.write(0x4011, 0x40, &mut irq_line);
+        assert_eq!(apu.output(), output);
+    }
+}
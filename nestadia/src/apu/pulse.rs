@@ -0,0 +1,249 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+
+/// http://wiki.nesdev.com/w/index.php/APU_Pulse
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+/// Both pulse channels are identical, except for the way their sweep unit negates the period.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PulseChannel {
+    /// Pulse 1 adds the ones' complement of the change amount
+    Pulse1,
+    /// Pulse 2 adds the two's complement of the change amount
+    Pulse2,
+}
+
+/// http://wiki.nesdev.com/w/index.php/APU_Sweep
+struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    divider: u8,
+    reload: bool,
+}
+
+impl_stateful!(Sweep {
+    enabled,
+    period,
+    negate,
+    shift,
+    divider,
+    reload
+});
+
+impl Sweep {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            period: 0,
+            negate: false,
+            shift: 0,
+            divider: 0,
+            reload: false,
+        }
+    }
+
+    fn write(&mut self, data: u8) {
+        self.enabled = data & 0x80 == 0x80;
+        self.period = (data >> 4) & 0x07;
+        self.negate = data & 0x08 == 0x08;
+        self.shift = data & 0x07;
+        self.reload = true;
+    }
+}
+
+pub struct Pulse {
+    channel: PulseChannel,
+    envelope: Envelope,
+    sweep: Sweep,
+    pub length_counter: LengthCounter,
+
+    duty: u8,
+    sequencer_step: u8,
+    timer_period: u16,
+    timer: u16,
+}
+
+impl_stateful!(Pulse {
+    envelope,
+    sweep,
+    length_counter,
+    duty,
+    sequencer_step,
+    timer_period,
+    timer
+});
+
+impl Pulse {
+    pub fn new(channel: PulseChannel) -> Self {
+        Self {
+            channel,
+            envelope: Default::default(),
+            sweep: Sweep::new(),
+            length_counter: Default::default(),
+
+            duty: 0,
+            sequencer_step: 0,
+            timer_period: 0,
+            timer: 0,
+        }
+    }
+
+    /// Writes one of the 4 registers of the channel. `addr` is the offset of the register (0 to 3).
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr & 0x03 {
+            0 => {
+                // DDLC VVVV
+                self.duty = data >> 6;
+                self.length_counter.set_halted(data & 0x20 == 0x20);
+                self.envelope.write(data);
+            }
+            1 => {
+                // EPPP NSSS
+                self.sweep.write(data);
+            }
+            2 => {
+                // Timer low
+                self.timer_period = (self.timer_period & 0x0700) | u16::from(data);
+            }
+            3 => {
+                // LLLL LHHH
+                self.timer_period = (self.timer_period & 0x00FF) | (u16::from(data & 0x07) << 8);
+                self.length_counter.load(data >> 3);
+
+                // Side effects: the sequencer and the envelope are restarted
+                self.sequencer_step = 0;
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocks the timer. Must be called once per APU cycle (every 2 CPU cycles).
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequencer_step = (self.sequencer_step + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocked by the quarter frame signal of the frame counter
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Clocked by the half frame signal of the frame counter
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+
+        // Sweep unit
+        if self.sweep.divider == 0
+            && self.sweep.enabled
+            && self.sweep.shift > 0
+            && !self.is_sweep_muting()
+        {
+            self.timer_period = self.sweep_target_period();
+        }
+
+        if self.sweep.divider == 0 || self.sweep.reload {
+            self.sweep.divider = self.sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    /// Current output of the channel, between 0 and 15
+    pub fn output(&self) -> u8 {
+        if self.is_sweep_muting()
+            || !self.length_counter.is_active()
+            || DUTY_TABLE[self.duty as usize][self.sequencer_step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+
+    fn sweep_target_period(&self) -> u16 {
+        let change = self.timer_period >> self.sweep.shift;
+
+        if self.sweep.negate {
+            match self.channel {
+                PulseChannel::Pulse1 => self.timer_period.wrapping_sub(change + 1),
+                PulseChannel::Pulse2 => self.timer_period.wrapping_sub(change),
+            }
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    /// The sweep unit mutes the channel even when disabled if the period is out of range
+    fn is_sweep_muting(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target_period() > 0x7FF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pulse_with_period(channel: PulseChannel, period: u16) -> Pulse {
+        let mut pulse = Pulse::new(channel);
+        pulse.length_counter.set_enabled(true);
+        pulse.write(0, 0xBF); // 50% duty, halted length counter, constant volume 15
+        pulse.write(2, (period & 0xFF) as u8);
+        pulse.write(3, (period >> 8) as u8);
+        pulse
+    }
+
+    #[test]
+    fn sweep_negate_differs_between_channels() {
+        let mut pulse1 = pulse_with_period(PulseChannel::Pulse1, 0x100);
+        let mut pulse2 = pulse_with_period(PulseChannel::Pulse2, 0x100);
+
+        // Enabled, period 0, negate, shift 1
+        pulse1.write(1, 0x89);
+        pulse2.write(1, 0x89);
+
+        // The divider starts at 0, so the first half frame updates the period
+        pulse1.clock_half_frame();
+        pulse2.clock_half_frame();
+
+        assert_eq!(pulse1.timer_period, 0x7F);
+        assert_eq!(pulse2.timer_period, 0x80);
+    }
+
+    #[test]
+    fn low_period_mutes_channel() {
+        let mut pulse = pulse_with_period(PulseChannel::Pulse1, 7);
+
+        for _ in 0..16 {
+            pulse.clock_timer();
+            assert_eq!(pulse.output(), 0);
+        }
+    }
+
+    #[test]
+    fn length_counter_silences_channel() {
+        let mut pulse = pulse_with_period(PulseChannel::Pulse2, 0x100);
+        pulse.write(0, 0x9F); // Not halted anymore
+        pulse.write(3, 0x19); // Length index 3 => 2 half frames
+
+        // Move the sequencer to the first high step of the duty cycle
+        pulse.clock_timer();
+        assert_eq!(pulse.output(), 15);
+
+        pulse.clock_half_frame();
+        pulse.clock_half_frame();
+        assert_eq!(pulse.output(), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+
+/// A write to a register of the APU or of the expansion audio chip of the cartridge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApuRegisterWrite {
+    /// Number of frames since the log was started
+    pub frame: u32,
+
+    /// Number of CPU cycles since the start of the frame
+    pub cycle: u32,
+
+    pub addr: u16,
+    pub data: u8,
+}
+
+/// Log of the writes to the audio registers, which is enough to replay the music of a game
+#[derive(Debug, Default, Clone)]
+pub struct ApuRegisterLog {
+    writes: Vec<ApuRegisterWrite>,
+    frame: u32,
+    cycle: u32,
+}
+
+impl ApuRegisterLog {
+    pub fn writes(&self) -> &[ApuRegisterWrite] {
+        &self.writes
+    }
+
+    /// Exports the log as text, with one write per line: `frame cycle $address $data`
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# frame cycle address data\n");
+
+        for write in self.writes.iter() {
+            // Writing to a String can't fail
+            let _ = writeln!(
+                text,
+                "{} {} ${:04X} ${:02X}",
+                write.frame, write.cycle, write.addr, write.data
+            );
+        }
+
+        text
+    }
+
+    pub(super) fn record(&mut self, addr: u16, data: u8) {
+        self.writes.push(ApuRegisterWrite {
+            frame: self.frame,
+            cycle: self.cycle,
+            addr,
+            data,
+        });
+    }
+
+    /// Must be called once per CPU cycle
+    pub(super) fn clock(&mut self) {
+        self.cycle += 1;
+    }
+
+    pub(super) fn end_frame(&mut self) {
+        self.frame += 1;
+        self.cycle = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_are_timestamped() {
+        let mut log = ApuRegisterLog::default();
+        log.clock();
+        log.record(0x4000, 0xBF);
+        log.end_frame();
+        log.clock();
+        log.clock();
+        log.record(0x4015, 0x0F);
+
+        assert_eq!(
+            log.to_text(),
+            "# frame cycle address data\n0 1 $4000 $BF\n1 2 $4015 $0F\n"
+        );
+    }
+}
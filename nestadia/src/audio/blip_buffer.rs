@@ -0,0 +1,174 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::f64::consts::PI;
+
+// Band-limited step synthesis, in the style of Blargg's blip_buf.
+// Instead of sampling the APU output at the output rate (which aliases every transition that
+// doesn't fall exactly on a sample), every change of the output is recorded as a delta and
+// spread over a few output samples using a windowed sinc. The output is then rebuilt by
+// integrating the deltas.
+// http://slack.net/~ant/bl-synth/
+
+/// Precision of the sub-sample position of a delta
+const PHASE_BITS: u32 = 5;
+const PHASE_COUNT: usize = 1 << PHASE_BITS;
+
+/// Number of output samples a delta is spread over
+const KERNEL_WIDTH: usize = 16;
+
+/// The kernel taps of a phase add up to 1 << KERNEL_BITS
+const KERNEL_BITS: u32 = 15;
+
+/// Fixed point precision of the time, in output samples
+const TIME_BITS: u32 = 32;
+
+/// Fraction of the output Nyquist frequency that is kept
+const CUTOFF: f64 = 0.9;
+
+pub struct BlipBuffer {
+    /// Output samples per clock, in fixed point
+    factor: u64,
+
+    /// Position of the start of the current frame, in fixed point output samples
+    offset: u64,
+
+    integrator: i32,
+    buffer: Vec<i32>,
+    kernel: [[i32; KERNEL_WIDTH]; PHASE_COUNT],
+}
+
+impl BlipBuffer {
+    /// Creates a buffer converting from `clock_rate` to `sample_rate`.
+    /// Frames can't be longer than `max_frame_clocks` clocks.
+    pub fn new(clock_rate: u32, sample_rate: u32, max_frame_clocks: u32) -> Self {
+        let factor = (u64::from(sample_rate) << TIME_BITS) / u64::from(clock_rate);
+        let max_samples = ((u64::from(max_frame_clocks) * factor) >> TIME_BITS) as usize + 1;
+
+        Self {
+            factor,
+            offset: 0,
+
+            integrator: 0,
+            buffer: vec![0; max_samples + KERNEL_WIDTH],
+            kernel: build_kernel(),
+        }
+    }
+
+    /// Adds a change of amplitude at `time` clocks after the start of the current frame
+    pub fn add_delta(&mut self, time: u32, delta: i32) {
+        let position = self.offset + u64::from(time) * self.factor;
+        let index = (position >> TIME_BITS) as usize;
+        let phase = ((position >> (TIME_BITS - PHASE_BITS)) as usize) & (PHASE_COUNT - 1);
+
+        for (sample, tap) in self.buffer[index..index + KERNEL_WIDTH]
+            .iter_mut()
+            .zip(self.kernel[phase].iter())
+        {
+            *sample += delta * tap;
+        }
+    }
+
+    /// Ends the current frame after `clocks` clocks, making its samples available
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += u64::from(clocks) * self.factor;
+    }
+
+    pub fn samples_available(&self) -> usize {
+        (self.offset >> TIME_BITS) as usize
+    }
+
+    /// Removes all the available samples and gives them to `output`, in order
+    pub fn read_samples(&mut self, mut output: impl FnMut(i16)) {
+        let count = self.samples_available();
+
+        for delta in &self.buffer[..count] {
+            self.integrator += delta;
+
+            let sample =
+                (self.integrator >> KERNEL_BITS).clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+            output(sample as i16);
+        }
+
+        // Keep the tails of the kernels that spill past the read samples
+        self.buffer.copy_within(count..count + KERNEL_WIDTH, 0);
+        for sample in &mut self.buffer[KERNEL_WIDTH..] {
+            *sample = 0;
+        }
+
+        self.offset -= (count as u64) << TIME_BITS;
+    }
+}
+
+/// Builds the impulse response of the low-pass filter for every phase.
+/// Integrating it gives the band-limited step.
+fn build_kernel() -> [[i32; KERNEL_WIDTH]; PHASE_COUNT] {
+    let mut kernel = [[0; KERNEL_WIDTH]; PHASE_COUNT];
+    let half_width = (KERNEL_WIDTH / 2) as f64;
+
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let fraction = phase as f64 / PHASE_COUNT as f64;
+
+        // Blackman-windowed sinc centered on the sub-sample position of the delta
+        let mut impulse = [0f64; KERNEL_WIDTH];
+        for (i, value) in impulse.iter_mut().enumerate() {
+            let x = i as f64 - half_width + 1.0 - fraction;
+
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                libm::sin(PI * CUTOFF * x) / (PI * CUTOFF * x)
+            };
+
+            let t = x / half_width;
+            let window = 0.42 + 0.5 * libm::cos(PI * t) + 0.08 * libm::cos(2.0 * PI * t);
+
+            *value = sinc * window;
+        }
+
+        // Normalize so a delta is fully added once integrated, without rounding errors
+        let sum: f64 = impulse.iter().sum();
+        let unit = f64::from(1 << KERNEL_BITS);
+
+        for (tap, value) in taps.iter_mut().zip(impulse.iter()) {
+            *tap = libm::round(value / sum * unit) as i32;
+        }
+
+        let error = (1 << KERNEL_BITS) - taps.iter().sum::<i32>();
+        taps[KERNEL_WIDTH / 2 - 1] += error;
+    }
+
+    kernel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_phases_are_normalized() {
+        for taps in build_kernel().iter() {
+            assert_eq!(taps.iter().sum::<i32>(), 1 << KERNEL_BITS);
+        }
+    }
+
+    #[test]
+    fn step_settles_to_amplitude() {
+        let mut blip = BlipBuffer::new(1_789_773, 44100, 4096);
+        blip.add_delta(100, 10000);
+        blip.end_frame(4096);
+
+        assert_eq!(blip.samples_available(), 100);
+
+        let mut samples = Vec::new();
+        blip.read_samples(|sample| samples.push(sample));
+
+        assert_eq!(samples[0], 0);
+        assert_eq!(*samples.last().unwrap(), 10000);
+
+        // The ringing around the step stays bounded
+        assert!(samples
+            .iter()
+            .all(|&sample| sample > -1500 && sample < 11500));
+    }
+}
@@ -0,0 +1,122 @@
+use core::f32::consts::PI;
+
+// The console doesn't output the mix as is: the path to the TV goes through a chain of
+// first-order filters, which removes the DC offset and softens the harsh edges of the squares.
+// http://wiki.nesdev.com/w/index.php/APU_Mixer
+
+/// First-order high-pass filter
+struct HighPass {
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl HighPass {
+    fn new(sample_rate: u32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+
+        Self {
+            alpha: rc / (rc + dt),
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.alpha * (self.previous_output + input - self.previous_input);
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+/// First-order low-pass filter
+struct LowPass {
+    alpha: f32,
+    previous_output: f32,
+}
+
+impl LowPass {
+    fn new(sample_rate: u32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+
+        Self {
+            alpha: dt / (rc + dt),
+            previous_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.previous_output += self.alpha * (input - self.previous_output);
+        self.previous_output
+    }
+}
+
+/// High-pass at 90Hz, high-pass at 440Hz, then low-pass at 14kHz
+pub struct FilterChain {
+    enabled: bool,
+    high_pass_90: HighPass,
+    high_pass_440: HighPass,
+    low_pass_14000: LowPass,
+}
+
+impl FilterChain {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            enabled: true,
+            high_pass_90: HighPass::new(sample_rate, 90.0),
+            high_pass_440: HighPass::new(sample_rate, 440.0),
+            low_pass_14000: LowPass::new(sample_rate, 14000.0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn process(&mut self, sample: i16) -> i16 {
+        // Keep the filters running even when disabled so toggling doesn't pop
+        let input = f32::from(sample);
+        let output = self
+            .low_pass_14000
+            .process(self.high_pass_440.process(self.high_pass_90.process(input)));
+
+        if self.enabled {
+            libm::roundf(output.clamp(f32::from(i16::MIN), f32::from(i16::MAX))) as i16
+        } else {
+            sample
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_dc_offset() {
+        let mut filters = FilterChain::new(96000);
+
+        let mut output = 0;
+        for _ in 0..96000 {
+            output = filters.process(10000);
+        }
+
+        assert_eq!(output, 0);
+    }
+
+    #[test]
+    fn disabled_is_passthrough() {
+        let mut filters = FilterChain::new(96000);
+        filters.set_enabled(false);
+
+        assert_eq!(filters.process(10000), 10000);
+        assert_eq!(filters.process(-1234), -1234);
+    }
+}
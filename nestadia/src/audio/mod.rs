@@ -0,0 +1,248 @@
+mod blip_buffer;
+mod filter;
+mod rate_control;
+mod resampler;
+mod wav;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use blip_buffer::BlipBuffer;
+use filter::FilterChain;
+use resampler::Resampler;
+use wav::WavRecorder;
+
+pub use rate_control::DynamicRateControl;
+pub use resampler::ResamplerKind;
+
+use crate::CPU_FREQUENCY;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Sample rate at which the APU output is synthesized, before being resampled to the output rate
+const SYNTHESIS_SAMPLE_RATE: u32 = 96000;
+
+/// Number of CPU cycles after which the synthesized samples are made available
+const FRAME_CYCLES: u32 = 1024;
+
+/// Converts the APU output, produced at the CPU frequency, to samples at the output sample rate
+/// and keeps them until the frontend drains them.
+pub struct AudioOutput {
+    sample_rate: u32,
+    samples: VecDeque<i16>,
+
+    blip: BlipBuffer,
+    amplitude: i32,
+    frame_cycle: u32,
+
+    filters: FilterChain,
+    resampler: Resampler,
+    rate_control: Option<DynamicRateControl>,
+    rate_adjustment: f64,
+    speed: u32,
+
+    recorder: Option<WavRecorder>,
+}
+
+impl Default for AudioOutput {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl AudioOutput {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: VecDeque::with_capacity(Self::max_buffered_samples(sample_rate)),
+
+            blip: BlipBuffer::new(CPU_FREQUENCY, SYNTHESIS_SAMPLE_RATE, FRAME_CYCLES),
+            amplitude: 0,
+            frame_cycle: 0,
+
+            filters: FilterChain::new(SYNTHESIS_SAMPLE_RATE),
+            resampler: Resampler::new(ResamplerKind::default(), SYNTHESIS_SAMPLE_RATE, sample_rate),
+            rate_control: None,
+            rate_adjustment: 1.0,
+            speed: 1,
+
+            recorder: None,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Changes the output sample rate. Samples that were not drained yet are discarded.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.resampler = self.build_resampler(self.resampler.kind());
+        self.samples.clear();
+    }
+
+    pub fn resampler_kind(&self) -> ResamplerKind {
+        self.resampler.kind()
+    }
+
+    pub fn set_resampler_kind(&mut self, kind: ResamplerKind) {
+        self.resampler = self.build_resampler(kind);
+    }
+
+    /// Squeezes the audio of `speed` frames into the duration of one, while fast-forwarding.
+    /// The audio plays faster and higher pitched, but stays in sync with the rendered frames.
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed.max(1);
+        self.resampler = self.build_resampler(self.resampler.kind());
+    }
+
+    fn build_resampler(&self, kind: ResamplerKind) -> Resampler {
+        // Sped up audio is resampled as if it was synthesized at a higher rate
+        let input_rate = SYNTHESIS_SAMPLE_RATE.saturating_mul(self.speed);
+        let mut resampler = Resampler::new(kind, input_rate, self.sample_rate);
+        resampler.set_rate_adjustment(self.rate_adjustment);
+        resampler
+    }
+
+    pub fn filters_enabled(&self) -> bool {
+        self.filters.is_enabled()
+    }
+
+    pub fn set_filters_enabled(&mut self, enabled: bool) {
+        self.filters.set_enabled(enabled);
+    }
+
+    pub fn rate_control(&self) -> Option<DynamicRateControl> {
+        self.rate_control
+    }
+
+    pub fn set_rate_control(&mut self, rate_control: Option<DynamicRateControl>) {
+        self.rate_control = rate_control;
+
+        if rate_control.is_none() {
+            self.rate_adjustment = 1.0;
+            self.resampler.set_rate_adjustment(1.0);
+        }
+    }
+
+    /// Adjusts the output rate according to the fill level of the frontend's buffer
+    pub fn report_buffer_level(&mut self, fill: usize, capacity: usize) {
+        if let Some(rate_control) = &self.rate_control {
+            self.rate_adjustment = rate_control.adjustment(fill, capacity);
+            self.resampler.set_rate_adjustment(self.rate_adjustment);
+        }
+    }
+
+    /// Starts recording the synthesized audio, dropping the ongoing recording if any.
+    /// The recording is done before resampling, so it isn't affected by the output settings.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(WavRecorder::new(SYNTHESIS_SAMPLE_RATE));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Stops the recording and returns it as a WAV file
+    pub fn stop_recording(&mut self) -> Option<Vec<u8>> {
+        self.recorder.take().map(WavRecorder::finish)
+    }
+
+    /// Must be called once per CPU cycle with the current output of the APU (between 0.0 and 1.0)
+    pub fn push(&mut self, output: f32) {
+        // Only the changes of the output are synthesized
+        let amplitude = (output.clamp(0.0, 1.0) * f32::from(i16::MAX)) as i32;
+        if amplitude != self.amplitude {
+            self.blip
+                .add_delta(self.frame_cycle, amplitude - self.amplitude);
+            self.amplitude = amplitude;
+        }
+
+        self.frame_cycle += 1;
+        if self.frame_cycle == FRAME_CYCLES {
+            self.frame_cycle = 0;
+            self.blip.end_frame(FRAME_CYCLES);
+
+            let max_buffered_samples = Self::max_buffered_samples(self.sample_rate);
+            let samples = &mut self.samples;
+            let resampler = &mut self.resampler;
+            let recorder = &mut self.recorder;
+            let filters = &mut self.filters;
+            self.blip.read_samples(|sample| {
+                let sample = filters.process(sample);
+
+                if let Some(recorder) = recorder {
+                    recorder.push(sample);
+                }
+
+                resampler.push(sample, |sample| {
+                    // Drop the oldest samples if the frontend doesn't keep up
+                    if samples.len() >= max_buffered_samples {
+                        samples.pop_front();
+                    }
+
+                    samples.push_back(sample);
+                })
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = i16> + '_ {
+        self.samples.drain(..)
+    }
+
+    /// Moves as many samples as possible to `output` and returns how many were written
+    pub fn read(&mut self, output: &mut [i16]) -> usize {
+        let count = output.len().min(self.samples.len());
+
+        for (dst, src) in output.iter_mut().zip(self.samples.drain(..count)) {
+            *dst = src;
+        }
+
+        count
+    }
+
+    /// Half a second of audio
+    fn max_buffered_samples(sample_rate: u32) -> usize {
+        (sample_rate / 2) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn produces_samples_at_output_rate() {
+        let mut audio = AudioOutput::new(44100);
+        audio.set_filters_enabled(false);
+
+        // One second of audio, only the last half second is kept
+        for _ in 0..CPU_FREQUENCY {
+            audio.push(0.5);
+        }
+
+        assert_eq!(audio.len(), 22050);
+        assert!(audio.drain().all(|sample| sample == i16::MAX / 2));
+        assert_eq!(audio.len(), 0);
+    }
+
+    #[test]
+    fn read_partially_drains() {
+        let mut audio = AudioOutput::new(44100);
+        audio.set_filters_enabled(false);
+        for _ in 0..(CPU_FREQUENCY / 100) {
+            audio.push(1.0);
+        }
+
+        let available = audio.len();
+        let mut output = [0i16; 400];
+        assert_eq!(audio.read(&mut output), 400);
+        assert_eq!(output[399], i16::MAX);
+        assert_eq!(audio.len(), available - 400);
+    }
+}
@@ -0,0 +1,49 @@
+/// Dynamic rate control: slightly speeds up or slows down the audio output depending on how full
+/// the frontend's audio buffer is, so it stays around half full instead of slowly drifting
+/// towards an underrun or an ever growing latency. The clocks of the emulator and of the audio
+/// device never match perfectly.
+/// https://github.com/libretro/docs/blob/master/archive/ratecontrol.pdf
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DynamicRateControl {
+    max_deviation: f64,
+}
+
+impl DynamicRateControl {
+    /// `max_deviation` is the largest relative change applied to the output sample rate.
+    /// Values around 0.005 are inaudible.
+    pub fn new(max_deviation: f64) -> Self {
+        Self {
+            max_deviation: max_deviation.clamp(0.0, 0.1),
+        }
+    }
+
+    pub fn max_deviation(&self) -> f64 {
+        self.max_deviation
+    }
+
+    /// Factor to apply to the output sample rate, given the number of samples in the buffer and
+    /// its capacity. An empty buffer gets more samples, a full one gets fewer.
+    pub fn adjustment(&self, fill: usize, capacity: usize) -> f64 {
+        if capacity == 0 {
+            return 1.0;
+        }
+
+        let fill = fill.min(capacity) as f64 / capacity as f64;
+        1.0 + self.max_deviation * (1.0 - 2.0 * fill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjustment_follows_fill_level() {
+        let rate_control = DynamicRateControl::new(0.005);
+
+        assert_eq!(rate_control.adjustment(0, 1000), 1.005);
+        assert_eq!(rate_control.adjustment(500, 1000), 1.0);
+        assert_eq!(rate_control.adjustment(1000, 1000), 0.995);
+        assert_eq!(rate_control.adjustment(5000, 1000), 0.995);
+    }
+}
@@ -0,0 +1,218 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use core::f64::consts::PI;
+
+/// Interpolation used to convert the synthesized audio to the output sample rate
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ResamplerKind {
+    /// Cheapest, but lets some aliasing through when downsampling
+    Linear,
+
+    /// Blackman-windowed sinc. Filters out what can't be represented at the output rate.
+    #[default]
+    WindowedSinc,
+}
+
+/// Number of input samples on each side of the interpolated position used by the windowed sinc
+const SINC_HALF_WIDTH: usize = 8;
+
+/// Number of precomputed sub-sample positions of the windowed sinc
+const SINC_PHASES: usize = 256;
+
+/// Fixed point precision of the position, in input samples
+const POSITION_BITS: u32 = 32;
+const POSITION_MASK: u64 = (1 << POSITION_BITS) - 1;
+
+pub struct Resampler {
+    kind: ResamplerKind,
+
+    /// Input samples per output sample, in fixed point
+    step: u64,
+    nominal_step: u64,
+
+    /// Position of the next output sample, relative to the start of `history`
+    position: u64,
+
+    history: VecDeque<f32>,
+
+    /// Taps of the windowed sinc for every phase, empty when unused
+    sinc_table: Vec<[f32; 2 * SINC_HALF_WIDTH]>,
+}
+
+impl Resampler {
+    pub fn new(kind: ResamplerKind, input_rate: u32, output_rate: u32) -> Self {
+        let step = (u64::from(input_rate) << POSITION_BITS) / u64::from(output_rate);
+
+        let sinc_table = match kind {
+            ResamplerKind::Linear => Vec::new(),
+            ResamplerKind::WindowedSinc => {
+                // When downsampling, the cutoff must be lowered to the output Nyquist frequency
+                let ratio = f64::from(output_rate) / f64::from(input_rate);
+                build_sinc_table(0.95 * ratio.min(1.0))
+            }
+        };
+
+        let mut resampler = Self {
+            kind,
+            step,
+            nominal_step: step,
+            position: 0,
+            history: VecDeque::new(),
+            sinc_table,
+        };
+
+        resampler.position = (resampler.taps_before() as u64) << POSITION_BITS;
+        resampler
+    }
+
+    pub fn kind(&self) -> ResamplerKind {
+        self.kind
+    }
+
+    /// Multiplies the output sample rate by `factor` without resetting the resampler
+    pub fn set_rate_adjustment(&mut self, factor: f64) {
+        self.step = (self.nominal_step as f64 / factor) as u64;
+    }
+
+    /// Adds an input sample and gives all the output samples that can now be computed to `output`
+    pub fn push(&mut self, sample: i16, mut output: impl FnMut(i16)) {
+        self.history.push_back(f32::from(sample));
+
+        while ((self.position >> POSITION_BITS) as usize) + self.taps_after() < self.history.len() {
+            let index = (self.position >> POSITION_BITS) as usize;
+            let fraction = (self.position & POSITION_MASK) as f32 / (1u64 << POSITION_BITS) as f32;
+
+            let value = match self.kind {
+                ResamplerKind::Linear => {
+                    self.history[index] * (1.0 - fraction) + self.history[index + 1] * fraction
+                }
+                ResamplerKind::WindowedSinc => {
+                    let phase = ((fraction * SINC_PHASES as f32) as usize).min(SINC_PHASES - 1);
+                    let first = index + 1 - SINC_HALF_WIDTH;
+
+                    self.sinc_table[phase]
+                        .iter()
+                        .zip(self.history.range(first..))
+                        .map(|(tap, sample)| tap * sample)
+                        .sum()
+                }
+            };
+
+            output(libm::roundf(value.clamp(f32::from(i16::MIN), f32::from(i16::MAX))) as i16);
+
+            self.position += self.step;
+        }
+
+        // Forget the samples that won't be needed anymore
+        while (self.position >> POSITION_BITS) as usize > self.taps_before()
+            && !self.history.is_empty()
+        {
+            self.history.pop_front();
+            self.position -= 1 << POSITION_BITS;
+        }
+    }
+
+    /// Number of input samples needed before the interpolated position
+    fn taps_before(&self) -> usize {
+        match self.kind {
+            ResamplerKind::Linear => 0,
+            ResamplerKind::WindowedSinc => SINC_HALF_WIDTH - 1,
+        }
+    }
+
+    /// Number of input samples needed after the interpolated position
+    fn taps_after(&self) -> usize {
+        match self.kind {
+            ResamplerKind::Linear => 1,
+            ResamplerKind::WindowedSinc => SINC_HALF_WIDTH,
+        }
+    }
+}
+
+/// `cutoff` is relative to the input Nyquist frequency
+fn build_sinc_table(cutoff: f64) -> Vec<[f32; 2 * SINC_HALF_WIDTH]> {
+    let half_width = SINC_HALF_WIDTH as f64;
+
+    (0..SINC_PHASES)
+        .map(|phase| {
+            let fraction = phase as f64 / SINC_PHASES as f64;
+
+            let mut taps = [0f64; 2 * SINC_HALF_WIDTH];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                // Distance between the input sample and the interpolated position
+                let x = i as f64 - half_width + 1.0 - fraction;
+
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    libm::sin(PI * cutoff * x) / (PI * cutoff * x)
+                };
+
+                let t = x / half_width;
+                let window = 0.42 + 0.5 * libm::cos(PI * t) + 0.08 * libm::cos(2.0 * PI * t);
+
+                *tap = sinc * window;
+            }
+
+            // Unity gain, so a constant input gives the same constant output
+            let sum: f64 = taps.iter().sum();
+
+            let mut normalized = [0f32; 2 * SINC_HALF_WIDTH];
+            for (dst, tap) in normalized.iter_mut().zip(taps.iter()) {
+                *dst = (tap / sum) as f32;
+            }
+
+            normalized
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resample(kind: ResamplerKind, input_rate: u32, output_rate: u32, input: &[i16]) -> Vec<i16> {
+        let mut resampler = Resampler::new(kind, input_rate, output_rate);
+        let mut output = Vec::new();
+
+        for &sample in input {
+            resampler.push(sample, |sample| output.push(sample));
+        }
+
+        output
+    }
+
+    #[test]
+    fn output_count_follows_ratio() {
+        let input = [0i16; 96000];
+
+        for &kind in [ResamplerKind::Linear, ResamplerKind::WindowedSinc].iter() {
+            for &rate in [44100, 48000, 22050].iter() {
+                let output = resample(kind, 96000, rate, &input);
+                let expected = rate as usize;
+
+                // Give or take the samples still waiting for their following taps
+                assert!((output.len() as isize - expected as isize).abs() < 10);
+            }
+        }
+    }
+
+    #[test]
+    fn constant_input_is_preserved() {
+        let input = [1234i16; 4800];
+
+        for &kind in [ResamplerKind::Linear, ResamplerKind::WindowedSinc].iter() {
+            let output = resample(kind, 96000, 44100, &input);
+            assert!(output[SINC_HALF_WIDTH..]
+                .iter()
+                .all(|&sample| sample == 1234));
+        }
+    }
+
+    #[test]
+    fn linear_interpolates_between_samples() {
+        let output = resample(ResamplerKind::Linear, 1, 2, &[0, 100, 200]);
+        assert_eq!(output, [0, 50, 100, 150]);
+    }
+}
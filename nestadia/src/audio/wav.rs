@@ -0,0 +1,80 @@
+use alloc::vec::Vec;
+
+const HEADER_SIZE: usize = 44;
+const CHANNELS: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Records mono 16 bits PCM samples and encodes them as a WAV file
+/// http://soundfile.sapp.org/doc/WaveFormat/
+pub struct WavRecorder {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+impl WavRecorder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, sample: i16) {
+        self.samples.push(sample);
+    }
+
+    /// Returns the content of the WAV file
+    pub fn finish(self) -> Vec<u8> {
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = self.sample_rate * u32::from(block_align);
+        let data_size = (self.samples.len() * 2) as u32;
+
+        let mut wav = Vec::with_capacity(HEADER_SIZE + data_size as usize);
+
+        // RIFF chunk
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+
+        // Format chunk
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&CHANNELS.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&byte_rate.to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+        // Data chunk
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        for sample in self.samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        wav
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_header_and_samples() {
+        let mut recorder = WavRecorder::new(44100);
+        recorder.push(1);
+        recorder.push(-2);
+
+        let wav = recorder.finish();
+
+        assert_eq!(wav.len(), HEADER_SIZE + 4);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &40u32.to_le_bytes());
+        assert_eq!(&wav[24..28], &44100u32.to_le_bytes());
+        assert_eq!(&wav[28..32], &88200u32.to_le_bytes());
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+        assert_eq!(&wav[44..], &[0x01, 0x00, 0xFE, 0xFF]);
+    }
+}
@@ -0,0 +1,345 @@
+// Configuration of a new emulator, checked as a whole before loading the ROM.
+//
+// The builder gathers the settings of the ROM loader and the ones that would otherwise be set
+// one by one after creating the emulator, so they are all applied before power-on. `build`
+// returns an error instead of running with a setting that can't be honored.
+
+use alloc::vec::Vec;
+
+use crate::cartridge::{Cartridge, LoadOptions};
+use crate::{
+    Emulator, FastForward, MapperRegistry, Overscan, PowerOnRam, RomDatabase, RomParserError,
+    Turbo, CPU_FREQUENCY, DEFAULT_PRG_RAM_SIZE, DEFAULT_SAMPLE_RATE, PPU_WARMUP_CYCLES,
+    RGB_PALETTE,
+};
+
+/// Video standard of the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+/// State of the emulation once built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionMode {
+    Running,
+    /// Paused before the first frame, see `Emulator::advance_frame`
+    Paused,
+    FastForward(FastForward),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    InvalidRom(RomParserError),
+    /// Only the NTSC console is emulated
+    UnsupportedRegion(Region),
+    /// The overscan crops the whole frame
+    InvalidOverscan(Overscan),
+    /// The sample rate is 0 or above the CPU frequency
+    InvalidSampleRate(u32),
+    /// Only controllers 0 to 3 exist
+    InvalidController(usize),
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+pub struct EmulatorBuilder<'a> {
+    save_data: Option<&'a [u8]>,
+    default_prg_ram_size: usize,
+    mapper_registry: Option<&'a MapperRegistry>,
+    rom_database: Option<&'a RomDatabase>,
+    region: Option<Region>,
+    palette: [[u8; 3]; 64],
+    overscan: Overscan,
+    power_on_ram: PowerOnRam,
+    ppu_warmup_cycles: u32,
+    sprite_limit: bool,
+    audio_sample_rate: u32,
+    four_score: bool,
+    turbo: Vec<(usize, Turbo)>,
+    execution_mode: ExecutionMode,
+}
+
+impl Default for EmulatorBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> EmulatorBuilder<'a> {
+    /// Starts with the settings of `Emulator::new`
+    pub fn new() -> Self {
+        Self {
+            save_data: None,
+            default_prg_ram_size: DEFAULT_PRG_RAM_SIZE,
+            mapper_registry: None,
+            rom_database: None,
+            region: None,
+            palette: RGB_PALETTE,
+            overscan: Overscan::NONE,
+            power_on_ram: PowerOnRam::default(),
+            ppu_warmup_cycles: PPU_WARMUP_CYCLES,
+            sprite_limit: true,
+            audio_sample_rate: DEFAULT_SAMPLE_RATE,
+            four_score: false,
+            turbo: Vec::new(),
+            execution_mode: ExecutionMode::Running,
+        }
+    }
+
+    /// Battery backed RAM saved from a previous session
+    pub fn save_data(mut self, save_data: &'a [u8]) -> Self {
+        self.save_data = Some(save_data);
+        self
+    }
+
+    /// Size of the PRG RAM of the cartridges whose iNES header doesn't give it
+    pub fn default_prg_ram_size(mut self, size: usize) -> Self {
+        self.default_prg_ram_size = size;
+        self
+    }
+
+    /// Mappers taking precedence over the built-in ones
+    pub fn mapper_registry(mut self, registry: &'a MapperRegistry) -> Self {
+        self.mapper_registry = Some(registry);
+        self
+    }
+
+    /// Database correcting the ROM headers, instead of the built-in one
+    pub fn rom_database(mut self, rom_database: &'a RomDatabase) -> Self {
+        self.rom_database = Some(rom_database);
+        self
+    }
+
+    /// Region of the console. The ROMs made for another region still run on a NTSC console
+    /// when it isn't given.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Colors of the palette indices, see `Emulator::set_palette`
+    pub fn palette(mut self, palette: [[u8; 3]; 64]) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn overscan(mut self, overscan: Overscan) -> Self {
+        self.overscan = overscan;
+        self
+    }
+
+    pub fn power_on_ram(mut self, power_on_ram: PowerOnRam) -> Self {
+        self.power_on_ram = power_on_ram;
+        self
+    }
+
+    /// Length of the PPU warm-up period, in CPU cycles
+    pub fn ppu_warmup_cycles(mut self, cycles: u32) -> Self {
+        self.ppu_warmup_cycles = cycles;
+        self
+    }
+
+    /// See `Emulator::set_sprite_limit`
+    pub fn sprite_limit(mut self, enabled: bool) -> Self {
+        self.sprite_limit = enabled;
+        self
+    }
+
+    pub fn audio_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.audio_sample_rate = sample_rate;
+        self
+    }
+
+    /// Plugs a Four Score, for 4 controllers
+    pub fn four_score(mut self, plugged: bool) -> Self {
+        self.four_score = plugged;
+        self
+    }
+
+    /// Turbo buttons of a controller, from 0 to 3
+    pub fn turbo(mut self, controller: usize, turbo: Turbo) -> Self {
+        self.turbo.push((controller, turbo));
+        self
+    }
+
+    pub fn execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = mode;
+        self
+    }
+
+    /// Checks the settings, then creates an emulator running an iNES/NES 2.0 ROM or an NSF file
+    pub fn build(self, rom: &[u8]) -> Result<Emulator, BuildError> {
+        let save_data = self.save_data;
+        self.build_with(|options| Cartridge::load_rom(rom, save_data, options))
+    }
+
+    /// Same as `build`, with the ROM received in chunks of any size, see
+    /// `Emulator::new_from_chunks`
+    pub fn build_from_chunks<I>(self, chunks: I) -> Result<Emulator, BuildError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let save_data = self.save_data;
+        self.build_with(|options| Cartridge::load_from_chunks(chunks, save_data, options))
+    }
+
+    fn build_with<F>(self, load: F) -> Result<Emulator, BuildError>
+    where
+        F: FnOnce(&LoadOptions) -> Result<Cartridge, RomParserError>,
+    {
+        self.validate()?;
+
+        let options = LoadOptions {
+            default_prg_ram_size: self.default_prg_ram_size,
+            registry: self.mapper_registry.cloned().unwrap_or_default(),
+            rom_database: self
+                .rom_database
+                .cloned()
+                .unwrap_or_else(RomDatabase::builtin),
+        };
+        let cartridge = load(&options).map_err(BuildError::InvalidRom)?;
+
+        let mut emulator = Emulator::with_cartridge(cartridge);
+        emulator.power_on_ram = self.power_on_ram;
+        emulator.ppu_warmup_cycles = self.ppu_warmup_cycles;
+        emulator.palette = self.palette;
+        emulator.overscan = self.overscan;
+        emulator.ppu.set_sprite_limit(self.sprite_limit);
+        emulator.audio.set_sample_rate(self.audio_sample_rate);
+        emulator.controllers.set_four_score(self.four_score);
+        for (controller, turbo) in self.turbo {
+            emulator.controllers.set_turbo(controller, turbo);
+        }
+        emulator.power_cycle();
+
+        match self.execution_mode {
+            ExecutionMode::Running => {}
+            // Power-on is a frame boundary
+            ExecutionMode::Paused => emulator.paused = true,
+            ExecutionMode::FastForward(fast_forward) => {
+                emulator.set_fast_forward(Some(fast_forward))
+            }
+        }
+
+        Ok(emulator)
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        match self.region {
+            Some(Region::Ntsc) | None => {}
+            Some(region) => return Err(BuildError::UnsupportedRegion(region)),
+        }
+
+        if !self.overscan.is_valid() {
+            return Err(BuildError::InvalidOverscan(self.overscan));
+        }
+
+        if self.audio_sample_rate == 0 || self.audio_sample_rate > CPU_FREQUENCY {
+            return Err(BuildError::InvalidSampleRate(self.audio_sample_rate));
+        }
+
+        match self.turbo.iter().find(|(controller, _)| *controller >= 4) {
+            Some((controller, _)) => Err(BuildError::InvalidController(*controller)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::{nrom, CHR_ROM_OFFSET, PRG_ROM_OFFSET};
+
+    /// NROM showing 10 sprites side by side on lines 51 to 58, drawn with color $16
+    fn sprites_rom() -> Vec<u8> {
+        #[rustfmt::skip]
+        let program = [
+            0xA9, 0x00, 0x8D, 0x03, 0x20, // LDA #$00; STA $2003
+            0xA2, 0x00, // LDX #$00
+            0xBD, 0x00, 0x90, 0x8D, 0x04, 0x20, // LDA $9000,X; STA $2004
+            0xE8, 0xE0, 0x28, 0xD0, 0xF5, // INX; CPX #$28; BNE $8007
+            0xA9, 0x3F, 0x8D, 0x06, 0x20, 0xA9, 0x11, 0x8D, 0x06, 0x20, // PPUADDR = $3F11
+            0xA9, 0x16, 0x8D, 0x07, 0x20, // PPUDATA = $16
+            0xA9, 0x14, 0x8D, 0x01, 0x20, // Show all the sprites
+            0x4C, 0x26, 0x80, // JMP $8026
+        ];
+        let mut rom = nrom(&program);
+        for sprite in 0..10 {
+            let oam = PRG_ROM_OFFSET + 0x1000 + sprite * 4;
+            rom[oam..oam + 4].copy_from_slice(&[50, 0, 0, sprite as u8 * 16]);
+        }
+        rom[CHR_ROM_OFFSET..CHR_ROM_OFFSET + 8].fill(0xFF); // Tile 0
+        rom
+    }
+
+    fn sprite_pixels(emulator: &mut Emulator) -> usize {
+        for _ in 0..2 {
+            while emulator.clock().is_none() {}
+        }
+        emulator.frame()[53 * 256..54 * 256]
+            .iter()
+            .filter(|&&pixel| pixel == 0x16)
+            .count()
+    }
+
+    #[test]
+    fn builds_with_settings() {
+        let builder = || Emulator::builder().ppu_warmup_cycles(0);
+        let mut emulator = builder().build(&sprites_rom()).unwrap();
+        assert_eq!(sprite_pixels(&mut emulator), 8 * 8);
+
+        let mut emulator = builder()
+            .sprite_limit(false)
+            .overscan(Overscan::NTSC)
+            .power_on_ram(PowerOnRam::Ones)
+            .audio_sample_rate(48000)
+            .four_score(true)
+            .execution_mode(ExecutionMode::Paused)
+            .build(&sprites_rom())
+            .unwrap();
+        assert!(emulator.is_paused() && emulator.is_four_score_plugged());
+        assert_eq!(emulator.audio_sample_rate(), 48000);
+        assert_eq!(emulator.peek_memory(0x0100), 0xFF);
+
+        emulator.resume();
+        assert_eq!(sprite_pixels(&mut emulator), 10 * 8);
+        let mut rgba = Vec::new();
+        assert_eq!(emulator.frame_rgba(&mut rgba), (256, 224));
+        assert_eq!(rgba.len(), 256 * 224 * 4);
+
+        // MMC1, which gets the default PRG RAM size unlike NROM
+        let mut rom = sprites_rom();
+        rom[6] = 0x10;
+        let emulator = builder()
+            .default_prg_ram_size(0x4000)
+            .build_from_chunks(rom.chunks(1000))
+            .unwrap();
+        assert_eq!(emulator.prg_ram_size(), 0x4000);
+
+        let invalid = |builder: EmulatorBuilder| builder.build(&sprites_rom()).err();
+        assert_eq!(
+            invalid(builder().region(Region::Pal)),
+            Some(BuildError::UnsupportedRegion(Region::Pal))
+        );
+        assert_eq!(
+            invalid(builder().audio_sample_rate(0)),
+            Some(BuildError::InvalidSampleRate(0))
+        );
+        assert_eq!(
+            invalid(builder().turbo(4, Turbo::new(0x80, 2))),
+            Some(BuildError::InvalidController(4))
+        );
+        assert_eq!(
+            invalid(builder().overscan(Overscan::new(0, 0, 128, 128))),
+            Some(BuildError::InvalidOverscan(Overscan::new(0, 0, 128, 128)))
+        );
+    }
+}
@@ -1,17 +1,20 @@
 use crate::cartridge::Cartridge;
 use crate::cartridge::Mirroring;
+use crate::controllers::Controllers;
+#[cfg(feature = "debugger")]
+use crate::debugger::{BusAccess, Debugger, TimingEventKind};
+use crate::irq::IrqLine;
+use crate::Apu;
 use crate::Ppu;
 use crate::RAM_SIZE;
 
 macro_rules! borrow_cpu_bus {
     ($owner:ident) => {{
         $crate::bus::CpuBus::borrow(
-            &mut $owner.controller1,
-            &mut $owner.controller2,
-            &mut $owner.controller_state,
-            &mut $owner.controller1_snapshot,
-            &mut $owner.controller2_snapshot,
+            &mut $owner.irq_line,
+            &mut $owner.controllers,
             &mut $owner.ram,
+            &mut $owner.apu,
             &mut $owner.cartridge,
             &mut $owner.ppu,
             &mut $owner.name_tables,
@@ -19,6 +22,17 @@ macro_rules! borrow_cpu_bus {
     }};
 }
 
+/// CPU bus of the emulation itself, whose accesses are reported to the debugger
+macro_rules! borrow_watched_cpu_bus {
+    ($owner:ident) => {{
+        #[allow(unused_mut)]
+        let mut bus = borrow_cpu_bus!($owner);
+        #[cfg(feature = "debugger")]
+        bus.attach_debugger(&mut $owner.debugger);
+        bus
+    }};
+}
+
 macro_rules! borrow_ppu_bus {
     ($owner:ident) => {{
         $crate::bus::PpuBus::borrow(&mut $owner.cartridge, &mut $owner.name_tables)
@@ -26,42 +40,45 @@ macro_rules! borrow_ppu_bus {
 }
 
 pub struct CpuBus<'a> {
-    controller1: &'a mut u8,
-    controller2: &'a mut u8,
-    controller_state: &'a mut bool,
-    controller1_snapshot: &'a mut u8,
-    controller2_snapshot: &'a mut u8,
+    irq_line: &'a mut IrqLine,
+    controllers: &'a mut Controllers,
     ram: &'a mut [u8; RAM_SIZE as usize],
+    apu: &'a mut Apu,
     cartridge: &'a mut Cartridge,
     ppu: &'a mut Ppu,
     name_tables: &'a mut [u8; 1024 * 4],
+    #[cfg(feature = "debugger")]
+    debugger: Option<&'a mut Debugger>,
 }
 
 impl<'a> CpuBus<'a> {
     #[allow(clippy::too_many_arguments)] // it's fine, it's used by a macro
     pub fn borrow(
-        controller1: &'a mut u8,
-        controller2: &'a mut u8,
-        controller_state: &'a mut bool,
-        controller1_snapshot: &'a mut u8,
-        controller2_snapshot: &'a mut u8,
+        irq_line: &'a mut IrqLine,
+        controllers: &'a mut Controllers,
         ram: &'a mut [u8; RAM_SIZE as usize],
+        apu: &'a mut Apu,
         cartridge: &'a mut Cartridge,
         ppu: &'a mut Ppu,
         name_tables: &'a mut [u8; 1024 * 4],
     ) -> Self {
         Self {
-            controller1,
-            controller2,
-            controller_state,
-            controller1_snapshot,
-            controller2_snapshot,
+            irq_line,
+            controllers,
             ram,
+            apu,
             cartridge,
             ppu,
             name_tables,
+            #[cfg(feature = "debugger")]
+            debugger: None,
         }
     }
+
+    #[cfg(feature = "debugger")]
+    pub fn attach_debugger(&mut self, debugger: &'a mut Debugger) {
+        self.debugger = Some(debugger);
+    }
 }
 
 impl CpuBus<'_> {
@@ -74,6 +91,8 @@ impl CpuBus<'_> {
     }
 
     pub fn write_ppu_register(&mut self, addr: u16, data: u8) {
+        self.cartridge.ppu_register_write(addr, data);
+
         let mut ppu_bus = borrow_ppu_bus!(self);
         self.ppu.write(&mut ppu_bus, addr, data);
     }
@@ -84,33 +103,45 @@ impl CpuBus<'_> {
         self.ppu.read(&mut ppu_bus, addr)
     }
 
+    pub fn write_apu_register(&mut self, addr: u16, data: u8) {
+        self.apu.write(addr, data, self.irq_line);
+    }
+
+    pub fn read_apu_status(&mut self) -> u8 {
+        self.apu.read_status(self.irq_line)
+    }
+
     pub fn controller_write(&mut self, data: u8) {
-        *self.controller_state = data & 0x01 == 0x01;
-        *self.controller1_snapshot = *self.controller1;
-        *self.controller2_snapshot = *self.controller2;
-    }
-
-    pub fn read_controller1_snapshot(&mut self) -> u8 {
-        if *self.controller_state {
-            *self.controller1 & 0x80 >> 7
-        } else {
-            let data = (*self.controller1_snapshot & 0x80) >> 7;
-            *self.controller1_snapshot <<= 1;
-            data
-        }
+        self.controllers.write(data);
     }
 
-    pub fn read_controller2_snapshot(&mut self) -> u8 {
-        if *self.controller_state {
-            *self.controller2 & 0x80 >> 7
-        } else {
-            let data = (*self.controller2_snapshot & 0x80) >> 7;
-            *self.controller2_snapshot <<= 1;
-            data
-        }
+    pub fn read_controller1(&mut self) -> u8 {
+        self.controllers.read(0)
+    }
+
+    pub fn read_controller2(&mut self) -> u8 {
+        self.controllers.read(1)
     }
 
     pub fn write_prg_mem(&mut self, addr: u16, data: u8) {
+        if self.cartridge.is_audio_register(addr) {
+            self.apu.log_expansion_write(addr, data);
+        }
+
+        #[cfg(feature = "debugger")]
+        if let Some(debugger) = self
+            .debugger
+            .as_deref_mut()
+            .filter(|d| d.breaks_on_bank_switch())
+        {
+            let before = self.cartridge.bank_mapping();
+            self.cartridge.write_prg_mem(addr, data);
+            let after = self.cartridge.bank_mapping();
+            let cartridge = &self.cartridge;
+            debugger.on_bank_switch(&before, &after, |addr| cartridge.get_prg_bank(addr));
+            return;
+        }
+
         self.cartridge.write_prg_mem(addr, data)
     }
 
@@ -121,6 +152,41 @@ impl CpuBus<'_> {
     pub fn write_ppu_oam_dma(&mut self, buffer: &[u8; 256]) {
         self.ppu.write_oam_dma(buffer);
     }
+
+    /// Reports a read to the debugger
+    #[cfg(feature = "debugger")]
+    pub fn watch_read(&mut self, addr: u16, data: u8) {
+        if let Some(debugger) = &mut self.debugger {
+            if debugger.is_watched(addr) {
+                debugger.on_access(addr, BusAccess::Read, data);
+            }
+        }
+    }
+
+    /// Reports a write to the debugger, before it's done
+    #[cfg(feature = "debugger")]
+    pub fn watch_write(&mut self, addr: u16, data: u8) {
+        if let Some(debugger) = &mut self.debugger {
+            if debugger.is_recording_timing() && matches!(addr, 0x2000..=0x3FFF | 0x4014) {
+                let addr = if addr == 0x4014 { addr } else { addr & 0x2007 };
+                let kind = TimingEventKind::RegisterWrite { addr, data };
+                debugger.record_timing_event(self.ppu.scanline(), self.ppu.dot(), kind);
+            }
+        }
+
+        if !matches!(&self.debugger, Some(debugger) if debugger.is_watched(addr)) {
+            return;
+        }
+
+        let previous = match addr {
+            0x0000..=0x1FFF => Some(self.ram[(addr & (RAM_SIZE - 1)) as usize]),
+            0x6000..=0x7FFF => Some(self.cartridge.peek_prg_mem(addr)),
+            _ => None,
+        };
+        if let Some(debugger) = &mut self.debugger {
+            debugger.on_access(addr, BusAccess::Write(previous), data);
+        }
+    }
 }
 
 pub struct PpuBus<'a> {
@@ -151,11 +217,27 @@ impl PpuBus<'_> {
         self.cartridge.write_chr_mem(addr, data);
     }
 
+    /// Notifies the cartridge of the end of a scanline
+    pub fn end_of_scanline(&mut self) {
+        self.cartridge.end_of_scanline();
+    }
+
     pub fn read_name_tables(&mut self, addr: u16) -> u8 {
+        if let Some(data) = self.cartridge.read_name_table(addr, &self.name_tables[..]) {
+            return data;
+        }
+
         self.name_tables[self.mirror_name_tables_addr(addr) as usize]
     }
 
     pub fn write_name_tables(&mut self, addr: u16, data: u8) {
+        if self
+            .cartridge
+            .write_name_table(addr, data, &mut self.name_tables[..])
+        {
+            return;
+        }
+
         self.name_tables[self.mirror_name_tables_addr(addr) as usize] = data;
     }
 
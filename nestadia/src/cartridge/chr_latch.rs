@@ -0,0 +1,83 @@
+/// CHR banking of the MMC2 and MMC4: each pattern table has two 4KB banks, and a latch selects
+/// which one is used. The latch flips after the PPU fetches tile $FD or $FE of the table, which
+/// lets games switch banks in the middle of a scanline without any CPU intervention.
+pub struct ChrLatch {
+    /// Banks used when the latch is $FD and $FE, for each pattern table
+    banks: [[u8; 2]; 2],
+    latches: [usize; 2],
+
+    /// The MMC2 only flips the latch of the first pattern table on the first row of the tiles
+    exact_first_table: bool,
+}
+
+impl_stateful!(ChrLatch {
+    banks,
+    latches,
+    exact_first_table
+});
+
+impl ChrLatch {
+    pub fn new(exact_first_table: bool) -> Self {
+        Self {
+            banks: [[0; 2]; 2],
+            latches: [1; 2],
+            exact_first_table,
+        }
+    }
+
+    /// `tile` is either $FD or $FE
+    pub fn set_bank(&mut self, table: usize, tile: u8, bank: u8) {
+        self.banks[table][(tile == 0xFE) as usize] = bank & 0x1F;
+    }
+
+    pub fn map(&self, addr: u16) -> usize {
+        let table = ((addr >> 12) & 0x01) as usize;
+        let bank = self.banks[table][self.latches[table]];
+
+        (bank as usize) * 0x1000 + (addr & 0x0FFF) as usize
+    }
+
+    /// Must be called after every PPU read of the pattern tables
+    pub fn update(&mut self, addr: u16) {
+        let table = ((addr >> 12) & 0x01) as usize;
+
+        let tile_row = if table == 0 && self.exact_first_table {
+            addr & 0x0FFF
+        } else {
+            addr & 0x0FF8
+        };
+
+        match tile_row {
+            0x0FD8 => self.latches[table] = 0,
+            0x0FE8 => self.latches[table] = 1,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latch_flips_after_the_fetch() {
+        let mut latch = ChrLatch::new(true);
+        latch.set_bank(1, 0xFD, 2);
+        latch.set_bank(1, 0xFE, 3);
+
+        assert_eq!(latch.map(0x1FD8), 0x3FD8);
+        latch.update(0x1FD8);
+        assert_eq!(latch.map(0x1FD8), 0x2FD8);
+
+        // Anywhere in the tile for the second table
+        latch.update(0x1FEF);
+        assert_eq!(latch.map(0x1000), 0x3000);
+
+        // Only on the first row for the first table on the MMC2
+        latch.set_bank(0, 0xFD, 4);
+        latch.update(0x0FD9);
+        assert_eq!(latch.map(0x0000), 0x0000);
+        latch.update(0x0FD8);
+        assert_eq!(latch.map(0x0000), 0x4000);
+    }
+}
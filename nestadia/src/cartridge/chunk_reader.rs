@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+
+/// Reads data received as a sequence of chunks of any size
+pub struct ChunkReader<I: Iterator> {
+    chunks: I,
+    chunk: Option<I::Item>,
+    position: usize, // Position in the current chunk
+}
+
+impl<I> ChunkReader<I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    pub fn new(chunks: I) -> Self {
+        Self {
+            chunks,
+            chunk: None,
+            position: 0,
+        }
+    }
+
+    /// Appends the next `len` bytes to `buf`. Returns false if the data ends before. `buf`
+    /// only grows as the chunks come, as `len` may be more than the data has.
+    pub fn read(&mut self, buf: &mut Vec<u8>, mut len: usize) -> bool {
+        while len > 0 {
+            let chunk = match &self.chunk {
+                Some(chunk) => &chunk.as_ref()[self.position..],
+                None => match self.chunks.next() {
+                    Some(chunk) => {
+                        self.chunk = Some(chunk);
+                        self.position = 0;
+                        continue;
+                    }
+                    None => return false,
+                },
+            };
+
+            let read_len = len.min(chunk.len());
+            buf.extend_from_slice(&chunk[..read_len]);
+            len -= read_len;
+
+            if read_len == chunk.len() {
+                self.chunk = None;
+            } else {
+                self.position += read_len;
+            }
+        }
+
+        true
+    }
+
+    /// Appends the rest of the data to `buf`
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) {
+        if let Some(chunk) = self.chunk.take() {
+            buf.extend_from_slice(&chunk.as_ref()[self.position..]);
+        }
+
+        for chunk in &mut self.chunks {
+            buf.extend_from_slice(chunk.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn reads_across_chunks() {
+        let chunks = vec![vec![1, 2, 3], vec![], vec![4], vec![5, 6, 7]];
+        let mut reader = ChunkReader::new(chunks.into_iter());
+        let mut buf = Vec::new();
+
+        assert!(reader.read(&mut buf, 2));
+        assert!(reader.read(&mut buf, 3));
+        assert_eq!(buf, [1, 2, 3, 4, 5]);
+
+        reader.read_to_end(&mut buf);
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7]);
+        assert!(!reader.read(&mut buf, 1));
+    }
+}
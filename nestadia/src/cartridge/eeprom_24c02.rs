@@ -0,0 +1,266 @@
+// Serial 24C02 EEPROM (256 bytes), accessed by bit-banging its I²C bus
+// http://wiki.nesdev.com/w/index.php/Bandai_FCG_board#Serial_EEPROM
+
+pub const EEPROM_SIZE: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Idle,
+    ChipAddress,
+    Address,
+    Read,
+    Write,
+    SendAck,
+    WaitAck,
+}
+
+impl_stateful_enum!(Mode {
+    Idle,
+    ChipAddress,
+    Address,
+    Read,
+    Write,
+    SendAck,
+    WaitAck,
+});
+
+pub struct Eeprom24C02 {
+    data: [u8; EEPROM_SIZE],
+
+    mode: Mode,
+    next_mode: Mode,
+    chip_address: u8,
+    address: u8,
+    shift_register: u8,
+    bit_counter: u8,
+
+    output: bool,
+    previous_scl: bool,
+    previous_sda: bool,
+}
+
+impl_stateful!(Eeprom24C02 {
+    data,
+    mode,
+    next_mode,
+    chip_address,
+    address,
+    shift_register,
+    bit_counter,
+    output,
+    previous_scl,
+    previous_sda
+});
+
+impl Eeprom24C02 {
+    pub fn new(save_data: Option<&[u8]>) -> Self {
+        let mut data = [0u8; EEPROM_SIZE];
+
+        // Load the save data
+        if let Some(save_data) = save_data {
+            data.iter_mut()
+                .zip(save_data.iter())
+                .for_each(|(r, s)| *r = *s)
+        };
+
+        Self {
+            data,
+
+            mode: Mode::Idle,
+            next_mode: Mode::Idle,
+            chip_address: 0,
+            address: 0,
+            shift_register: 0,
+            bit_counter: 0,
+
+            output: true,
+            previous_scl: false,
+            previous_sda: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// State of the data line driven by the EEPROM
+    pub fn read(&self) -> bool {
+        self.output
+    }
+
+    /// Sets the clock and data lines driven by the cartridge
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        if self.previous_scl && scl && !sda && self.previous_sda {
+            // Start condition
+            self.mode = Mode::ChipAddress;
+            self.bit_counter = 0;
+            self.output = true;
+        } else if self.previous_scl && scl && sda && !self.previous_sda {
+            // Stop condition
+            self.mode = Mode::Idle;
+            self.output = true;
+        } else if scl && !self.previous_scl {
+            self.clock_rising_edge(sda);
+        } else if !scl && self.previous_scl {
+            self.clock_falling_edge();
+        }
+
+        self.previous_scl = scl;
+        self.previous_sda = sda;
+    }
+
+    fn clock_rising_edge(&mut self, sda: bool) {
+        match self.mode {
+            Mode::ChipAddress => self.chip_address = self.shift_in(self.chip_address, sda),
+            Mode::Address => self.address = self.shift_in(self.address, sda),
+            Mode::Write => self.shift_register = self.shift_in(self.shift_register, sda),
+            Mode::Read => {
+                if self.bit_counter < 8 {
+                    self.output = self.shift_register & (0x80 >> self.bit_counter) != 0;
+                    self.bit_counter += 1;
+                }
+            }
+            Mode::SendAck => self.output = false,
+            Mode::WaitAck => {
+                // The CPU acknowledges to keep reading
+                if !sda {
+                    self.next_mode = Mode::Read;
+                    self.shift_register = self.data[self.address as usize];
+                }
+            }
+            Mode::Idle => (),
+        }
+    }
+
+    fn clock_falling_edge(&mut self) {
+        match self.mode {
+            Mode::ChipAddress if self.bit_counter == 8 => {
+                self.bit_counter = 0;
+                self.output = true;
+
+                if self.chip_address & 0xF0 == 0xA0 {
+                    self.mode = Mode::SendAck;
+
+                    if self.chip_address & 0x01 == 0x01 {
+                        self.next_mode = Mode::Read;
+                        self.shift_register = self.data[self.address as usize];
+                    } else {
+                        self.next_mode = Mode::Address;
+                    }
+                } else {
+                    self.mode = Mode::Idle;
+                }
+            }
+            Mode::Address if self.bit_counter == 8 => {
+                self.bit_counter = 0;
+                self.output = true;
+                self.mode = Mode::SendAck;
+                self.next_mode = Mode::Write;
+            }
+            Mode::Write if self.bit_counter == 8 => {
+                self.bit_counter = 0;
+                self.output = true;
+                self.mode = Mode::SendAck;
+                self.next_mode = Mode::Write;
+
+                self.data[self.address as usize] = self.shift_register;
+                self.address = self.address.wrapping_add(1);
+            }
+            Mode::Read if self.bit_counter == 8 => {
+                self.mode = Mode::WaitAck;
+                self.next_mode = Mode::Idle;
+                self.address = self.address.wrapping_add(1);
+            }
+            Mode::SendAck | Mode::WaitAck => {
+                self.mode = self.next_mode;
+                self.bit_counter = 0;
+                self.output = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn shift_in(&mut self, value: u8, bit: bool) -> u8 {
+        if self.bit_counter >= 8 {
+            return value;
+        }
+
+        let mask = 0x80 >> self.bit_counter;
+        self.bit_counter += 1;
+
+        if bit {
+            value | mask
+        } else {
+            value & !mask
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Bus<'a>(&'a mut Eeprom24C02);
+
+    impl Bus<'_> {
+        fn start(&mut self) {
+            self.0.write(false, true);
+            self.0.write(true, true);
+            self.0.write(true, false);
+            self.0.write(false, false);
+        }
+
+        fn stop(&mut self) {
+            self.0.write(false, false);
+            self.0.write(true, false);
+            self.0.write(true, true);
+        }
+
+        fn clock_bit(&mut self, bit: bool) -> bool {
+            self.0.write(false, bit);
+            self.0.write(true, bit);
+            let output = self.0.read();
+            self.0.write(false, bit);
+            output
+        }
+
+        /// Returns whether the EEPROM acknowledged
+        fn send(&mut self, byte: u8) -> bool {
+            for i in 0..8 {
+                self.clock_bit(byte & (0x80 >> i) != 0);
+            }
+            !self.clock_bit(true)
+        }
+
+        fn receive(&mut self, ack: bool) -> u8 {
+            let byte = (0..8).fold(0, |byte, _| (byte << 1) | self.clock_bit(true) as u8);
+            self.clock_bit(!ack);
+            byte
+        }
+    }
+
+    #[test]
+    fn write_then_read() {
+        let mut eeprom = Eeprom24C02::new(None);
+        let mut bus = Bus(&mut eeprom);
+
+        bus.start();
+        assert!(bus.send(0xA0));
+        assert!(bus.send(0x10));
+        assert!(bus.send(0x12));
+        assert!(bus.send(0x34));
+        bus.stop();
+
+        // Random read: sets the address with a write, then restarts as a read
+        bus.start();
+        assert!(bus.send(0xA0));
+        assert!(bus.send(0x10));
+        bus.start();
+        assert!(bus.send(0xA1));
+        assert_eq!(bus.receive(true), 0x12);
+        assert_eq!(bus.receive(false), 0x34);
+        bus.stop();
+
+        assert_eq!(eeprom.data()[0x10..0x12], [0x12, 0x34]);
+    }
+}
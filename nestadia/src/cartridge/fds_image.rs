@@ -0,0 +1,150 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::cartridge::{RomFormat, RomParserError, RomSection};
+
+/// Size of a disk side in a .fds file, without the gaps and CRCs
+const SIDE_SIZE: usize = 65500;
+
+/// Size of the optional header of .fds files (fwNES format)
+const HEADER_SIZE: usize = 16;
+
+/// Gap of zeroes before the first block of a side, then between blocks, in bytes
+const LEADING_GAP_SIZE: usize = 28300 / 8;
+const BLOCK_GAP_SIZE: usize = 976 / 8;
+
+/// Byte preceding every block on the disk
+const BLOCK_START_MARK: u8 = 0x80;
+
+/// Disk image of the Famicom Disk System.
+/// .fds files only store the blocks of each side, so the gaps and CRCs read by the drive are
+/// added back to get the data as it is laid out on the disk.
+/// http://wiki.nesdev.com/w/index.php/FDS_disk_format
+pub struct FdsImage {
+    sides: Vec<Vec<u8>>,
+}
+
+impl FdsImage {
+    pub fn into_sides(self) -> Vec<Vec<u8>> {
+        self.sides
+    }
+
+    fn raw_side(side: &[u8]) -> Vec<u8> {
+        let mut raw = vec![0u8; LEADING_GAP_SIZE];
+        let mut position = 0;
+        let mut file_size = 0;
+
+        while position < side.len() {
+            let block_size = match side[position] {
+                1 => 56, // Disk info
+                2 => 2,  // File amount
+                3 => 16, // File header
+                4 => 1 + file_size,
+                _ => break,
+            };
+
+            let block = match side.get(position..position + block_size) {
+                Some(block) => block,
+                None => break,
+            };
+
+            if block[0] == 3 {
+                file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+            }
+
+            raw.push(BLOCK_START_MARK);
+            raw.extend_from_slice(block);
+
+            // CRC, never checked since CRC errors are not reported
+            raw.extend_from_slice(&[0, 0]);
+            raw.resize(raw.len() + BLOCK_GAP_SIZE, 0);
+
+            position += block_size;
+        }
+
+        raw
+    }
+}
+
+impl TryFrom<&[u8]> for FdsImage {
+    type Error = RomParserError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        const HEADER_MAGIC_BYTES: [u8; 4] = [0x46, 0x44, 0x53, 0x1a];
+        const DISK_MAGIC_BYTES: &[u8] = b"\x01*NINTENDO-HVC*";
+
+        let header_size = if data.starts_with(&HEADER_MAGIC_BYTES) {
+            HEADER_SIZE
+        } else {
+            0
+        };
+
+        if data.len() < header_size + SIDE_SIZE {
+            return Err(RomParserError::TooShort {
+                format: RomFormat::Fds,
+                section: RomSection::DiskSide,
+                expected_size: header_size + SIDE_SIZE,
+                actual_size: data.len(),
+            });
+        }
+
+        let sides = data[header_size..]
+            .chunks_exact(SIDE_SIZE)
+            .enumerate()
+            .map(|(i, side)| {
+                if side.starts_with(DISK_MAGIC_BYTES) {
+                    Ok(Self::raw_side(side))
+                } else {
+                    Err(RomParserError::InvalidMagicBytes {
+                        format: RomFormat::Fds,
+                        offset: header_size + i * SIDE_SIZE,
+                    })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { sides })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_gaps_and_crcs() {
+        let mut data = vec![0u8; SIDE_SIZE * 2];
+        for side in data.chunks_exact_mut(SIDE_SIZE) {
+            side[..15].copy_from_slice(b"\x01*NINTENDO-HVC*");
+            side[56..58].copy_from_slice(&[2, 1]);
+            side[58] = 3;
+            side[58 + 13] = 4; // File size
+            side[74..79].copy_from_slice(&[4, 1, 2, 3, 4]);
+        }
+
+        let sides = FdsImage::try_from(&data[..]).unwrap().into_sides();
+        assert_eq!(sides.len(), 2);
+
+        let side = &sides[0];
+        assert_eq!(
+            side.len(),
+            LEADING_GAP_SIZE + 4 * (1 + 2 + BLOCK_GAP_SIZE) + 79
+        );
+        assert_eq!(side[LEADING_GAP_SIZE], BLOCK_START_MARK);
+        assert_eq!(side[LEADING_GAP_SIZE + 1], 1);
+
+        let file_data = side.len() - BLOCK_GAP_SIZE - 2 - 5;
+        assert_eq!(side[file_data - 1], BLOCK_START_MARK);
+        assert_eq!(side[file_data..file_data + 5], [4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_invalid_disk() {
+        let data = vec![0u8; SIDE_SIZE];
+        assert!(matches!(
+            FdsImage::try_from(&data[..]),
+            Err(RomParserError::InvalidMagicBytes { offset: 0, .. })
+        ));
+    }
+}
@@ -4,18 +4,31 @@ use core::convert::TryFrom;
 
 use bitflags::bitflags;
 
-use crate::cartridge::RomParserError;
+use crate::cartridge::{RomFormat, RomParserError, RomSection};
 
-#[derive(Debug)]
+const PRG_ROM_UNIT: usize = 16384;
+const CHR_ROM_UNIT: usize = 8192;
+
+/// Header of an iNES or NES 2.0 ROM
+/// http://wiki.nesdev.com/w/index.php/INES
+/// http://wiki.nesdev.com/w/index.php/NES_2.0
+#[derive(Debug, Clone)]
 pub struct INesHeader {
-    pub mapper_id: u8,
-    pub prg_size: u8,
-    pub chr_size: u8,
+    pub nes2: bool,
+    pub mapper_id: u16,
+    pub submapper_id: u8,
+
+    // Sizes are in bytes
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+
     pub flags6: Flags6,
-    pub flags7: Flags7,
-    pub flags8: u8, // Flags 8 is actually the PRG ram size
-    pub flags9: Flags9,
-    pub flags10: Flags10,
+    pub console_type: ConsoleType,
+    pub timing: Timing,
 }
 
 bitflags! {
@@ -27,26 +40,177 @@ bitflags! {
     }
 }
 
-bitflags! {
-    pub struct Flags7: u8 {
-        const VS_UNISYSTEM = (1 << 0);
-        const PLAYCHOICE_10 = (1 << 1);
-        const NES2 = (1 << 2) | (1 << 3);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+
+    /// Extended console type of NES 2.0 headers (byte 13)
+    Extended(u8),
+}
+
+/// CPU/PPU timing of the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+impl INesHeader {
+    /// Number of 16KB PRG ROM banks, as expected by the mappers
+    pub fn prg_banks(&self) -> u8 {
+        (self.prg_rom_size / PRG_ROM_UNIT).min(u8::MAX as usize) as u8
+    }
+
+    /// Size of the 512-byte trainer following the header, if any
+    pub fn trainer_size(&self) -> usize {
+        if self.flags6.contains(Flags6::TRAINER) {
+            512
+        } else {
+            0
+        }
+    }
+
+    pub fn format(&self) -> RomFormat {
+        if self.nes2 {
+            RomFormat::Nes2
+        } else {
+            RomFormat::INes
+        }
+    }
+
+    /// Error for a ROM of `rom_size` bytes, ending before the sections declared by the header
+    pub fn too_short_error(&self, rom_size: usize) -> RomParserError {
+        let prg_start = 16 + self.trainer_size();
+        let chr_start = prg_start.saturating_add(self.prg_rom_size);
+
+        let section = if rom_size < prg_start {
+            RomSection::Trainer
+        } else if rom_size < chr_start {
+            RomSection::PrgRom
+        } else {
+            RomSection::ChrRom
+        };
+
+        RomParserError::TooShort {
+            format: self.format(),
+            section,
+            expected_size: chr_start.saturating_add(self.chr_rom_size),
+            actual_size: rom_size,
+        }
+    }
+
+    fn parse_ines(data: &[u8]) -> Self {
+        let flags6 = Flags6::from_bits_truncate(data[6]);
+
+        let console_type = match data[7] & 0x03 {
+            0x01 => ConsoleType::VsSystem,
+            0x02 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Nes,
+        };
+
+        let timing = if data[9] & 0x01 == 0x01 {
+            Timing::Pal
+        } else {
+            Timing::Ntsc
+        };
+
+        // A size of 0 is left for the loader to infer, most dumps not giving it
+        let prg_ram_size = usize::from(data[8]) * 8192;
+
+        let (prg_ram_size, prg_nvram_size) = if flags6.contains(Flags6::PRG_RAM) {
+            (0, prg_ram_size)
+        } else {
+            (prg_ram_size, 0)
+        };
+
+        let chr_ram_size = if data[5] == 0 { CHR_ROM_UNIT } else { 0 };
+
+        Self {
+            nes2: false,
+            mapper_id: u16::from((data[6] >> 4) | (data[7] & 0xf0)),
+            submapper_id: 0,
+
+            prg_rom_size: usize::from(data[4]) * PRG_ROM_UNIT,
+            chr_rom_size: usize::from(data[5]) * CHR_ROM_UNIT,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size: 0,
+
+            flags6,
+            console_type,
+            timing,
+        }
+    }
+
+    fn parse_nes2(data: &[u8]) -> Result<Self, RomParserError> {
+        let console_type = match data[7] & 0x03 {
+            0x00 => ConsoleType::Nes,
+            0x01 => ConsoleType::VsSystem,
+            0x02 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(data[13] & 0x0F),
+        };
+
+        let timing = match data[12] & 0x03 {
+            0x00 => Timing::Ntsc,
+            0x01 => Timing::Pal,
+            0x02 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+
+        let rom_size = |lsb, msb, unit, section| {
+            nes2_rom_size(lsb, msb, unit).ok_or(RomParserError::RomTooLarge {
+                format: RomFormat::Nes2,
+                section,
+            })
+        };
+
+        Ok(Self {
+            nes2: true,
+            mapper_id: u16::from(data[6] >> 4)
+                | u16::from(data[7] & 0xf0)
+                | (u16::from(data[8] & 0x0f) << 8),
+            submapper_id: data[8] >> 4,
+
+            prg_rom_size: rom_size(data[4], data[9] & 0x0f, PRG_ROM_UNIT, RomSection::PrgRom)?,
+            chr_rom_size: rom_size(data[5], data[9] >> 4, CHR_ROM_UNIT, RomSection::ChrRom)?,
+            prg_ram_size: nes2_ram_size(data[10] & 0x0f),
+            prg_nvram_size: nes2_ram_size(data[10] >> 4),
+            chr_ram_size: nes2_ram_size(data[11] & 0x0f),
+            chr_nvram_size: nes2_ram_size(data[11] >> 4),
+
+            flags6: Flags6::from_bits_truncate(data[6]),
+            console_type,
+            timing,
+        })
     }
 }
 
-bitflags! {
-    pub struct Flags9: u8 {
-        const TV_SYSTEM = (1 << 0);
+/// ROM sizes are either a number of `unit` (with the MSB in byte 9),
+/// or use an exponent-multiplier notation if the MSB nibble is $F.
+/// `None` if the size doesn't fit in a `usize`.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Option<usize> {
+    if msb == 0x0f {
+        let exponent = u32::from(lsb >> 2);
+        let multiplier = usize::from(lsb & 0x03) * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+    } else {
+        (usize::from(msb) << 8 | usize::from(lsb)).checked_mul(unit)
     }
 }
 
-bitflags! {
-    pub struct Flags10: u8 {
-        const PAL = (2 << 0);
-        const DUAL = (1 << 0);
-        const PRG_RAM = (1 << 4);
-        const BUS_CONFLICT = (1 << 5);
+/// RAM sizes are shift counts: 64 << n bytes, or 0 if n is 0
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
     }
 }
 
@@ -57,33 +221,92 @@ impl TryFrom<&[u8]> for INesHeader {
         const MAGIC_BYTES: [u8; 4] = [0x4e, 0x45, 0x53, 0x1a];
 
         if data.len() < 16 {
-            return Err(RomParserError::TooShort);
+            return Err(RomParserError::TooShort {
+                format: RomFormat::INes,
+                section: RomSection::Header,
+                expected_size: 16,
+                actual_size: data.len(),
+            });
         };
 
         if data[..4] != MAGIC_BYTES {
-            return Err(RomParserError::InvalidMagicBytes);
+            return Err(RomParserError::InvalidMagicBytes {
+                format: RomFormat::INes,
+                offset: 0,
+            });
         };
 
-        let prg_size = data[4];
-        let chr_size = data[5];
+        // NES 2.0 is identified by bits 2-3 of byte 7 being 0b10
+        if data[7] & 0x0C == 0x08 {
+            Self::parse_nes2(data)
+        } else {
+            Ok(Self::parse_ines(data))
+        }
+    }
+}
 
-        let mapper_id = (data[6] >> 4) | (data[7] & 0xf0);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let flags6 = Flags6::from_bits_truncate(data[6]);
-        let flags7 = Flags7::from_bits_truncate(data[7]);
-        let flags8 = data[8];
-        let flags9 = Flags9::from_bits_truncate(data[9]);
-        let flags10 = Flags10::from_bits_truncate(data[10]);
-
-        Ok(INesHeader {
-            mapper_id,
-            prg_size,
-            chr_size,
-            flags6,
-            flags7,
-            flags8,
-            flags9,
-            flags10,
-        })
+    fn header(bytes: [u8; 12]) -> [u8; 16] {
+        let mut header = [0u8; 16];
+        header[..4].copy_from_slice(b"NES\x1A");
+        header[4..].copy_from_slice(&bytes);
+        header
+    }
+
+    #[test]
+    fn parses_ines() {
+        let data = header([0x08, 0x00, 0x13, 0x40, 0x01, 0x01, 0, 0, 0, 0, 0, 0]);
+        let header = INesHeader::try_from(&data[..]).unwrap();
+
+        assert!(!header.nes2);
+        assert_eq!(header.mapper_id, 0x41);
+        assert_eq!(header.prg_rom_size, 128 * 1024);
+        assert_eq!(header.chr_ram_size, 8192);
+        assert_eq!(header.prg_nvram_size, 8192);
+        assert_eq!(header.timing, Timing::Pal);
+    }
+
+    #[test]
+    fn parses_nes2() {
+        let data = header([
+            0x02, // PRG ROM LSB
+            0x07, // CHR ROM: 2^1 * 7 bytes with the exponent notation
+            0x41, // Mapper low nibble, vertical mirroring
+            0x08, // NES 2.0, mapper middle nibble 0
+            0x31, // Submapper 3, mapper high nibble 1
+            0xF1, // PRG ROM MSB 1, CHR ROM MSB $F
+            0x70, // 8KB PRG RAM
+            0x07, // 8KB CHR RAM
+            0x01, // PAL
+            0, 0, 0,
+        ]);
+        let header = INesHeader::try_from(&data[..]).unwrap();
+
+        assert!(header.nes2);
+        assert_eq!(header.mapper_id, 0x104);
+        assert_eq!(header.submapper_id, 3);
+        assert_eq!(header.prg_rom_size, 0x102 * 16384);
+        assert_eq!(header.prg_banks(), 255);
+        assert_eq!(header.chr_rom_size, 14);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.prg_nvram_size, 8192);
+        assert_eq!(header.chr_ram_size, 8192);
+        assert_eq!(header.timing, Timing::Pal);
+    }
+
+    #[test]
+    fn rejects_sizes_overflowing() {
+        // PRG ROM of 2^63 * 7 bytes with the exponent notation
+        let data = header([0xFF, 0x00, 0x00, 0x08, 0x00, 0x0F, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            INesHeader::try_from(&data[..]).err(),
+            Some(RomParserError::RomTooLarge {
+                format: RomFormat::Nes2,
+                section: RomSection::PrgRom,
+            })
+        );
     }
 }
@@ -1,30 +1,51 @@
-use super::{CartridgeReadTarget, Mapper, Mirroring};
+use super::{prg_ram::PrgRam, CartridgeReadTarget, Mapper, Mirroring};
 
 pub struct Mapper000 {
     prg_banks: u8,
     mirroring: Mirroring,
+    /// Only on a few boards, like the one of Family BASIC
+    prg_ram: PrgRam,
 }
 
+impl_stateful!(Mapper000 {
+    prg_banks,
+    mirroring,
+    prg_ram
+});
+
 impl Mapper000 {
-    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
         Self {
             prg_banks,
             mirroring,
+            prg_ram,
         }
     }
 }
 
 impl Mapper for Mapper000 {
     fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
-        let mask = if self.prg_banks > 1 { 0x7fff } else { 0x3fff };
-        CartridgeReadTarget::PrgRom((addr & mask) as usize)
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram.data().is_some() => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            _ => {
+                let mask = if self.prg_banks > 1 { 0x7fff } else { 0x3fff };
+                CartridgeReadTarget::PrgRom((addr & mask) as usize)
+            }
+        }
     }
 
     fn cpu_map_write(&mut self, addr: u16, data: u8) {
-        log::warn!(
-            "attempted to write {:#X} on PRG memory at {:#X}, but this is not supported by this mapper",
-            data, addr
-        );
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram.data().is_some() => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data)
+            }
+            _ => log::warn!(
+                "attempted to write {:#X} on PRG memory at {:#X}, but this is not supported by this mapper",
+                data, addr
+            ),
+        }
     }
 
     fn ppu_map_read(&mut self, addr: u16) -> usize {
@@ -40,7 +61,7 @@ impl Mapper for Mapper000 {
     }
 
     fn get_sram(&self) -> Option<&[u8]> {
-        None
+        self.prg_ram.data()
     }
 
     #[cfg(feature = "debugger")]
@@ -1,3 +1,4 @@
+use super::prg_ram::PrgRam;
 use super::{CartridgeReadTarget, Mapper, Mirroring};
 
 const CHR_MODE_MASK: u8 = 0b10000;
@@ -14,22 +15,27 @@ pub struct Mapper001 {
     load_register: u8,
     load_register_count: u8,
     control_register: u8,
-    ram_data: [u8; 0x2000],
+    prg_ram: PrgRam,
     mirroring: Mirroring,
 }
 
-impl Mapper001 {
-    pub fn new(prg_banks: u8, mirroring: Mirroring, save_data: Option<&[u8]>) -> Self {
-        let mut ram_data = [0u8; 0x2000];
-
-        // Load the save data
-        if let Some(save_data) = save_data {
-            ram_data
-                .iter_mut()
-                .zip(save_data.iter())
-                .for_each(|(r, s)| *r = *s)
-        };
+impl_stateful!(Mapper001 {
+    prg_banks,
+    prg_bank_selector_32,
+    prg_bank_selector_16_lo,
+    prg_bank_selector_16_hi,
+    chr_bank_selector_8,
+    chr_bank_selector_4_lo,
+    chr_bank_selector_4_hi,
+    load_register,
+    load_register_count,
+    control_register,
+    prg_ram,
+    mirroring
+});
 
+impl Mapper001 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
         Self {
             prg_banks,
             prg_bank_selector_32: 0,
@@ -41,7 +47,7 @@ impl Mapper001 {
             load_register: 0,
             load_register_count: 0,
             control_register: 0x0C,
-            ram_data,
+            prg_ram,
             mirroring,
         }
     }
@@ -52,7 +58,7 @@ impl Mapper for Mapper001 {
         match addr {
             0x6000..=0x7FFF => {
                 // Read from RAM
-                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize])
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
                 // TODO: windowed RAM?
             }
             _ => {
@@ -85,7 +91,7 @@ impl Mapper for Mapper001 {
     fn cpu_map_write(&mut self, addr: u16, data: u8) {
         if (0x6000..=0x7FFF).contains(&addr) {
             // Write to RAM
-            self.ram_data[(addr & 0x1FFF) as usize] = data; // TODO: windowed RAM?
+            self.prg_ram.write((addr & 0x1FFF) as usize, data); // TODO: windowed RAM?
             return;
         }
 
@@ -180,7 +186,7 @@ impl Mapper for Mapper001 {
     }
 
     fn get_sram(&self) -> Option<&[u8]> {
-        Some(&self.ram_data)
+        self.prg_ram.data()
     }
 
     #[cfg(feature = "debugger")]
@@ -202,4 +208,18 @@ impl Mapper for Mapper001 {
             }
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        if (self.control_register & CHR_MODE_MASK) != 0 {
+            let bank = if addr < 0x1000 {
+                self.chr_bank_selector_4_lo
+            } else {
+                self.chr_bank_selector_4_hi
+            };
+            Some((bank as usize) * 0x1000 + (addr & 0x0FFF) as usize)
+        } else {
+            Some((self.chr_bank_selector_8 as usize) * 0x2000 + (addr & 0x1FFF) as usize)
+        }
+    }
 }
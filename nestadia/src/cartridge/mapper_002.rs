@@ -6,6 +6,12 @@ pub struct Mapper002 {
     mirroring: Mirroring,
 }
 
+impl_stateful!(Mapper002 {
+    prg_bank_selector,
+    prg_banks,
+    mirroring
+});
+
 impl Mapper002 {
     pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
         Self {
@@ -6,6 +6,12 @@ pub struct Mapper003 {
     mirroring: Mirroring,
 }
 
+impl_stateful!(Mapper003 {
+    chr_bank_selector,
+    prg_banks,
+    mirroring
+});
+
 impl Mapper003 {
     pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
         Self {
@@ -51,4 +57,9 @@ impl Mapper for Mapper003 {
             _ => None,
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + (addr & 0x1fff) as usize)
+    }
 }
@@ -1,6 +1,4 @@
-use alloc::vec;
-use alloc::vec::Vec;
-
+use super::prg_ram::PrgRam;
 use super::{CartridgeReadTarget, Mapper, Mirroring};
 
 pub struct Mapper004 {
@@ -12,9 +10,7 @@ pub struct Mapper004 {
     chr_inverson: bool,
     register: [u8; 8],
     target_register: u8,
-    ram_data: Vec<u8>,
-
-    last_chr_bank_bit: bool, // Used to detect changed between sprites and background rendering for scanline counter
+    prg_ram: PrgRam,
 
     irq_enabled: bool,
     irq_active: bool,
@@ -23,8 +19,25 @@ pub struct Mapper004 {
     irq_latch: u8,
 }
 
+impl_stateful!(Mapper004 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    prg_mode,
+    chr_inverson,
+    register,
+    target_register,
+    prg_ram,
+    irq_enabled,
+    irq_active,
+    irq_reload,
+    irq_counter,
+    irq_latch
+});
+
 impl Mapper004 {
-    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
         Self {
             prg_banks,
             prg_bank_selector: [0u8, 0u8, 0u8, prg_banks * 2 - 1],
@@ -34,9 +47,7 @@ impl Mapper004 {
             chr_inverson: false,
             register: [0u8; 8],
             target_register: 0,
-            ram_data: vec![0u8; 0x2000],
-
-            last_chr_bank_bit: false,
+            prg_ram,
 
             irq_active: false,
             irq_enabled: false,
@@ -45,6 +56,11 @@ impl Mapper004 {
             irq_latch: 0,
         }
     }
+
+    /// 1KB CHR bank mapped at `addr` in the pattern tables
+    pub fn chr_bank(&self, addr: u16) -> u8 {
+        self.chr_bank_selector[((addr >> 10) & 0x07) as usize]
+    }
 }
 
 impl Mapper for Mapper004 {
@@ -52,7 +68,7 @@ impl Mapper for Mapper004 {
         match addr {
             0x6000..=0x7FFF => {
                 // Read from RAM
-                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize])
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
             }
             0x8000..=0x9FFF => CartridgeReadTarget::PrgRom(
                 (self.prg_bank_selector[0] as usize) * 0x2000 + (addr & 0x1FFF) as usize,
@@ -77,7 +93,7 @@ impl Mapper for Mapper004 {
         match addr {
             0x6000..=0x7FFF => {
                 // Write to RAM
-                self.ram_data[(addr & 0x1FFF) as usize] = data;
+                self.prg_ram.write((addr & 0x1FFF) as usize, data);
             }
             0x8000..=0x9FFF => {
                 if (addr & 0x01) == 0 {
@@ -106,14 +122,14 @@ impl Mapper for Mapper004 {
                         self.chr_bank_selector[2] = self.register[4];
                         self.chr_bank_selector[3] = self.register[5];
                         self.chr_bank_selector[4] = self.register[0] & 0xFE;
-                        self.chr_bank_selector[5] = self.register[0] + 1;
+                        self.chr_bank_selector[5] = self.register[0] | 0x01;
                         self.chr_bank_selector[6] = self.register[1] & 0xFE;
-                        self.chr_bank_selector[7] = self.register[1] + 1;
+                        self.chr_bank_selector[7] = self.register[1] | 0x01;
                     } else {
                         self.chr_bank_selector[0] = self.register[0] & 0xFE;
-                        self.chr_bank_selector[1] = self.register[0] + 1;
+                        self.chr_bank_selector[1] = self.register[0] | 0x01;
                         self.chr_bank_selector[2] = self.register[1] & 0xFE;
-                        self.chr_bank_selector[3] = self.register[1] + 1;
+                        self.chr_bank_selector[3] = self.register[1] | 0x01;
                         self.chr_bank_selector[4] = self.register[2];
                         self.chr_bank_selector[5] = self.register[3];
                         self.chr_bank_selector[6] = self.register[4];
@@ -166,23 +182,6 @@ impl Mapper for Mapper004 {
     }
 
     fn ppu_map_read(&mut self, addr: u16) -> usize {
-        let chr_bank_bit = addr & 0x1000 == 0x1000;
-        if !self.last_chr_bank_bit && chr_bank_bit {
-            // Rising edge of scanline counter
-            if self.irq_counter == 0 || self.irq_reload {
-                self.irq_counter = self.irq_latch;
-                self.irq_reload = false;
-            } else {
-                self.irq_counter -= 1;
-            };
-
-            if self.irq_counter == 0 && self.irq_enabled {
-                self.irq_active = true;
-            };
-        }
-
-        self.last_chr_bank_bit = chr_bank_bit;
-
         match addr {
             0x0000..=0x03FF => {
                 (self.chr_bank_selector[0] as usize) * 0x0400 + (addr & 0x03FF) as usize
@@ -226,16 +225,26 @@ impl Mapper for Mapper004 {
         self.mirroring
     }
 
-    fn irq_state(&self) -> bool {
+    fn irq_pending(&self) -> bool {
         self.irq_active
     }
 
-    fn irq_clear(&mut self) {
-        self.irq_active = false;
+    fn ppu_a12_edge(&mut self) {
+        // Clocks the scanline counter
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        };
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_active = true;
+        };
     }
 
     fn get_sram(&self) -> Option<&[u8]> {
-        Some(&self.ram_data)
+        self.prg_ram.data()
     }
 
     #[cfg(feature = "debugger")]
@@ -249,4 +258,9 @@ impl Mapper for Mapper004 {
             0xE000..=0xFFFF => Some(self.prg_bank_selector[3] / 2),
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank(addr) as usize) * 0x0400 + (addr & 0x03FF) as usize)
+    }
 }
@@ -0,0 +1,625 @@
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// CPU cycles without PPU reads after which the PPU is considered idle (VBlank or rendering off)
+const PPU_IDLE_CYCLES: u8 = 3;
+
+/// MMC5 (ExROM)
+/// http://wiki.nesdev.com/w/index.php/MMC5
+pub struct Mapper005 {
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect: [u8; 2],
+    exram_mode: u8,
+    name_table_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+
+    /// $5113-$5117
+    prg_registers: [u8; 5],
+
+    /// $5120-$512B, with the upper bits of $5130
+    chr_registers: [u16; 12],
+    chr_upper_bits: u8,
+
+    /// Whether $5128-$512B were written last. This set is used for accesses outside rendering.
+    last_chr_set_b: bool,
+
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    irq_target: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    multiplicand: u8,
+    multiplier: u8,
+
+    prg_ram: PrgRam,
+    exram: [u8; 0x400],
+
+    // PPU state snooped from its registers
+    sprites_8x16: bool,
+    rendering_enabled: bool,
+
+    // Scanline detection, from the PPU reads
+    in_frame: bool,
+    scanline: u8,
+    last_name_table_addr: u16,
+    name_table_addr_matches: u8,
+    ppu_idle_cycles: u8,
+    tile_fetches: u8,
+    chr_fetches: u8,
+
+    // Tile being fetched by the PPU
+    tile_ex_attribute: Option<u8>,
+    split_tile: Option<(u16, u16)>,
+    split_fine_y: u8,
+}
+
+impl_stateful!(Mapper005 {
+    prg_mode,
+    chr_mode,
+    prg_ram_protect,
+    exram_mode,
+    name_table_mapping,
+    fill_tile,
+    fill_attribute,
+    prg_registers,
+    chr_registers,
+    chr_upper_bits,
+    last_chr_set_b,
+    split_control,
+    split_scroll,
+    split_bank,
+    irq_target,
+    irq_enabled,
+    irq_pending,
+    multiplicand,
+    multiplier,
+    prg_ram,
+    exram,
+    sprites_8x16,
+    rendering_enabled,
+    in_frame,
+    scanline,
+    last_name_table_addr,
+    name_table_addr_matches,
+    ppu_idle_cycles,
+    tile_fetches,
+    chr_fetches,
+    tile_ex_attribute,
+    split_tile,
+    split_fine_y
+});
+
+impl Mapper005 {
+    pub const PRG_RAM_SIZE: usize = 0x10000;
+
+    pub fn new(prg_ram: PrgRam) -> Self {
+        Self {
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0; 2],
+            exram_mode: 0,
+            name_table_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+
+            prg_registers: [0, 0, 0, 0, 0xFF],
+
+            chr_registers: [0; 12],
+            chr_upper_bits: 0,
+            last_chr_set_b: false,
+
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+
+            irq_target: 0,
+            irq_enabled: false,
+            irq_pending: false,
+
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+
+            prg_ram,
+            exram: [0u8; 0x400],
+
+            sprites_8x16: false,
+            rendering_enabled: false,
+
+            in_frame: false,
+            scanline: 0,
+            last_name_table_addr: 0,
+            name_table_addr_matches: 0,
+            ppu_idle_cycles: 0,
+            tile_fetches: 0,
+            chr_fetches: 0,
+
+            tile_ex_attribute: None,
+            split_tile: None,
+            split_fine_y: 0,
+        }
+    }
+
+    /// 8KB bank mapped at `addr` ($6000-$FFFF), and whether it is ROM
+    fn prg_bank(&self, addr: u16) -> (u8, bool) {
+        if addr < 0x8000 {
+            return (self.prg_registers[0], false);
+        }
+
+        // Index of the 8KB slot in $8000-$FFFF
+        let slot = ((addr - 0x8000) >> 13) as u8;
+
+        // The low bits of the register are replaced by the slot in the larger banks
+        let (register, mask) = match (self.prg_mode, slot) {
+            (0, _) => (4, 0x03),
+            (1, 0..=1) | (2, 0..=1) => (2, 0x01),
+            (1, _) => (4, 0x01),
+            (2, 2) => (3, 0x00),
+            (2, _) => (4, 0x00),
+            (_, _) => (1 + slot as usize, 0x00),
+        };
+        let bank = self.prg_registers[register];
+
+        // $5117 always maps ROM
+        let rom = register == 4 || bank & 0x80 == 0x80;
+        ((bank & 0x7F & !mask) | (slot & mask), rom)
+    }
+
+    fn prg_ram_address(&self, bank: u8, addr: u16) -> usize {
+        ((bank & 0x07) as usize) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect[0] & 0x03 == 0x02 && self.prg_ram_protect[1] & 0x03 == 0x01
+    }
+
+    /// CHR address with the registers of set A ($5120-$5127) or B ($5128-$512B)
+    fn chr_address(&self, addr: u16, set_b: bool) -> usize {
+        let size = 0x2000 >> self.chr_mode;
+
+        let register = if !set_b {
+            let slot = addr as usize / size;
+            (slot + 1) * (8 >> self.chr_mode) - 1
+        } else if self.chr_mode == 0 {
+            11
+        } else {
+            // Set B only has registers for 4KB, mapped in both pattern tables
+            let slot = (addr & 0x0FFF) as usize / size;
+            8 + (slot + 1) * (8 >> self.chr_mode) - 1
+        };
+
+        (self.chr_registers[register] as usize) * size + (addr as usize % size)
+    }
+
+    /// Whether the PPU is fetching sprites, which happens after the 32 tiles of the scanline
+    fn fetching_sprites(&self) -> bool {
+        self.in_frame && self.tile_fetches == 32 && self.chr_fetches > 2
+    }
+
+    fn leave_frame(&mut self) {
+        self.in_frame = false;
+        self.name_table_addr_matches = 0;
+
+        // The next fetches will be the first tiles of the next frame
+        self.tile_fetches = 32;
+    }
+
+    /// The PPU reads the same name table address 3 times in a row at the end of every scanline
+    fn detect_scanline(&mut self, addr: u16) {
+        if addr == self.last_name_table_addr {
+            self.name_table_addr_matches = self.name_table_addr_matches.saturating_add(1);
+        } else {
+            self.name_table_addr_matches = 0;
+        }
+        self.last_name_table_addr = addr;
+
+        if self.name_table_addr_matches == 2 {
+            if self.in_frame {
+                self.scanline = self.scanline.wrapping_add(1);
+                if self.scanline == self.irq_target {
+                    self.irq_pending = true;
+                }
+            } else {
+                self.in_frame = true;
+                self.scanline = 0;
+            }
+
+            self.tile_fetches = 0;
+        }
+    }
+
+    /// Name table data for the split screen region, or `None` outside of it
+    fn read_split(&mut self, is_attribute: bool) -> Option<u8> {
+        if is_attribute {
+            let (row, column) = self.split_tile?;
+            let attribute = self.exram[(0x3C0 + (row >> 2) * 8 + (column >> 2)) as usize];
+            let shift = ((row & 0x02) << 1) | (column & 0x02);
+
+            // The PPU selects the quadrant from its own position, so every quadrant is the same
+            return Some(((attribute >> shift) & 0x03) * 0x55);
+        }
+
+        self.split_tile = None;
+
+        if self.split_control & 0x80 == 0 {
+            return None;
+        }
+
+        // The two last tile fetches are the first tiles of the next scanline
+        let (tile, scanline) = if self.tile_fetches <= 32 {
+            (self.tile_fetches + 1, self.scanline)
+        } else if self.in_frame {
+            (self.tile_fetches - 33, self.scanline.wrapping_add(1))
+        } else {
+            (self.tile_fetches - 33, 0)
+        };
+
+        let threshold = self.split_control & 0x1F;
+        let in_split = if self.split_control & 0x40 == 0x40 {
+            tile >= threshold
+        } else {
+            tile < threshold
+        };
+
+        if !in_split {
+            return None;
+        }
+
+        // The split region is a name table stored in ExRAM, which doesn't scroll horizontally
+        let y = (u16::from(self.split_scroll) + u16::from(scanline)) % 240;
+        let row = y >> 3;
+        let column = u16::from(tile & 0x1F);
+
+        self.split_tile = Some((row, column));
+        self.split_fine_y = (y & 0x07) as u8;
+
+        Some(self.exram[(row * 32 + column) as usize])
+    }
+}
+
+impl Mapper for Mapper005 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x5204 => {
+                let mut status = 0;
+                if self.irq_pending {
+                    status |= 0x80;
+                }
+                if self.in_frame {
+                    status |= 0x40;
+                }
+                CartridgeReadTarget::PrgRam(status)
+            }
+            0x5205 => CartridgeReadTarget::PrgRam(
+                (u16::from(self.multiplicand) * u16::from(self.multiplier)) as u8,
+            ),
+            0x5206 => CartridgeReadTarget::PrgRam(
+                ((u16::from(self.multiplicand) * u16::from(self.multiplier)) >> 8) as u8,
+            ),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => {
+                CartridgeReadTarget::PrgRam(self.exram[(addr & 0x3FF) as usize])
+            }
+            0x6000..=0xFFFF => match self.prg_bank(addr) {
+                (bank, true) => {
+                    CartridgeReadTarget::PrgRom((bank as usize) * 0x2000 + (addr & 0x1FFF) as usize)
+                }
+                (bank, false) => {
+                    CartridgeReadTarget::PrgRam(self.prg_ram.read(self.prg_ram_address(bank, addr)))
+                }
+            },
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_read_side_effects(&mut self, addr: u16) {
+        match addr {
+            0x5204 => self.irq_pending = false,
+
+            // Fetching the NMI vector means VBlank started
+            0xFFFA | 0xFFFB => self.leave_frame(),
+            _ => (),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5100 => self.prg_mode = data & 0x03,
+            0x5101 => self.chr_mode = data & 0x03,
+            0x5102 => self.prg_ram_protect[0] = data,
+            0x5103 => self.prg_ram_protect[1] = data,
+            0x5104 => self.exram_mode = data & 0x03,
+            0x5105 => self.name_table_mapping = data,
+            0x5106 => self.fill_tile = data,
+            0x5107 => self.fill_attribute = data & 0x03,
+            0x5113..=0x5117 => self.prg_registers[(addr - 0x5113) as usize] = data,
+            0x5120..=0x512B => {
+                self.chr_registers[(addr - 0x5120) as usize] =
+                    u16::from(data) | (u16::from(self.chr_upper_bits) << 8);
+                self.last_chr_set_b = addr >= 0x5128;
+            }
+            0x5130 => self.chr_upper_bits = data & 0x03,
+            0x5200 => self.split_control = data,
+            0x5201 => self.split_scroll = data,
+            0x5202 => self.split_bank = data,
+            0x5203 => self.irq_target = data,
+            0x5204 => self.irq_enabled = data & 0x80 == 0x80,
+            0x5205 => self.multiplicand = data,
+            0x5206 => self.multiplier = data,
+            0x5C00..=0x5FFF => {
+                // Used for rendering in modes 0 and 1, where it can only be written during rendering
+                match self.exram_mode {
+                    0 | 1 if !self.in_frame => self.exram[(addr & 0x3FF) as usize] = 0,
+                    0..=2 => self.exram[(addr & 0x3FF) as usize] = data,
+                    _ => (),
+                }
+            }
+            0x6000..=0xDFFF => {
+                if let (bank, false) = self.prg_bank(addr) {
+                    if self.prg_ram_writable() {
+                        let addr = self.prg_ram_address(bank, addr);
+                        self.prg_ram.write(addr, data);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn is_sram_write(&self, addr: u16) -> bool {
+        matches!(addr, 0x6000..=0xDFFF) && !self.prg_bank(addr).1
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.ppu_idle_cycles = 0;
+        self.chr_fetches = self.chr_fetches.saturating_add(1);
+
+        let sprites = self.fetching_sprites();
+
+        if self.rendering_enabled && !sprites {
+            if self.split_tile.is_some() {
+                let bank = self.split_bank as usize;
+                return bank * 0x1000 + (addr as usize & 0x0FF8) + self.split_fine_y as usize;
+            }
+
+            if let Some(ex_attribute) = self.tile_ex_attribute {
+                let bank = (ex_attribute & 0x3F) as usize | ((self.chr_upper_bits as usize) << 6);
+                return bank * 0x1000 + (addr as usize & 0x0FFF);
+            }
+        }
+
+        // With 8x16 sprites, the background uses set B. Otherwise, the last written set is used.
+        let set_b = if self.sprites_8x16 && self.in_frame {
+            !sprites
+        } else {
+            self.last_chr_set_b
+        };
+
+        self.chr_address(addr, set_b)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_address(addr, self.last_chr_set_b))
+    }
+
+    fn read_name_table(&mut self, addr: u16, vram: &[u8], _chr: &[u8]) -> Option<u8> {
+        let offset = addr & 0x3FF;
+        let is_attribute = offset >= 0x3C0;
+
+        self.ppu_idle_cycles = 0;
+        self.detect_scanline(addr);
+
+        if !is_attribute {
+            self.tile_fetches = self.tile_fetches.saturating_add(1);
+            self.chr_fetches = 0;
+            self.tile_ex_attribute = None;
+        }
+
+        if self.rendering_enabled {
+            if let Some(data) = self.read_split(is_attribute) {
+                return Some(data);
+            }
+
+            if self.exram_mode == 1 {
+                // Extended attributes: each tile has its own palette and 4KB CHR bank
+                if is_attribute {
+                    let palette = self.tile_ex_attribute.map_or(0, |ex| ex >> 6);
+                    return Some(palette * 0x55);
+                }
+
+                self.tile_ex_attribute = Some(self.exram[offset as usize]);
+            }
+        } else {
+            self.split_tile = None;
+            self.tile_ex_attribute = None;
+        }
+
+        let slot = (addr >> 10) & 0x03;
+        let data = match (self.name_table_mapping >> (slot * 2)) & 0x03 {
+            0 => vram[offset as usize],
+            1 => vram[0x400 + offset as usize],
+            2 if self.exram_mode <= 1 => self.exram[offset as usize],
+            2 => 0,
+            _ if is_attribute => self.fill_attribute * 0x55,
+            _ => self.fill_tile,
+        };
+
+        Some(data)
+    }
+
+    fn write_name_table(&mut self, addr: u16, data: u8, vram: &mut [u8]) -> bool {
+        let offset = (addr & 0x3FF) as usize;
+        let slot = (addr >> 10) & 0x03;
+
+        match (self.name_table_mapping >> (slot * 2)) & 0x03 {
+            0 => vram[offset] = data,
+            1 => vram[0x400 + offset] = data,
+            2 if self.exram_mode <= 1 => self.exram[offset] = data,
+            _ => (),
+        }
+
+        true
+    }
+
+    fn ppu_register_write(&mut self, addr: u16, data: u8) {
+        match addr & 0x2007 {
+            0x2000 => self.sprites_8x16 = data & 0x20 == 0x20,
+            0x2001 => {
+                self.rendering_enabled = data & 0x18 != 0;
+                if !self.rendering_enabled {
+                    self.leave_frame();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.ppu_idle_cycles >= PPU_IDLE_CYCLES {
+            self.leave_frame();
+        } else {
+            self.ppu_idle_cycles += 1;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        // Name tables are mapped by `read_name_table`, this is only an approximation
+        match self.name_table_mapping {
+            0x00 => Mirroring::OneScreenLower,
+            0x55 => Mirroring::OneScreenUpper,
+            0x50 => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_enabled && self.irq_pending
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr).0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(mapper: &mut Mapper005, addr: u16) -> u8 {
+        let data = match mapper.cpu_map_read(addr) {
+            CartridgeReadTarget::PrgRam(data) => data,
+            CartridgeReadTarget::PrgRom(rom_addr) => (rom_addr / 0x2000) as u8,
+        };
+        mapper.cpu_read_side_effects(addr);
+        data
+    }
+
+    /// Name table reads of the two first tiles of the next scanline, then the two unused reads
+    fn prefetch_tiles(mapper: &mut Mapper005, vram: &[u8]) {
+        for tile in 0..2u16 {
+            mapper.read_name_table(0x2000 + tile, vram, &[]);
+            mapper.read_name_table(0x23C0, vram, &[]);
+        }
+        mapper.read_name_table(0x2002, vram, &[]);
+        mapper.read_name_table(0x2002, vram, &[]);
+    }
+
+    fn render_scanline(mapper: &mut Mapper005, vram: &[u8]) {
+        for tile in 2..34u16 {
+            mapper.read_name_table(0x2000 + tile, vram, &[]);
+            mapper.read_name_table(0x23C0, vram, &[]);
+        }
+        prefetch_tiles(mapper, vram);
+    }
+
+    #[test]
+    fn prg_banking_modes() {
+        let mut mapper = Mapper005::new(PrgRam::new(Mapper005::PRG_RAM_SIZE, None));
+
+        // 8KB banks, $5117 is initially the last bank
+        mapper.cpu_map_write(0x5114, 0x81);
+        assert_eq!(read(&mut mapper, 0x8000), 1);
+        assert_eq!(read(&mut mapper, 0xE000), 0x7F);
+
+        // 16KB banks
+        mapper.cpu_map_write(0x5100, 1);
+        mapper.cpu_map_write(0x5115, 0x85);
+        assert_eq!(read(&mut mapper, 0x8000), 4);
+        assert_eq!(read(&mut mapper, 0xA000), 5);
+        assert_eq!(read(&mut mapper, 0xC000), 0x7E);
+
+        // PRG RAM at $8000 is only writable once unprotected
+        mapper.cpu_map_write(0x5115, 0x01);
+        mapper.cpu_map_write(0x8000, 0x42);
+        assert_eq!(read(&mut mapper, 0x8000), 0);
+        mapper.cpu_map_write(0x5102, 0x02);
+        mapper.cpu_map_write(0x5103, 0x01);
+        mapper.cpu_map_write(0x8000, 0x42);
+        assert_eq!(read(&mut mapper, 0x8000), 0x42);
+    }
+
+    #[test]
+    fn multiplier() {
+        let mut mapper = Mapper005::new(PrgRam::new(Mapper005::PRG_RAM_SIZE, None));
+        mapper.cpu_map_write(0x5205, 200);
+        mapper.cpu_map_write(0x5206, 100);
+
+        assert_eq!(read(&mut mapper, 0x5205), (20000u16 & 0xFF) as u8);
+        assert_eq!(read(&mut mapper, 0x5206), (20000u16 >> 8) as u8);
+    }
+
+    #[test]
+    fn scanline_irq() {
+        let vram = [0u8; 0x1000];
+        let mut mapper = Mapper005::new(PrgRam::new(Mapper005::PRG_RAM_SIZE, None));
+        mapper.ppu_register_write(0x2001, 0x18);
+        mapper.cpu_map_write(0x5203, 10);
+        mapper.cpu_map_write(0x5204, 0x80);
+
+        // Pre-render scanline
+        prefetch_tiles(&mut mapper, &vram);
+        assert!(!mapper.in_frame);
+
+        render_scanline(&mut mapper, &vram);
+        assert!(mapper.in_frame);
+
+        for _ in 0..9 {
+            render_scanline(&mut mapper, &vram);
+            assert!(!mapper.irq_pending());
+        }
+
+        render_scanline(&mut mapper, &vram);
+        assert!(mapper.irq_pending());
+        assert_eq!(read(&mut mapper, 0x5204), 0xC0);
+        assert!(!mapper.irq_pending());
+
+        // The PPU stops reading during VBlank
+        for _ in 0..PPU_IDLE_CYCLES + 1 {
+            mapper.cpu_clock();
+        }
+        assert_eq!(read(&mut mapper, 0x5204), 0x00);
+    }
+
+    #[test]
+    fn fill_mode() {
+        let vram = [0u8; 0x1000];
+        let mut mapper = Mapper005::new(PrgRam::new(Mapper005::PRG_RAM_SIZE, None));
+        mapper.cpu_map_write(0x5105, 0xFF);
+        mapper.cpu_map_write(0x5106, 0x12);
+        mapper.cpu_map_write(0x5107, 0x02);
+
+        assert_eq!(mapper.read_name_table(0x2400, &vram, &[]), Some(0x12));
+        assert_eq!(mapper.read_name_table(0x27C0, &vram, &[]), Some(0xAA));
+    }
+}
@@ -0,0 +1,118 @@
+use super::chr_latch::ChrLatch;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// MMC2 (PxROM)
+/// http://wiki.nesdev.com/w/index.php/MMC2
+pub struct Mapper009 {
+    prg_banks: u8,
+    prg_bank_selector: u8,
+    chr_latch: ChrLatch,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper009 {
+    prg_banks,
+    prg_bank_selector,
+    chr_latch,
+    mirroring
+});
+
+impl Mapper009 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: 0,
+            chr_latch: ChrLatch::new(true),
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Mapper009 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0x9FFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            // The last 3 8KB banks are fixed. Smaller ROMs than 32KB wrap around.
+            0xA000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                ((self.prg_banks as usize) * 0x4000).saturating_sub(0x6000)
+                    + (addr - 0xA000) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xA000..=0xAFFF => self.prg_bank_selector = data & 0x0F,
+            0xB000..=0xBFFF => self.chr_latch.set_bank(0, 0xFD, data),
+            0xC000..=0xCFFF => self.chr_latch.set_bank(0, 0xFE, data),
+            0xD000..=0xDFFF => self.chr_latch.set_bank(1, 0xFD, data),
+            0xE000..=0xEFFF => self.chr_latch.set_bank(1, 0xFE, data),
+            0xF000..=0xFFFF => {
+                self.mirroring = if data & 0x01 == 0x01 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        let mapped = self.chr_latch.map(addr);
+        self.chr_latch.update(addr);
+        mapped
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_latch.map(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0x9FFF => Some(self.prg_bank_selector),
+            0xA000..=0xFFFF => Some(
+                self.prg_banks.saturating_mul(2).saturating_sub(3) + ((addr - 0xA000) >> 13) as u8,
+            ),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_latch.map(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_prg_roms_under_32kb() {
+        let mapper = Mapper009::new(1, Mirroring::Vertical);
+        assert!(matches!(
+            mapper.cpu_map_read(0xA000),
+            CartridgeReadTarget::PrgRom(0x0000)
+        ));
+        assert!(matches!(
+            mapper.cpu_map_read(0xFFFF),
+            CartridgeReadTarget::PrgRom(0x5FFF)
+        ));
+
+        #[cfg(feature = "debugger")]
+        assert_eq!(mapper.get_prg_bank(0xE000), Some(2));
+    }
+}
@@ -0,0 +1,101 @@
+use super::chr_latch::ChrLatch;
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// MMC4 (FxROM). Same CHR latch as the MMC2, but with 16KB PRG banks and PRG RAM.
+/// http://wiki.nesdev.com/w/index.php/MMC4
+pub struct Mapper010 {
+    prg_banks: u8,
+    prg_bank_selector: u8,
+    chr_latch: ChrLatch,
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+}
+
+impl_stateful!(Mapper010 {
+    prg_banks,
+    prg_bank_selector,
+    chr_latch,
+    mirroring,
+    prg_ram
+});
+
+impl Mapper010 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: 0,
+            chr_latch: ChrLatch::new(false),
+            mirroring,
+            prg_ram,
+        }
+    }
+}
+
+impl Mapper for Mapper010 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xBFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize - 1) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.write((addr & 0x1FFF) as usize, data),
+            0xA000..=0xAFFF => self.prg_bank_selector = data & 0x0F,
+            0xB000..=0xBFFF => self.chr_latch.set_bank(0, 0xFD, data),
+            0xC000..=0xCFFF => self.chr_latch.set_bank(0, 0xFE, data),
+            0xD000..=0xDFFF => self.chr_latch.set_bank(1, 0xFD, data),
+            0xE000..=0xEFFF => self.chr_latch.set_bank(1, 0xFE, data),
+            0xF000..=0xFFFF => {
+                self.mirroring = if data & 0x01 == 0x01 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        let mapped = self.chr_latch.map(addr);
+        self.chr_latch.update(addr);
+        mapped
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_latch.map(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xBFFF => Some(self.prg_bank_selector),
+            0xC000..=0xFFFF => Some(self.prg_banks - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_latch.map(addr))
+    }
+}
@@ -0,0 +1,72 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Color Dreams: 32KB PRG banks and 8KB CHR banks selected by a single register
+/// http://wiki.nesdev.com/w/index.php/Color_Dreams
+pub struct Mapper011 {
+    prg_bank_selector: u8,
+    chr_bank_selector: u8,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper011 {
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring
+});
+
+impl Mapper011 {
+    pub fn new(mirroring: Mirroring) -> Self {
+        Self {
+            prg_bank_selector: 0,
+            chr_bank_selector: 0,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Mapper011 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x8000 + (addr & 0x7FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank_selector = data & 0x03;
+            self.chr_bank_selector = data >> 4;
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector as usize) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank_selector),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+}
@@ -0,0 +1,75 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// CPROM: 16KB of CHR RAM, the upper 4KB being switchable
+/// http://wiki.nesdev.com/w/index.php/CPROM
+pub struct Mapper013 {
+    chr_bank_selector: u8,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper013 {
+    chr_bank_selector,
+    mirroring
+});
+
+impl Mapper013 {
+    pub const CHR_RAM_SIZE: usize = 0x4000;
+
+    pub fn new(mirroring: Mirroring) -> Self {
+        Self {
+            chr_bank_selector: 0,
+            mirroring,
+        }
+    }
+
+    fn map_chr(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x0FFF => addr as usize,
+            _ => (self.chr_bank_selector as usize) * 0x1000 + (addr & 0x0FFF) as usize,
+        }
+    }
+}
+
+impl Mapper for Mapper013 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom((addr & 0x7FFF) as usize),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank_selector = data & 0x03;
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.map_chr(addr)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(self.map_chr(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(0),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(self.map_chr(addr))
+    }
+}
@@ -0,0 +1,150 @@
+use super::eeprom_24c02::Eeprom24C02;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Bandai FCG boards (FCG-1, FCG-2 and LZ93D50): 16KB PRG banking, 1KB CHR banking,
+/// a CPU cycle IRQ counter and a serial 24C02 EEPROM for the saves.
+/// The registers of the FCG boards are at $6000-$7FFF, those of the LZ93D50 at $8000-$FFFF;
+/// both ranges are decoded since iNES headers don't tell them apart.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_016
+pub struct Mapper016 {
+    prg_banks: u8,
+    prg_bank_selector: u8,
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    eeprom: Eeprom24C02,
+
+    irq_enabled: bool,
+    irq_active: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+}
+
+impl_stateful!(Mapper016 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    eeprom,
+    irq_enabled,
+    irq_active,
+    irq_counter,
+    irq_latch
+});
+
+impl Mapper016 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, save_data: Option<&[u8]>) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: 0,
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            eeprom: Eeprom24C02::new(save_data),
+
+            irq_enabled: false,
+            irq_active: false,
+            irq_counter: 0,
+            irq_latch: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper016 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            // The data line of the EEPROM is read on bit 4
+            0x6000..=0x7FFF => CartridgeReadTarget::PrgRam((self.eeprom.read() as u8) << 4),
+            0x8000..=0xBFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize - 1) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if addr < 0x6000 {
+            return;
+        }
+
+        match addr & 0x000F {
+            0x0..=0x7 => self.chr_bank_selector[(addr & 0x07) as usize] = data,
+            0x8 => self.prg_bank_selector = data & 0x0F,
+            0x9 => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                }
+            }
+            0xA => {
+                // The LZ93D50 reloads the counter from the latch. The FCG boards write the
+                // counter directly through $xxxB/$xxxC, but games enable the IRQ afterwards.
+                self.irq_enabled = data & 0x01 == 0x01;
+                self.irq_counter = self.irq_latch;
+                self.irq_active = false;
+            }
+            0xB => self.irq_latch = (self.irq_latch & 0xFF00) | u16::from(data),
+            0xC => self.irq_latch = (self.irq_latch & 0x00FF) | (u16::from(data) << 8),
+            0xD => self.eeprom.write(data & 0x20 == 0x20, data & 0x40 == 0x40),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        Some(self.eeprom.data())
+    }
+
+    // The EEPROM is written serially through $xxxD
+    fn is_sram_write(&self, addr: u16) -> bool {
+        addr >= 0x6000 && addr & 0x000F == 0x000D
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        // The counter is checked before being decremented, which both Famicom Jump II
+        // and Magical Taruruuto-kun 2 need
+        if self.irq_counter == 0 {
+            self.irq_active = true;
+        }
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_active
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xBFFF => Some(self.prg_bank_selector),
+            0xC000..=0xFFFF => Some(self.prg_banks - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
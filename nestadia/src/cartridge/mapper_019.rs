@@ -0,0 +1,215 @@
+use super::namco_163::Namco163;
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// CHR banks from this value select the console's VRAM instead of CHR ROM
+const VRAM_BANKS: u8 = 0xE0;
+
+/// Namco 163: 8KB PRG banking, 1KB CHR banking, name tables mappable to CHR ROM,
+/// a CPU cycle IRQ counter, 8KB of PRG RAM and the N163 wavetable audio.
+/// Pattern table banks selecting the console's VRAM are not emulated, they read CHR ROM.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_019
+pub struct Mapper019 {
+    prg_banks: u8,
+    prg_bank_selector: [u8; 3],
+    chr_bank_selector: [u8; 8],
+    name_table_selector: [u8; 4],
+    prg_ram: PrgRam,
+    ram_write_protect: u8,
+
+    audio: Namco163,
+    audio_enabled: bool,
+
+    irq_enabled: bool,
+    irq_counter: u16,
+}
+
+impl_stateful!(Mapper019 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    name_table_selector,
+    prg_ram,
+    ram_write_protect,
+    audio,
+    audio_enabled,
+    irq_enabled,
+    irq_counter
+});
+
+impl Mapper019 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        // Follows the mirroring of the header until the game sets the name tables
+        let name_table_selector = match mirroring {
+            Mirroring::Horizontal => [0xE0, 0xE0, 0xE1, 0xE1],
+            _ => [0xE0, 0xE1, 0xE0, 0xE1],
+        };
+
+        Self {
+            prg_banks,
+            prg_bank_selector: [0u8; 3],
+            chr_bank_selector: [0u8; 8],
+            name_table_selector,
+            prg_ram,
+            ram_write_protect: 0,
+
+            audio: Namco163::new(),
+            audio_enabled: true,
+
+            irq_enabled: false,
+            irq_counter: 0,
+        }
+    }
+
+    fn ram_writable(&self, addr: u16) -> bool {
+        // Writes are enabled by $4X in $F800, then each bit protects a 2KB window
+        self.ram_write_protect & 0xF0 == 0x40
+            && self.ram_write_protect & (1 << ((addr & 0x1FFF) >> 11)) == 0
+    }
+}
+
+impl Mapper for Mapper019 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x4800..=0x4FFF => CartridgeReadTarget::PrgRam(self.audio.peek_data()),
+            0x5000..=0x57FF => CartridgeReadTarget::PrgRam(self.irq_counter as u8),
+            0x5800..=0x5FFF => CartridgeReadTarget::PrgRam(
+                (self.irq_counter >> 8) as u8 | if self.irq_enabled { 0x80 } else { 0 },
+            ),
+            0x6000..=0x7FFF => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xDFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector[((addr - 0x8000) >> 13) as usize] as usize) * 0x2000
+                    + (addr & 0x1FFF) as usize,
+            ),
+            0xE000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize * 2 - 1) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_read_side_effects(&mut self, addr: u16) {
+        if let 0x4800..=0x4FFF = addr {
+            self.audio.increment_address();
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4800..=0x4FFF => self.audio.write_data(data),
+            0x5000..=0x57FF => self.irq_counter = (self.irq_counter & 0x7F00) | u16::from(data),
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (u16::from(data & 0x7F) << 8);
+                self.irq_enabled = data & 0x80 == 0x80;
+            }
+            0x6000..=0x7FFF if self.ram_writable(addr) => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data)
+            }
+            0x8000..=0xBFFF => self.chr_bank_selector[((addr - 0x8000) >> 11) as usize] = data,
+            0xC000..=0xDFFF => self.name_table_selector[((addr - 0xC000) >> 11) as usize] = data,
+            0xE000..=0xE7FF => {
+                self.prg_bank_selector[0] = data & 0x3F;
+                self.audio_enabled = data & 0x40 == 0;
+            }
+            0xE800..=0xEFFF => self.prg_bank_selector[1] = data & 0x3F,
+            0xF000..=0xF7FF => self.prg_bank_selector[2] = data & 0x3F,
+            0xF800..=0xFFFF => {
+                self.ram_write_protect = data;
+                self.audio.write_address(data);
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        // Unused, the name tables are handled by `read_name_table`
+        Mirroring::FourScreen
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn read_name_table(&mut self, addr: u16, vram: &[u8], chr: &[u8]) -> Option<u8> {
+        let bank = self.name_table_selector[((addr >> 10) & 0x03) as usize];
+        let offset = (addr & 0x03FF) as usize;
+
+        if bank >= VRAM_BANKS {
+            Some(vram[(bank as usize & 0x01) * 0x0400 + offset])
+        } else {
+            Some(chr[(bank as usize * 0x0400 + offset) % chr.len()])
+        }
+    }
+
+    fn write_name_table(&mut self, addr: u16, data: u8, vram: &mut [u8]) -> bool {
+        let bank = self.name_table_selector[((addr >> 10) & 0x03) as usize];
+
+        // Name tables mapped to CHR ROM can't be written
+        if bank >= VRAM_BANKS {
+            vram[(bank as usize & 0x01) * 0x0400 + (addr & 0x03FF) as usize] = data;
+        }
+
+        true
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+        }
+    }
+
+    fn clock_audio(&mut self) {
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        if self.audio_enabled {
+            self.audio.output()
+        } else {
+            0.0
+        }
+    }
+
+    fn audio_channel_count(&self) -> u8 {
+        Namco163::CHANNEL_COUNT
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        matches!(addr, 0x4800..=0x4FFF | 0xF800..=0xFFFF)
+    }
+
+    fn set_muted_audio_channels(&mut self, muted_channels: u8) {
+        self.audio.set_muted_channels(muted_channels);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_enabled && self.irq_counter == 0x7FFF
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xDFFF => Some(self.prg_bank_selector[((addr - 0x8000) >> 13) as usize]),
+            0xE000..=0xFFFF => Some(self.prg_banks * 2 - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
@@ -0,0 +1,111 @@
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// BNROM and NINA-001, which share mapper 34: 32KB PRG banks.
+/// BNROM selects the bank by writing to $8000-$FFFF and uses 8KB of CHR RAM.
+/// NINA-001 has its registers at $7FFD-$7FFF, over 8KB of PRG RAM, and 4KB CHR ROM banks.
+/// Only NINA-001 games have more than 8KB of CHR ROM, which tells the boards apart.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_034
+pub struct Mapper034 {
+    nina_001: bool,
+    prg_bank_selector: u8,
+    chr_bank_selector: [u8; 2],
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+}
+
+impl_stateful!(Mapper034 {
+    nina_001,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    prg_ram
+});
+
+impl Mapper034 {
+    pub fn new(nina_001: bool, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            nina_001,
+            prg_bank_selector: 0,
+            chr_bank_selector: [0, 1],
+            mirroring,
+            prg_ram,
+        }
+    }
+}
+
+impl Mapper for Mapper034 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF if self.nina_001 => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x8000 + (addr & 0x7FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.nina_001 => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data);
+
+                match addr {
+                    0x7FFD => self.prg_bank_selector = data & 0x01,
+                    0x7FFE => self.chr_bank_selector[0] = data & 0x0F,
+                    0x7FFF => self.chr_bank_selector[1] = data & 0x0F,
+                    _ => (),
+                }
+            }
+            0x8000..=0xFFFF if !self.nina_001 => self.prg_bank_selector = data,
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        if self.nina_001 {
+            (self.chr_bank_selector[(addr >> 12) as usize] as usize) * 0x1000
+                + (addr & 0x0FFF) as usize
+        } else {
+            addr as usize
+        }
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(addr as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        if self.nina_001 {
+            self.prg_ram.data()
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank_selector),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        if self.nina_001 {
+            Some(
+                (self.chr_bank_selector[(addr >> 12) as usize] as usize) * 0x1000
+                    + (addr & 0x0FFF) as usize,
+            )
+        } else {
+            Some(addr as usize)
+        }
+    }
+}
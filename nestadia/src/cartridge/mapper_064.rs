@@ -0,0 +1,237 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Tengen RAMBO-1: a MMC3 with 3 switchable PRG banks, an optional 1KB CHR mode for the first
+/// 2 banks, and an IRQ counter clocked either by the scanlines (PPU A12) or every 4 CPU cycles.
+/// http://wiki.nesdev.com/w/index.php/RAMBO-1
+pub struct Mapper064 {
+    prg_banks: u8,
+    mirroring: Mirroring,
+    register: [u8; 16],
+    target_register: u8,
+    prg_mode: bool,
+    chr_inversion: bool,
+    chr_1k_mode: bool,
+
+    irq_enabled: bool,
+    irq_active: bool,
+    irq_reload: bool,
+    irq_cycle_mode: bool,
+    irq_prescaler: u8,
+    irq_counter: u8,
+    irq_latch: u8,
+}
+
+impl_stateful!(Mapper064 {
+    prg_banks,
+    mirroring,
+    register,
+    target_register,
+    prg_mode,
+    chr_inversion,
+    chr_1k_mode,
+    irq_enabled,
+    irq_active,
+    irq_reload,
+    irq_cycle_mode,
+    irq_prescaler,
+    irq_counter,
+    irq_latch
+});
+
+impl Mapper064 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+        Self {
+            prg_banks,
+            mirroring,
+            register: [0u8; 16],
+            target_register: 0,
+            prg_mode: false,
+            chr_inversion: false,
+            chr_1k_mode: false,
+
+            irq_enabled: false,
+            irq_active: false,
+            irq_reload: false,
+            irq_cycle_mode: false,
+            irq_prescaler: 0,
+            irq_counter: 0,
+            irq_latch: 0,
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> u8 {
+        match (addr, self.prg_mode) {
+            (0x8000..=0x9FFF, false) | (0xA000..=0xBFFF, true) => self.register[6],
+            (0xA000..=0xBFFF, false) | (0xC000..=0xDFFF, true) => self.register[7],
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => self.register[15],
+            _ => self.prg_banks * 2 - 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: u16) -> u8 {
+        // The inversion swaps the 2 pattern tables
+        let addr = if self.chr_inversion {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+
+        match addr >> 10 {
+            0 if self.chr_1k_mode => self.register[0],
+            1 if self.chr_1k_mode => self.register[8],
+            2 if self.chr_1k_mode => self.register[1],
+            3 if self.chr_1k_mode => self.register[9],
+            0 => self.register[0] & 0xFE,
+            1 => self.register[0] | 0x01,
+            2 => self.register[1] & 0xFE,
+            3 => self.register[1] | 0x01,
+            bank => self.register[(bank - 2) as usize],
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_reload {
+            // A reload counts one more clock than the latch, except for latches of 0 and 1.
+            // Hard Drivin' relies on it.
+            self.irq_counter = self
+                .irq_latch
+                .wrapping_add(if self.irq_latch <= 1 { 1 } else { 2 });
+            self.irq_reload = false;
+        } else if self.irq_counter == 0 {
+            self.irq_counter = self.irq_latch.wrapping_add(1);
+        }
+
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_active = true;
+        }
+    }
+}
+
+impl Mapper for Mapper064 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank(addr) as usize) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match (addr, addr & 0x01 == 0) {
+            (0x8000..=0x9FFF, true) => {
+                self.target_register = data & 0x0F;
+                self.chr_1k_mode = data & 0x20 == 0x20;
+                self.prg_mode = data & 0x40 == 0x40;
+                self.chr_inversion = data & 0x80 == 0x80;
+            }
+            (0x8000..=0x9FFF, false) => self.register[self.target_register as usize] = data,
+            (0xA000..=0xBFFF, true) => {
+                self.mirroring = if data & 0x01 == 0x01 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                }
+            }
+            (0xC000..=0xDFFF, true) => self.irq_latch = data,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_cycle_mode = data & 0x01 == 0x01;
+                self.irq_prescaler = 0;
+                self.irq_reload = true;
+            }
+            (0xE000..=0xFFFF, true) => {
+                self.irq_enabled = false;
+                self.irq_active = false;
+            }
+            (0xE000..=0xFFFF, false) => self.irq_enabled = true,
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank(addr) as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank(addr) as usize) * 0x0400 + (addr & 0x03FF) as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_cycle_mode {
+            self.irq_prescaler = (self.irq_prescaler + 1) & 0x03;
+            if self.irq_prescaler == 0 {
+                self.clock_irq_counter();
+            }
+        }
+    }
+
+    fn ppu_a12_edge(&mut self) {
+        if !self.irq_cycle_mode {
+            self.clock_irq_counter();
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_active
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank(addr) as usize) * 0x0400 + (addr & 0x03FF) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_mode_irq() {
+        let mut mapper = Mapper064::new(8, Mirroring::Vertical);
+        mapper.cpu_map_write(0xC000, 2);
+        mapper.cpu_map_write(0xC001, 0x01);
+        mapper.cpu_map_write(0xE001, 0);
+
+        // Clocked every 4 cycles, the IRQ fires latch + 2 clocks after a reload
+        for _ in 0..(4 * 4 - 1) {
+            mapper.cpu_clock();
+        }
+        assert!(!mapper.irq_pending());
+
+        mapper.cpu_clock();
+        assert!(mapper.irq_pending());
+
+        mapper.cpu_map_write(0xE000, 0);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn chr_1k_mode() {
+        let mut mapper = Mapper064::new(8, Mirroring::Vertical);
+        for (register, bank) in [(0, 0x10), (8, 0x21), (1, 0x32), (9, 0x43)] {
+            mapper.cpu_map_write(0x8000, 0x20 | register);
+            mapper.cpu_map_write(0x8001, bank);
+        }
+
+        let banks: [usize; 4] =
+            [0x0000, 0x0400, 0x0800, 0x0C00].map(|addr| mapper.ppu_map_read(addr) / 0x0400);
+        assert_eq!(banks, [0x10, 0x21, 0x32, 0x43]);
+    }
+}
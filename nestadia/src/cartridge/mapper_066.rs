@@ -6,6 +6,12 @@ pub struct Mapper066 {
     mirroring: Mirroring,
 }
 
+impl_stateful!(Mapper066 {
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring
+});
+
 impl Mapper066 {
     pub fn new(mirroring: Mirroring) -> Self {
         Self {
@@ -51,4 +57,9 @@ impl Mapper for Mapper066 {
             _ => None,
         }
     }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + addr as usize)
+    }
 }
@@ -0,0 +1,168 @@
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Sunsoft-4: 16KB PRG banking, 2KB CHR banking, 8KB of PRG RAM, and name tables that can be
+/// mapped to 1KB banks of the last 128KB of CHR ROM (After Burner).
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_068
+pub struct Mapper068 {
+    prg_banks: u8,
+    prg_bank_selector: u8,
+    chr_bank_selector: [u8; 4],
+    name_table_selector: [u8; 2],
+    chr_name_tables: bool,
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+    ram_enabled: bool,
+}
+
+impl_stateful!(Mapper068 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    name_table_selector,
+    chr_name_tables,
+    mirroring,
+    prg_ram,
+    ram_enabled
+});
+
+impl Mapper068 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: 0,
+            chr_bank_selector: [0u8; 4],
+            name_table_selector: [0u8; 2],
+            chr_name_tables: false,
+            mirroring,
+            prg_ram,
+            ram_enabled: false,
+        }
+    }
+
+    /// Name table register used for the name table at `addr`, following the mirroring
+    fn name_table_register(&self, addr: u16) -> usize {
+        let slot = (addr >> 10) & 0x03;
+
+        match self.mirroring {
+            Mirroring::Horizontal => (slot >> 1) as usize,
+            Mirroring::OneScreenLower => 0,
+            Mirroring::OneScreenUpper => 1,
+            _ => (slot & 0x01) as usize,
+        }
+    }
+}
+
+impl Mapper for Mapper068 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xBFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize - 1) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data)
+            }
+            0x8000..=0xBFFF => self.chr_bank_selector[((addr - 0x8000) >> 12) as usize] = data,
+            // Only the banks in the last 128KB of CHR ROM can be used as name tables
+            0xC000..=0xDFFF => {
+                self.name_table_selector[((addr - 0xC000) >> 12) as usize] = data | 0x80
+            }
+            0xE000..=0xEFFF => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+                self.chr_name_tables = data & 0x10 == 0x10;
+            }
+            0xF000..=0xFFFF => {
+                self.prg_bank_selector = data & 0x0F;
+                self.ram_enabled = data & 0x10 == 0x10;
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 11) as usize] as usize) * 0x0800 + (addr & 0x07FF) as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn read_name_table(&mut self, addr: u16, _vram: &[u8], chr: &[u8]) -> Option<u8> {
+        if !self.chr_name_tables {
+            return None;
+        }
+
+        let bank = self.name_table_selector[self.name_table_register(addr)] as usize;
+        Some(chr[(bank * 0x0400 + (addr & 0x03FF) as usize) % chr.len()])
+    }
+
+    fn write_name_table(&mut self, _addr: u16, _data: u8, _vram: &mut [u8]) -> bool {
+        // Name tables mapped to CHR ROM can't be written
+        self.chr_name_tables
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xBFFF => Some(self.prg_bank_selector),
+            0xC000..=0xFFFF => Some(self.prg_banks - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 11) as usize] as usize) * 0x0800
+                + (addr & 0x07FF) as usize,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn chr_rom_name_tables() {
+        let chr: Vec<u8> = (0..0x40000).map(|i| (i / 0x0400) as u8).collect();
+        let mut mapper = Mapper068::new(8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+        mapper.cpu_map_write(0xC000, 0x01);
+        mapper.cpu_map_write(0xD000, 0x02);
+
+        assert_eq!(mapper.read_name_table(0x2000, &[], &chr), None);
+
+        // Horizontal mirroring, with the name tables in CHR ROM
+        mapper.cpu_map_write(0xE000, 0x11);
+        assert_eq!(mapper.read_name_table(0x2400, &[], &chr), Some(0x81));
+        assert_eq!(mapper.read_name_table(0x2800, &[], &chr), Some(0x82));
+        assert!(mapper.write_name_table(0x2800, 0, &mut []));
+    }
+}
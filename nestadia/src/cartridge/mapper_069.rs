@@ -0,0 +1,242 @@
+use super::prg_ram::PrgRam;
+use super::sunsoft_5b::Sunsoft5B;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Sunsoft FME-7 and 5B: 8KB PRG banking including $6000-$7FFF, which can map 8KB of PRG RAM,
+/// 1KB CHR banking and a CPU cycle IRQ counter, all written through a command register.
+/// The 5B (Gimmick!) adds its expansion audio, which is emulated for every board since the
+/// others never write to its registers.
+/// http://wiki.nesdev.com/w/index.php/Sunsoft_FME-7
+pub struct Mapper069 {
+    prg_banks: u8,
+    command: u8,
+    prg_bank_selector: [u8; 4],
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+    ram_selected: bool,
+    ram_enabled: bool,
+    audio: Sunsoft5B,
+
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_active: bool,
+    irq_counter: u16,
+}
+
+impl_stateful!(Mapper069 {
+    prg_banks,
+    command,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    prg_ram,
+    ram_selected,
+    ram_enabled,
+    audio,
+    irq_enabled,
+    irq_counter_enabled,
+    irq_active,
+    irq_counter
+});
+
+impl Mapper069 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            prg_banks,
+            command: 0,
+            prg_bank_selector: [0u8; 4],
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            prg_ram,
+            ram_selected: false,
+            ram_enabled: false,
+            audio: Sunsoft5B::new(),
+
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_active: false,
+            irq_counter: 0,
+        }
+    }
+
+    fn write_parameter(&mut self, data: u8) {
+        match self.command {
+            0x0..=0x7 => self.chr_bank_selector[self.command as usize] = data,
+            0x8 => {
+                self.prg_bank_selector[0] = data & 0x3F;
+                self.ram_selected = data & 0x40 == 0x40;
+                self.ram_enabled = data & 0x80 == 0x80;
+            }
+            0x9..=0xB => self.prg_bank_selector[(self.command - 0x8) as usize] = data & 0x3F,
+            0xC => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                }
+            }
+            0xD => {
+                self.irq_enabled = data & 0x01 == 0x01;
+                self.irq_counter_enabled = data & 0x80 == 0x80;
+                self.irq_active = false;
+            }
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | u16::from(data),
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | (u16::from(data) << 8),
+        }
+    }
+}
+
+impl Mapper for Mapper069 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected => {
+                if self.ram_enabled {
+                    CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+                } else {
+                    CartridgeReadTarget::PrgRam(0)
+                }
+            }
+            0x6000..=0xDFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector[((addr - 0x6000) >> 13) as usize] as usize) * 0x2000
+                    + (addr & 0x1FFF) as usize,
+            ),
+            0xE000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize * 2 - 1) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected && self.ram_enabled => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data)
+            }
+            0x8000..=0x9FFF => self.command = data & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(data),
+            0xC000..=0xDFFF => self.audio.write_register_select(data),
+            0xE000..=0xFFFF => self.audio.write_register_data(data),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        // Some boards have CHR RAM instead of ROM
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.irq_counter_enabled {
+            return;
+        }
+
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0xFFFF && self.irq_enabled {
+            self.irq_active = true;
+        }
+    }
+
+    fn clock_audio(&mut self) {
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn audio_channel_count(&self) -> u8 {
+        Sunsoft5B::CHANNEL_COUNT
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        addr >= 0xC000
+    }
+
+    fn set_muted_audio_channels(&mut self, muted_channels: u8) {
+        self.audio.set_muted_channels(muted_channels);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_active
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected => None,
+            0x6000..=0xDFFF => Some(self.prg_bank_selector[((addr - 0x6000) >> 13) as usize]),
+            0xE000..=0xFFFF => Some(self.prg_banks * 2 - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_command(mapper: &mut Mapper069, command: u8, parameter: u8) {
+        mapper.cpu_map_write(0x8000, command);
+        mapper.cpu_map_write(0xA000, parameter);
+    }
+
+    #[test]
+    fn irq_fires_when_the_counter_wraps() {
+        let mut mapper = Mapper069::new(8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+        write_command(&mut mapper, 0xE, 0x02);
+        write_command(&mut mapper, 0xF, 0x00);
+        write_command(&mut mapper, 0xD, 0x81);
+
+        mapper.cpu_clock();
+        mapper.cpu_clock();
+        assert!(!mapper.irq_pending());
+
+        mapper.cpu_clock();
+        assert!(mapper.irq_pending());
+
+        write_command(&mut mapper, 0xD, 0x00);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn ram_at_6000() {
+        let mut mapper = Mapper069::new(8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+        write_command(&mut mapper, 0x8, 0x03);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6000),
+            CartridgeReadTarget::PrgRom(0x6000)
+        ));
+
+        write_command(&mut mapper, 0x8, 0xC0);
+        mapper.cpu_map_write(0x6000, 0x42);
+        assert!(matches!(
+            mapper.cpu_map_read(0x6000),
+            CartridgeReadTarget::PrgRam(0x42)
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Camerica / Codemasters boards: UxROM-like 16KB PRG banks, selected by writing to
+/// $C000-$FFFF. The BF9097 board of Fire Hawk adds one-screen mirroring, selected at
+/// $9000-$9FFF, which is enabled once the game writes there.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_071
+pub struct Mapper071 {
+    prg_bank_selector: u8,
+    prg_banks: u8,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper071 {
+    prg_bank_selector,
+    prg_banks,
+    mirroring
+});
+
+impl Mapper071 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+        Self {
+            prg_bank_selector: 0,
+            prg_banks,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Mapper071 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xBFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize - 1) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x9000..=0x9FFF => {
+                self.mirroring = if data & 0x10 == 0x10 {
+                    Mirroring::OneScreenUpper
+                } else {
+                    Mirroring::OneScreenLower
+                }
+            }
+            0xC000..=0xFFFF => self.prg_bank_selector = data & 0x0F,
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(addr as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xBFFF => Some(self.prg_bank_selector),
+            0xC000..=0xFFFF => Some(self.prg_banks - 1),
+            _ => None,
+        }
+    }
+}
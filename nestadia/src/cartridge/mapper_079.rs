@@ -0,0 +1,73 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// AVE NINA-03 and NINA-06: 32KB PRG banks and 8KB CHR banks, selected by writing to
+/// $4100-$5FFF at addresses matching $4100 with the mask $E100.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_079
+pub struct Mapper079 {
+    prg_bank_selector: u8,
+    chr_bank_selector: u8,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper079 {
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring
+});
+
+impl Mapper079 {
+    pub fn new(mirroring: Mirroring) -> Self {
+        Self {
+            prg_bank_selector: 0,
+            chr_bank_selector: 0,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Mapper079 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector as usize) * 0x8000 + (addr & 0x7FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if addr & 0xE100 == 0x4100 {
+            self.prg_bank_selector = (data >> 3) & 0x01;
+            self.chr_bank_selector = data & 0x07;
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector as usize) * 0x2000 + addr as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank_selector),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + addr as usize)
+    }
+}
@@ -0,0 +1,183 @@
+use super::prg_ram::PrgRam;
+use super::vrc7_audio::Vrc7Audio;
+use super::vrc_irq::VrcIrq;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Konami VRC7: 8KB PRG banking, 1KB CHR banking, 8KB of PRG RAM, the VRC IRQ and FM audio.
+/// The second register of each pair is selected by A4 on the VRC7a (Lagrange Point) and by A3
+/// on the VRC7b (Tiny Toon Adventures 2), so both are decoded.
+/// http://wiki.nesdev.com/w/index.php/VRC7
+pub struct Mapper085 {
+    prg_banks: u8,
+    prg_bank_selector: [u8; 3],
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+    ram_enabled: bool,
+    irq: VrcIrq,
+    audio: Vrc7Audio,
+    audio_silenced: bool,
+}
+
+impl_stateful!(Mapper085 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    prg_ram,
+    ram_enabled,
+    irq,
+    audio,
+    audio_silenced
+});
+
+impl Mapper085 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: [0u8; 3],
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            prg_ram,
+            ram_enabled: false,
+            irq: VrcIrq::new(),
+            audio: Vrc7Audio::new(),
+            audio_silenced: false,
+        }
+    }
+
+    /// Register written by the CPU, as $x000 or $x008
+    fn register(addr: u16) -> u16 {
+        if addr & 0x0018 != 0 {
+            (addr & 0xF000) | 0x0008
+        } else {
+            addr & 0xF000
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xDFFF => self.prg_bank_selector[((addr - 0x8000) >> 13) as usize],
+            _ => self.prg_banks * 2 - 1,
+        }
+    }
+}
+
+impl Mapper for Mapper085 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank(addr) as usize) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match Self::register(addr) {
+            _ if addr & 0xF030 == 0x9010 => self.audio.write_register_select(data),
+            _ if addr & 0xF030 == 0x9030 => self.audio.write_register_data(data),
+            0x6000..=0x7FFF if self.ram_enabled => {
+                self.prg_ram.write((addr & 0x1FFF) as usize, data)
+            }
+            0x8000 => self.prg_bank_selector[0] = data & 0x3F,
+            0x8008 => self.prg_bank_selector[1] = data & 0x3F,
+            0x9000 => self.prg_bank_selector[2] = data & 0x3F,
+            register @ 0xA000..=0xD008 => {
+                let bank = (((register >> 12) - 0xA) * 2 + ((register >> 3) & 0x01)) as usize;
+                self.chr_bank_selector[bank] = data;
+            }
+            0xE000 => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+                self.ram_enabled = data & 0x80 == 0x80;
+
+                self.audio_silenced = data & 0x40 == 0x40;
+                if self.audio_silenced {
+                    self.audio.reset();
+                }
+            }
+            0xE008 => self.irq.write_latch(data),
+            0xF000 => self.irq.write_control(data),
+            0xF008 => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        // Lagrange Point has CHR RAM
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn clock_audio(&mut self) {
+        if !self.audio_silenced {
+            self.audio.clock();
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        if self.audio_silenced {
+            0.0
+        } else {
+            self.audio.output()
+        }
+    }
+
+    fn audio_channel_count(&self) -> u8 {
+        Vrc7Audio::CHANNEL_COUNT
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        matches!(addr & 0xF030, 0x9010 | 0x9030)
+    }
+
+    fn set_muted_audio_channels(&mut self, muted_channels: u8) {
+        self.audio.set_muted_channels(muted_channels);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
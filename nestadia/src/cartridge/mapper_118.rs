@@ -0,0 +1,107 @@
+use super::mapper_004::Mapper004;
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// TxSROM: a MMC3 whose mirroring is controlled by the CHR banks instead of $A000.
+/// Bit 7 of the 1KB CHR bank mapped in the first pattern table at the same offset as a name
+/// table selects its page of the console's VRAM.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_118
+pub struct Mapper118 {
+    mmc3: Mapper004,
+}
+
+impl_stateful!(Mapper118 { mmc3 });
+
+impl Mapper118 {
+    pub fn new(prg_banks: u8, prg_ram: PrgRam) -> Self {
+        Self {
+            mmc3: Mapper004::new(prg_banks, Mirroring::Vertical, prg_ram),
+        }
+    }
+
+    fn vram_addr(&self, addr: u16) -> usize {
+        let page = (self.mmc3.chr_bank(addr & 0x0C00) >> 7) as usize;
+        page * 0x0400 + (addr & 0x03FF) as usize
+    }
+}
+
+impl Mapper for Mapper118 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        self.mmc3.cpu_map_read(addr)
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        self.mmc3.cpu_map_write(addr, data);
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.mmc3.ppu_map_read(addr)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        self.mmc3.ppu_map_write(addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        // Unused, the name tables are handled by `read_name_table`
+        Mirroring::FourScreen
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.mmc3.get_sram()
+    }
+
+    fn read_name_table(&mut self, addr: u16, vram: &[u8], _chr: &[u8]) -> Option<u8> {
+        Some(vram[self.vram_addr(addr)])
+    }
+
+    fn write_name_table(&mut self, addr: u16, data: u8, vram: &mut [u8]) -> bool {
+        vram[self.vram_addr(addr)] = data;
+        true
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.mmc3.irq_pending()
+    }
+
+    fn ppu_a12_edge(&mut self) {
+        self.mmc3.ppu_a12_edge();
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        self.mmc3.get_prg_bank(addr)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        self.mmc3.peek_chr_map(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chr_banks_select_the_name_tables() {
+        let mut mapper = Mapper118::new(8, PrgRam::new(0x2000, None));
+        let mut vram = [0u8; 0x1000];
+
+        // R0 (2KB at $0000) uses the second page, R1 (2KB at $0800) the first one
+        for (register, bank) in [(0, 0x80), (1, 0x00)] {
+            mapper.cpu_map_write(0x8000, register);
+            mapper.cpu_map_write(0x8001, bank);
+        }
+
+        mapper.write_name_table(0x2400, 0x12, &mut vram);
+        mapper.write_name_table(0x2800, 0x34, &mut vram);
+        assert_eq!(vram[0x0400], 0x12);
+        assert_eq!(vram[0x0000], 0x34);
+
+        // With the CHR inversion, the name tables follow the 1KB banks R2-R5
+        mapper.cpu_map_write(0x8000, 0x80 | 2);
+        mapper.cpu_map_write(0x8001, 0x80);
+        assert_eq!(mapper.read_name_table(0x2000, &vram, &[]), Some(0x12));
+    }
+}
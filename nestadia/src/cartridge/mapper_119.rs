@@ -0,0 +1,97 @@
+use super::mapper_004::Mapper004;
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// TQROM: a MMC3 with both 64KB of CHR ROM and 8KB of CHR RAM.
+/// Bit 6 of a CHR bank selects the CHR RAM.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_119
+pub struct Mapper119 {
+    mmc3: Mapper004,
+}
+
+impl_stateful!(Mapper119 { mmc3 });
+
+impl Mapper119 {
+    pub const CHR_RAM_SIZE: usize = 0x2000;
+
+    pub fn new(prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            mmc3: Mapper004::new(prg_banks, mirroring, prg_ram),
+        }
+    }
+}
+
+impl Mapper for Mapper119 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        self.mmc3.cpu_map_read(addr)
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        self.mmc3.cpu_map_write(addr, data);
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.mmc3.ppu_map_read(addr)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some((self.mmc3.chr_bank(addr) as usize) * 0x0400 + (addr & 0x03FF) as usize)
+    }
+
+    fn maps_chr_ram(&self, addr: u16) -> bool {
+        self.mmc3.chr_bank(addr) & 0x40 == 0x40
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mmc3.mirroring()
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.mmc3.get_sram()
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.mmc3.irq_pending()
+    }
+
+    fn ppu_a12_edge(&mut self) {
+        self.mmc3.ppu_a12_edge();
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        self.mmc3.get_prg_bank(addr)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        self.mmc3.peek_chr_map(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::super::Cartridge;
+
+    #[test]
+    fn chr_banks_select_rom_or_ram() {
+        let mut rom = vec![0u8; 16 + 2 * 0x4000 + 0x10000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 8, 0x70, 0x70]);
+        rom[16 + 2 * 0x4000 + 0x0400] = 0xAA; // First byte of CHR ROM bank 1
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+
+        // R2 (1KB at $1000) maps CHR ROM bank 1, R3 (1KB at $1400) CHR RAM bank 1
+        for (register, bank) in [(2, 0x01), (3, 0x41)] {
+            cartridge.write_prg_mem(0x8000, register);
+            cartridge.write_prg_mem(0x8001, bank);
+        }
+
+        cartridge.write_chr_mem(0x1000, 0x12);
+        cartridge.write_chr_mem(0x1400, 0x34);
+        assert_eq!(cartridge.read_chr_mem(0x1000), 0xAA);
+        assert_eq!(cartridge.read_chr_mem(0x1400), 0x34);
+        assert_eq!(cartridge.chr_ram[0x0400], 0x34);
+    }
+}
@@ -0,0 +1,62 @@
+use super::mapper_004::Mapper004;
+use super::prg_ram::PrgRam;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// DxROM (Namcot 108 / Tengen MIMIC-1): the predecessor of the MMC3, with only its bank select
+/// and bank data registers. There is no IRQ, no mirroring control, and the PRG and CHR modes
+/// are fixed to 0.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_206
+pub struct Mapper206 {
+    mmc3: Mapper004,
+}
+
+impl_stateful!(Mapper206 { mmc3 });
+
+impl Mapper206 {
+    pub fn new(prg_banks: u8, mirroring: Mirroring) -> Self {
+        Self {
+            // The DxROM boards have no PRG RAM
+            mmc3: Mapper004::new(prg_banks, mirroring, PrgRam::new(0, None)),
+        }
+    }
+}
+
+impl Mapper for Mapper206 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        self.mmc3.cpu_map_read(addr)
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match (addr, addr & 0x01 == 0) {
+            (0x8000..=0x9FFF, true) => self.mmc3.cpu_map_write(addr, data & 0x07),
+            (0x8000..=0x9FFF, false) => self.mmc3.cpu_map_write(addr, data & 0x3F),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.mmc3.ppu_map_read(addr)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        self.mmc3.ppu_map_write(addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mmc3.mirroring()
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        self.mmc3.get_prg_bank(addr)
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        self.mmc3.peek_chr_map(addr)
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Namco 175 and 340: the banking of the Namco 163 without its IRQ, audio and name table
+/// mapping. The 175 has fixed mirroring and 2KB of PRG RAM, the 340 has no PRG RAM and
+/// selects the mirroring with the first PRG bank.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_210
+pub struct Mapper210 {
+    namco_340: bool,
+    prg_banks: u8,
+    prg_bank_selector: [u8; 3],
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    ram_data: Vec<u8>,
+    ram_enabled: bool,
+}
+
+impl_stateful!(Mapper210 {
+    namco_340,
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    ram_data,
+    ram_enabled
+});
+
+impl Mapper210 {
+    pub fn new(
+        namco_340: bool,
+        prg_banks: u8,
+        mirroring: Mirroring,
+        save_data: Option<&[u8]>,
+    ) -> Self {
+        let mut ram_data = vec![0u8; 0x0800];
+
+        // Load the save data
+        if let Some(save_data) = save_data {
+            ram_data
+                .iter_mut()
+                .zip(save_data.iter())
+                .for_each(|(r, s)| *r = *s)
+        };
+
+        Self {
+            namco_340,
+            prg_banks,
+            prg_bank_selector: [0u8; 3],
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            ram_data,
+            ram_enabled: false,
+        }
+    }
+}
+
+impl Mapper for Mapper210 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            // The 2KB of RAM are mirrored
+            0x6000..=0x7FFF if self.ram_enabled => {
+                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x07FF) as usize])
+            }
+            0x8000..=0xDFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank_selector[((addr - 0x8000) >> 13) as usize] as usize) * 0x2000
+                    + (addr & 0x1FFF) as usize,
+            ),
+            0xE000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_banks as usize * 2 - 1) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => self.ram_data[(addr & 0x07FF) as usize] = data,
+            0x8000..=0xBFFF => self.chr_bank_selector[((addr - 0x8000) >> 11) as usize] = data,
+            0xC000..=0xC7FF if !self.namco_340 => self.ram_enabled = data & 0x01 == 0x01,
+            0xE000..=0xE7FF => {
+                self.prg_bank_selector[0] = data & 0x3F;
+
+                if self.namco_340 {
+                    self.mirroring = match data >> 6 {
+                        0 => Mirroring::OneScreenLower,
+                        1 => Mirroring::Vertical,
+                        2 => Mirroring::Horizontal,
+                        _ => Mirroring::OneScreenUpper,
+                    };
+                }
+            }
+            0xE800..=0xEFFF => self.prg_bank_selector[1] = data & 0x3F,
+            0xF000..=0xF7FF => self.prg_bank_selector[2] = data & 0x3F,
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        if self.namco_340 {
+            None
+        } else {
+            Some(&self.ram_data)
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xDFFF => Some(self.prg_bank_selector[((addr - 0x8000) >> 13) as usize]),
+            0xE000..=0xFFFF => Some(self.prg_banks * 2 - 1),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
@@ -0,0 +1,133 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Active Enterprises boards (Action 52, Cheetahmen II): the banks are selected by the address
+/// written to, not the data. Action 52 has 3 PRG chips of 512KB, selected as chips 0, 1 and 3;
+/// chip 2 doesn't exist and reads as open bus. There are also 4 nibbles of RAM at $4020-$5FFF.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_228
+pub struct Mapper228 {
+    /// 16KB banks at $8000 and $C000, or `None` when the missing chip is selected
+    prg_bank_selector: Option<[u16; 2]>,
+    chr_bank_selector: u8,
+    mirroring: Mirroring,
+    ram_data: [u8; 4],
+}
+
+impl_stateful!(Mapper228 {
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    ram_data
+});
+
+impl Mapper228 {
+    pub fn new() -> Self {
+        Self {
+            prg_bank_selector: Some([0, 1]),
+            chr_bank_selector: 0,
+            mirroring: Mirroring::Vertical,
+            ram_data: [0u8; 4],
+        }
+    }
+}
+
+impl Mapper for Mapper228 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match (addr, self.prg_bank_selector) {
+            (0x4020..=0x5FFF, _) => {
+                CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x03) as usize])
+            }
+            (0x8000..=0xFFFF, Some(prg_bank_selector)) => CartridgeReadTarget::PrgRom(
+                (prg_bank_selector[((addr - 0x8000) >> 14) as usize] as usize) * 0x4000
+                    + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4020..=0x5FFF => self.ram_data[(addr & 0x03) as usize] = data & 0x0F,
+            0x8000..=0xFFFF => {
+                // A~[..MH HPPP PPO. CCCC], D~[.... ..cc]: mirroring, chip, page, 16KB mode, CHR bank
+                self.chr_bank_selector = (((addr & 0x0F) << 2) as u8) | (data & 0x03);
+                self.mirroring = if addr & 0x2000 == 0x2000 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+
+                let chip = match (addr >> 11) & 0x03 {
+                    2 => {
+                        self.prg_bank_selector = None;
+                        return;
+                    }
+                    3 => 2,
+                    chip => chip,
+                };
+                let bank = (chip << 5) | ((addr >> 6) & 0x1F);
+
+                self.prg_bank_selector = if addr & 0x20 == 0x20 {
+                    Some([bank, bank])
+                } else {
+                    Some([bank & !0x01, bank | 0x01])
+                };
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector as usize) * 0x2000 + addr as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => self
+                .prg_bank_selector
+                .map(|banks| banks[((addr - 0x8000) >> 14) as usize] as u8),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some((self.chr_bank_selector as usize) * 0x2000 + addr as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_the_missing_chip() {
+        let mut mapper = Mapper228::new();
+
+        // Chip 3, page 1, 16KB mode
+        mapper.cpu_map_write(0x8000 | (3 << 11) | (1 << 6) | 0x20, 0);
+        assert!(matches!(
+            mapper.cpu_map_read(0xC000),
+            CartridgeReadTarget::PrgRom(0x104000)
+        ));
+
+        // Chip 2
+        mapper.cpu_map_write(0x8000 | (2 << 11), 0);
+        assert!(matches!(
+            mapper.cpu_map_read(0x8000),
+            CartridgeReadTarget::PrgRam(0)
+        ));
+    }
+}
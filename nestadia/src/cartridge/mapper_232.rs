@@ -0,0 +1,78 @@
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Camerica Quattro (BF9096): a 64KB outer block selected at $8000-$BFFF, containing an
+/// UxROM-like 16KB bank at $8000 selected at $C000-$FFFF, and its last bank at $C000.
+/// http://wiki.nesdev.com/w/index.php/INES_Mapper_232
+pub struct Mapper232 {
+    outer_bank_selector: u8,
+    inner_bank_selector: u8,
+    mirroring: Mirroring,
+}
+
+impl_stateful!(Mapper232 {
+    outer_bank_selector,
+    inner_bank_selector,
+    mirroring
+});
+
+impl Mapper232 {
+    pub fn new(mirroring: Mirroring) -> Self {
+        Self {
+            outer_bank_selector: 0,
+            inner_bank_selector: 0,
+            mirroring,
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> u8 {
+        let inner_bank = match addr {
+            0x8000..=0xBFFF => self.inner_bank_selector,
+            _ => 3,
+        };
+
+        self.outer_bank_selector * 4 + inner_bank
+    }
+}
+
+impl Mapper for Mapper232 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank(addr) as usize) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000..=0xBFFF => self.outer_bank_selector = (data >> 3) & 0x03,
+            0xC000..=0xFFFF => self.inner_bank_selector = data & 0x03,
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(addr as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// CPU cycles needed by the drive to transfer a byte (about 96.4 kbit/s)
+const BYTE_TRANSFER_CYCLES: u32 = 150;
+
+/// CPU cycles needed by the drive to get back to the start of the disk
+const SPIN_UP_CYCLES: u32 = 50000;
+
+/// How long a disk side reads as ejected after being inserted, so games notice the switch
+const INSERT_DELAY_CYCLES: u32 = crate::CPU_FREQUENCY;
+
+/// RAM adapter of the Famicom Disk System: 32KB of PRG RAM at $6000-$DFFF, the 8KB BIOS at
+/// $E000-$FFFF, 8KB of CHR RAM, a timer IRQ and the disk drive I/O registers.
+/// http://wiki.nesdev.com/w/index.php/Family_Computer_Disk_System
+pub struct MapperFds {
+    ram_data: Vec<u8>,
+    mirroring: Mirroring,
+    disk_registers_enabled: bool,
+
+    // Timer IRQ
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_repeat: bool,
+    irq_enabled: bool,
+    timer_irq: bool,
+
+    // Disk drive
+    sides: Vec<Vec<u8>>,
+    inserted_side: Option<u8>,
+    insert_delay: u32,
+
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    disk_ready: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+
+    transfer_complete: bool,
+    read_data: u8,
+    write_data: u8,
+
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+    position: usize,
+    delay: u32,
+}
+
+impl_stateful!(MapperFds {
+    ram_data,
+    mirroring,
+    disk_registers_enabled,
+    irq_reload,
+    irq_counter,
+    irq_repeat,
+    irq_enabled,
+    timer_irq,
+    sides,
+    inserted_side,
+    insert_delay,
+    motor_on,
+    reset_transfer,
+    read_mode,
+    crc_control,
+    disk_ready,
+    disk_irq_enabled,
+    disk_irq,
+    transfer_complete,
+    read_data,
+    write_data,
+    end_of_head,
+    scanning,
+    gap_ended,
+    position,
+    delay
+});
+
+impl MapperFds {
+    /// `sides` are the raw disk sides, with their gaps. The first side is inserted.
+    pub fn new(sides: Vec<Vec<u8>>) -> Self {
+        Self {
+            ram_data: vec![0u8; 0x8000],
+            mirroring: Mirroring::Vertical,
+            disk_registers_enabled: false,
+
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_repeat: false,
+            irq_enabled: false,
+            timer_irq: false,
+
+            sides,
+            inserted_side: Some(0),
+            insert_delay: 0,
+
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            disk_ready: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+
+            transfer_complete: false,
+            read_data: 0,
+            write_data: 0,
+
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            position: 0,
+            delay: 0,
+        }
+    }
+
+    fn disk_inserted(&self) -> bool {
+        self.inserted_side.is_some() && self.insert_delay == 0
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+
+        if self.irq_counter == 0 {
+            self.timer_irq = true;
+            self.irq_counter = self.irq_reload;
+
+            if !self.irq_repeat {
+                self.irq_enabled = false;
+            }
+        } else {
+            self.irq_counter -= 1;
+        }
+    }
+
+    fn clock_drive(&mut self) {
+        if self.insert_delay > 0 {
+            self.insert_delay -= 1;
+            return;
+        }
+
+        let side = match self.inserted_side {
+            Some(side) if self.motor_on => side as usize,
+            _ => {
+                self.end_of_head = true;
+                self.scanning = false;
+                return;
+            }
+        };
+
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+
+        if self.end_of_head {
+            // The head goes back to the start of the disk
+            self.delay = SPIN_UP_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        let disk = &mut self.sides[side];
+
+        if self.read_mode {
+            let data = disk[self.position];
+            let mut irq = self.disk_irq_enabled;
+
+            if !self.disk_ready {
+                self.gap_ended = false;
+            } else if data != 0 && !self.gap_ended {
+                // Start mark of a block, which is transferred without an IRQ
+                self.gap_ended = true;
+                irq = false;
+            }
+
+            if self.gap_ended {
+                self.transfer_complete = true;
+                self.read_data = data;
+                self.disk_irq |= irq;
+            }
+        } else {
+            if !self.crc_control {
+                self.transfer_complete = true;
+                self.disk_irq |= self.disk_irq_enabled;
+            }
+
+            // CRCs are written as zeroes, as they are never checked
+            disk[self.position] = if self.disk_ready && !self.crc_control {
+                self.write_data
+            } else {
+                0
+            };
+            self.gap_ended = false;
+        }
+
+        self.position += 1;
+        if self.position >= disk.len() {
+            self.motor_on = false;
+        } else {
+            self.delay = BYTE_TRANSFER_CYCLES;
+        }
+    }
+}
+
+impl Mapper for MapperFds {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x4030 => {
+                let mut status = 0;
+                if self.timer_irq {
+                    status |= 0x01;
+                }
+                if self.transfer_complete {
+                    status |= 0x02;
+                }
+                if self.end_of_head {
+                    status |= 0x40;
+                }
+                CartridgeReadTarget::PrgRam(status)
+            }
+            0x4031 => CartridgeReadTarget::PrgRam(self.read_data),
+            0x4032 => {
+                let mut status = 0x40;
+                if !self.disk_inserted() {
+                    // Not inserted, not ready and write protected
+                    status |= 0x07;
+                } else if !self.scanning {
+                    status |= 0x02;
+                }
+                CartridgeReadTarget::PrgRam(status)
+            }
+            0x4033 => CartridgeReadTarget::PrgRam(0x80), // Battery is good
+            0x6000..=0xDFFF => CartridgeReadTarget::PrgRam(self.ram_data[(addr - 0x6000) as usize]),
+            0xE000..=0xFFFF => CartridgeReadTarget::PrgRom((addr & 0x1FFF) as usize),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_read_side_effects(&mut self, addr: u16) {
+        match addr {
+            0x4030 => {
+                self.timer_irq = false;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4031 => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            _ => (),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4023 => {
+                self.disk_registers_enabled = data & 0x01 == 0x01;
+                if !self.disk_registers_enabled {
+                    self.irq_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            0x4020..=0x4026 if !self.disk_registers_enabled => (),
+            0x4020 => self.irq_reload = (self.irq_reload & 0xFF00) | u16::from(data),
+            0x4021 => self.irq_reload = (self.irq_reload & 0x00FF) | (u16::from(data) << 8),
+            0x4022 => {
+                self.irq_repeat = data & 0x01 == 0x01;
+                self.irq_enabled = data & 0x02 == 0x02;
+
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = data;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.motor_on = data & 0x01 == 0x01;
+                self.reset_transfer = data & 0x02 == 0x02;
+                self.read_mode = data & 0x04 == 0x04;
+                self.mirroring = if data & 0x08 == 0x08 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.crc_control = data & 0x10 == 0x10;
+                self.disk_ready = data & 0x40 == 0x40;
+                self.disk_irq_enabled = data & 0x80 == 0x80;
+                self.disk_irq = false;
+            }
+            0x6000..=0xDFFF => self.ram_data[(addr - 0x6000) as usize] = data,
+            _ => (), // External connector and sound registers
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(addr as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn cpu_clock(&mut self) {
+        self.clock_timer();
+        self.clock_drive();
+    }
+
+    fn disk_side_count(&self) -> u8 {
+        self.sides.len() as u8
+    }
+
+    fn inserted_disk_side(&self) -> Option<u8> {
+        self.inserted_side
+    }
+
+    fn insert_disk_side(&mut self, side: Option<u8>) {
+        match side {
+            Some(side) if usize::from(side) >= self.sides.len() => {
+                log::warn!(
+                    "Attempted to insert disk side {}, which doesn't exist",
+                    side
+                );
+            }
+            Some(side) => {
+                self.inserted_side = Some(side);
+                self.insert_delay = INSERT_DELAY_CYCLES;
+            }
+            None => self.inserted_side = None,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0xE000..=0xFFFF => Some(0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(mapper: &mut MapperFds, addr: u16) -> u8 {
+        let data = match mapper.cpu_map_read(addr) {
+            CartridgeReadTarget::PrgRam(data) => data,
+            CartridgeReadTarget::PrgRom(_) => unreachable!(),
+        };
+        mapper.cpu_read_side_effects(addr);
+        data
+    }
+
+    #[test]
+    fn timer_irq_fires_after_reload() {
+        let mut mapper = MapperFds::new(vec![vec![0u8; 16]]);
+        mapper.cpu_map_write(0x4023, 0x01);
+        mapper.cpu_map_write(0x4020, 100);
+        mapper.cpu_map_write(0x4021, 0);
+        mapper.cpu_map_write(0x4022, 0x02);
+
+        for _ in 0..100 {
+            mapper.cpu_clock();
+        }
+        assert!(!mapper.irq_pending());
+
+        mapper.cpu_clock();
+        assert!(mapper.irq_pending());
+
+        assert_eq!(read(&mut mapper, 0x4030) & 0x01, 0x01);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn reads_blocks_after_the_gap() {
+        let mut side = vec![0u8; 64];
+        side[32..35].copy_from_slice(&[0x80, 0x01, 0x2A]);
+
+        let mut mapper = MapperFds::new(vec![side]);
+        mapper.cpu_map_write(0x4023, 0x01);
+        mapper.cpu_map_write(0x4025, 0x65); // Motor on, read mode, disk ready
+
+        let mut transferred = Vec::new();
+        while transferred.len() < 3 {
+            mapper.cpu_clock();
+
+            if read(&mut mapper, 0x4030) & 0x02 == 0x02 {
+                transferred.push(read(&mut mapper, 0x4031));
+            }
+        }
+
+        assert_eq!(transferred, [0x80, 0x01, 0x2A]);
+        assert_eq!(read(&mut mapper, 0x4032) & 0x07, 0x00);
+    }
+
+    #[test]
+    fn inserted_side_reads_as_ejected_for_a_while() {
+        let mut mapper = MapperFds::new(vec![vec![0u8; 16], vec![0u8; 16]]);
+        mapper.insert_disk_side(Some(1));
+        assert_eq!(read(&mut mapper, 0x4032) & 0x01, 0x01);
+
+        for _ in 0..INSERT_DELAY_CYCLES {
+            mapper.cpu_clock();
+        }
+        assert_eq!(read(&mut mapper, 0x4032) & 0x01, 0x00);
+        assert_eq!(mapper.inserted_disk_side(), Some(1));
+    }
+}
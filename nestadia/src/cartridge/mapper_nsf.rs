@@ -0,0 +1,344 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::nsf_header::{NsfHeader, SoundChips};
+use super::sunsoft_5b::Sunsoft5B;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Where the built-in player is mapped. Nothing else lives there on an NSF cartridge.
+const DRIVER_ADDRESS: u16 = 0x4100;
+
+const BANK_SIZE: usize = 0x1000;
+
+/// Mapper playing NSF files. The music data is mapped at $8000-$FFFF (with optional 4KB
+/// bankswitching) and a small player program, mapped at $4100, calls the init routine of the
+/// selected song on reset, then the play routine on every NMI.
+/// http://wiki.nesdev.com/w/index.php/NSF
+pub struct MapperNsf {
+    header: NsfHeader,
+    track: u8,
+
+    prg_banks: [u8; 8],
+    ram_data: Vec<u8>,
+    driver: Vec<u8>,
+    vectors: [u8; 6],
+
+    sunsoft_5b: Option<Sunsoft5B>,
+}
+
+impl_stateful!(MapperNsf {
+    track,
+    prg_banks,
+    ram_data,
+    vectors,
+    sunsoft_5b
+});
+
+impl MapperNsf {
+    pub fn new(header: NsfHeader) -> Self {
+        if !(header.extra_sound_chips - SoundChips::SUNSOFT_5B).is_empty() {
+            log::warn!(
+                "NSF uses unsupported expansion audio: {:?}",
+                header.extra_sound_chips - SoundChips::SUNSOFT_5B
+            );
+        }
+
+        let sunsoft_5b = if header.extra_sound_chips.contains(SoundChips::SUNSOFT_5B) {
+            Some(Sunsoft5B::new())
+        } else {
+            None
+        };
+
+        let mut mapper = Self {
+            track: header.starting_song.saturating_sub(1),
+            prg_banks: Self::initial_banks(&header),
+            ram_data: vec![0u8; 0x2000],
+            driver: Vec::new(),
+            vectors: [0u8; 6],
+            sunsoft_5b,
+            header,
+        };
+
+        mapper.build_driver();
+        mapper
+    }
+
+    /// Lays out the song data so that the banks are aligned on 4KB
+    pub fn prg_memory(header: &NsfHeader, data: &[u8]) -> Vec<u8> {
+        let padding = if header.is_bankswitched() {
+            (header.load_address & 0x0FFF) as usize
+        } else {
+            header.load_address.saturating_sub(0x8000) as usize
+        };
+
+        let len = padding + data.len();
+        let mut prg_memory = vec![0u8; len.max(0x8000) + (BANK_SIZE - len % BANK_SIZE) % BANK_SIZE];
+        prg_memory[padding..len].copy_from_slice(data);
+        prg_memory
+    }
+
+    fn initial_banks(header: &NsfHeader) -> [u8; 8] {
+        if header.is_bankswitched() {
+            header.bankswitch_init
+        } else {
+            [0, 1, 2, 3, 4, 5, 6, 7]
+        }
+    }
+
+    /// Assembles the player program
+    #[rustfmt::skip]
+    fn build_driver(&mut self) {
+        let [init_low, init_high] = self.header.init_address.to_le_bytes();
+        let [play_low, play_high] = self.header.play_address.to_le_bytes();
+
+        // NMI: call the play routine, preserving the registers
+        let nmi = DRIVER_ADDRESS;
+        let mut code = vec![
+            0x48, // PHA
+            0x8A, // TXA
+            0x48, // PHA
+            0x98, // TYA
+            0x48, // PHA
+            0x20, play_low, play_high, // JSR play
+            0x68, // PLA
+            0xA8, // TAY
+            0x68, // PLA
+            0xAA, // TAX
+            0x68, // PLA
+            0x40, // RTI
+        ];
+
+        let irq = DRIVER_ADDRESS + code.len() as u16;
+        code.push(0x40); // RTI
+
+        let reset = DRIVER_ADDRESS + code.len() as u16;
+        code.extend_from_slice(&[
+            0x78, // SEI
+            0xD8, // CLD
+            0xA2, 0xFF, // LDX #$FF
+            0x9A, // TXS
+            // Wait for the PPU to warm up before enabling NMIs
+            0x2C, 0x02, 0x20, // BIT $2002
+            0x10, 0xFB, // BPL -5
+            0x2C, 0x02, 0x20, // BIT $2002
+            0x10, 0xFB, // BPL -5
+            // Clear the internal RAM
+            0xA9, 0x00, // LDA #$00
+            0xAA, // TAX
+            0x95, 0x00, // STA $00,X
+            0x9D, 0x00, 0x01, // STA $0100,X
+            0x9D, 0x00, 0x02, // STA $0200,X
+            0x9D, 0x00, 0x03, // STA $0300,X
+            0x9D, 0x00, 0x04, // STA $0400,X
+            0x9D, 0x00, 0x05, // STA $0500,X
+            0x9D, 0x00, 0x06, // STA $0600,X
+            0x9D, 0x00, 0x07, // STA $0700,X
+            0xE8, // INX
+            0xD0, 0xE6, // BNE -26
+            // Initialize the APU
+            0xA2, 0x13, // LDX #$13
+            0x9D, 0x00, 0x40, // STA $4000,X
+            0xCA, // DEX
+            0x10, 0xFA, // BPL -6
+            0xA9, 0x0F, // LDA #$0F
+            0x8D, 0x15, 0x40, // STA $4015
+            0xA9, 0x40, // LDA #$40
+            0x8D, 0x17, 0x40, // STA $4017
+        ]);
+
+        if self.header.is_bankswitched() {
+            for (i, &bank) in self.header.bankswitch_init.iter().enumerate() {
+                code.extend_from_slice(&[
+                    0xA9, bank, // LDA #bank
+                    0x8D, 0xF8 + i as u8, 0x5F, // STA $5FF8+i
+                ]);
+            }
+        }
+
+        let idle = DRIVER_ADDRESS + code.len() as u16 + 12;
+        let [idle_low, idle_high] = idle.to_le_bytes();
+        code.extend_from_slice(&[
+            0xA9, self.track, // LDA #track
+            0xA2, 0x00, // LDX #$00 (NTSC)
+            0x20, init_low, init_high, // JSR init
+            // Enable NMIs, which call the play routine every frame
+            0xA9, 0x80, // LDA #$80
+            0x8D, 0x00, 0x20, // STA $2000
+            0x4C, idle_low, idle_high, // JMP idle
+        ]);
+
+        let [nmi_low, nmi_high] = nmi.to_le_bytes();
+        let [reset_low, reset_high] = reset.to_le_bytes();
+        let [irq_low, irq_high] = irq.to_le_bytes();
+
+        self.driver = code;
+        self.vectors = [nmi_low, nmi_high, reset_low, reset_high, irq_low, irq_high];
+    }
+}
+
+impl Mapper for MapperNsf {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0xFFFA..=0xFFFF => CartridgeReadTarget::PrgRam(self.vectors[(addr - 0xFFFA) as usize]),
+            0x8000..=0xFFFF => {
+                let bank = self.prg_banks[((addr - 0x8000) as usize) / BANK_SIZE] as usize;
+                CartridgeReadTarget::PrgRom(bank * BANK_SIZE + (addr as usize & (BANK_SIZE - 1)))
+            }
+            0x6000..=0x7FFF => CartridgeReadTarget::PrgRam(self.ram_data[(addr & 0x1FFF) as usize]),
+            _ => {
+                let offset = addr.wrapping_sub(DRIVER_ADDRESS) as usize;
+                CartridgeReadTarget::PrgRam(self.driver.get(offset).copied().unwrap_or(0))
+            }
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5FF8..=0x5FFF if self.header.is_bankswitched() => {
+                self.prg_banks[(addr - 0x5FF8) as usize] = data;
+            }
+            0x6000..=0x7FFF => self.ram_data[(addr & 0x1FFF) as usize] = data,
+            0xC000..=0xDFFF => {
+                if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+                    sunsoft_5b.write_register_select(data);
+                }
+            }
+            0xE000..=0xFFFF => {
+                if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+                    sunsoft_5b.write_register_data(data);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        Some(addr as usize)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn clock_audio(&mut self) {
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            sunsoft_5b.clock();
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.sunsoft_5b
+            .as_ref()
+            .map(Sunsoft5B::output)
+            .unwrap_or(0.0)
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        self.sunsoft_5b.is_some() && addr >= 0xC000
+    }
+
+    fn audio_channel_count(&self) -> u8 {
+        if self.sunsoft_5b.is_some() {
+            Sunsoft5B::CHANNEL_COUNT
+        } else {
+            0
+        }
+    }
+
+    fn set_muted_audio_channels(&mut self, muted_channels: u8) {
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            sunsoft_5b.set_muted_channels(muted_channels);
+        }
+    }
+
+    fn select_track(&mut self, track: u8) {
+        self.track = track;
+        self.prg_banks = Self::initial_banks(&self.header);
+        self.ram_data.iter_mut().for_each(|data| *data = 0);
+
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            *sunsoft_5b = Sunsoft5B::new();
+        }
+
+        self.build_driver();
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_banks[((addr - 0x8000) as usize) / BANK_SIZE]),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use crate::{Emulator, LoadedImage};
+
+    /// NSF whose init routine stores the song number at $6000
+    /// and whose play routine increments $6001
+    fn test_nsf() -> Vec<u8> {
+        let mut nsf = vec![0u8; 0x80];
+        nsf[..5].copy_from_slice(b"NESM\x1A");
+        nsf[0x05] = 1; // Version
+        nsf[0x06] = 3; // Total songs
+        nsf[0x07] = 1; // Starting song
+        nsf[0x08..0x0A].copy_from_slice(&0x8000u16.to_le_bytes()); // Load
+        nsf[0x0A..0x0C].copy_from_slice(&0x8000u16.to_le_bytes()); // Init
+        nsf[0x0C..0x0E].copy_from_slice(&0x8004u16.to_le_bytes()); // Play
+        nsf[0x0E..0x12].copy_from_slice(b"Test");
+        nsf[0x6E..0x70].copy_from_slice(&16639u16.to_le_bytes());
+
+        nsf.extend_from_slice(&[
+            0x8D, 0x00, 0x60, // STA $6000
+            0x60, // RTS
+            0xEE, 0x01, 0x60, // INC $6001
+            0x60, // RTS
+        ]);
+
+        nsf
+    }
+
+    fn run_frames(emulator: &mut Emulator, frames: usize) {
+        for _ in 0..frames {
+            while emulator.clock().is_none() {}
+        }
+    }
+
+    #[test]
+    fn calls_init_then_play() {
+        let mut emulator = Emulator::new_nsf(&test_nsf()).unwrap();
+        assert_eq!(emulator.nsf_header().unwrap().song_name, "Test");
+
+        run_frames(&mut emulator, 10);
+        assert_eq!(emulator.cartridge.read_prg_mem(0x6000), 0);
+        let play_calls = emulator.cartridge.read_prg_mem(0x6001);
+        assert!(play_calls > 5);
+
+        emulator.select_nsf_track(2);
+        run_frames(&mut emulator, 10);
+        assert_eq!(emulator.cartridge.read_prg_mem(0x6000), 2);
+        assert!(emulator.cartridge.read_prg_mem(0x6001) > 5);
+    }
+
+    #[test]
+    fn is_recognized_by_the_rom_loader() {
+        let emulator = Emulator::new(&test_nsf(), None).unwrap();
+        assert!(matches!(
+            emulator.loaded_image(),
+            LoadedImage::Nsf(header) if header.total_songs == 3
+        ));
+    }
+}
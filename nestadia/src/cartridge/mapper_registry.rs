@@ -0,0 +1,140 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use super::{Mapper, Mirroring};
+
+/// Board of a ROM, as described by its header, given to the mapper factories
+#[derive(Debug, Clone, Copy)]
+pub struct BoardInfo {
+    pub mapper_id: u16,
+    pub submapper_id: u8,
+
+    // Sizes are in bytes
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+
+    pub mirroring: Mirroring,
+    pub battery: bool,
+}
+
+/// Creates the mapper of a board. The save data of the cartridge is given if any.
+pub type MapperFactory = fn(&BoardInfo, Option<&[u8]>) -> Box<dyn Mapper>;
+
+/// Mappers implemented outside of the crate, consulted before the built-in ones when loading
+/// a ROM
+#[derive(Default, Clone)]
+pub struct MapperRegistry {
+    factories: Vec<(u16, Option<u8>, MapperFactory)>,
+}
+
+impl MapperRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the mapper of the boards with `mapper_id` and `submapper_id`, or any
+    /// submapper with `None`. It replaces any mapper previously registered with the same ids.
+    pub fn register(&mut self, mapper_id: u16, submapper_id: Option<u8>, factory: MapperFactory) {
+        self.factories
+            .retain(|(id, submapper, _)| (*id, *submapper) != (mapper_id, submapper_id));
+        self.factories.push((mapper_id, submapper_id, factory));
+    }
+
+    /// Finds the mapper of a board, preferring the ones registered for its submapper
+    pub fn find(&self, mapper_id: u16, submapper_id: u8) -> Option<MapperFactory> {
+        let find = |submapper: Option<u8>| {
+            self.factories
+                .iter()
+                .find(|(id, s, _)| *id == mapper_id && *s == submapper)
+                .map(|(_, _, factory)| *factory)
+        };
+
+        find(Some(submapper_id)).or_else(|| find(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::super::{Cartridge, CartridgeReadTarget, RomParserError};
+    use super::*;
+
+    /// Board with a single register at $8000, read back at $6000
+    struct RegisterBoard {
+        register: u8,
+        mirroring: Mirroring,
+    }
+
+    impl Mapper for RegisterBoard {
+        fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+            match addr {
+                0x6000..=0x7FFF => CartridgeReadTarget::PrgRam(self.register),
+                _ => CartridgeReadTarget::PrgRom((addr & 0x7FFF) as usize),
+            }
+        }
+
+        fn cpu_map_write(&mut self, addr: u16, data: u8) {
+            if addr >= 0x8000 {
+                self.register = data;
+            }
+        }
+
+        fn ppu_map_read(&mut self, addr: u16) -> usize {
+            addr as usize
+        }
+
+        fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+            Some(addr as usize)
+        }
+
+        fn mirroring(&self) -> Mirroring {
+            self.mirroring
+        }
+
+        fn get_sram(&self) -> Option<&[u8]> {
+            None
+        }
+
+        #[cfg(feature = "debugger")]
+        fn get_prg_bank(&self, _addr: u16) -> Option<u8> {
+            None
+        }
+    }
+
+    impl_stateful!(RegisterBoard { register });
+
+    fn register_board(board: &BoardInfo, _save_data: Option<&[u8]>) -> Box<dyn Mapper> {
+        Box::new(RegisterBoard {
+            register: board.submapper_id,
+            mirroring: board.mirroring,
+        })
+    }
+
+    #[test]
+    fn registered_mapper_is_loaded() {
+        // NES 2.0 mapper 1000, submapper 3
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..9].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x80, 0xE8, 0x33]);
+        assert!(matches!(
+            Cartridge::load(&rom, None),
+            Err(RomParserError::MapperNotImplemented {
+                mapper_id: 1000,
+                submapper_id: 3,
+                ..
+            })
+        ));
+
+        let mut registry = MapperRegistry::new();
+        registry.register(1000, None, register_board);
+        assert!(registry.find(1000, 3).is_some());
+        assert!(registry.find(1000 + 1, 3).is_none());
+
+        let mut cartridge = Cartridge::load_with_registry(&rom, None, &registry).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 3);
+
+        cartridge.write_prg_mem(0x8000, 0x42);
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x42);
+    }
+}
@@ -0,0 +1,243 @@
+use super::prg_ram::PrgRam;
+use super::vrc_irq::VrcIrq;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Konami VRC4 (mappers 21, 23 and 25): 8KB PRG banking with a swappable fixed bank,
+/// 1KB CHR banking, 8KB of PRG RAM and the VRC IRQ.
+/// Also handles the VRC2, a VRC4 without the IRQ, the PRG swap mode and the one screen
+/// mirroring. The VRC2a (mapper 22) ignores the low bit of the CHR banks; the VRC2b and VRC2c
+/// use mappers 23 and 25 and never write the registers missing from the VRC2.
+/// The boards wire the 2 register select pins to different CPU address lines; as no game writes
+/// to addresses mixing both wirings of a mapper, they are ORed together to decode any of them.
+/// http://wiki.nesdev.com/w/index.php/VRC2_and_VRC4
+pub struct MapperVrc4 {
+    prg_banks: u8,
+    prg_bank_selector: [u8; 2],
+    prg_swap_mode: bool,
+    chr_bank_selector: [u16; 8],
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+    irq: VrcIrq,
+    vrc2a: bool,
+
+    /// Address lines of the register select pins 0 and 1
+    register_lines: (u16, u16),
+}
+
+impl_stateful!(MapperVrc4 {
+    prg_banks,
+    prg_bank_selector,
+    prg_swap_mode,
+    chr_bank_selector,
+    mirroring,
+    prg_ram,
+    irq,
+    vrc2a,
+    register_lines
+});
+
+impl MapperVrc4 {
+    pub fn new(mapper_id: u16, prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        let register_lines = match mapper_id {
+            21 => (0x0042, 0x0084), // VRC4a: A1, A2. VRC4c: A6, A7
+            22 => (0x0002, 0x0001), // VRC2a: A1, A0
+            23 => (0x0005, 0x000A), // VRC4f: A0, A1. VRC4e: A2, A3
+            _ => (0x000A, 0x0005),  // VRC4b: A1, A0. VRC4d: A3, A2
+        };
+
+        Self {
+            prg_banks,
+            prg_bank_selector: [0u8; 2],
+            prg_swap_mode: false,
+            chr_bank_selector: [0u16; 8],
+            mirroring,
+            prg_ram,
+            irq: VrcIrq::new(),
+            vrc2a: mapper_id == 22,
+
+            register_lines,
+        }
+    }
+
+    /// Register written by the CPU, as $x000-$x003
+    fn register(&self, addr: u16) -> u16 {
+        let mut register = addr & 0xF000;
+        if addr & self.register_lines.0 != 0 {
+            register |= 0x01;
+        }
+        if addr & self.register_lines.1 != 0 {
+            register |= 0x02;
+        }
+        register
+    }
+
+    fn prg_bank(&self, addr: u16) -> u8 {
+        // NES 2.0 headers can declare less than 16KB of PRG ROM
+        let second_last_bank = self.prg_banks.saturating_mul(2).saturating_sub(2);
+
+        match (addr, self.prg_swap_mode) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.prg_bank_selector[0],
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => second_last_bank,
+            (0xA000..=0xBFFF, _) => self.prg_bank_selector[1],
+            _ => second_last_bank + 1,
+        }
+    }
+
+    fn chr_address(&self, addr: u16) -> usize {
+        let mut bank = self.chr_bank_selector[(addr >> 10) as usize] as usize;
+        if self.vrc2a {
+            bank >>= 1;
+        }
+
+        bank * 0x0400 + (addr & 0x03FF) as usize
+    }
+}
+
+impl Mapper for MapperVrc4 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank(addr) as usize) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram.write((addr & 0x1FFF) as usize, data);
+            return;
+        }
+
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_bank_selector[0] = data & 0x1F,
+            0x9000..=0x9003 if self.vrc2a => {
+                self.mirroring = if data & 0x01 == 0x01 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                }
+            }
+            0x9000..=0x9001 => {
+                self.mirroring = match data & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                }
+            }
+            0x9002..=0x9003 => self.prg_swap_mode = data & 0x02 == 0x02,
+            0xA000..=0xA003 => self.prg_bank_selector[1] = data & 0x1F,
+            register @ 0xB000..=0xEFFF => {
+                // 2 registers per bank: the low 4 bits, then the high 5 bits
+                let bank = (((register >> 12) - 0xB) * 2 + ((register >> 1) & 0x01)) as usize;
+                let selector = &mut self.chr_bank_selector[bank];
+
+                *selector = if register & 0x01 == 0 {
+                    (*selector & 0x01F0) | u16::from(data & 0x0F)
+                } else {
+                    (*selector & 0x000F) | (u16::from(data & 0x1F) << 4)
+                };
+            }
+            0xF000..=0xF003 if self.vrc2a => (),
+            0xF000 => self.irq.write_latch_low(data),
+            0xF001 => self.irq.write_latch_high(data),
+            0xF002 => self.irq.write_control(data),
+            0xF003 => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        self.chr_address(addr)
+    }
+
+    fn ppu_map_write(&self, addr: u16) -> Option<usize> {
+        // Some boards have CHR RAM instead of ROM
+        Some(self.chr_address(addr))
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_address(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_address_line_variants() {
+        // $B001 (CHR bank 0 high bits) for each wiring of mapper 21
+        for addr in [0xB002, 0xB040] {
+            let mut mapper = MapperVrc4::new(21, 8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+            mapper.cpu_map_write(addr, 0x01);
+            mapper.cpu_map_write(0xB000, 0x02);
+            assert_eq!(mapper.ppu_map_read(0x0005), 0x12 * 0x0400 + 5);
+        }
+
+        // $9002 (PRG swap mode) for each wiring of mapper 25
+        for addr in [0x9001, 0x9004] {
+            let mut mapper = MapperVrc4::new(25, 8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+            mapper.cpu_map_write(0x8000, 0x03);
+            mapper.cpu_map_write(addr, 0x02);
+            assert!(matches!(
+                mapper.cpu_map_read(0xC000),
+                CartridgeReadTarget::PrgRom(0x6000)
+            ));
+            assert!(matches!(
+                mapper.cpu_map_read(0x8000),
+                CartridgeReadTarget::PrgRom(0x1C000)
+            ));
+        }
+    }
+
+    #[test]
+    fn vrc2a_ignores_the_low_chr_bit() {
+        let mut mapper = MapperVrc4::new(22, 8, Mirroring::Vertical, PrgRam::new(0x2000, None));
+        mapper.cpu_map_write(0xD000, 0x07); // CHR bank 4 low bits
+        mapper.cpu_map_write(0xD002, 0x01); // CHR bank 4 high bits, A1 selects register 1
+        assert_eq!(mapper.ppu_map_read(0x1000), 0x0B * 0x0400);
+    }
+
+    #[test]
+    fn maps_prg_roms_under_16kb() {
+        let mapper = MapperVrc4::new(21, 0, Mirroring::Vertical, PrgRam::new(0x2000, None));
+        assert!(matches!(
+            mapper.cpu_map_read(0xC000),
+            CartridgeReadTarget::PrgRom(0x0000)
+        ));
+        assert!(matches!(
+            mapper.cpu_map_read(0xE000),
+            CartridgeReadTarget::PrgRom(0x2000)
+        ));
+    }
+}
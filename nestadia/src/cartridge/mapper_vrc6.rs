@@ -0,0 +1,168 @@
+use super::prg_ram::PrgRam;
+use super::vrc6_audio::Vrc6Audio;
+use super::vrc_irq::VrcIrq;
+use super::{CartridgeReadTarget, Mapper, Mirroring};
+
+/// Konami VRC6 (mappers 24 and 26): 16KB + 8KB PRG banking, 1KB CHR banking, 8KB of PRG RAM,
+/// the VRC IRQ and the VRC6 expansion audio. Mapper 26 swaps the A0 and A1 address lines.
+/// Only the CHR banking mode used by the games (mode 0) is emulated.
+/// http://wiki.nesdev.com/w/index.php/VRC6
+pub struct MapperVrc6 {
+    prg_banks: u8,
+    prg_bank_selector: [u8; 2],
+    chr_bank_selector: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram: PrgRam,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+    swapped_address_lines: bool,
+}
+
+impl_stateful!(MapperVrc6 {
+    prg_banks,
+    prg_bank_selector,
+    chr_bank_selector,
+    mirroring,
+    prg_ram,
+    irq,
+    audio,
+    swapped_address_lines
+});
+
+impl MapperVrc6 {
+    pub fn new(mapper_id: u16, prg_banks: u8, mirroring: Mirroring, prg_ram: PrgRam) -> Self {
+        Self {
+            prg_banks,
+            prg_bank_selector: [0u8; 2],
+            chr_bank_selector: [0u8; 8],
+            mirroring,
+            prg_ram,
+            irq: VrcIrq::new(),
+            audio: Vrc6Audio::new(),
+            swapped_address_lines: mapper_id == 26,
+        }
+    }
+
+    /// Register written by the CPU, as $x000-$x003
+    fn register(&self, addr: u16) -> u16 {
+        if self.swapped_address_lines {
+            (addr & 0xF000) | ((addr & 0x01) << 1) | ((addr & 0x02) >> 1)
+        } else {
+            addr & 0xF003
+        }
+    }
+
+    fn prg_bank(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF => self.prg_bank_selector[0] * 2,
+            0xA000..=0xBFFF => self.prg_bank_selector[0] * 2 + 1,
+            0xC000..=0xDFFF => self.prg_bank_selector[1],
+            _ => self.prg_banks * 2 - 1,
+        }
+    }
+}
+
+impl Mapper for MapperVrc6 {
+    fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget {
+        match addr {
+            0x6000..=0x7FFF => {
+                CartridgeReadTarget::PrgRam(self.prg_ram.read((addr & 0x1FFF) as usize))
+            }
+            0x8000..=0xFFFF => CartridgeReadTarget::PrgRom(
+                (self.prg_bank(addr) as usize) * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => CartridgeReadTarget::PrgRam(0),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram.write((addr & 0x1FFF) as usize, data);
+            return;
+        }
+
+        let register = self.register(addr);
+        if self.audio.write_register(register, data) {
+            return;
+        }
+
+        match register {
+            0x8000..=0x8003 => self.prg_bank_selector[0] = data & 0x0F,
+            0xB003 => {
+                self.mirroring = match (data >> 2) & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                }
+            }
+            0xC000..=0xC003 => self.prg_bank_selector[1] = data & 0x1F,
+            0xD000..=0xD003 => self.chr_bank_selector[(register & 0x03) as usize] = data,
+            0xE000..=0xE003 => self.chr_bank_selector[(register & 0x03) as usize + 4] = data,
+            0xF000 => self.irq.write_latch(data),
+            0xF001 => self.irq.write_control(data),
+            0xF002 => self.irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> usize {
+        (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    fn ppu_map_write(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn get_sram(&self) -> Option<&[u8]> {
+        self.prg_ram.data()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn clock_audio(&mut self) {
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn audio_channel_count(&self) -> u8 {
+        Vrc6Audio::CHANNEL_COUNT
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        matches!(self.register(addr), 0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002)
+    }
+
+    fn set_muted_audio_channels(&mut self, muted_channels: u8) {
+        self.audio.set_muted_channels(muted_channels);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    #[cfg(feature = "debugger")]
+    fn get_prg_bank(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(self.prg_bank(addr)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, addr: u16) -> Option<usize> {
+        Some(
+            (self.chr_bank_selector[(addr >> 10) as usize] as usize) * 0x0400
+                + (addr & 0x03FF) as usize,
+        )
+    }
+}
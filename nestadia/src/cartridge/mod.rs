@@ -1,25 +1,118 @@
+mod chr_latch;
+mod chunk_reader;
+mod eeprom_24c02;
+mod fds_image;
 mod ines_header;
 mod mapper_000;
 mod mapper_001;
 mod mapper_002;
 mod mapper_003;
 mod mapper_004;
+mod mapper_005;
+mod mapper_007;
+mod mapper_009;
+mod mapper_010;
+mod mapper_011;
+mod mapper_013;
+mod mapper_016;
+mod mapper_019;
+mod mapper_034;
+mod mapper_064;
 mod mapper_066;
+mod mapper_068;
+mod mapper_069;
+mod mapper_071;
+mod mapper_079;
+mod mapper_085;
+mod mapper_118;
+mod mapper_119;
+mod mapper_206;
+mod mapper_210;
+mod mapper_228;
+mod mapper_232;
+mod mapper_fds;
+mod mapper_nsf;
+mod mapper_registry;
+mod mapper_vrc4;
+mod mapper_vrc6;
+mod md5;
+mod namco_163;
+mod nsf_header;
+mod prg_ram;
+mod rom_database;
+mod sunsoft_5b;
+mod vrc6_audio;
+mod vrc7_audio;
+mod vrc_irq;
 
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::convert::TryFrom as _;
 
+use crate::irq::{IrqLine, IrqSource};
+use crate::save_data::{self, SaveDataError, SaveDataKind, SaveDataOrigin};
+use crate::state::Stateful;
+
+use self::chunk_reader::ChunkReader;
+use self::fds_image::FdsImage;
 use self::ines_header::{Flags6, INesHeader};
 use self::mapper_000::Mapper000;
 use self::mapper_001::Mapper001;
 use self::mapper_002::Mapper002;
 use self::mapper_003::Mapper003;
 use self::mapper_004::Mapper004;
+use self::mapper_005::Mapper005;
+use self::mapper_007::Mapper007;
+use self::mapper_009::Mapper009;
+use self::mapper_010::Mapper010;
+use self::mapper_011::Mapper011;
+use self::mapper_013::Mapper013;
+use self::mapper_016::Mapper016;
+use self::mapper_019::Mapper019;
+use self::mapper_034::Mapper034;
+use self::mapper_064::Mapper064;
 use self::mapper_066::Mapper066;
+use self::mapper_068::Mapper068;
+use self::mapper_069::Mapper069;
+use self::mapper_071::Mapper071;
+use self::mapper_079::Mapper079;
+use self::mapper_085::Mapper085;
+use self::mapper_118::Mapper118;
+use self::mapper_119::Mapper119;
+use self::mapper_206::Mapper206;
+use self::mapper_210::Mapper210;
+use self::mapper_228::Mapper228;
+use self::mapper_232::Mapper232;
+use self::mapper_fds::MapperFds;
+use self::mapper_nsf::MapperNsf;
+use self::mapper_vrc4::MapperVrc4;
+use self::mapper_vrc6::MapperVrc6;
+use self::prg_ram::PrgRam;
+use self::rom_database::Crc32;
+
+pub use self::ines_header::{ConsoleType, Timing};
+pub use self::mapper_registry::{BoardInfo, MapperFactory, MapperRegistry};
+pub use self::nsf_header::{NsfHeader, SoundChips};
+pub use self::rom_database::{HeaderOverride, RomDatabase};
+
+pub(crate) use self::md5::Md5;
+pub(crate) use self::rom_database::crc32;
+
+/// Size of the PRG RAM given to the cartridges whose iNES header doesn't give it
+pub const DEFAULT_PRG_RAM_SIZE: usize = 8192;
+
+const CHR_BANK_SIZE: usize = 8192;
 
-#[derive(Debug, Clone, Copy)]
+/// The pattern tables are at $0000-$1FFF. The PPU forms higher addresses for the sprites out of
+/// range, whose upper bits the mappers don't decode.
+const CHR_ADDR_MASK: u16 = 0x1FFF;
+
+/// Largest PRG or CHR ROM loaded from chunks, far above any board, as the header declaring its
+/// size is all there is to check before receiving it
+const MAX_STREAMED_ROM_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mirroring {
     Horizontal,
     Vertical,
@@ -28,58 +121,611 @@ pub enum Mirroring {
     OneScreenUpper,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl_stateful_enum!(Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    OneScreenLower,
+    OneScreenUpper,
+});
+
+/// Description of a loaded cartridge, after the corrections of the ROM database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeInfo {
+    pub format: RomFormat,
+    pub mapper_id: u16,
+    pub submapper_id: u8,
+
+    // Sizes are in bytes
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub chr_ram_size: usize,
+
+    /// Mirroring of the board at power on
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+
+    /// CRC32 of the PRG and CHR ROM, identifying the ROM in the databases. It is the CRC32 of
+    /// the whole file for the NSF and FDS images.
+    pub crc32: u32,
+    /// CRC32 of the PRG ROM only, same as `crc32` for the NSF and FDS images
+    pub prg_crc32: u32,
+}
+
+/// Kind of image loaded in the emulator, so frontends can adapt their UI
+#[derive(Debug, Clone)]
+pub enum LoadedImage {
+    Rom,
+    Nsf(NsfHeader),
+    Fds { disk_sides: u8 },
+}
+
+/// Format of a loaded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    INes,
+    Nes2,
+    Nsf,
+    Fds,
+}
+
+impl core::fmt::Display for RomFormat {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::INes => "iNES",
+            Self::Nes2 => "NES 2.0",
+            Self::Nsf => "NSF",
+            Self::Fds => "FDS",
+        })
+    }
+}
+
+/// Part of a loaded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomSection {
+    Header,
+    Trainer,
+    PrgRom,
+    ChrRom,
+    FdsBios,
+    DiskSide,
+}
+
+impl core::fmt::Display for RomSection {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Header => "header",
+            Self::Trainer => "trainer",
+            Self::PrgRom => "PRG ROM",
+            Self::ChrRom => "CHR ROM",
+            Self::FdsBios => "BIOS",
+            Self::DiskSide => "disk side",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RomParserError {
-    TooShort,
-    InvalidMagicBytes,
-    MapperNotImplemented,
+    /// The file ends in `section`, before the size expected from its header
+    TooShort {
+        format: RomFormat,
+        section: RomSection,
+        expected_size: usize,
+        actual_size: usize,
+    },
+    /// The file doesn't have the magic bytes of `format` at `offset`
+    InvalidMagicBytes {
+        format: RomFormat,
+        offset: usize,
+    },
+    MapperNotImplemented {
+        format: RomFormat,
+        mapper_id: u16,
+        submapper_id: u8,
+    },
+    /// The header declares a `section` too large to be loaded
+    RomTooLarge {
+        format: RomFormat,
+        section: RomSection,
+    },
+    InvalidSaveData(SaveDataError),
+    /// The ROM couldn't be read, see `Emulator::new_from_reader`
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
 }
 
 impl core::fmt::Display for RomParserError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "{:?}", &self)
+        match self {
+            Self::TooShort {
+                format,
+                section,
+                expected_size,
+                actual_size,
+            } => write!(
+                f,
+                "{} file ends in its {}: expected {} bytes, but it has {}",
+                format, section, expected_size, actual_size
+            ),
+            Self::InvalidMagicBytes { format, offset } => write!(
+                f,
+                "not a valid {} file: missing magic bytes at offset {}",
+                format, offset
+            ),
+            Self::MapperNotImplemented {
+                format,
+                mapper_id,
+                submapper_id,
+            } => write!(
+                f,
+                "{} ROM uses mapper {}, submapper {}, which isn't implemented",
+                format, mapper_id, submapper_id
+            ),
+            Self::RomTooLarge { format, section } => {
+                write!(
+                    f,
+                    "{} header declares a {} too large to load",
+                    format, section
+                )
+            }
+            Self::InvalidSaveData(e) => write!(f, "invalid save data: {}", e),
+            #[cfg(feature = "std")]
+            Self::Io(kind) => write!(f, "can't read the ROM: {:?}", kind),
+        }
     }
 }
 
-enum CartridgeReadTarget {
+/// Target of a CPU read of the cartridge
+pub enum CartridgeReadTarget {
+    /// Data read from the mapper itself: its PRG RAM, a register, or open bus
     PrgRam(u8),
+    /// Offset in the PRG ROM
     PrgRom(usize),
 }
 
-trait Mapper: Send + Sync {
+/// Memory mapper of a cartridge. Boards missing from the crate can implement it, and be loaded
+/// through a `MapperRegistry`. Its registers and RAM are saved in the save states through
+/// `Stateful`.
+pub trait Mapper: Send + Sync + Stateful {
+    /// Maps a CPU read of the cartridge ($4020-$FFFF)
     fn cpu_map_read(&self, addr: u16) -> CartridgeReadTarget;
+
+    /// Called after every CPU read of the cartridge, for registers whose reads have side effects.
+    /// `cpu_map_read` must not have any, since it is also used to peek at the memory.
+    fn cpu_read_side_effects(&mut self, _addr: u16) {}
+
     fn cpu_map_write(&mut self, addr: u16, data: u8);
+
+    /// Maps a PPU read of the pattern tables to an offset in the CHR memory
     fn ppu_map_read(&mut self, addr: u16) -> usize; // This is mutable because of side effects on some mapper that serves as a scanline counter
+
+    /// Maps a PPU write of the pattern tables to an offset in the CHR RAM, `None` if read-only
     fn ppu_map_write(&self, addr: u16) -> Option<usize>;
+
+    /// Whether the pattern tables at `addr` map the CHR RAM, for boards having both CHR ROM and
+    /// CHR RAM. Boards without CHR ROM always use their CHR RAM.
+    fn maps_chr_ram(&self, _addr: u16) -> bool {
+        false
+    }
+
     fn mirroring(&self) -> Mirroring;
+
+    /// PRG RAM to save, if the board has any
     fn get_sram(&self) -> Option<&[u8]>;
 
-    fn irq_state(&self) -> bool {
+    /// Whether the CHR RAM is battery-backed, to be saved after `get_sram`. NES 2.0 headers can
+    /// also declare it.
+    fn has_battery_backed_chr_ram(&self) -> bool {
+        false
+    }
+
+    /// Reads the name tables ($2000-$2FFF), for mappers controlling them.
+    /// `None` reads the console's VRAM (4KB with four screen mirroring), following `mirroring`.
+    /// `chr` is the CHR memory, for mappers able to use it as name tables.
+    fn read_name_table(&mut self, _addr: u16, _vram: &[u8], _chr: &[u8]) -> Option<u8> {
+        None
+    }
+
+    /// Writes the name tables ($2000-$2FFF), for mappers controlling them.
+    /// Returns `false` to write the console's VRAM, following `mirroring`.
+    fn write_name_table(&mut self, _addr: u16, _data: u8, _vram: &mut [u8]) -> bool {
         false
     }
-    fn irq_clear(&mut self) {}
 
+    /// Called on CPU writes to the PPU registers, for mappers snooping them
+    fn ppu_register_write(&mut self, _addr: u16, _data: u8) {}
+
+    /// Called once per CPU cycle
+    fn cpu_clock(&mut self) {}
+
+    /// Called on the rising edges of the PPU A12 line, seen on its pattern table accesses.
+    /// The MMC3 counts the scanlines with them.
+    fn ppu_a12_edge(&mut self) {}
+
+    /// Called by the PPU at the end of every scanline, whether it is rendering or not
+    fn end_of_scanline(&mut self) {}
+
+    /// Clocks the expansion audio chip of the cartridge, if any. Called once per CPU cycle.
+    fn clock_audio(&mut self) {}
+
+    /// Output of the expansion audio chip of the cartridge, in the same scale as the APU output
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    /// Number of channels of the expansion audio chip of the cartridge
+    fn audio_channel_count(&self) -> u8 {
+        0
+    }
+
+    /// Whether a CPU write to `addr` goes to the expansion audio chip of the cartridge
+    fn is_audio_register(&self, _addr: u16) -> bool {
+        false
+    }
+
+    /// Whether a CPU write to `addr` can change the memory returned by `get_sram`
+    fn is_sram_write(&self, addr: u16) -> bool {
+        matches!(addr, 0x6000..=0x7FFF)
+    }
+
+    /// Bit n mutes the channel n of the expansion audio chip
+    fn set_muted_audio_channels(&mut self, _muted_channels: u8) {}
+
+    /// Selects the song to play on the next reset. Only used by the NSF player.
+    fn select_track(&mut self, _track: u8) {}
+
+    /// Number of disk sides. Only used by the Famicom Disk System.
+    fn disk_side_count(&self) -> u8 {
+        0
+    }
+
+    fn inserted_disk_side(&self) -> Option<u8> {
+        None
+    }
+
+    /// Inserts a disk side, or ejects the disk with `None`
+    fn insert_disk_side(&mut self, _side: Option<u8>) {}
+
+    /// Level of the IRQ output of the mapper, driving `IrqSource::MAPPER` on the CPU's /IRQ line.
+    /// Mappers acknowledge it themselves, usually on a register write.
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    /// PRG ROM bank mapped at `addr`, shown by the debugger
     #[cfg(feature = "debugger")]
     fn get_prg_bank(&self, addr: u16) -> Option<u8>;
+
+    /// Maps the pattern tables like `ppu_map_read` but without its side effects, for the debugger
+    /// to see the CHR banks switched. `None` for boards that don't tell.
+    #[cfg(feature = "debugger")]
+    fn peek_chr_map(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+}
+
+/// Memory mapped by the mapper, compared by the debugger to see the banks switched
+#[cfg(feature = "debugger")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BankMapping {
+    /// Offsets in the PRG ROM of $6000-$FFFF, by 4KB, `None` where it isn't mapped
+    pub prg: [Option<usize>; 10],
+    /// Offsets in the CHR memory of the pattern tables, by 1KB
+    pub chr: [Option<usize>; 8],
+}
+
+/// Settings of the ROM loader
+pub(crate) struct LoadOptions {
+    pub default_prg_ram_size: usize,
+    pub registry: MapperRegistry,
+    pub rom_database: RomDatabase,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            default_prg_ram_size: DEFAULT_PRG_RAM_SIZE,
+            registry: MapperRegistry::default(),
+            rom_database: RomDatabase::builtin(),
+        }
+    }
+}
+
+/// What the mapper of an iNES ROM is built from, to build it again on power-on
+struct MapperBuilder {
+    header: INesHeader,
+    board: BoardInfo,
+    /// Factory registered for the board, consulted before the built-in mappers
+    factory: Option<MapperFactory>,
+}
+
+impl MapperBuilder {
+    /// Builds the mapper in its power-on state, with the PRG RAM loaded from `save_data`
+    fn build(&self, save_data: Option<&[u8]>) -> Result<Box<dyn Mapper>, RomParserError> {
+        if let Some(factory) = self.factory {
+            return Ok(factory(&self.board, save_data));
+        }
+
+        let header = &self.header;
+        let mirroring = self.board.mirroring;
+        let prg_ram = PrgRam::new(self.board.prg_ram_size, save_data);
+        let mapper: Box<dyn Mapper> = match header.mapper_id {
+            0 => Box::new(Mapper000::new(header.prg_banks(), mirroring, prg_ram)),
+            1 => Box::new(Mapper001::new(header.prg_banks(), mirroring, prg_ram)),
+            2 => Box::new(Mapper002::new(header.prg_banks(), mirroring)),
+            3 => Box::new(Mapper003::new(header.prg_banks(), mirroring)),
+            4 => Box::new(Mapper004::new(header.prg_banks(), mirroring, prg_ram)),
+            5 => Box::new(Mapper005::new(prg_ram)),
+            7 => Box::new(Mapper007::new()),
+            9 => Box::new(Mapper009::new(header.prg_banks(), mirroring)),
+            10 => Box::new(Mapper010::new(header.prg_banks(), mirroring, prg_ram)),
+            11 => Box::new(Mapper011::new(mirroring)),
+            13 => Box::new(Mapper013::new(mirroring)),
+            16 => Box::new(Mapper016::new(header.prg_banks(), mirroring, save_data)),
+            19 => Box::new(Mapper019::new(header.prg_banks(), mirroring, prg_ram)),
+            21..=23 | 25 => Box::new(MapperVrc4::new(
+                header.mapper_id,
+                header.prg_banks(),
+                mirroring,
+                prg_ram,
+            )),
+            24 | 26 => Box::new(MapperVrc6::new(
+                header.mapper_id,
+                header.prg_banks(),
+                mirroring,
+                prg_ram,
+            )),
+            34 => Box::new(Mapper034::new(
+                header.chr_rom_size > CHR_BANK_SIZE,
+                mirroring,
+                prg_ram,
+            )),
+            64 => Box::new(Mapper064::new(header.prg_banks(), mirroring)),
+            66 => Box::new(Mapper066::new(mirroring)),
+            68 => Box::new(Mapper068::new(header.prg_banks(), mirroring, prg_ram)),
+            69 => Box::new(Mapper069::new(header.prg_banks(), mirroring, prg_ram)),
+            71 => Box::new(Mapper071::new(header.prg_banks(), mirroring)),
+            79 => Box::new(Mapper079::new(mirroring)),
+            85 => Box::new(Mapper085::new(header.prg_banks(), mirroring, prg_ram)),
+            118 => Box::new(Mapper118::new(header.prg_banks(), prg_ram)),
+            119 => Box::new(Mapper119::new(header.prg_banks(), mirroring, prg_ram)),
+            206 => Box::new(Mapper206::new(header.prg_banks(), mirroring)),
+            210 => {
+                // Submapper 1 is the Namco 175 and 2 the Namco 340. Without NES 2.0 headers,
+                // only the 175 boards have a battery.
+                let namco_340 = if header.nes2 && header.submapper_id != 0 {
+                    header.submapper_id == 2
+                } else {
+                    !header.flags6.contains(Flags6::PRG_RAM)
+                };
+
+                Box::new(Mapper210::new(
+                    namco_340,
+                    header.prg_banks(),
+                    mirroring,
+                    save_data,
+                ))
+            }
+            228 => Box::new(Mapper228::new()),
+            232 => Box::new(Mapper232::new(mirroring)),
+            _ => {
+                return Err(RomParserError::MapperNotImplemented {
+                    format: header.format(),
+                    mapper_id: header.mapper_id,
+                    submapper_id: header.submapper_id,
+                })
+            }
+        };
+
+        Ok(mapper)
+    }
 }
 
 pub struct Cartridge {
-    chr_ram: bool,
     prg_memory: Vec<u8>, // program ROM, used by CPU
-    chr_memory: Vec<u8>, // character ROM, used by PPU
+    chr_rom: Vec<u8>,    // character ROM, used by PPU
+    chr_ram: Vec<u8>, // character RAM, used by PPU when there is no CHR ROM or the mapper maps it
+    chr_ram_battery: bool,
+    info: CartridgeInfo,
+    origin: SaveDataOrigin, // ROM identification stored with the save data
     mapper: Box<dyn Mapper>,
+    mapper_builder: Option<MapperBuilder>, // None for the NSF and FDS images
+    nsf_header: Option<NsfHeader>,
+    muted_audio_channels: u8,
+    ppu_a12: bool,         // Level of the PPU A12 line on its last pattern table access
+    save_data_dirty: bool, // Battery-backed memory written since it was last saved
 }
 
+impl_stateful!(Cartridge {
+    chr_ram,
+    ppu_a12,
+    mapper
+});
+
 impl Cartridge {
+    /// Loads an iNES/NES 2.0 ROM, or a NSF file
     pub fn load(rom: &[u8], save_data: Option<&[u8]>) -> Result<Self, RomParserError> {
-        const PRG_BANK_SIZE: usize = 16384;
-        const CHR_BANK_SIZE: usize = 8192;
+        Self::load_rom(rom, save_data, &LoadOptions::default())
+    }
+
+    /// Loads an iNES/NES 2.0 ROM, or a NSF file. iNES headers not giving the size of the PRG RAM
+    /// get `default_prg_ram_size` bytes, unless the board is known to have more.
+    pub fn load_with_default_prg_ram_size(
+        rom: &[u8],
+        save_data: Option<&[u8]>,
+        default_prg_ram_size: usize,
+    ) -> Result<Self, RomParserError> {
+        let options = LoadOptions {
+            default_prg_ram_size,
+            ..LoadOptions::default()
+        };
+        Self::load_rom(rom, save_data, &options)
+    }
+
+    /// Loads an iNES/NES 2.0 ROM, or a NSF file, with the mappers of `registry` taking
+    /// precedence over the built-in ones
+    pub fn load_with_registry(
+        rom: &[u8],
+        save_data: Option<&[u8]>,
+        registry: &MapperRegistry,
+    ) -> Result<Self, RomParserError> {
+        let options = LoadOptions {
+            registry: registry.clone(),
+            ..LoadOptions::default()
+        };
+        Self::load_rom(rom, save_data, &options)
+    }
+
+    /// Loads an iNES/NES 2.0 ROM, or a NSF file, correcting its header with `rom_database`
+    /// instead of the built-in database
+    pub fn load_with_rom_database(
+        rom: &[u8],
+        save_data: Option<&[u8]>,
+        rom_database: &RomDatabase,
+    ) -> Result<Self, RomParserError> {
+        let options = LoadOptions {
+            rom_database: rom_database.clone(),
+            ..LoadOptions::default()
+        };
+        Self::load_rom(rom, save_data, &options)
+    }
+
+    /// Loads an iNES/NES 2.0 ROM, or a NSF file, received in chunks of any size. The PRG and
+    /// CHR ROM are copied from the chunks without buffering the whole ROM, so large ROMs can be
+    /// loaded from a network stream or a flash memory.
+    pub(crate) fn load_from_chunks<I>(
+        chunks: I,
+        save_data: Option<&[u8]>,
+        options: &LoadOptions,
+    ) -> Result<Self, RomParserError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut reader = ChunkReader::new(chunks.into_iter());
+
+        let mut header_data = Vec::new();
+        reader.read(&mut header_data, 16);
+        if NsfHeader::is_nsf(&header_data) {
+            // NSF files are small enough to be loaded as a whole
+            reader.read_to_end(&mut header_data);
+            return Self::load_nsf(&header_data);
+        }
+
+        let header = INesHeader::try_from(header_data.as_slice())?;
+
+        // Unlike a buffered ROM, the header can't be checked against the size of the data
+        for (section, size) in [
+            (RomSection::PrgRom, header.prg_rom_size),
+            (RomSection::ChrRom, header.chr_rom_size),
+        ] {
+            if size > MAX_STREAMED_ROM_SIZE {
+                return Err(RomParserError::RomTooLarge {
+                    format: header.format(),
+                    section,
+                });
+            }
+        }
+
+        let mut trainer = Vec::new();
+        let mut prg_memory = Vec::new();
+        let mut chr_rom = Vec::new();
+        if !reader.read(&mut trainer, header.trainer_size())
+            || !reader.read(&mut prg_memory, header.prg_rom_size)
+            || !reader.read(&mut chr_rom, header.chr_rom_size)
+        {
+            let rom_size = 16 + trainer.len() + prg_memory.len() + chr_rom.len();
+            return Err(header.too_short_error(rom_size));
+        }
+
+        Self::load_ines(header, &trainer, prg_memory, chr_rom, save_data, options)
+    }
+
+    pub(crate) fn load_rom(
+        rom: &[u8],
+        save_data: Option<&[u8]>,
+        options: &LoadOptions,
+    ) -> Result<Self, RomParserError> {
+        if NsfHeader::is_nsf(rom) {
+            return Self::load_nsf(rom);
+        }
 
         let header: INesHeader = INesHeader::try_from(rom)?;
 
+        // The sizes of the header can add up past the end of the address space
+        let prg_start = 16 + header.trainer_size();
+        let (prg_end, chr_end) = match prg_start
+            .checked_add(header.prg_rom_size)
+            .and_then(|prg_end| Some((prg_end, prg_end.checked_add(header.chr_rom_size)?)))
+        {
+            Some((prg_end, chr_end)) if chr_end <= rom.len() => (prg_end, chr_end),
+            _ => return Err(header.too_short_error(rom.len())),
+        };
+
+        Self::load_ines(
+            header,
+            &rom[16..prg_start],
+            rom[prg_start..prg_end].to_vec(),
+            rom[prg_end..chr_end].to_vec(),
+            save_data,
+            options,
+        )
+    }
+
+    /// Loads the parts of an iNES/NES 2.0 ROM
+    fn load_ines(
+        mut header: INesHeader,
+        trainer: &[u8],
+        prg_memory: Vec<u8>,
+        chr_rom: Vec<u8>,
+        save_data: Option<&[u8]>,
+        options: &LoadOptions,
+    ) -> Result<Self, RomParserError> {
         log::info!("ROM info: {:?}", &header);
 
-        let mirroring = if header.flags6.contains(Flags6::FOUR_SCREEN) {
+        // The databases identify the ROMs by their PRG and CHR ROM
+        let mut crc = Crc32::new();
+        crc.update(&prg_memory);
+        let prg_crc = crc.finish();
+        crc.update(&chr_rom);
+        let crc = crc.finish();
+        let header_override = options.rom_database.find(crc);
+        if let Some(header_override) = &header_override {
+            log::info!("ROM {:08X} is in the database: {:?}", crc, header_override);
+            header_override.apply(&mut header);
+        }
+
+        let origin = SaveDataOrigin {
+            mapper_id: header.mapper_id,
+            rom_crc: crc,
+        };
+        let save_data = save_data
+            .map(|data| save_data::unpack(data, SaveDataKind::BatteryRam, origin))
+            .transpose()
+            .map_err(RomParserError::InvalidSaveData)?;
+        let save_data = save_data.as_deref();
+
+        if header.timing != Timing::Ntsc {
+            log::warn!(
+                "ROM uses {:?} timing, but only NTSC is emulated",
+                header.timing
+            );
+        }
+
+        if header.console_type != ConsoleType::Nes {
+            log::warn!(
+                "ROM targets {:?}, which isn't supported",
+                header.console_type
+            );
+        }
+
+        let mirroring = if let Some(mirroring) = header_override.and_then(|h| h.mirroring) {
+            mirroring
+        } else if header.flags6.contains(Flags6::FOUR_SCREEN) {
             Mirroring::FourScreen
         } else if header.flags6.contains(Flags6::MIRRORING) {
             Mirroring::Vertical
@@ -87,64 +733,241 @@ impl Cartridge {
             Mirroring::Horizontal
         };
 
-        let mapper: Box<dyn Mapper> = match header.mapper_id {
-            0 => Box::new(Mapper000::new(header.prg_size, mirroring)),
-            1 => Box::new(Mapper001::new(header.prg_size, mirroring, save_data)),
-            2 => Box::new(Mapper002::new(header.prg_size, mirroring)),
-            3 => Box::new(Mapper003::new(header.prg_size, mirroring)),
-            4 => Box::new(Mapper004::new(header.prg_size, mirroring)),
-            66 => Box::new(Mapper066::new(mirroring)),
-            _ => return Err(RomParserError::MapperNotImplemented),
+        // NES 2.0 headers give the size of the PRG RAM, most iNES ones don't
+        let header_prg_ram_size = header.prg_ram_size + header.prg_nvram_size;
+        let prg_ram_size = match (
+            header_override.and_then(|h| h.prg_ram_size),
+            header.mapper_id,
+        ) {
+            (Some(size), _) => size,
+            _ if header.nes2 || header_prg_ram_size > 0 => header_prg_ram_size,
+            (None, 5) => Mapper005::PRG_RAM_SIZE,
+            // Most NROM boards have none, unless a battery or a trainer tells otherwise
+            (None, 0) if !header.flags6.contains(Flags6::PRG_RAM) && trainer.is_empty() => 0,
+            (None, _) => options.default_prg_ram_size,
         };
 
-        let chr_memory_len = CHR_BANK_SIZE * (header.chr_size as usize);
-        let prg_memory_len = PRG_BANK_SIZE * (header.prg_size as usize);
-
-        let prg_start = if header.flags6.contains(Flags6::TRAINER) {
-            512 + 16
-        } else {
-            16
+        let board = BoardInfo {
+            mapper_id: header.mapper_id,
+            submapper_id: header.submapper_id,
+            prg_rom_size: header.prg_rom_size,
+            chr_rom_size: header.chr_rom_size,
+            prg_ram_size,
+            mirroring,
+            battery: header.flags6.contains(Flags6::PRG_RAM),
+        };
+        let mapper_builder = MapperBuilder {
+            header: header.clone(),
+            board,
+            factory: options.registry.find(board.mapper_id, board.submapper_id),
         };
+        let mut mapper = mapper_builder.build(save_data)?;
 
-        let expected_rom_size = prg_start + prg_memory_len + chr_memory_len;
-        if rom.len() < expected_rom_size {
-            log::error!(
-                "Invalid ROM size: expected {} bytes of memory, but ROM has {}",
-                expected_rom_size,
-                rom.len()
-            );
-            return Err(RomParserError::TooShort);
+        // Trainer, loaded into $7000-$71FF by the copiers before running the game
+        if !trainer.is_empty() {
+            if mapper.get_sram().is_some() {
+                for (addr, data) in (0x7000..).zip(trainer) {
+                    mapper.cpu_map_write(addr, *data);
+                }
+            } else {
+                log::warn!("ROM has a trainer, but its board has no PRG RAM to load it into");
+            }
         }
 
-        // PRG memory
-        let prg_end = prg_start + prg_memory_len;
-        let prg_memory = rom[prg_start..prg_end].to_vec();
-        assert_eq!(prg_memory.len(), prg_memory_len);
+        // NES 2.0 headers give the size of the CHR RAM, iNES ones assume 8KB without CHR ROM
+        // unless the board is known to have more
+        let board_chr_ram_size = match header.mapper_id {
+            13 => Mapper013::CHR_RAM_SIZE,
+            119 => Mapper119::CHR_RAM_SIZE,
+            _ if chr_rom.is_empty() => CHR_BANK_SIZE,
+            _ => 0,
+        };
+        let chr_ram_size = header.chr_ram_size + header.chr_nvram_size;
+        let mut chr_ram = vec![0u8; chr_ram_size.max(board_chr_ram_size)];
 
-        // CHR memory
-        // Don't parse if it's RAM
-        let chr_ram = header.chr_size == 0;
-        let chr_memory = if !chr_ram {
-            let chr_start = prg_end;
-            let chr_end = prg_end + chr_memory_len;
-            rom[chr_start..chr_end].to_vec()
-        } else {
-            vec![0u8; CHR_BANK_SIZE]
+        // Battery-backed CHR RAM is saved after the PRG RAM
+        let chr_ram_battery = header.chr_nvram_size > 0 || mapper.has_battery_backed_chr_ram();
+        if let (true, Some(save_data)) = (chr_ram_battery, save_data) {
+            let prg_save_len = mapper.get_sram().map_or(0, <[u8]>::len);
+            chr_ram
+                .iter_mut()
+                .zip(save_data.iter().skip(prg_save_len))
+                .for_each(|(r, s)| *r = *s);
+        }
+
+        let info = CartridgeInfo {
+            format: header.format(),
+            mapper_id: header.mapper_id,
+            submapper_id: header.submapper_id,
+            prg_rom_size: prg_memory.len(),
+            chr_rom_size: chr_rom.len(),
+            prg_ram_size,
+            chr_ram_size: chr_ram.len(),
+            mirroring,
+            battery: board.battery || chr_ram_battery,
+            trainer: !trainer.is_empty(),
+            timing: header.timing,
+            console_type: header.console_type,
+            crc32: crc,
+            prg_crc32: prg_crc,
         };
 
         Ok(Cartridge {
-            chr_ram,
             prg_memory,
-            chr_memory,
+            chr_rom,
+            chr_ram,
+            chr_ram_battery,
+            info,
+            origin,
             mapper,
+            mapper_builder: Some(mapper_builder),
+            nsf_header: None,
+            muted_audio_channels: 0,
+            ppu_a12: false,
+            save_data_dirty: false,
+        })
+    }
+
+    /// Loads a NES Sound Format file, played by a built-in player
+    pub fn load_nsf(nsf: &[u8]) -> Result<Self, RomParserError> {
+        const CHR_RAM_SIZE: usize = 8192;
+        const PRG_RAM_SIZE: usize = 8192;
+
+        let header = NsfHeader::try_from(nsf)?;
+
+        log::info!("NSF info: {:?}", &header);
+
+        let prg_memory = MapperNsf::prg_memory(&header, &nsf[nsf_header::NSF_HEADER_SIZE..]);
+        let mapper = MapperNsf::new(header.clone());
+        let crc = crc32(nsf);
+
+        Ok(Cartridge {
+            chr_rom: Vec::new(),
+            chr_ram: vec![0u8; CHR_RAM_SIZE],
+            chr_ram_battery: false,
+            info: CartridgeInfo {
+                format: RomFormat::Nsf,
+                mapper_id: 0,
+                submapper_id: 0,
+                prg_rom_size: prg_memory.len(),
+                chr_rom_size: 0,
+                prg_ram_size: PRG_RAM_SIZE,
+                chr_ram_size: CHR_RAM_SIZE,
+                mirroring: mapper.mirroring(),
+                battery: false,
+                trainer: false,
+                timing: Timing::Ntsc,
+                console_type: ConsoleType::Nes,
+                crc32: crc,
+                prg_crc32: crc,
+            },
+            prg_memory,
+            origin: SaveDataOrigin {
+                mapper_id: 0,
+                rom_crc: crc,
+            },
+            mapper: Box::new(mapper),
+            mapper_builder: None,
+            nsf_header: Some(header),
+            muted_audio_channels: 0,
+            ppu_a12: false,
+            save_data_dirty: false,
+        })
+    }
+
+    /// Loads a Famicom Disk System disk image, run by the given 8KB BIOS
+    pub fn load_fds(bios: &[u8], disk: &[u8]) -> Result<Self, RomParserError> {
+        const BIOS_SIZE: usize = 8192;
+        const CHR_RAM_SIZE: usize = 8192;
+        const PRG_RAM_SIZE: usize = 0x8000;
+
+        let bios = bios.get(..BIOS_SIZE).ok_or(RomParserError::TooShort {
+            format: RomFormat::Fds,
+            section: RomSection::FdsBios,
+            expected_size: BIOS_SIZE,
+            actual_size: bios.len(),
+        })?;
+        let image = FdsImage::try_from(disk)?;
+        let mapper = MapperFds::new(image.into_sides());
+        let crc = crc32(disk);
+
+        Ok(Cartridge {
+            prg_memory: bios.to_vec(),
+            chr_rom: Vec::new(),
+            chr_ram: vec![0u8; CHR_RAM_SIZE],
+            chr_ram_battery: false,
+            info: CartridgeInfo {
+                format: RomFormat::Fds,
+                mapper_id: 0,
+                submapper_id: 0,
+                prg_rom_size: BIOS_SIZE,
+                chr_rom_size: 0,
+                prg_ram_size: PRG_RAM_SIZE,
+                chr_ram_size: CHR_RAM_SIZE,
+                mirroring: mapper.mirroring(),
+                battery: false,
+                trainer: false,
+                timing: Timing::Ntsc,
+                console_type: ConsoleType::Nes,
+                crc32: crc,
+                prg_crc32: crc,
+            },
+            origin: SaveDataOrigin {
+                mapper_id: 0,
+                rom_crc: crc,
+            },
+            mapper: Box::new(mapper),
+            mapper_builder: None,
+            nsf_header: None,
+            muted_audio_channels: 0,
+            ppu_a12: false,
+            save_data_dirty: false,
         })
     }
 
+    pub fn loaded_image(&self) -> LoadedImage {
+        if let Some(header) = &self.nsf_header {
+            LoadedImage::Nsf(header.clone())
+        } else if self.mapper.disk_side_count() > 0 {
+            LoadedImage::Fds {
+                disk_sides: self.mapper.disk_side_count(),
+            }
+        } else {
+            LoadedImage::Rom
+        }
+    }
+
+    pub fn nsf_header(&self) -> Option<&NsfHeader> {
+        self.nsf_header.as_ref()
+    }
+
+    pub fn select_nsf_track(&mut self, track: u8) {
+        self.mapper.select_track(track);
+        self.mapper
+            .set_muted_audio_channels(self.muted_audio_channels);
+    }
+
+    /// Size of the PRG RAM of the cartridge, battery-backed or not
+    pub fn prg_ram_size(&self) -> usize {
+        self.info.prg_ram_size
+    }
+
+    pub fn info(&self) -> &CartridgeInfo {
+        &self.info
+    }
+
     pub fn mirroring(&self) -> Mirroring {
         self.mapper.mirroring()
     }
 
-    pub fn read_prg_mem(&self, addr: u16) -> u8 {
+    pub fn read_prg_mem(&mut self, addr: u16) -> u8 {
+        let data = self.peek_prg_mem(addr);
+        self.mapper.cpu_read_side_effects(addr);
+        data
+    }
+
+    /// Reads the PRG memory without the side effects of a CPU read
+    pub fn peek_prg_mem(&self, addr: u16) -> u8 {
         match self.mapper.cpu_map_read(addr) {
             CartridgeReadTarget::PrgRom(rom_addr) => {
                 self.prg_memory[rom_addr % self.prg_memory.len()]
@@ -154,18 +977,53 @@ impl Cartridge {
     }
 
     pub fn write_prg_mem(&mut self, addr: u16, data: u8) {
+        // Checked before the write, since it can change the memory mapping
+        if self.mapper.is_sram_write(addr) && self.mapper.get_sram().is_some() {
+            self.save_data_dirty = true;
+        }
+
         self.mapper.cpu_map_write(addr, data);
     }
 
+    fn watch_ppu_a12(&mut self, addr: u16) {
+        let ppu_a12 = addr & 0x1000 == 0x1000;
+        if ppu_a12 && !self.ppu_a12 {
+            self.mapper.ppu_a12_edge();
+        }
+        self.ppu_a12 = ppu_a12;
+    }
+
+    /// Whether the pattern tables at `addr` use the CHR RAM instead of the CHR ROM
+    fn maps_chr_ram(&self, addr: u16) -> bool {
+        self.chr_rom.is_empty() || self.mapper.maps_chr_ram(addr)
+    }
+
     pub fn read_chr_mem(&mut self, addr: u16) -> u8 {
+        let addr = addr & CHR_ADDR_MASK;
+        self.watch_ppu_a12(addr);
+
+        let chr_ram = self.maps_chr_ram(addr);
         let addr = self.mapper.ppu_map_read(addr);
-        self.chr_memory[addr % self.chr_memory.len()]
+        let memory = if chr_ram {
+            &self.chr_ram
+        } else {
+            &self.chr_rom
+        };
+        memory[addr % memory.len()]
     }
 
     pub fn write_chr_mem(&mut self, addr: u16, data: u8) {
-        if self.chr_ram {
+        let addr = addr & CHR_ADDR_MASK;
+        self.watch_ppu_a12(addr);
+
+        if self.maps_chr_ram(addr) {
             if let Some(addr) = self.mapper.ppu_map_write(addr) {
-                self.chr_memory[addr] = data;
+                let len = self.chr_ram.len();
+                self.chr_ram[addr % len] = data;
+
+                if self.chr_ram_battery {
+                    self.save_data_dirty = true;
+                }
             } else {
                 log::warn!(
                     "attempted to write on CHR memory at {}, but this is not supported by this mapper",
@@ -180,18 +1038,471 @@ impl Cartridge {
         };
     }
 
-    pub fn get_save_data(&self) -> Option<&[u8]> {
-        self.mapper.get_sram()
+    pub fn read_name_table(&mut self, addr: u16, vram: &[u8]) -> Option<u8> {
+        let chr = if self.chr_rom.is_empty() {
+            &self.chr_ram
+        } else {
+            &self.chr_rom
+        };
+        self.mapper.read_name_table(addr, vram, chr)
+    }
+
+    pub fn write_name_table(&mut self, addr: u16, data: u8, vram: &mut [u8]) -> bool {
+        self.mapper.write_name_table(addr, data, vram)
+    }
+
+    pub fn ppu_register_write(&mut self, addr: u16, data: u8) {
+        self.mapper.ppu_register_write(addr, data);
+    }
+
+    /// Battery-backed memory of the cartridge, in a container identifying the ROM
+    pub fn get_save_data(&self) -> Option<Vec<u8>> {
+        self.battery_data()
+            .map(|data| save_data::pack(SaveDataKind::BatteryRam, self.origin, &data))
+    }
+
+    /// Whether the battery-backed memory was written since the last call to
+    /// `mark_save_data_saved`, so it can be saved only when needed
+    pub fn is_save_data_dirty(&self) -> bool {
+        self.save_data_dirty
+    }
+
+    /// Tells that the save data was written out, until the next write to the battery-backed memory
+    pub fn mark_save_data_saved(&mut self) {
+        self.save_data_dirty = false;
+    }
+
+    /// MD5 of the PRG and CHR ROM, identifying the ROM in the FCEUX movies
+    pub fn rom_md5(&self) -> [u8; 16] {
+        let mut md5 = Md5::new();
+        md5.update(&self.prg_memory);
+        md5.update(&self.chr_rom);
+        md5.finish()
+    }
+
+    /// Identification of the ROM, stored with the data saved from it
+    pub fn origin(&self) -> SaveDataOrigin {
+        self.origin
+    }
+
+    /// Checks that save data can be restored on this cartridge
+    pub fn check_save_data(&self, data: &[u8]) -> Result<(), SaveDataError> {
+        save_data::unpack(data, SaveDataKind::BatteryRam, self.origin).map(|_| ())
+    }
+
+    /// Battery-backed memory of the cartridge: its PRG RAM, followed by its CHR RAM if it is
+    /// battery-backed
+    fn battery_data(&self) -> Option<Vec<u8>> {
+        let sram = self.mapper.get_sram();
+        if !self.chr_ram_battery {
+            return sram.map(<[u8]>::to_vec);
+        }
+
+        let mut save_data = sram.map_or_else(Vec::new, <[u8]>::to_vec);
+        save_data.extend_from_slice(&self.chr_ram);
+        Some(save_data)
+    }
+
+    /// Turns the cartridge off and on: the mapper is built again with its power-on registers,
+    /// keeping only its PRG RAM, and the CHR RAM. The mappers of the NSF and FDS images are kept
+    /// as they are, so the disk keeps its writes.
+    pub fn power_on(&mut self) {
+        self.ppu_a12 = false;
+        let builder = match &self.mapper_builder {
+            Some(builder) => builder,
+            None => return,
+        };
+
+        let sram = self.mapper.get_sram().map(<[u8]>::to_vec);
+        // It was built the same way when loading the ROM, so this can't fail
+        if let Ok(mapper) = builder.build(sram.as_deref()) {
+            self.mapper = mapper;
+            self.mapper
+                .set_muted_audio_channels(self.muted_audio_channels);
+        }
+    }
+
+    pub fn cpu_clock(&mut self) {
+        self.mapper.cpu_clock();
+    }
+
+    pub fn end_of_scanline(&mut self) {
+        self.mapper.end_of_scanline();
+    }
+
+    pub fn disk_side_count(&self) -> u8 {
+        self.mapper.disk_side_count()
+    }
+
+    pub fn inserted_disk_side(&self) -> Option<u8> {
+        self.mapper.inserted_disk_side()
+    }
+
+    pub fn insert_disk_side(&mut self, side: Option<u8>) {
+        self.mapper.insert_disk_side(side);
+    }
+
+    pub fn clock_audio(&mut self) {
+        self.mapper.clock_audio();
+    }
+
+    pub fn audio_output(&self) -> f32 {
+        self.mapper.audio_output()
+    }
+
+    pub fn is_audio_register(&self, addr: u16) -> bool {
+        self.mapper.is_audio_register(addr)
+    }
+
+    pub fn audio_channel_count(&self) -> u8 {
+        self.mapper.audio_channel_count()
+    }
+
+    pub fn set_audio_channel_muted(&mut self, channel: u8, muted: bool) {
+        if channel >= 8 {
+            return;
+        }
+
+        if muted {
+            self.muted_audio_channels |= 1 << channel;
+        } else {
+            self.muted_audio_channels &= !(1 << channel);
+        }
+
+        self.mapper
+            .set_muted_audio_channels(self.muted_audio_channels);
+    }
+
+    pub fn is_audio_channel_muted(&self, channel: u8) -> bool {
+        channel < 8 && self.muted_audio_channels & (1 << channel) != 0
     }
 
-    pub fn take_irq_set_state(&mut self) -> bool {
-        let state = self.mapper.irq_state();
-        self.mapper.irq_clear();
-        state
+    pub fn update_irq_line(&self, irq_line: &mut IrqLine) {
+        irq_line.set(IrqSource::MAPPER, self.mapper.irq_pending());
     }
 
     #[cfg(feature = "debugger")]
     pub fn get_prg_bank(&self, addr: u16) -> Option<u8> {
         self.mapper.get_prg_bank(addr)
     }
+
+    #[cfg(feature = "debugger")]
+    pub(crate) fn bank_mapping(&self) -> BankMapping {
+        let mut mapping = BankMapping {
+            prg: [None; 10],
+            chr: [None; 8],
+        };
+        for (i, offset) in mapping.prg.iter_mut().enumerate() {
+            let addr = 0x6000 + (i as u16) * 0x1000;
+            if let CartridgeReadTarget::PrgRom(rom_offset) = self.mapper.cpu_map_read(addr) {
+                *offset = Some(rom_offset);
+            }
+        }
+        for (i, offset) in mapping.chr.iter_mut().enumerate() {
+            *offset = self
+                .mapper
+                .peek_chr_map(((i as u16) * 0x400) & CHR_ADDR_MASK);
+        }
+        mapping
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn trainer_is_loaded_at_0x7000() {
+        // MMC3 with a trainer, 2 PRG ROM banks and no CHR ROM
+        let mut rom = vec![0u8; 16 + 512 + 2 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x44, 0x00]);
+        rom[16] = 0x12;
+        rom[16 + 511] = 0x34;
+        rom[16 + 512] = 0x56; // First byte of PRG ROM
+        let cartridge = Cartridge::load(&rom, None).unwrap();
+
+        assert_eq!(cartridge.peek_prg_mem(0x7000), 0x12);
+        assert_eq!(cartridge.peek_prg_mem(0x71FF), 0x34);
+        assert_eq!(cartridge.prg_memory[0], 0x56);
+    }
+
+    #[test]
+    fn power_on_resets_mapper_and_keeps_prg_ram() {
+        // MMC1 with 4 PRG ROM banks, each starting with its number, and no CHR ROM
+        let mut rom = vec![0u8; 16 + 4 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 4, 0, 0x12, 0x00]);
+        for bank in 0..4 {
+            rom[16 + bank * 0x4000] = bank as u8;
+        }
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x8000), 0);
+
+        // Bank 2 at $8000, written bit by bit
+        for bit in [0, 1, 0, 0, 0] {
+            cartridge.write_prg_mem(0xE000, bit);
+        }
+        cartridge.write_prg_mem(0x6000, 0x34);
+        cartridge.write_chr_mem(0x0000, 0x56);
+        assert_eq!(cartridge.peek_prg_mem(0x8000), 2);
+
+        cartridge.power_on();
+        assert_eq!(cartridge.peek_prg_mem(0x8000), 0);
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x34);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x56);
+    }
+
+    #[test]
+    fn nrom_has_prg_ram_only_when_declared() {
+        // NROM with 1 PRG ROM bank and 1 CHR ROM bank
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 1, 1, 0x00, 0x00]);
+        rom[16] = 0x12; // First byte of PRG ROM
+        let cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), 0);
+        assert_eq!(cartridge.get_save_data(), None);
+
+        // With a battery, like Family BASIC
+        rom[6] = 0x02;
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), DEFAULT_PRG_RAM_SIZE);
+        cartridge.write_prg_mem(0x6000, 0x34);
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x34);
+        assert_eq!(cartridge.peek_prg_mem(0x8000), 0x12);
+
+        // With a trainer
+        let mut rom_with_trainer = rom[..16].to_vec();
+        rom_with_trainer[6] = 0x04;
+        rom_with_trainer.extend_from_slice(&[0x56; 512]);
+        rom_with_trainer.extend_from_slice(&rom[16..]);
+        let cartridge = Cartridge::load(&rom_with_trainer, None).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x7000), 0x56);
+        assert_eq!(cartridge.peek_prg_mem(0x71FF), 0x56);
+        assert_eq!(cartridge.peek_prg_mem(0x8000), 0x12);
+
+        // NES 2.0 header declaring 2KB
+        rom[6] = 0x00;
+        rom[7] = 0x08;
+        rom[10] = 0x05;
+        let cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), 0x0800);
+    }
+
+    #[test]
+    fn loads_rom_from_chunks() {
+        // MMC3 with a trainer, 2 PRG ROM banks and 1 CHR ROM bank
+        let mut rom = vec![0u8; 16 + 512 + 2 * 0x4000 + 0x2000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 1, 0x44, 0x00]);
+        rom[16] = 0x12;
+        rom[16 + 512] = 0x34; // First byte of PRG ROM
+        rom[16 + 512 + 2 * 0x4000] = 0x56; // First byte of CHR ROM
+
+        let options = LoadOptions::default();
+        let mut cartridge = Cartridge::load_from_chunks(rom.chunks(1000), None, &options).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x7000), 0x12);
+        assert_eq!(cartridge.prg_memory, &rom[16 + 512..16 + 512 + 2 * 0x4000]);
+        assert_eq!(cartridge.read_chr_mem(0x0000), 0x56);
+        assert_eq!(
+            cartridge.origin,
+            Cartridge::load(&rom, None).unwrap().origin
+        );
+
+        let short_rom = &rom[..rom.len() - 1];
+        let error = RomParserError::TooShort {
+            format: RomFormat::INes,
+            section: RomSection::ChrRom,
+            expected_size: rom.len(),
+            actual_size: short_rom.len(),
+        };
+        assert_eq!(
+            Cartridge::load_from_chunks(short_rom.chunks(1000), None, &options).err(),
+            Some(error)
+        );
+        assert_eq!(Cartridge::load(short_rom, None).err(), Some(error));
+
+        // 1TB of PRG ROM, rejected before receiving any of it
+        let mut huge_rom = rom[..16].to_vec();
+        huge_rom[4] = 0xA0; // 2^40 bytes with the exponent notation
+        huge_rom[7] = 0x08;
+        huge_rom[9] = 0x0F;
+        assert_eq!(
+            Cartridge::load_from_chunks(huge_rom.chunks(1000), None, &options).err(),
+            Some(RomParserError::RomTooLarge {
+                format: RomFormat::Nes2,
+                section: RomSection::PrgRom,
+            })
+        );
+    }
+
+    #[test]
+    fn huge_rom_sizes_are_rejected() {
+        // NES 2.0 with 2^62 bytes of PRG ROM and of CHR ROM, adding up past the address space
+        let mut rom = vec![0u8; 16 + 0x4000];
+        rom[..10].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 0xF8, 0xF8, 0x00, 0x08, 0x00, 0xFF]);
+
+        assert!(matches!(
+            Cartridge::load(&rom, None),
+            Err(RomParserError::TooShort {
+                format: RomFormat::Nes2,
+                section: RomSection::PrgRom,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn prg_ram_is_sized_from_header() {
+        // MMC1 with 2 PRG ROM banks and no CHR ROM
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x10, 0x00]);
+
+        let cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), DEFAULT_PRG_RAM_SIZE);
+
+        let cartridge = Cartridge::load_with_default_prg_ram_size(&rom, None, 0).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), 0);
+        assert_eq!(cartridge.get_save_data(), None);
+
+        // NES 2.0 header with 2KB of PRG RAM, mirrored over $6000-$7FFF
+        rom[7] = 0x08;
+        rom[10] = 0x05;
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        assert_eq!(cartridge.prg_ram_size(), 0x0800);
+
+        cartridge.write_prg_mem(0x6001, 0x12);
+        assert_eq!(cartridge.peek_prg_mem(0x6801), 0x12);
+        assert_eq!(
+            cartridge.battery_data().map(|data| data.len()),
+            Some(0x0800)
+        );
+    }
+
+    #[test]
+    fn battery_backed_chr_ram_is_saved() {
+        // NES 2.0 MMC1 with 8KB of PRG NVRAM and 8KB of CHR NVRAM
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..12].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x12, 0x08, 0, 0, 0x70, 0x70]);
+
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        cartridge.write_prg_mem(0x6000, 0x12);
+        cartridge.write_chr_mem(0x0001, 0x34);
+
+        let battery_data = cartridge.battery_data().unwrap();
+        assert_eq!(battery_data.len(), 0x4000);
+        assert_eq!(battery_data[0x2001], 0x34);
+
+        let save_data = cartridge.get_save_data().unwrap();
+        assert_eq!(cartridge.check_save_data(&save_data), Ok(()));
+
+        let mut cartridge = Cartridge::load(&rom, Some(&save_data)).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x12);
+        assert_eq!(cartridge.read_chr_mem(0x0001), 0x34);
+    }
+
+    #[test]
+    fn save_data_is_dirty_after_battery_writes() {
+        // NES 2.0 MMC1 with 8KB of PRG NVRAM and 8KB of CHR NVRAM
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..12].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x12, 0x08, 0, 0, 0x70, 0x70]);
+
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        cartridge.write_prg_mem(0x8000, 0x80);
+        assert!(!cartridge.is_save_data_dirty());
+
+        cartridge.write_prg_mem(0x6000, 0x12);
+        assert!(cartridge.is_save_data_dirty());
+        cartridge.get_save_data();
+        assert!(cartridge.is_save_data_dirty());
+        cartridge.mark_save_data_saved();
+        assert!(!cartridge.is_save_data_dirty());
+
+        cartridge.write_chr_mem(0x0001, 0x34);
+        assert!(cartridge.is_save_data_dirty());
+    }
+
+    #[test]
+    fn mmc3_irq_follows_ppu_a12() {
+        // MMC3 with 2 PRG ROM banks and no CHR ROM
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x40, 0x00]);
+        let mut cartridge = Cartridge::load(&rom, None).unwrap();
+        let mut irq_line = IrqLine::default();
+
+        cartridge.write_prg_mem(0xC000, 2); // IRQ latch
+        cartridge.write_prg_mem(0xC001, 0); // IRQ reload
+        cartridge.write_prg_mem(0xE001, 0); // IRQ enable
+
+        // The reload happens on the first edge, then the counter reaches 0 two edges later
+        for _ in 0..3 {
+            cartridge.update_irq_line(&mut irq_line);
+            assert!(!irq_line.is_asserted());
+
+            cartridge.read_chr_mem(0x0000);
+            cartridge.read_chr_mem(0x1000);
+            cartridge.read_chr_mem(0x1008); // A12 is still high
+        }
+        cartridge.update_irq_line(&mut irq_line);
+        assert!(irq_line.is_asserted_by(IrqSource::MAPPER));
+
+        cartridge.write_prg_mem(0xE000, 0); // IRQ disable and acknowledge
+        cartridge.update_irq_line(&mut irq_line);
+        assert!(!irq_line.is_asserted());
+    }
+
+    #[test]
+    fn rom_database_corrects_header() {
+        // Header claiming a NROM with horizontal mirroring
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x00, 0x00]);
+
+        let mut rom_database = RomDatabase::new();
+        rom_database.insert(
+            crc32(&rom[16..]),
+            HeaderOverride {
+                mapper_id: Some(1),
+                mirroring: Some(Mirroring::Vertical),
+                prg_ram_size: Some(0x2000),
+                ..Default::default()
+            },
+        );
+
+        let mut cartridge = Cartridge::load_with_rom_database(&rom, None, &rom_database).unwrap();
+        assert!(matches!(cartridge.mirroring(), Mirroring::Vertical));
+        assert_eq!(cartridge.prg_ram_size(), 0x2000);
+
+        let info = cartridge.info();
+        assert_eq!(info.format, RomFormat::INes);
+        assert_eq!(info.mapper_id, 1);
+        assert_eq!(info.mirroring, Mirroring::Vertical);
+        assert_eq!((info.prg_rom_size, info.chr_rom_size), (0x8000, 0));
+        assert_eq!(info.chr_ram_size, 0x2000);
+        assert_eq!(info.crc32, crc32(&rom[16..]));
+        assert!(!info.battery);
+
+        // MMC1 PRG RAM
+        cartridge.write_prg_mem(0x6000, 0x12);
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x12);
+    }
+
+    #[test]
+    fn save_data_of_another_rom_is_rejected() {
+        // MMC1 with 2 PRG ROM banks and no CHR ROM
+        let mut rom = vec![0u8; 16 + 2 * 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x12, 0x00]);
+        let save_data = Cartridge::load(&rom, None)
+            .unwrap()
+            .get_save_data()
+            .unwrap();
+
+        rom[16] = 0xFF;
+        assert!(matches!(
+            Cartridge::load(&rom, Some(&save_data)),
+            Err(RomParserError::InvalidSaveData(SaveDataError::RomMismatch))
+        ));
+
+        // Saves without a container are restored as is
+        let cartridge = Cartridge::load(&rom, Some(&[0x12])).unwrap();
+        assert_eq!(cartridge.peek_prg_mem(0x6000), 0x12);
+    }
 }
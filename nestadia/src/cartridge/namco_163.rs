@@ -0,0 +1,202 @@
+// Namco 163 expansion audio: up to 8 wavetable channels, whose registers and 4 bits samples
+// share 128 bytes of internal RAM. The chip updates one channel every 15 CPU cycles, so the
+// more channels are enabled, the lower their sample rate.
+// http://wiki.nesdev.com/w/index.php/Namco_163_audio
+
+/// Output of a channel at full volume, roughly the level of a full volume pulse channel
+const CHANNEL_LEVEL: f32 = 0.15;
+
+/// CPU cycles spent updating a channel
+const CHANNEL_UPDATE_CYCLES: u8 = 15;
+
+/// Registers of the channel n are at $40 + 8 * n
+const CHANNEL_REGISTERS: usize = 0x40;
+
+pub struct Namco163 {
+    ram: [u8; 0x80],
+    address: u8,
+    auto_increment: bool,
+
+    current_channel: u8,
+    update_timer: u8,
+
+    /// Last output of each channel, between 0 and 225
+    outputs: [u8; 8],
+
+    /// One bit per channel
+    muted_channels: u8,
+}
+
+impl_stateful!(Namco163 {
+    ram,
+    address,
+    auto_increment,
+    current_channel,
+    update_timer,
+    outputs
+});
+
+impl Namco163 {
+    pub fn new() -> Self {
+        Self {
+            ram: [0u8; 0x80],
+            address: 0,
+            auto_increment: false,
+
+            current_channel: 7,
+            update_timer: 0,
+
+            outputs: [0u8; 8],
+
+            muted_channels: 0,
+        }
+    }
+
+    pub const CHANNEL_COUNT: u8 = 8;
+
+    /// Bit n mutes the channel n
+    pub fn set_muted_channels(&mut self, muted_channels: u8) {
+        self.muted_channels = muted_channels;
+    }
+
+    /// Write to $F800-$FFFF: selects the address of the internal RAM
+    pub fn write_address(&mut self, data: u8) {
+        self.address = data & 0x7F;
+        self.auto_increment = data & 0x80 == 0x80;
+    }
+
+    /// Read from $4800-$4FFF, without the auto increment
+    pub fn peek_data(&self) -> u8 {
+        self.ram[self.address as usize]
+    }
+
+    /// Called after every read from $4800-$4FFF
+    pub fn increment_address(&mut self) {
+        if self.auto_increment {
+            self.address = (self.address + 1) & 0x7F;
+        }
+    }
+
+    /// Write to $4800-$4FFF
+    pub fn write_data(&mut self, data: u8) {
+        self.ram[self.address as usize] = data;
+        self.increment_address();
+    }
+
+    /// Number of enabled channels, from 1 to 8. The enabled channels are the last ones.
+    fn enabled_channels(&self) -> u8 {
+        ((self.ram[0x7F] >> 4) & 0x07) + 1
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {
+        self.update_timer += 1;
+        if self.update_timer < CHANNEL_UPDATE_CYCLES {
+            return;
+        }
+        self.update_timer = 0;
+
+        self.update_channel(self.current_channel);
+
+        let first_channel = 8 - self.enabled_channels();
+        self.current_channel = if self.current_channel <= first_channel {
+            7
+        } else {
+            self.current_channel - 1
+        };
+    }
+
+    fn update_channel(&mut self, channel: u8) {
+        let registers = CHANNEL_REGISTERS + channel as usize * 8;
+        let register = |offset: usize| u32::from(self.ram[registers + offset]);
+
+        let frequency = register(0) | (register(2) << 8) | ((register(4) & 0x03) << 16);
+        let length = 256 - (register(4) & 0xFC);
+        let wave_address = register(6);
+        let volume = register(7) & 0x0F;
+
+        // The phase is a 24 bits fixed point sample index, with 16 fractional bits
+        let mut phase = register(1) | (register(3) << 8) | (register(5) << 16);
+        phase = (phase + frequency) % (length << 16);
+
+        self.ram[registers + 1] = phase as u8;
+        self.ram[registers + 3] = (phase >> 8) as u8;
+        self.ram[registers + 5] = (phase >> 16) as u8;
+
+        // Samples are nibbles, the low nibble of a byte comes first
+        let sample_address = ((phase >> 16) + wave_address) & 0xFF;
+        let byte = self.ram[(sample_address >> 1) as usize];
+        let sample = if sample_address & 0x01 == 0x01 {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+
+        self.outputs[channel as usize] = sample * volume as u8;
+    }
+
+    /// Current output of the chip, in the same scale as the APU output.
+    /// The real chip outputs one channel at a time, which is heard as a loud whine. Enabled
+    /// channels are averaged instead, as the filters of the cartridge and console would do.
+    pub fn output(&self) -> f32 {
+        let enabled_channels = self.enabled_channels();
+
+        let sum: u32 = ((8 - enabled_channels)..8)
+            .filter(|channel| self.muted_channels & (0x01 << channel) == 0)
+            .map(|channel| u32::from(self.outputs[channel as usize]))
+            .sum();
+
+        sum as f32 / 225.0 * CHANNEL_LEVEL / f32::from(enabled_channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Namco163 {
+        fn write(&mut self, address: u8, data: u8) {
+            self.write_address(address);
+            self.write_data(data);
+        }
+    }
+
+    #[test]
+    fn internal_ram_auto_increments() {
+        let mut chip = Namco163::new();
+        chip.write_address(0x80 | 0x10);
+        chip.write_data(0x12);
+        chip.write_data(0x34);
+
+        chip.write_address(0x80 | 0x10);
+        assert_eq!(chip.peek_data(), 0x12);
+        chip.increment_address();
+        assert_eq!(chip.peek_data(), 0x34);
+    }
+
+    #[test]
+    fn plays_the_wave_of_the_last_channel() {
+        let mut chip = Namco163::new();
+
+        // Wave of 4 samples at address 0: 0, 15, 0, 15
+        chip.write(0x00, 0xF0);
+        chip.write(0x01, 0xF0);
+
+        // Channel 7, one sample per update
+        chip.write(0x78, 0x00);
+        chip.write(0x7A, 0x00);
+        chip.write(0x7C, (256 - 4) as u8 | 0x01);
+        chip.write(0x7E, 0x00);
+        chip.write(0x7F, 0x0F); // Full volume, 1 channel enabled
+
+        let mut outputs = [0.0; 4];
+        for output in outputs.iter_mut() {
+            for _ in 0..CHANNEL_UPDATE_CYCLES {
+                chip.clock();
+            }
+            *output = chip.output();
+        }
+
+        assert_eq!(outputs, [CHANNEL_LEVEL, 0.0, CHANNEL_LEVEL, 0.0]);
+    }
+}
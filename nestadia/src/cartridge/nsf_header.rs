@@ -0,0 +1,111 @@
+use alloc::string::String;
+use core::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::cartridge::{RomFormat, RomParserError, RomSection};
+
+pub const NSF_HEADER_SIZE: usize = 0x80;
+
+const MAGIC_BYTES: [u8; 5] = [0x4e, 0x45, 0x53, 0x4d, 0x1a];
+
+/// Header of a NES Sound Format file
+/// http://wiki.nesdev.com/w/index.php/NSF
+#[derive(Debug, Clone)]
+pub struct NsfHeader {
+    pub version: u8,
+    pub total_songs: u8,
+
+    /// 1-based
+    pub starting_song: u8,
+
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+
+    pub song_name: String,
+    pub artist: String,
+    pub copyright: String,
+
+    /// Duration between two calls of the play routine, in µs
+    pub ntsc_play_speed: u16,
+
+    /// Banks initially mapped at $8000-$FFFF. The file uses bankswitching if any of them is non-zero.
+    pub bankswitch_init: [u8; 8],
+
+    pub extra_sound_chips: SoundChips,
+}
+
+bitflags! {
+    pub struct SoundChips: u8 {
+        const VRC6 = (1 << 0);
+        const VRC7 = (1 << 1);
+        const FDS = (1 << 2);
+        const MMC5 = (1 << 3);
+        const NAMCO_163 = (1 << 4);
+        const SUNSOFT_5B = (1 << 5);
+    }
+}
+
+impl NsfHeader {
+    /// Whether `data` starts like a NSF file
+    pub fn is_nsf(data: &[u8]) -> bool {
+        data.starts_with(&MAGIC_BYTES)
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.bankswitch_init.iter().any(|&bank| bank != 0)
+    }
+}
+
+impl TryFrom<&[u8]> for NsfHeader {
+    type Error = RomParserError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() < NSF_HEADER_SIZE {
+            return Err(RomParserError::TooShort {
+                format: RomFormat::Nsf,
+                section: RomSection::Header,
+                expected_size: NSF_HEADER_SIZE,
+                actual_size: data.len(),
+            });
+        };
+
+        if data[..5] != MAGIC_BYTES {
+            return Err(RomParserError::InvalidMagicBytes {
+                format: RomFormat::Nsf,
+                offset: 0,
+            });
+        };
+
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+
+        // Strings are null-terminated and padded to 32 bytes
+        let string = |offset: usize| {
+            let field = &data[offset..offset + 32];
+            let end = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+
+        let mut bankswitch_init = [0u8; 8];
+        bankswitch_init.copy_from_slice(&data[0x70..0x78]);
+
+        Ok(NsfHeader {
+            version: data[0x05],
+            total_songs: data[0x06],
+            starting_song: data[0x07],
+
+            load_address: word(0x08),
+            init_address: word(0x0A),
+            play_address: word(0x0C),
+
+            song_name: string(0x0E),
+            artist: string(0x2E),
+            copyright: string(0x4E),
+
+            ntsc_play_speed: word(0x6E),
+            bankswitch_init,
+            extra_sound_chips: SoundChips::from_bits_truncate(data[0x7B]),
+        })
+    }
+}
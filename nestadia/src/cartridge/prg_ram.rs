@@ -0,0 +1,51 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Work RAM or battery-backed RAM of a cartridge, sized from the ROM header.
+/// Addresses are mirrored over its size, and boards without PRG RAM read 0.
+pub struct PrgRam {
+    data: Vec<u8>,
+}
+
+impl_stateful!(PrgRam { data });
+
+impl PrgRam {
+    pub fn new(size: usize, save_data: Option<&[u8]>) -> Self {
+        let mut data = vec![0u8; size];
+
+        // Load the save data
+        if let Some(save_data) = save_data {
+            data.iter_mut()
+                .zip(save_data.iter())
+                .for_each(|(r, s)| *r = *s)
+        };
+
+        Self { data }
+    }
+
+    pub fn read(&self, addr: usize) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+
+        self.data[addr % self.data.len()]
+    }
+
+    pub fn write(&mut self, addr: usize, data: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+
+        let len = self.data.len();
+        self.data[addr % len] = data;
+    }
+
+    /// Contents of the RAM, `None` on boards without PRG RAM
+    pub fn data(&self) -> Option<&[u8]> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(&self.data)
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use alloc::vec::Vec;
+
+use super::ines_header::{Flags6, INesHeader};
+use super::Mirroring;
+
+/// Corrections of the settings given by a ROM header. `None` keeps the header's value.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeaderOverride {
+    pub mapper_id: Option<u16>,
+    pub submapper_id: Option<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub prg_ram_size: Option<usize>,
+    pub battery: Option<bool>,
+}
+
+impl HeaderOverride {
+    pub(super) fn apply(&self, header: &mut INesHeader) {
+        if let Some(mapper_id) = self.mapper_id {
+            header.mapper_id = mapper_id;
+        }
+
+        if let Some(submapper_id) = self.submapper_id {
+            header.submapper_id = submapper_id;
+        }
+
+        if let Some(battery) = self.battery {
+            header.flags6.set(Flags6::PRG_RAM, battery);
+        }
+    }
+}
+
+/// Header corrections of known ROMs, keyed by the CRC32 of their PRG and CHR ROM.
+/// Many circulating dumps have a wrong iNES header.
+#[derive(Debug, Default, Clone)]
+pub struct RomDatabase {
+    entries: Vec<(u32, HeaderOverride)>,
+}
+
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Database shipped with the crate, empty without the `rom-db` feature
+    pub fn builtin() -> Self {
+        #[cfg(feature = "rom-db")]
+        return Self::parse(include_str!("rom_database.txt"));
+
+        #[cfg(not(feature = "rom-db"))]
+        Self::new()
+    }
+
+    /// Parses a database with a ROM per line: its CRC32 in hexadecimal, its mapper with an
+    /// optional submapper (`4` or `4.1`), its mirroring (`H`, `V` or `4`), its PRG RAM size in
+    /// bytes, and whether it has a battery (`0` or `1`). `-` keeps the header's value, and the
+    /// lines starting with `#` are comments. Invalid lines are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut database = Self::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_entry(line) {
+                Some((crc, header_override)) => database.insert(crc, header_override),
+                None => log::warn!("Invalid ROM database entry: {}", line),
+            }
+        }
+
+        database
+    }
+
+    /// Adds the corrections of a ROM, replacing any previous entry for it
+    pub fn insert(&mut self, crc: u32, header_override: HeaderOverride) {
+        self.entries.retain(|(c, _)| *c != crc);
+        self.entries.push((crc, header_override));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn find(&self, crc: u32) -> Option<HeaderOverride> {
+        self.entries
+            .iter()
+            .find(|(c, _)| *c == crc)
+            .map(|(_, header_override)| *header_override)
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(u32, HeaderOverride)> {
+    let mut fields = line.split_whitespace();
+    let crc = u32::from_str_radix(fields.next()?, 16).ok()?;
+
+    let mut header_override = HeaderOverride::default();
+
+    match fields.next()? {
+        "-" => (),
+        mapper => {
+            let mut ids = mapper.splitn(2, '.');
+            header_override.mapper_id = Some(ids.next()?.parse().ok()?);
+            if let Some(submapper_id) = ids.next() {
+                header_override.submapper_id = Some(submapper_id.parse().ok()?);
+            }
+        }
+    }
+
+    header_override.mirroring = match fields.next()? {
+        "-" => None,
+        "H" => Some(Mirroring::Horizontal),
+        "V" => Some(Mirroring::Vertical),
+        "4" => Some(Mirroring::FourScreen),
+        _ => return None,
+    };
+
+    header_override.prg_ram_size = match fields.next()? {
+        "-" => None,
+        size => Some(size.parse().ok()?),
+    };
+
+    header_override.battery = match fields.next()? {
+        "-" => None,
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => return None,
+    };
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some((crc, header_override))
+}
+
+/// CRC32 (IEEE 802.3), as used by the ROM databases
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// CRC32 of data given in several parts
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self(!0)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 ^= u32::from(*byte);
+            for _ in 0..8 {
+                self.0 = if self.0 & 0x01 == 0x01 {
+                    (self.0 >> 1) ^ 0xEDB8_8320
+                } else {
+                    self.0 >> 1
+                };
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn parses_entries() {
+        let database = RomDatabase::parse(
+            "# Comment\n\
+             0123ABCD 4.1 V 8192 1\n\
+             89ABCDEF - 4 - -\n\
+             DEADBEEF 4 X - -\n",
+        );
+
+        let entry = database.find(0x0123_ABCD).unwrap();
+        assert_eq!(entry.mapper_id, Some(4));
+        assert_eq!(entry.submapper_id, Some(1));
+        assert!(matches!(entry.mirroring, Some(Mirroring::Vertical)));
+        assert_eq!(entry.prg_ram_size, Some(8192));
+        assert_eq!(entry.battery, Some(true));
+
+        let entry = database.find(0x89AB_CDEF).unwrap();
+        assert_eq!(entry.mapper_id, None);
+        assert!(matches!(entry.mirroring, Some(Mirroring::FourScreen)));
+
+        assert!(database.find(0xDEAD_BEEF).is_none());
+    }
+
+    #[cfg(feature = "rom-db")]
+    #[test]
+    fn builtin_database_has_entries() {
+        let database = RomDatabase::builtin();
+        assert!(!database.is_empty());
+
+        // Every line that isn't a comment is a valid entry
+        let lines = include_str!("rom_database.txt")
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .count();
+        assert_eq!(database.len(), lines);
+
+        let entry = database.find(0x3337_EC46).unwrap();
+        assert_eq!(entry.mapper_id, Some(0));
+        assert!(matches!(entry.mirroring, Some(Mirroring::Vertical)));
+    }
+}
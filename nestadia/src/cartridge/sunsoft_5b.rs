@@ -0,0 +1,325 @@
+// Sunsoft 5B expansion audio, found alongside the FME-7 on the cartridge of Gimmick!.
+// It is a YM2149 (AY-3-8910 family) with 3 square channels, a noise generator and an envelope.
+// http://wiki.nesdev.com/w/index.php/Sunsoft_5B_audio
+
+/// Output of a channel at full volume. The real chip is much louder than the APU and would clip
+/// the mix, so it is brought back to roughly the level of a full volume pulse channel.
+const CHANNEL_LEVEL: f32 = 0.15;
+
+/// The chip internally divides the CPU clock by 16 before clocking its units
+const PRESCALER_PERIOD: u8 = 16;
+
+#[derive(Default)]
+struct Tone {
+    period: u16,
+    timer: u16,
+    output: bool,
+}
+
+impl_stateful!(Tone {
+    period,
+    timer,
+    output
+});
+
+impl Tone {
+    fn clock(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period.max(1) - 1;
+            self.output = !self.output;
+        } else {
+            self.timer -= 1;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Envelope {
+    period: u16,
+    timer: u16,
+    step: u8,
+    attack: bool,
+    continues: bool,
+    alternate: bool,
+    hold: bool,
+    holding: bool,
+}
+
+impl_stateful!(Envelope {
+    period,
+    timer,
+    step,
+    attack,
+    continues,
+    alternate,
+    hold,
+    holding
+});
+
+impl Envelope {
+    /// Writes the `CAaH` shape register, which restarts the envelope
+    fn write_shape(&mut self, data: u8) {
+        self.continues = data & 0x08 == 0x08;
+        self.attack = data & 0x04 == 0x04;
+        self.alternate = data & 0x02 == 0x02;
+        self.hold = data & 0x01 == 0x01;
+
+        self.step = 0;
+        self.timer = 0;
+        self.holding = false;
+    }
+
+    fn clock(&mut self) {
+        if self.holding {
+            return;
+        }
+
+        if self.timer == 0 {
+            self.timer = self.period.max(1) - 1;
+        } else {
+            self.timer -= 1;
+            return;
+        }
+
+        if self.step < 31 {
+            self.step += 1;
+            return;
+        }
+
+        // End of a ramp
+        if !self.continues {
+            // Drops to 0 and stays there
+            self.holding = true;
+            self.attack = false;
+        } else if self.hold {
+            self.holding = true;
+            if self.alternate {
+                self.attack = !self.attack;
+            }
+        } else {
+            if self.alternate {
+                self.attack = !self.attack;
+            }
+            self.step = 0;
+        }
+    }
+
+    /// Current level, between 0 and 31
+    fn level(&self) -> u8 {
+        if self.attack {
+            self.step
+        } else {
+            31 - self.step
+        }
+    }
+}
+
+pub struct Sunsoft5B {
+    register_select: u8,
+
+    tones: [Tone; 3],
+    noise_period: u8,
+    noise_timer: u8,
+    noise_shift_register: u32,
+    envelope: Envelope,
+
+    /// Bits 0-2 disable the tones, bits 3-5 disable the noise, for each channel
+    mixer: u8,
+
+    /// Bits 0-3 are the volume, bit 4 uses the envelope instead
+    volumes: [u8; 3],
+
+    prescaler: u8,
+    level_table: [f32; 32],
+
+    /// One bit per channel
+    muted_channels: u8,
+}
+
+impl_stateful!(Sunsoft5B {
+    register_select,
+    tones,
+    noise_period,
+    noise_timer,
+    noise_shift_register,
+    envelope,
+    mixer,
+    volumes,
+    prescaler
+});
+
+impl Default for Sunsoft5B {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sunsoft5B {
+    pub fn new() -> Self {
+        // Levels are logarithmic, each step is 1.5dB
+        let mut level_table = [0f32; 32];
+        for (level, value) in level_table.iter_mut().enumerate().skip(1) {
+            *value = libm::powf(10.0, (level as f32 - 31.0) * 1.5 / 20.0);
+        }
+
+        Self {
+            register_select: 0,
+
+            tones: Default::default(),
+            noise_period: 0,
+            noise_timer: 0,
+            noise_shift_register: 1,
+            envelope: Default::default(),
+
+            mixer: 0,
+            volumes: [0; 3],
+
+            prescaler: 0,
+            level_table,
+
+            muted_channels: 0,
+        }
+    }
+
+    pub const CHANNEL_COUNT: u8 = 3;
+
+    /// Bit n mutes the channel n
+    pub fn set_muted_channels(&mut self, muted_channels: u8) {
+        self.muted_channels = muted_channels;
+    }
+
+    /// Write to $C000-$DFFF: selects the internal register
+    pub fn write_register_select(&mut self, data: u8) {
+        self.register_select = data & 0x0F;
+    }
+
+    /// Write to $E000-$FFFF: writes the selected internal register
+    pub fn write_register_data(&mut self, data: u8) {
+        match self.register_select {
+            0x00 | 0x02 | 0x04 => {
+                let tone = &mut self.tones[(self.register_select / 2) as usize];
+                tone.period = (tone.period & 0x0F00) | u16::from(data);
+            }
+            0x01 | 0x03 | 0x05 => {
+                let tone = &mut self.tones[(self.register_select / 2) as usize];
+                tone.period = (tone.period & 0x00FF) | (u16::from(data & 0x0F) << 8);
+            }
+            0x06 => self.noise_period = data & 0x1F,
+            0x07 => self.mixer = data & 0x3F,
+            0x08..=0x0A => self.volumes[(self.register_select - 0x08) as usize] = data & 0x1F,
+            0x0B => self.envelope.period = (self.envelope.period & 0xFF00) | u16::from(data),
+            0x0C => self.envelope.period = (self.envelope.period & 0x00FF) | (u16::from(data) << 8),
+            0x0D => self.envelope.write_shape(data),
+            _ => (), // I/O ports, unused
+        }
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < PRESCALER_PERIOD {
+            return;
+        }
+        self.prescaler = 0;
+
+        for tone in self.tones.iter_mut() {
+            tone.clock();
+        }
+
+        // The noise is clocked at half the rate of the tones
+        if self.noise_timer == 0 {
+            self.noise_timer = self.noise_period.max(1) * 2 - 1;
+
+            // 17 bits LFSR
+            let feedback = (self.noise_shift_register ^ (self.noise_shift_register >> 3)) & 0x01;
+            self.noise_shift_register = (self.noise_shift_register >> 1) | (feedback << 16);
+        } else {
+            self.noise_timer -= 1;
+        }
+
+        self.envelope.clock();
+    }
+
+    /// Current output of the chip, in the same scale as the APU output
+    pub fn output(&self) -> f32 {
+        let noise = self.noise_shift_register & 0x01 == 0x01;
+
+        (0..3)
+            .map(|channel| {
+                let tone_disabled = self.mixer & (0x01 << channel) != 0;
+                let noise_disabled = self.mixer & (0x08 << channel) != 0;
+
+                if self.muted_channels & (0x01 << channel) != 0 {
+                    0.0
+                } else if (tone_disabled || self.tones[channel].output) && (noise_disabled || noise)
+                {
+                    let volume = self.volumes[channel];
+                    let level = if volume & 0x10 == 0x10 {
+                        self.envelope.level()
+                    } else if volume & 0x0F == 0 {
+                        0
+                    } else {
+                        (volume & 0x0F) * 2 + 1
+                    };
+
+                    self.level_table[level as usize] * CHANNEL_LEVEL
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Sunsoft5B {
+        fn write(&mut self, register: u8, data: u8) {
+            self.write_register_select(register);
+            self.write_register_data(data);
+        }
+    }
+
+    #[test]
+    fn tone_toggles_at_period() {
+        let mut chip = Sunsoft5B::new();
+        chip.write(0x00, 4); // Channel A period
+        chip.write(0x07, 0x3E); // Only the tone of channel A is enabled
+        chip.write(0x08, 0x0F); // Full volume
+
+        let mut toggles = 0;
+        let mut last_output = chip.output();
+        for _ in 0..(16 * 4 * 10) {
+            chip.clock();
+
+            let output = chip.output();
+            if output != last_output {
+                toggles += 1;
+                last_output = output;
+            }
+        }
+
+        assert_eq!(toggles, 10);
+        assert!(last_output == 0.0 || last_output == CHANNEL_LEVEL);
+    }
+
+    #[test]
+    fn envelope_holds_after_attack() {
+        let mut chip = Sunsoft5B::new();
+        chip.write(0x07, 0x3F); // Everything disabled, the channels output their level
+        chip.write(0x08, 0x10); // Channel A follows the envelope
+        chip.write(0x0B, 1);
+        chip.write(0x0D, 0x0D); // Attack then hold
+
+        assert_eq!(chip.output(), 0.0);
+
+        for _ in 0..(16 * 64) {
+            chip.clock();
+        }
+
+        assert_eq!(chip.envelope.level(), 31);
+        assert_eq!(chip.output(), CHANNEL_LEVEL);
+    }
+}
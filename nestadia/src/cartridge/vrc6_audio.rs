@@ -0,0 +1,265 @@
+// Konami VRC6 expansion audio: 2 pulse channels with 8 duty cycles and a sawtooth channel
+// http://wiki.nesdev.com/w/index.php/VRC6_audio
+
+/// Output of a pulse channel at full volume, roughly the level of a full volume APU pulse.
+/// The sawtooth goes twice as high.
+const PULSE_LEVEL: f32 = 0.15;
+
+#[derive(Default)]
+struct Pulse {
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    period: u16,
+    enabled: bool,
+
+    timer: u16,
+    step: u8,
+}
+
+impl_stateful!(Pulse {
+    volume,
+    duty,
+    ignore_duty,
+    period,
+    enabled,
+    timer,
+    step
+});
+
+impl Pulse {
+    fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.volume = data & 0x0F;
+                self.duty = (data >> 4) & 0x07;
+                self.ignore_duty = data & 0x80 == 0x80;
+            }
+            1 => self.period = (self.period & 0x0F00) | u16::from(data),
+            _ => {
+                self.period = (self.period & 0x00FF) | (u16::from(data & 0x0F) << 8);
+                self.enabled = data & 0x80 == 0x80;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, period_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer == 0 {
+            self.timer = self.period >> period_shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Between 0 and 15
+    fn output(&self) -> u8 {
+        if self.enabled && (self.ignore_duty || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sawtooth {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl_stateful!(Sawtooth {
+    rate,
+    period,
+    enabled,
+    timer,
+    step,
+    accumulator
+});
+
+impl Sawtooth {
+    fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => self.rate = data & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | u16::from(data),
+            _ => {
+                self.period = (self.period & 0x00FF) | (u16::from(data & 0x0F) << 8);
+                self.enabled = data & 0x80 == 0x80;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, period_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period >> period_shift;
+
+        // The rate is added every other step, and the accumulator resets on the 14th step
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 0x01 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    /// Between 0 and 31
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+pub struct Vrc6Audio {
+    pulses: [Pulse; 2],
+    sawtooth: Sawtooth,
+    halted: bool,
+    period_shift: u8,
+
+    /// One bit per channel
+    muted_channels: u8,
+}
+
+impl_stateful!(Vrc6Audio {
+    pulses,
+    sawtooth,
+    halted,
+    period_shift
+});
+
+impl Vrc6Audio {
+    pub fn new() -> Self {
+        Self {
+            pulses: Default::default(),
+            sawtooth: Default::default(),
+            halted: false,
+            period_shift: 0,
+
+            muted_channels: 0,
+        }
+    }
+
+    pub const CHANNEL_COUNT: u8 = 3;
+
+    /// Bit n mutes the channel n: pulse 1, pulse 2, then the sawtooth
+    pub fn set_muted_channels(&mut self, muted_channels: u8) {
+        self.muted_channels = muted_channels;
+    }
+
+    /// Write to $9000-$B002, with the address lines already swapped for mapper 26.
+    /// Returns `false` if `addr` isn't an audio register.
+    pub fn write_register(&mut self, addr: u16, data: u8) -> bool {
+        let register = addr & 0x0003;
+
+        match addr & 0xF000 {
+            0x9000 if register == 3 => {
+                self.halted = data & 0x01 == 0x01;
+                self.period_shift = if data & 0x04 == 0x04 {
+                    8
+                } else if data & 0x02 == 0x02 {
+                    4
+                } else {
+                    0
+                };
+            }
+            0x9000 => self.pulses[0].write(register, data),
+            0xA000 if register != 3 => self.pulses[1].write(register, data),
+            0xB000 if register != 3 => self.sawtooth.write(register, data),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {
+        if self.halted {
+            return;
+        }
+
+        for pulse in self.pulses.iter_mut() {
+            pulse.clock(self.period_shift);
+        }
+        self.sawtooth.clock(self.period_shift);
+    }
+
+    /// Current output of the chip, in the same scale as the APU output
+    pub fn output(&self) -> f32 {
+        let outputs = [
+            self.pulses[0].output(),
+            self.pulses[1].output(),
+            self.sawtooth.output(),
+        ];
+
+        let sum: u8 = outputs
+            .iter()
+            .enumerate()
+            .filter(|(channel, _)| self.muted_channels & (0x01 << channel) == 0)
+            .map(|(_, output)| output)
+            .sum();
+
+        f32::from(sum) / 15.0 * PULSE_LEVEL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sawtooth_ramps_up_then_resets() {
+        let mut chip = Vrc6Audio::new();
+        chip.write_register(0xB000, 0x2A); // Maximum rate without overflowing
+        chip.write_register(0xB001, 0x00);
+        chip.write_register(0xB002, 0x80); // Enabled, period of 1 cycle
+
+        let mut outputs = [0u8; 14];
+        for output in outputs.iter_mut() {
+            chip.clock();
+            *output = chip.sawtooth.output();
+        }
+
+        assert_eq!(
+            outputs,
+            [0, 5, 5, 10, 10, 15, 15, 21, 21, 26, 26, 31, 31, 0]
+        );
+    }
+
+    #[test]
+    fn pulse_duty_cycle() {
+        let mut chip = Vrc6Audio::new();
+        chip.write_register(0x9000, 0x3F); // Duty 3 (4/16), full volume
+        chip.write_register(0x9001, 0x00);
+        chip.write_register(0x9002, 0x80);
+
+        let high_steps = (0..16)
+            .filter(|_| {
+                chip.clock();
+                chip.pulses[0].output() == 15
+            })
+            .count();
+        assert_eq!(high_steps, 4);
+    }
+}
@@ -0,0 +1,57 @@
+// Konami VRC7 expansion audio: a YM2413 (OPLL) derivative with 6 FM channels.
+// The FM synthesis isn't emulated yet: the registers are latched, but the chip is silent.
+// http://wiki.nesdev.com/w/index.php/VRC7_audio
+
+pub struct Vrc7Audio {
+    register_select: u8,
+    registers: [u8; 0x40],
+
+    /// One bit per channel
+    muted_channels: u8,
+}
+
+impl_stateful!(Vrc7Audio {
+    register_select,
+    registers
+});
+
+impl Vrc7Audio {
+    pub fn new() -> Self {
+        Self {
+            register_select: 0,
+            registers: [0u8; 0x40],
+
+            muted_channels: 0,
+        }
+    }
+
+    pub const CHANNEL_COUNT: u8 = 6;
+
+    /// Bit n mutes the channel n
+    pub fn set_muted_channels(&mut self, muted_channels: u8) {
+        self.muted_channels = muted_channels;
+    }
+
+    /// Write to $9010: selects the internal register
+    pub fn write_register_select(&mut self, data: u8) {
+        self.register_select = data & 0x3F;
+    }
+
+    /// Write to $9030: writes the selected internal register
+    pub fn write_register_data(&mut self, data: u8) {
+        self.registers[self.register_select as usize] = data;
+    }
+
+    /// Resets the registers, while the sound is reset through $E000
+    pub fn reset(&mut self) {
+        self.registers = [0u8; 0x40];
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {}
+
+    /// Current output of the chip, in the same scale as the APU output
+    pub fn output(&self) -> f32 {
+        0.0
+    }
+}
@@ -0,0 +1,155 @@
+// IRQ counter shared by the Konami VRC4, VRC6 and VRC7. An 8 bits counter counts up to $FF and
+// reloads from the latch, either every CPU cycle or every scanline, using a prescaler
+// dividing the CPU clock by 113.667.
+// http://wiki.nesdev.com/w/index.php/VRC_IRQ
+
+/// Prescaler period, in PPU cycles (3 per CPU cycle)
+const SCANLINE_PPU_CYCLES: i16 = 341;
+
+pub struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+
+    enabled: bool,
+    enabled_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl_stateful!(VrcIrq {
+    latch,
+    counter,
+    prescaler,
+    enabled,
+    enabled_after_ack,
+    cycle_mode,
+    pending
+});
+
+impl VrcIrq {
+    pub fn new() -> Self {
+        Self {
+            latch: 0,
+            counter: 0,
+            prescaler: SCANLINE_PPU_CYCLES,
+
+            enabled: false,
+            enabled_after_ack: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub fn write_latch(&mut self, data: u8) {
+        self.latch = data;
+    }
+
+    /// The VRC4 writes the latch 4 bits at a time
+    pub fn write_latch_low(&mut self, data: u8) {
+        self.latch = (self.latch & 0xF0) | (data & 0x0F);
+    }
+
+    pub fn write_latch_high(&mut self, data: u8) {
+        self.latch = (self.latch & 0x0F) | (data << 4);
+    }
+
+    pub fn write_control(&mut self, data: u8) {
+        self.enabled_after_ack = data & 0x01 == 0x01;
+        self.enabled = data & 0x02 == 0x02;
+        self.cycle_mode = data & 0x04 == 0x04;
+        self.pending = false;
+
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = SCANLINE_PPU_CYCLES;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enabled_after_ack;
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Must be called once per CPU cycle
+    pub fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.cycle_mode {
+            self.clock_counter();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += SCANLINE_PPU_CYCLES;
+                self.clock_counter();
+            }
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_mode() {
+        let mut irq = VrcIrq::new();
+        irq.write_latch_low(0x0D);
+        irq.write_latch_high(0x0F);
+        irq.write_control(0x06);
+
+        irq.clock();
+        irq.clock();
+        assert!(!irq.pending());
+
+        irq.clock();
+        assert!(irq.pending());
+
+        irq.acknowledge();
+        assert!(!irq.pending());
+
+        // Disabled until the control register is written again
+        for _ in 0..0x100 {
+            irq.clock();
+        }
+        assert!(!irq.pending());
+    }
+
+    #[test]
+    fn scanline_mode() {
+        let mut irq = VrcIrq::new();
+        irq.write_latch_low(0x0E);
+        irq.write_latch_high(0x0F);
+        irq.write_control(0x03);
+
+        // 2 scanlines, of 113.667 CPU cycles
+        for _ in 0..227 {
+            irq.clock();
+        }
+        assert!(!irq.pending());
+
+        irq.clock();
+        assert!(irq.pending());
+
+        irq.acknowledge();
+        for _ in 0..(256 * 114) {
+            irq.clock();
+        }
+        assert!(irq.pending());
+    }
+}
@@ -0,0 +1,98 @@
+// Raw RAM cheats, like the Pro Action Replay: a value written at an address of the CPU RAM or
+// of the PRG RAM. Frozen cheats are written again at the end of every frame, so the game can't
+// change the value for long.
+
+use core::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatError {
+    /// The code isn't "AAAA:VV" or "AAAAVV", in hexadecimal
+    InvalidFormat,
+    /// The address isn't in the CPU RAM ($0000-$1FFF) or the PRG RAM ($6000-$7FFF)
+    UnsupportedAddress(u16),
+}
+
+impl core::fmt::Display for CheatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+    /// Written at the end of every frame instead of once
+    pub freeze: bool,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    pub fn new(address: u16, value: u8, freeze: bool) -> Result<Self, CheatError> {
+        if !matches!(address, 0x0000..=0x1FFF | 0x6000..=0x7FFF) {
+            return Err(CheatError::UnsupportedAddress(address));
+        }
+
+        Ok(Self {
+            address,
+            value,
+            freeze,
+            enabled: true,
+        })
+    }
+
+    /// Parses a Pro Action Replay code, "AAAA:VV" or "AAAAVV". These cheats are frozen.
+    pub fn from_code(code: &str) -> Result<Self, CheatError> {
+        let code = code.trim();
+        let (address, value) = match code.split_once(':') {
+            Some(parts) => parts,
+            None if code.len() == 6 && code.is_char_boundary(4) => code.split_at(4),
+            None => return Err(CheatError::InvalidFormat),
+        };
+
+        if address.is_empty() || address.len() > 4 || value.is_empty() || value.len() > 2 {
+            return Err(CheatError::InvalidFormat);
+        }
+        let address = u16::from_str_radix(address, 16).map_err(|_| CheatError::InvalidFormat)?;
+        let value = u8::from_str_radix(value, 16).map_err(|_| CheatError::InvalidFormat)?;
+
+        Self::new(address, value, true)
+    }
+}
+
+impl TryFrom<&str> for Cheat {
+    type Error = CheatError;
+
+    fn try_from(code: &str) -> Result<Self, Self::Error> {
+        Self::from_code(code)
+    }
+}
+
+impl core::fmt::Display for Cheat {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:04X}:{:02X}", self.address, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn parses_codes() {
+        let cheat = Cheat::from_code("0075:09").unwrap();
+        assert_eq!(cheat, Cheat::new(0x0075, 0x09, true).unwrap());
+        assert_eq!(Cheat::from_code("6001ff"), Cheat::new(0x6001, 0xFF, true));
+        assert_eq!(cheat.to_string(), "0075:09");
+
+        assert_eq!(Cheat::from_code("75:9"), Cheat::new(0x0075, 0x09, true));
+        assert_eq!(Cheat::from_code("0075"), Err(CheatError::InvalidFormat));
+        assert_eq!(Cheat::from_code("0075:100"), Err(CheatError::InvalidFormat));
+        assert_eq!(
+            Cheat::from_code("2000:80"),
+            Err(CheatError::UnsupportedAddress(0x2000))
+        );
+    }
+}
@@ -0,0 +1,427 @@
+// Controllers plugged in the ports read at $4016 and $4017.
+// https://wiki.nesdev.com/w/index.php/Standard_controller
+// https://wiki.nesdev.com/w/index.php/Four_Score
+// https://wiki.nesdev.com/w/index.php/Arkanoid_controller
+// https://wiki.nesdev.com/w/index.php/Power_Pad
+// https://wiki.nesdev.com/w/index.php/Controller_port_registers
+//
+// Writing 1 then 0 to $4016 latches the buttons in a shift register per port, read one bit at a
+// time, A first. With a Four Score, each port gives the buttons of two controllers (1 and 3 on
+// $4016, 2 and 4 on $4017), followed by a signature telling the game the adapter is there.
+//
+// Turbo buttons are applied when latching: a held turbo button is pressed and released in turn,
+// every `frames` frames, so every frontend gets the same behavior. The buttons latched during a
+// frame are kept for the input displays, as they are exactly what the game read.
+//
+// Other devices can be plugged in a port instead of the controllers. The Arkanoid paddle of the
+// NES sends the position of its knob on D4, 8 bits inverted from the high one, and its button
+// on D3. The Power Pad sends its 12 buttons on two lines at once, 8 on D3 and 4 on D4, then 1s.
+//
+// The microphone of the second controller of the Famicom isn't latched: D2 of $4016 is 1 while
+// it picks up sound.
+
+/// Signatures read after the buttons of the Four Score, in reading order
+const FOUR_SCORE_SIGNATURES: [u8; 2] = [0b0001_0000, 0b0010_0000];
+
+/// Positions of the knob of the Arkanoid paddle at both ends of its course
+const PADDLE_RANGE: (u8, u8) = (98, 242);
+
+/// Power Pad buttons, numbered from 1, sent on D3 and D4 in reading order
+const POWER_PAD_D3_BUTTONS: [u8; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const POWER_PAD_D4_BUTTONS: [u8; 4] = [4, 3, 12, 8];
+
+/// Device plugged in a controller port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortDevice {
+    /// Standard controller, or the Four Score when it's plugged
+    #[default]
+    Controller,
+    /// Vaus controller bundled with Arkanoid
+    ArkanoidPaddle,
+    /// Mat of World Class Track Meet, side B facing up
+    PowerPad,
+}
+
+/// Input of an Arkanoid paddle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArkanoidPaddle {
+    /// Position of the knob, from 0 on the left to 255 on the right
+    pub position: u8,
+    pub fire: bool,
+}
+
+impl_stateful!(ArkanoidPaddle { position, fire });
+
+impl ArkanoidPaddle {
+    pub fn new(position: u8, fire: bool) -> Self {
+        Self { position, fire }
+    }
+
+    /// Value of the potentiometer, within the range of the real paddle
+    fn potentiometer(&self) -> u8 {
+        let (min, max) = PADDLE_RANGE;
+        min + (u16::from(self.position) * u16::from(max - min) / 255) as u8
+    }
+}
+
+/// Turbo setting of a controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Turbo {
+    /// Buttons repeating while held, with the bits of the controller state (0x80 for A, 0x40
+    /// for B)
+    pub buttons: u8,
+    /// Number of frames the buttons stay pressed, then released. 0 is the same as 1.
+    pub frames: u8,
+}
+
+impl Turbo {
+    pub fn new(buttons: u8, frames: u8) -> Self {
+        Self { buttons, frames }
+    }
+
+    /// Buttons released by the turbo on `frame`
+    fn released_buttons(&self, frame: u32) -> u8 {
+        let frames = u32::from(self.frames.max(1));
+        if (frame / frames) & 1 == 0 {
+            0
+        } else {
+            self.buttons
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Controllers {
+    /// Buttons of the 4 controllers, A in the high bit
+    states: [u8; 4],
+    strobe: bool,
+    shift_registers: [u32; 2],
+    /// Frames run so far, for the turbo buttons
+    frame: u32,
+    four_score: bool,
+    turbo: [Turbo; 4],
+    devices: [PortDevice; 2],
+    paddles: [ArkanoidPaddle; 2],
+    /// Buttons of the Power Pads, bit 0 for button 1
+    power_pads: [u16; 2],
+    microphone: bool,
+    /// Buttons of the 4 controllers latched during the current frame, then the last one
+    latched: Option<[u8; 4]>,
+    frame_latched: Option<[u8; 4]>,
+}
+
+// Plugging the Four Score, the turbo and the devices are settings, left as is when loading a
+// state. The latched buttons are only displayed, and not saved either.
+impl_stateful!(Controllers {
+    states,
+    strobe,
+    shift_registers,
+    frame,
+    paddles,
+    power_pads,
+    microphone
+});
+
+impl Controllers {
+    pub fn set_state(&mut self, controller: usize, state: u8) {
+        self.states[controller] = state;
+    }
+
+    pub fn state(&self, controller: usize) -> u8 {
+        self.states[controller]
+    }
+
+    pub fn set_four_score(&mut self, four_score: bool) {
+        self.four_score = four_score;
+    }
+
+    pub fn four_score(&self) -> bool {
+        self.four_score
+    }
+
+    pub fn set_turbo(&mut self, controller: usize, turbo: Turbo) {
+        self.turbo[controller] = turbo;
+    }
+
+    pub fn turbo(&self, controller: usize) -> Turbo {
+        self.turbo[controller]
+    }
+
+    /// Plugs a device in a port, 0 for $4016 and 1 for $4017
+    pub fn set_device(&mut self, port: usize, device: PortDevice) {
+        self.devices[port] = device;
+    }
+
+    pub fn device(&self, port: usize) -> PortDevice {
+        self.devices[port]
+    }
+
+    pub fn set_paddle(&mut self, port: usize, paddle: ArkanoidPaddle) {
+        self.paddles[port] = paddle;
+    }
+
+    pub fn paddle(&self, port: usize) -> ArkanoidPaddle {
+        self.paddles[port]
+    }
+
+    pub fn set_power_pad(&mut self, port: usize, buttons: u16) {
+        self.power_pads[port] = buttons & 0x0FFF;
+    }
+
+    pub fn power_pad(&self, port: usize) -> u16 {
+        self.power_pads[port]
+    }
+
+    pub fn set_microphone(&mut self, active: bool) {
+        self.microphone = active;
+    }
+
+    pub fn microphone(&self) -> bool {
+        self.microphone
+    }
+
+    /// Clears the shift registers at power-on. The buttons held and the settings are kept.
+    pub fn power_on(&mut self) {
+        self.strobe = false;
+        self.shift_registers = [0; 2];
+        self.frame = 0;
+        self.latched = None;
+        self.frame_latched = None;
+    }
+
+    /// Called at the end of every frame, to alternate the turbo buttons
+    pub fn end_frame(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        self.frame_latched = self.latched.take();
+    }
+
+    /// Buttons of the 4 controllers last latched during the previous frame, turbo applied, or
+    /// `None` if the game didn't read them. Controllers that can't be read, like the ones 3 and
+    /// 4 without a Four Score, are released.
+    pub fn frame_latched(&self) -> Option<[u8; 4]> {
+        self.frame_latched
+    }
+
+    /// Write to $4016
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 0x01 == 0x01;
+        self.latch();
+
+        // The buttons are latched when the strobe goes low
+        if !self.strobe {
+            self.latched = Some([0, 1, 2, 3].map(|controller| {
+                let port = controller % 2;
+                let connected = self.devices[port] == PortDevice::Controller
+                    && (controller < 2 || self.four_score);
+                if connected {
+                    self.latched_state(controller)
+                } else {
+                    0
+                }
+            }));
+        }
+    }
+
+    /// Read from $4016 (port 0) or $4017 (port 1)
+    pub fn read(&mut self, port: usize) -> u8 {
+        let data = self.peek(port);
+
+        if !self.strobe {
+            let register = self.shift_registers[port];
+            self.shift_registers[port] = match self.devices[port] {
+                // The Four Score returns 1 once all its bits are read, the controllers 0
+                PortDevice::Controller => (register << 1) | u32::from(self.four_score),
+                PortDevice::ArkanoidPaddle => register << 1,
+                // Both lines are shifted separately
+                PortDevice::PowerPad => ((register << 1) & 0xFFFE_FFFE) | 0x0001_0001,
+            };
+        }
+
+        data
+    }
+
+    /// Reads a port without shifting its register
+    pub fn peek(&self, port: usize) -> u8 {
+        let register = if self.strobe {
+            // The inputs are latched continuously, so their first bit is returned
+            self.latched_register(port)
+        } else {
+            self.shift_registers[port]
+        };
+        let serial = (register >> 31) as u8;
+        let data = match self.devices[port] {
+            PortDevice::Controller => serial,
+            PortDevice::ArkanoidPaddle => serial << 4 | u8::from(self.paddles[port].fire) << 3,
+            PortDevice::PowerPad => ((register >> 11) & 0x10) as u8 | serial << 3,
+        };
+
+        if port == 0 && self.microphone {
+            data | 0x04
+        } else {
+            data
+        }
+    }
+
+    /// Buttons of a controller, with the turbo applied
+    fn latched_state(&self, controller: usize) -> u8 {
+        self.states[controller] & !self.turbo[controller].released_buttons(self.frame)
+    }
+
+    /// Bits sent by the device of a port, from the high one
+    fn latched_register(&self, port: usize) -> u32 {
+        match self.devices[port] {
+            PortDevice::Controller => {
+                let first = u32::from(self.latched_state(port)) << 24;
+                if self.four_score {
+                    first
+                        | u32::from(self.latched_state(port + 2)) << 16
+                        | u32::from(FOUR_SCORE_SIGNATURES[port]) << 8
+                        | 0xFF
+                } else {
+                    first
+                }
+            }
+            PortDevice::ArkanoidPaddle => u32::from(!self.paddles[port].potentiometer()) << 24,
+            // D3 in the high half, D4 in the low half
+            PortDevice::PowerPad => {
+                let buttons = self.power_pads[port];
+                // The buttons are followed by 1s
+                let serialize = |order: &[u8], ones: u32| {
+                    let bits = order.iter().fold(0, |bits, button| {
+                        bits << 1 | u32::from((buttons >> (button - 1)) & 0x01)
+                    });
+                    (bits << ones) | ((1 << ones) - 1)
+                };
+                serialize(&POWER_PAD_D3_BUTTONS, 8) << 16 | serialize(&POWER_PAD_D4_BUTTONS, 12)
+            }
+        }
+    }
+
+    fn latch(&mut self) {
+        for port in 0..2 {
+            self.shift_registers[port] = self.latched_register(port);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_bits(controllers: &mut Controllers, port: usize, count: usize) -> u32 {
+        (0..count).fold(0, |bits, _| bits << 1 | u32::from(controllers.read(port)))
+    }
+
+    #[test]
+    fn reads_four_score_reports() {
+        let mut controllers = Controllers::default();
+        for (controller, state) in [0x81, 0x42, 0x24, 0x18].iter().enumerate() {
+            controllers.set_state(controller, *state);
+        }
+
+        controllers.write(1);
+        controllers.write(0);
+        assert_eq!(read_bits(&mut controllers, 0, 8), 0x81);
+        assert_eq!(read_bits(&mut controllers, 0, 8), 0);
+
+        controllers.set_four_score(true);
+        controllers.write(1);
+        controllers.write(0);
+        assert_eq!(read_bits(&mut controllers, 0, 24), 0x81_24_10);
+        assert_eq!(read_bits(&mut controllers, 1, 24), 0x42_18_20);
+        assert_eq!(read_bits(&mut controllers, 0, 4), 0xF);
+    }
+
+    #[test]
+    fn alternates_turbo_buttons() {
+        let mut controllers = Controllers::default();
+        controllers.set_state(0, 0xC1);
+        controllers.set_turbo(0, Turbo::new(0x80, 2));
+
+        let mut latched = alloc::vec::Vec::new();
+        for _ in 0..6 {
+            controllers.write(1);
+            controllers.write(0);
+            latched.push(read_bits(&mut controllers, 0, 8));
+            controllers.end_frame();
+        }
+        assert_eq!(latched, [0xC1, 0xC1, 0x41, 0x41, 0xC1, 0xC1]);
+    }
+
+    #[test]
+    fn keeps_buttons_latched_in_frame() {
+        let mut controllers = Controllers::default();
+        controllers.set_turbo(1, Turbo::new(0x40, 1));
+        controllers.set_state(0, 0x10);
+        controllers.set_state(1, 0x41);
+        controllers.set_state(2, 0x08);
+
+        controllers.write(1);
+        controllers.write(0);
+        controllers.set_state(0, 0x20);
+        controllers.end_frame();
+        assert_eq!(controllers.frame_latched(), Some([0x10, 0x41, 0, 0]));
+
+        controllers.set_four_score(true);
+        controllers.write(1);
+        controllers.write(0);
+        controllers.end_frame();
+        assert_eq!(controllers.frame_latched(), Some([0x20, 0x01, 0x08, 0]));
+
+        controllers.end_frame();
+        assert_eq!(controllers.frame_latched(), None);
+    }
+
+    #[test]
+    fn reads_arkanoid_paddle() {
+        let mut controllers = Controllers::default();
+        controllers.set_device(1, PortDevice::ArkanoidPaddle);
+        controllers.set_paddle(1, ArkanoidPaddle::new(255, true));
+
+        controllers.write(1);
+        assert_eq!(controllers.read(1), 0x08);
+        controllers.write(0);
+        let bits: alloc::vec::Vec<u8> = (0..9).map(|_| controllers.read(1)).collect();
+        // 242 inverted is 0b0000_1101
+        assert_eq!(bits, [0x08, 0x08, 0x08, 0x08, 0x18, 0x18, 0x08, 0x18, 0x08]);
+
+        controllers.set_paddle(1, ArkanoidPaddle::new(0, false));
+        controllers.write(1);
+        controllers.write(0);
+        // 98 is 0b0110_0010
+        let position = (0..8).fold(0, |bits, _| bits << 1 | controllers.read(1) >> 4);
+        assert_eq!(position, 0b1001_1101);
+    }
+
+    #[test]
+    fn reads_power_pad() {
+        let mut controllers = Controllers::default();
+        controllers.set_device(1, PortDevice::PowerPad);
+        // Buttons 1, 3, 7 and 12
+        controllers.set_power_pad(1, 0b1000_0100_0101);
+
+        controllers.write(1);
+        controllers.write(0);
+        let bits: alloc::vec::Vec<u8> = (0..10).map(|_| controllers.read(1)).collect();
+        assert_eq!(
+            bits,
+            [0x00, 0x18, 0x10, 0x00, 0x10, 0x10, 0x10, 0x18, 0x18, 0x18]
+        );
+    }
+
+    #[test]
+    fn reads_microphone() {
+        let mut controllers = Controllers::default();
+        controllers.set_state(0, 0x80);
+        controllers.set_microphone(true);
+
+        controllers.write(1);
+        controllers.write(0);
+        assert_eq!(controllers.read(0), 0x05);
+        assert_eq!(controllers.read(0), 0x04);
+        assert_eq!(controllers.read(1), 0x00);
+
+        controllers.set_microphone(false);
+        assert_eq!(controllers.read(0), 0x00);
+    }
+}
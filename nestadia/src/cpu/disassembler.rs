@@ -68,17 +68,31 @@ impl AddressingMode {
     }
 }
 
+/// Size in bytes of the instruction starting with `opcode`, 1 for the unknown ones
+pub(crate) fn instruction_size(opcode: u8) -> u16 {
+    Opcode::try_from(opcode).map_or(1, |opcode| opcode.addressing_mode().required_bytes() + 1)
+}
+
 pub fn disassemble(
     cart: &crate::cartridge::Cartridge,
     start: u16,
+) -> Vec<(Option<u8>, u16, String)> {
+    disassemble_instructions(cart, start, usize::MAX)
+}
+
+/// Disassembles at most `count` instructions from `start`
+pub fn disassemble_instructions(
+    cart: &crate::cartridge::Cartridge,
+    start: u16,
+    count: usize,
 ) -> Vec<(Option<u8>, u16, String)> {
     let mut addr: u16 = start;
     let mut disassembly = Vec::new();
 
-    while addr < 0xFFFF {
+    while addr < 0xFFFF && disassembly.len() < count {
         let mut disas = String::new();
         let prg_bank = cart.get_prg_bank(addr);
-        if let Ok(opcode) = Opcode::try_from(cart.read_prg_mem(addr)) {
+        if let Ok(opcode) = Opcode::try_from(cart.peek_prg_mem(addr)) {
             disas += &format!("{:?}", &opcode)[..3].to_lowercase();
 
             let required_bytes = opcode.addressing_mode().required_bytes();
@@ -88,7 +102,7 @@ pub fn disassemble(
                 addr += 1;
             } else if required_bytes < (0xFFFF - addr) {
                 let data = (0..required_bytes)
-                    .map(|i| cart.read_prg_mem(addr + i + 1))
+                    .map(|i| cart.peek_prg_mem(addr + i + 1))
                     .collect::<Vec<_>>();
 
                 disas += " ";
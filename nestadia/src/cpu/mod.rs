@@ -6,7 +6,7 @@ use core::convert::TryFrom as _;
 
 use bitflags::bitflags;
 
-use self::opcode::Opcode;
+pub(crate) use self::opcode::Opcode;
 use crate::bus::CpuBus;
 
 const STACK_BASE: u16 = 0x0100;
@@ -27,6 +27,8 @@ bitflags! {
     }
 }
 
+impl_stateful_bitflags!(StatusRegister);
+
 #[derive(Clone, Debug)]
 pub struct Cpu {
     pub a: u8,
@@ -38,6 +40,16 @@ pub struct Cpu {
     pub status_register: StatusRegister,
 }
 
+impl_stateful!(Cpu {
+    a,
+    x,
+    y,
+    st,
+    pc,
+    cycles,
+    status_register
+});
+
 impl Default for Cpu {
     fn default() -> Self {
         Self {
@@ -63,6 +75,16 @@ impl Cpu {
         self.pc = u16::from(bus.read(PC_START)) | (u16::from(bus.read(PC_START + 1)) << 8);
     }
 
+    /// Reset signal while running: the registers are kept, but the stack pointer is decremented
+    /// by 3 as the pushes of an interrupt are turned into reads.
+    /// http://wiki.nesdev.com/w/index.php/CPU_power_up_state#After_reset
+    pub fn soft_reset(&mut self, bus: &mut CpuBus<'_>) {
+        self.st = self.st.wrapping_sub(3);
+        self.cycles = 8;
+        self.status_register.insert(StatusRegister::I);
+        self.pc = u16::from(bus.read(PC_START)) | (u16::from(bus.read(PC_START + 1)) << 8);
+    }
+
     pub fn irq(&mut self, bus: &mut CpuBus<'_>) {
         if !self.status_register.contains(StatusRegister::I) {
             // Push current PC
@@ -101,6 +123,12 @@ impl Cpu {
         self.cycles = 8;
     }
 
+    /// Reads a byte on behalf of a DMA unit, stalling the CPU for the duration of the transfer
+    pub fn dma_read(&mut self, bus: &mut CpuBus<'_>, addr: u16, stall_cycles: u8) -> u8 {
+        self.cycles += stall_cycles;
+        bus.read(addr)
+    }
+
     pub fn clock(&mut self, bus: &mut CpuBus<'_>) {
         if self.cycles == 0 {
             let opcode = match Opcode::try_from(bus.read(self.pc)) {
@@ -1528,11 +1556,14 @@ impl Cpu {
 }
 
 impl CpuBus<'_> {
-    fn write(&mut self, addr: u16, data: u8) {
+    pub(crate) fn write(&mut self, addr: u16, data: u8) {
+        #[cfg(feature = "debugger")]
+        self.watch_write(addr, data);
+
         match addr {
             0..=0x1FFF => self.write_ram(addr, data),
             0x2000..=0x3FFF => self.write_ppu_register(addr, data),
-            0x4000..=0x4013 | 0x4015 => (), // TODO: APU
+            0x4000..=0x4013 | 0x4015 => self.write_apu_register(addr, data),
             0x4014 => {
                 // https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDMA
                 let page_begin = u16::from(data) << 8;
@@ -1555,21 +1586,30 @@ impl CpuBus<'_> {
                 // to get PPU working ASAP.
             }
             0x4016 => self.controller_write(data),
-            0x4017 => {}
+            0x4017 => self.write_apu_register(addr, data),
             0x4018..=0x401F => (), // APU and I/O functionality that is normally disabled.
             0x4020..=0xFFFF => self.write_prg_mem(addr, data),
         };
     }
 
     #[track_caller]
-    fn read(&mut self, addr: u16) -> u8 {
+    pub(crate) fn read(&mut self, addr: u16) -> u8 {
+        let data = self.read_unwatched(addr);
+        #[cfg(feature = "debugger")]
+        self.watch_read(addr, data);
+        data
+    }
+
+    #[track_caller]
+    fn read_unwatched(&mut self, addr: u16) -> u8 {
         match addr {
             0..=0x1FFF => self.read_ram(addr),
             0x2000..=0x3FFF => self.read_ppu_register(addr),
-            0x4000..=0x4013 | 0x4015 => 0, // TODO: APU
-            0x4014 => 0,                   // OAMDMA is write-only
-            0x4016 => self.read_controller1_snapshot(),
-            0x4017 => self.read_controller2_snapshot(),
+            0x4000..=0x4013 => 0, // APU channels registers are write-only
+            0x4014 => 0,          // OAMDMA is write-only
+            0x4015 => self.read_apu_status(),
+            0x4016 => self.read_controller1(),
+            0x4017 => self.read_controller2(),
             0x4018..=0x401F => 0, // APU and I/O functionality that is normally disabled.
             0x4020..=0xFFFF => self.read_prg_mem(addr),
         }
@@ -1579,6 +1619,9 @@ impl CpuBus<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::controllers::Controllers;
+    use crate::irq::IrqLine;
+    use crate::Apu;
     use crate::Cartridge;
     use crate::Ppu;
     use crate::RAM_SIZE;
@@ -1586,12 +1629,10 @@ mod tests {
 
     struct MockEmulator {
         cpu: Cpu,
-        controller1: u8,
-        controller2: u8,
-        controller_state: bool,
-        controller1_snapshot: u8,
-        controller2_snapshot: u8,
+        irq_line: IrqLine,
+        controllers: Controllers,
         ram: [u8; RAM_SIZE as usize],
+        apu: Apu,
         cartridge: Cartridge,
         ppu: Ppu,
         name_tables: [u8; 1024 * 4],
@@ -1620,14 +1661,12 @@ mod tests {
 
         let mut emu = MockEmulator {
             cpu: Default::default(),
-            controller1: 0,
-            controller2: 0,
-            controller_state: false,
-            controller1_snapshot: 0,
-            controller2_snapshot: 0,
+            irq_line: Default::default(),
+            controllers: Default::default(),
             cartridge: Cartridge::load(&rom, None).unwrap(),
 
             ram: [0u8; RAM_SIZE as usize],
+            apu: Apu::default(),
             ppu: Ppu::default(),
             name_tables: [0u8; 1024 * 4],
         };
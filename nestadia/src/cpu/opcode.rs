@@ -346,6 +346,51 @@ impl Opcode {
         }
     }
 
+    /// Whether the instruction writes to the memory, storing a register or modifying a value
+    #[cfg(feature = "debugger")]
+    pub fn writes_memory(&self) -> bool {
+        matches!(
+            self,
+            Opcode::AslZp
+                | Opcode::AslAbs
+                | Opcode::AslZpX
+                | Opcode::AslAbsX
+                | Opcode::RolZp
+                | Opcode::RolAbs
+                | Opcode::RolZpX
+                | Opcode::RolAbsX
+                | Opcode::LsrZp
+                | Opcode::LsrAbs
+                | Opcode::LsrZpX
+                | Opcode::LsrAbsX
+                | Opcode::RorZp
+                | Opcode::RorAbs
+                | Opcode::RorZpX
+                | Opcode::RorAbsX
+                | Opcode::StaIndX
+                | Opcode::StyZp
+                | Opcode::StaZp
+                | Opcode::StxZp
+                | Opcode::StyAbs
+                | Opcode::StaAbs
+                | Opcode::StxAbs
+                | Opcode::StaIndY
+                | Opcode::StyZpX
+                | Opcode::StaZpX
+                | Opcode::StxZpY
+                | Opcode::StaAbsY
+                | Opcode::StaAbsX
+                | Opcode::DecZp
+                | Opcode::DecAbs
+                | Opcode::DecZpX
+                | Opcode::DecAbsX
+                | Opcode::IncZp
+                | Opcode::IncAbs
+                | Opcode::IncZpX
+                | Opcode::IncAbsX
+        )
+    }
+
     #[cfg(feature = "debugger")]
     pub fn addressing_mode(&self) -> AddressingMode {
         match self {
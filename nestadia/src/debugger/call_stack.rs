@@ -0,0 +1,206 @@
+// Best-effort call stack, rebuilt from the JSR, BRK and interrupts seen by the CPU.
+//
+// The 6502 doesn't keep track of the calls, and games often play with the stack: they discard
+// return addresses with PLA, push one to jump with RTS, or reset the stack pointer with TXS. So
+// instead of waiting for the RTS or RTI matching a call, its frame is dropped as soon as the
+// stack pointer rises above what the call pushed, whatever the instruction doing it.
+
+use alloc::vec::Vec;
+
+use crate::cpu::Opcode;
+
+/// Frames kept at most, the oldest being dropped first
+const MAX_DEPTH: usize = 256;
+
+/// How a routine was entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Jsr,
+    Brk,
+    Nmi,
+    Irq,
+}
+
+impl CallKind {
+    /// Bytes pushed on the stack by the call
+    fn pushed(self) -> u8 {
+        match self {
+            CallKind::Jsr => 2,
+            CallKind::Brk | CallKind::Nmi | CallKind::Irq => 3,
+        }
+    }
+}
+
+/// Call of a subroutine or an interrupt handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    pub kind: CallKind,
+    /// Address of the JSR or BRK, or of the instruction the interrupt came before
+    pub caller: u16,
+    /// PRG ROM bank of the caller, when mapped from the ROM
+    pub caller_bank: Option<u8>,
+    /// First instruction of the routine or handler
+    pub target: u16,
+    pub target_bank: Option<u8>,
+    /// Address the routine returns to
+    pub return_address: u16,
+    /// Stack pointer once the call pushed the return address, and the status for interrupts
+    pub stack_pointer: u8,
+}
+
+impl StackFrame {
+    /// Whether the stack pointer `st` rose above what the call pushed
+    fn is_returned(&self, st: u8) -> bool {
+        st.wrapping_sub(self.stack_pointer) as i8 >= self.kind.pushed() as i8
+    }
+}
+
+/// JSR or BRK about to run, whose frame is pushed once the CPU reaches the routine
+#[derive(Debug, Clone, Copy)]
+struct PendingCall {
+    kind: CallKind,
+    caller: u16,
+    caller_bank: Option<u8>,
+    stack_pointer: u8,
+}
+
+#[derive(Default)]
+pub(crate) struct CallStack {
+    frames: Vec<StackFrame>,
+    pending: Option<PendingCall>,
+}
+
+impl CallStack {
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.pending = None;
+    }
+
+    /// Called before running `opcode` at `pc`, with the stack pointer `st`. `prg_bank` gives the
+    /// PRG ROM bank mapped at an address.
+    pub fn before_instruction<F>(&mut self, pc: u16, st: u8, opcode: u8, prg_bank: F)
+    where
+        F: Fn(u16) -> Option<u8>,
+    {
+        while self.frames.last().is_some_and(|f| f.is_returned(st)) {
+            self.frames.pop();
+        }
+
+        // The routine of the previous instruction is entered, if it pushed its return address
+        if let Some(call) = self.pending.take() {
+            if call.stack_pointer.wrapping_sub(st) == call.kind.pushed() {
+                let return_address = match call.kind {
+                    CallKind::Jsr => call.caller.wrapping_add(3),
+                    _ => call.caller.wrapping_add(2),
+                };
+                self.push(StackFrame {
+                    kind: call.kind,
+                    caller: call.caller,
+                    caller_bank: call.caller_bank,
+                    target: pc,
+                    target_bank: prg_bank(pc),
+                    return_address,
+                    stack_pointer: st,
+                });
+            }
+        }
+
+        let kind = if opcode == Opcode::JsrAbs as u8 {
+            CallKind::Jsr
+        } else if opcode == Opcode::Brk as u8 {
+            CallKind::Brk
+        } else {
+            return;
+        };
+        self.pending = Some(PendingCall {
+            kind,
+            caller: pc,
+            caller_bank: prg_bank(pc),
+            stack_pointer: st,
+        });
+    }
+
+    /// Called once the CPU took an interrupt, which came before the instruction at `caller`
+    pub fn on_interrupt<F>(&mut self, kind: CallKind, caller: u16, pc: u16, st: u8, prg_bank: F)
+    where
+        F: Fn(u16) -> Option<u8>,
+    {
+        // The instruction that was about to run didn't
+        self.pending = None;
+        self.push(StackFrame {
+            kind,
+            caller,
+            caller_bank: prg_bank(caller),
+            target: pc,
+            target_bank: prg_bank(pc),
+            return_address: caller,
+            stack_pointer: st,
+        });
+    }
+
+    fn push(&mut self, frame: StackFrame) {
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank(addr: u16) -> Option<u8> {
+        (addr >= 0x8000).then_some((addr >> 14) as u8)
+    }
+
+    #[test]
+    fn follows_calls_and_returns() {
+        let mut calls = CallStack::default();
+        let jsr = Opcode::JsrAbs as u8;
+        calls.before_instruction(0x8000, 0xFD, jsr, bank);
+        calls.before_instruction(0xC000, 0xFB, jsr, bank);
+        calls.on_interrupt(CallKind::Nmi, 0xC000, 0xE000, 0xF8, bank);
+        calls.before_instruction(0xE000, 0xF8, 0xEA, bank); // NOP
+        assert_eq!(
+            calls.frames(),
+            [
+                StackFrame {
+                    kind: CallKind::Jsr,
+                    caller: 0x8000,
+                    caller_bank: Some(2),
+                    target: 0xC000,
+                    target_bank: Some(3),
+                    return_address: 0x8003,
+                    stack_pointer: 0xFB,
+                },
+                StackFrame {
+                    kind: CallKind::Nmi,
+                    caller: 0xC000,
+                    caller_bank: Some(3),
+                    target: 0xE000,
+                    target_bank: Some(3),
+                    return_address: 0xC000,
+                    stack_pointer: 0xF8,
+                },
+            ]
+        );
+
+        // RTI, then the JSR that was interrupted runs
+        calls.before_instruction(0xC000, 0xFB, jsr, bank);
+        assert_eq!(calls.frames().len(), 1);
+        calls.before_instruction(0x0300, 0xF9, 0x68, bank); // PLA
+        assert_eq!(calls.frames()[1].target_bank, None);
+
+        // The return address of the routine is pulled, then the stack is reset
+        calls.before_instruction(0x0301, 0xFA, 0x68, bank);
+        calls.before_instruction(0x0302, 0xFB, 0x9A, bank); // TXS
+        assert_eq!(calls.frames().len(), 1);
+        calls.before_instruction(0x0303, 0xFF, 0xEA, bank);
+        assert!(calls.frames().is_empty());
+    }
+}
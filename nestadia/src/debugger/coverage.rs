@@ -0,0 +1,100 @@
+// Code coverage: the addresses that were run at least once, for the test coverage of homebrews
+// or to find the code paths a game never took.
+//
+// The addresses are marked by PRG ROM bank like in the rest of the debugger, every byte of the
+// instructions run included, in a bitmap of the CPU address space per bank.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+use crate::cpu::disassembler::instruction_size;
+
+/// Bytes of a bitmap of the CPU address space
+pub const COVERAGE_BITMAP_SIZE: usize = 0x10000 / 8;
+
+#[derive(Default)]
+pub(crate) struct Coverage {
+    enabled: bool,
+    bitmaps: BTreeMap<Option<u8>, Box<[u8; COVERAGE_BITMAP_SIZE]>>,
+}
+
+impl Coverage {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn clear(&mut self) {
+        self.bitmaps.clear();
+    }
+
+    /// Marks the instruction starting with `opcode` at `pc`
+    pub fn on_instruction(&mut self, bank: Option<u8>, pc: u16, opcode: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        let bitmap = self
+            .bitmaps
+            .entry(bank)
+            .or_insert_with(|| Box::new([0; COVERAGE_BITMAP_SIZE]));
+        for i in 0..instruction_size(opcode) {
+            let addr = usize::from(pc.wrapping_add(i));
+            bitmap[addr / 8] |= 1 << (addr % 8);
+        }
+    }
+
+    pub fn is_covered(&self, bank: Option<u8>, addr: u16) -> bool {
+        let addr = usize::from(addr);
+        self.bitmaps
+            .get(&bank)
+            .is_some_and(|bitmap| bitmap[addr / 8] & (1 << (addr % 8)) != 0)
+    }
+
+    pub fn bitmap(&self, bank: Option<u8>) -> Option<&[u8; COVERAGE_BITMAP_SIZE]> {
+        self.bitmaps.get(&bank).map(|bitmap| &**bitmap)
+    }
+
+    pub fn banks(&self) -> impl Iterator<Item = Option<u8>> + '_ {
+        self.bitmaps.keys().copied()
+    }
+
+    /// Bytes marked in the PRG ROM banks
+    pub fn rom_bytes(&self) -> usize {
+        self.bitmaps
+            .iter()
+            .filter(|(bank, _)| bank.is_some())
+            .flat_map(|(_, bitmap)| bitmap.iter())
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_instructions_run() {
+        let mut coverage = Coverage::default();
+        coverage.on_instruction(Some(0), 0x8000, 0xEA);
+        assert!(!coverage.is_covered(Some(0), 0x8000));
+
+        coverage.set_enabled(true);
+        coverage.on_instruction(Some(0), 0x8000, 0x4C); // JMP $xxxx
+        coverage.on_instruction(Some(1), 0x8000, 0xE6); // INC $xx
+        coverage.on_instruction(None, 0x0300, 0xEA); // NOP
+        assert!((0x8000..0x8003).all(|addr| coverage.is_covered(Some(0), addr)));
+        assert!(!coverage.is_covered(Some(0), 0x8003));
+        assert!(!coverage.is_covered(Some(2), 0x8000));
+        assert_eq!(coverage.bitmap(Some(1)).unwrap()[0x1000], 0b11);
+        assert_eq!(coverage.banks().count(), 3);
+        assert_eq!(coverage.rom_bytes(), 5);
+
+        coverage.clear();
+        assert_eq!(coverage.rom_bytes(), 0);
+    }
+}
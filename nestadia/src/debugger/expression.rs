@@ -0,0 +1,472 @@
+// Expressions of the debugger, like `A == 0x20 && [$00FE] > 3`, for the conditions of the
+// breakpoints and the watches.
+//
+// They use the operators of C with its precedences, on 64 bits signed integers. The variables
+// are the CPU registers (A, X, Y, SP, PC, P), its flags (C, Z, I, D, V, N), and the CYCLE,
+// FRAME and SCANLINE counters, in any case. `[addr]` reads a byte of the CPU address space
+// without side effects. Numbers are decimal, or hexadecimal with `0x` or `$`. Comparisons and
+// logical operators give 1 or 0, and a condition is true when it isn't 0. Dividing by 0 gives 0.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+
+use crate::cpu::StatusRegister;
+use crate::Emulator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// Byte offset of a character that doesn't start a token
+    UnexpectedCharacter(usize),
+    /// Byte offset of a token out of place
+    UnexpectedToken(usize),
+    UnexpectedEnd,
+    /// Byte offset of a name that isn't a variable
+    UnknownVariable(usize),
+    /// Byte offset of a number too large for 64 bits
+    InvalidNumber(usize),
+}
+
+impl core::fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    A,
+    X,
+    Y,
+    Sp,
+    Pc,
+    /// Status register
+    P,
+    Carry,
+    Zero,
+    InterruptDisable,
+    Decimal,
+    Overflow,
+    Negative,
+    /// CPU cycles since power-on
+    Cycle,
+    /// Frames since power-on
+    Frame,
+    /// Scanline of the PPU, from -1 to 260
+    Scanline,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        let variables = [
+            ("A", Variable::A),
+            ("X", Variable::X),
+            ("Y", Variable::Y),
+            ("SP", Variable::Sp),
+            ("S", Variable::Sp),
+            ("PC", Variable::Pc),
+            ("P", Variable::P),
+            ("C", Variable::Carry),
+            ("Z", Variable::Zero),
+            ("I", Variable::InterruptDisable),
+            ("D", Variable::Decimal),
+            ("V", Variable::Overflow),
+            ("N", Variable::Negative),
+            ("CYCLE", Variable::Cycle),
+            ("FRAME", Variable::Frame),
+            ("SCANLINE", Variable::Scanline),
+        ];
+
+        variables
+            .iter()
+            .find(|(variable, _)| variable.eq_ignore_ascii_case(name))
+            .map(|(_, variable)| *variable)
+    }
+}
+
+/// Source of the values of the variables and the memory
+pub trait ExpressionContext {
+    fn variable(&self, variable: Variable) -> i64;
+    /// Reads the CPU address space without side effects
+    fn peek(&self, addr: u16) -> u8;
+}
+
+impl ExpressionContext for Emulator {
+    fn variable(&self, variable: Variable) -> i64 {
+        let cpu = &self.cpu;
+        let flag = |flag| i64::from(cpu.status_register.contains(flag));
+
+        match variable {
+            Variable::A => i64::from(cpu.a),
+            Variable::X => i64::from(cpu.x),
+            Variable::Y => i64::from(cpu.y),
+            Variable::Sp => i64::from(cpu.st),
+            Variable::Pc => i64::from(cpu.pc),
+            Variable::P => i64::from(cpu.status_register.bits()),
+            Variable::Carry => flag(StatusRegister::C),
+            Variable::Zero => flag(StatusRegister::Z),
+            Variable::InterruptDisable => flag(StatusRegister::I),
+            Variable::Decimal => flag(StatusRegister::D),
+            Variable::Overflow => flag(StatusRegister::V),
+            Variable::Negative => flag(StatusRegister::N),
+            Variable::Cycle => self.debugger.cycles() as i64,
+            Variable::Frame => self.frame_count as i64,
+            Variable::Scanline => i64::from(self.ppu.scanline()),
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.peek_memory(addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOp {
+    /// Operators with their precedence, the longest first so "<<" isn't read as "<"
+    const OPERATORS: [(&str, BinaryOp, u8); 18] = [
+        ("||", BinaryOp::Or, 1),
+        ("&&", BinaryOp::And, 2),
+        ("==", BinaryOp::Equal, 6),
+        ("!=", BinaryOp::NotEqual, 6),
+        ("<=", BinaryOp::LessOrEqual, 7),
+        (">=", BinaryOp::GreaterOrEqual, 7),
+        ("<<", BinaryOp::ShiftLeft, 8),
+        (">>", BinaryOp::ShiftRight, 8),
+        ("|", BinaryOp::BitOr, 3),
+        ("^", BinaryOp::BitXor, 4),
+        ("&", BinaryOp::BitAnd, 5),
+        ("<", BinaryOp::Less, 7),
+        (">", BinaryOp::Greater, 7),
+        ("+", BinaryOp::Add, 9),
+        ("-", BinaryOp::Subtract, 9),
+        ("*", BinaryOp::Multiply, 10),
+        ("/", BinaryOp::Divide, 10),
+        ("%", BinaryOp::Remainder, 10),
+    ];
+
+    fn apply(self, left: i64, right: i64) -> i64 {
+        match self {
+            BinaryOp::Or => i64::from(left != 0 || right != 0),
+            BinaryOp::And => i64::from(left != 0 && right != 0),
+            BinaryOp::BitOr => left | right,
+            BinaryOp::BitXor => left ^ right,
+            BinaryOp::BitAnd => left & right,
+            BinaryOp::Equal => i64::from(left == right),
+            BinaryOp::NotEqual => i64::from(left != right),
+            BinaryOp::Less => i64::from(left < right),
+            BinaryOp::LessOrEqual => i64::from(left <= right),
+            BinaryOp::Greater => i64::from(left > right),
+            BinaryOp::GreaterOrEqual => i64::from(left >= right),
+            BinaryOp::ShiftLeft => left.wrapping_shl(right as u32),
+            BinaryOp::ShiftRight => left.wrapping_shr(right as u32),
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Subtract => left.wrapping_sub(right),
+            BinaryOp::Multiply => left.wrapping_mul(right),
+            BinaryOp::Divide => left.checked_div(right).unwrap_or(0),
+            BinaryOp::Remainder => left.checked_rem(right).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Variable(Variable),
+    Memory(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, context: &dyn ExpressionContext) -> i64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Variable(variable) => context.variable(*variable),
+            Node::Memory(addr) => i64::from(context.peek(addr.evaluate(context) as u16)),
+            Node::Unary(op, operand) => {
+                let value = operand.evaluate(context);
+                match op {
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Not => i64::from(value == 0),
+                    UnaryOp::Complement => !value,
+                }
+            }
+            // Only evaluates the right side of the logical operators when needed
+            Node::Binary(BinaryOp::And, left, right) => {
+                i64::from(left.evaluate(context) != 0 && right.evaluate(context) != 0)
+            }
+            Node::Binary(BinaryOp::Or, left, right) => {
+                i64::from(left.evaluate(context) != 0 || right.evaluate(context) != 0)
+            }
+            Node::Binary(op, left, right) => {
+                op.apply(left.evaluate(context), right.evaluate(context))
+            }
+        }
+    }
+}
+
+/// Parsed expression, see the syntax above
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser { source, offset: 0 };
+        let root = parser.expression(0)?;
+        parser.skip_whitespace();
+        if parser.offset < source.len() {
+            return Err(ExpressionError::UnexpectedToken(parser.offset));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn evaluate(&self, context: &dyn ExpressionContext) -> i64 {
+        self.root.evaluate(context)
+    }
+
+    /// Whether the expression isn't 0
+    pub fn is_true(&self, context: &dyn ExpressionContext) -> bool {
+        self.evaluate(context) != 0
+    }
+
+    /// Text the expression was parsed from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl core::fmt::Display for Expression {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Recursive descent parser, with precedence climbing for the binary operators
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if it's next
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses the operations whose operators have at least `min_precedence`
+    fn expression(&mut self, min_precedence: u8) -> Result<Node, ExpressionError> {
+        let mut left = self.unary()?;
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            let operator = BinaryOp::OPERATORS.iter().find(|(token, _, precedence)| {
+                rest.starts_with(token) && *precedence >= min_precedence
+            });
+            let (token, op, precedence) = match operator {
+                Some(operator) => *operator,
+                None => return Ok(left),
+            };
+            // A lower precedence operator starting the same way, like "|" for "||"
+            let longer = BinaryOp::OPERATORS
+                .iter()
+                .any(|(other, ..)| other.len() > token.len() && rest.starts_with(other));
+            if longer {
+                return Ok(left);
+            }
+
+            self.offset += token.len();
+            let right = self.expression(precedence + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        let op = if self.eat("-") {
+            UnaryOp::Negate
+        } else if self.eat("!") {
+            UnaryOp::Not
+        } else if self.eat("~") {
+            UnaryOp::Complement
+        } else {
+            return self.primary();
+        };
+
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        self.skip_whitespace();
+        let start = self.offset;
+
+        if self.eat("(") {
+            let node = self.expression(0)?;
+            return self.close(")").map(|_| node);
+        }
+        if self.eat("[") {
+            let node = self.expression(0)?;
+            return self.close("]").map(|_| Node::Memory(Box::new(node)));
+        }
+
+        let rest = self.rest();
+        let (digits, radix) = if let Some(hex) = rest.strip_prefix('$') {
+            (hex, 16)
+        } else if let Some(hex) = rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+            (hex, 16)
+        } else {
+            (rest, 10)
+        };
+        let prefix = rest.len() - digits.len();
+        let token_len = rest[prefix..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - prefix);
+        let token = &rest[prefix..prefix + token_len];
+
+        let first = match token.chars().next() {
+            Some(first) => first,
+            None if prefix > 0 => return Err(ExpressionError::InvalidNumber(start)),
+            None if rest.is_empty() => return Err(ExpressionError::UnexpectedEnd),
+            None => return Err(ExpressionError::UnexpectedCharacter(start)),
+        };
+        self.offset += prefix + token_len;
+
+        if prefix > 0 || first.is_ascii_digit() {
+            i64::from_str_radix(token, radix)
+                .map(Node::Number)
+                .map_err(|_| ExpressionError::InvalidNumber(start))
+        } else {
+            Variable::from_name(token)
+                .map(Node::Variable)
+                .ok_or(ExpressionError::UnknownVariable(start))
+        }
+    }
+
+    fn close(&mut self, token: &str) -> Result<(), ExpressionError> {
+        if self.eat(token) {
+            Ok(())
+        } else if self.rest().is_empty() {
+            Err(ExpressionError::UnexpectedEnd)
+        } else {
+            Err(ExpressionError::UnexpectedToken(self.offset))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Context;
+
+    impl ExpressionContext for Context {
+        fn variable(&self, variable: Variable) -> i64 {
+            match variable {
+                Variable::A => 0x20,
+                Variable::X => 3,
+                Variable::Carry => 1,
+                _ => 0,
+            }
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            addr as u8 ^ 0xFF
+        }
+    }
+
+    fn evaluate(source: &str) -> i64 {
+        Expression::parse(source).unwrap().evaluate(&Context)
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        assert_eq!(evaluate("A == 0x20 && [$00FE] > 0"), 1);
+        assert_eq!(evaluate("1 + 2 * 3 - 4 / 2"), 5);
+        assert_eq!(evaluate("(1 + 2) * -x"), -9);
+        assert_eq!(evaluate("a >> 4 | 1 << x"), 0x0A);
+        assert_eq!(evaluate("c || 1 / 0"), 1);
+        assert_eq!(evaluate("!c + ~0 + 7 % 0"), -1);
+        assert_eq!(evaluate("[[$10] & 0x0F]"), 0xF0);
+        assert_eq!(evaluate("a >= 32 == 1 != 0"), 1);
+        assert_eq!(evaluate("1 | 2 & 3 ^ 4"), 7);
+
+        let expression = Expression::parse(" A<3 ").unwrap();
+        assert_eq!(expression.to_string(), " A<3 ");
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(
+            Expression::parse("A == "),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Expression::parse("A == foo"),
+            Err(ExpressionError::UnknownVariable(5))
+        );
+        assert_eq!(
+            Expression::parse("[$10"),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Expression::parse("A # 2"),
+            Err(ExpressionError::UnexpectedToken(2))
+        );
+        assert_eq!(
+            Expression::parse("$G0"),
+            Err(ExpressionError::InvalidNumber(0))
+        );
+        assert_eq!(
+            Expression::parse("@"),
+            Err(ExpressionError::UnexpectedCharacter(0))
+        );
+    }
+}
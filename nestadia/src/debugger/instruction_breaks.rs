@@ -0,0 +1,130 @@
+// Breaks on the instructions themselves rather than on their address, to find where a game goes
+// off the rails: it usually ends up running data, full of BRK and unofficial opcodes, or writes
+// to the PPU from code that shouldn't.
+//
+// The address an instruction writes to is computed from the registers and the memory before it
+// runs, so it stops right before the write.
+
+use core::convert::TryFrom;
+
+use super::{ExpressionContext, Variable};
+use crate::cpu::disassembler::AddressingMode;
+use crate::cpu::Opcode;
+
+/// Instructions the debugger can break on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionClass {
+    /// A single opcode, like 0x00 for BRK
+    Opcode(u8),
+    /// Any opcode the 6502 doesn't document
+    Unofficial,
+    /// Any instruction writing to the registers of the PPU, $2000-$2007 and their mirrors
+    PpuRegisterWrite,
+}
+
+impl InstructionClass {
+    /// Whether the instruction at `pc` belongs to the class, in `context`, the emulator
+    pub(crate) fn matches(&self, pc: u16, context: &dyn ExpressionContext) -> bool {
+        let opcode = context.peek(pc);
+        match self {
+            InstructionClass::Opcode(class_opcode) => opcode == *class_opcode,
+            InstructionClass::Unofficial => Opcode::try_from(opcode).is_err(),
+            InstructionClass::PpuRegisterWrite => match Opcode::try_from(opcode) {
+                Ok(opcode) if opcode.writes_memory() => {
+                    let addr = target_address(opcode.addressing_mode(), pc, context);
+                    matches!(addr, Some(0x2000..=0x3FFF))
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Address accessed by the instruction at `pc`, computed before it runs, or `None` for the zero
+/// page and the modes not accessing the memory
+fn target_address(mode: AddressingMode, pc: u16, context: &dyn ExpressionContext) -> Option<u16> {
+    let peek_word =
+        |addr: u16, next: u16| u16::from(context.peek(addr)) | u16::from(context.peek(next)) << 8;
+    let operand = peek_word(pc.wrapping_add(1), pc.wrapping_add(2));
+    let x = context.variable(Variable::X) as u16;
+    let y = context.variable(Variable::Y) as u16;
+
+    match mode {
+        AddressingMode::Absolute => Some(operand),
+        AddressingMode::AbsoluteX => Some(operand.wrapping_add(x)),
+        AddressingMode::AbsoluteY => Some(operand.wrapping_add(y)),
+        // The pointers wrap around the zero page
+        AddressingMode::IndirectX => {
+            let pointer = (operand + x) & 0xFF;
+            Some(peek_word(pointer, (pointer + 1) & 0xFF))
+        }
+        AddressingMode::IndirectY => {
+            let pointer = operand & 0xFF;
+            Some(peek_word(pointer, (pointer + 1) & 0xFF).wrapping_add(y))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Registers and memory of a CPU about to run code at $8000
+    struct Context {
+        x: u8,
+        y: u8,
+        code: [u8; 3],
+    }
+
+    impl ExpressionContext for Context {
+        fn variable(&self, variable: Variable) -> i64 {
+            match variable {
+                Variable::X => i64::from(self.x),
+                Variable::Y => i64::from(self.y),
+                _ => 0,
+            }
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            match addr {
+                0x8000..=0x8002 => self.code[usize::from(addr - 0x8000)],
+                // Pointers to $2000 at $10, and to $1FF0 at $FF
+                0x0011 => 0x20,
+                0x00FF => 0xF0,
+                0x0000 => 0x1F,
+                _ => 0,
+            }
+        }
+    }
+
+    fn matches(class: InstructionClass, x: u8, y: u8, code: [u8; 3]) -> bool {
+        class.matches(0x8000, &Context { x, y, code })
+    }
+
+    #[test]
+    fn matches_opcodes_and_classes() {
+        assert!(matches(InstructionClass::Opcode(0x00), 0, 0, [0x00; 3]));
+        assert!(!matches(InstructionClass::Opcode(0x00), 0, 0, [0xEA; 3]));
+        assert!(matches(InstructionClass::Unofficial, 0, 0, [0x02; 3]));
+        assert!(!matches(InstructionClass::Unofficial, 0, 0, [0xEA; 3]));
+    }
+
+    #[test]
+    fn matches_writes_to_ppu_registers() {
+        let ppu_write = InstructionClass::PpuRegisterWrite;
+        // STA $2007, LDA $2002, INC $3FFF
+        assert!(matches(ppu_write, 0, 0, [0x8D, 0x07, 0x20]));
+        assert!(!matches(ppu_write, 0, 0, [0xAD, 0x02, 0x20]));
+        assert!(matches(ppu_write, 0, 0, [0xEE, 0xFF, 0x3F]));
+        // STA $1FFF,X and STA $1FFF,Y
+        assert!(matches(ppu_write, 1, 0, [0x9D, 0xFF, 0x1F]));
+        assert!(!matches(ppu_write, 0, 1, [0x9D, 0xFF, 0x1F]));
+        assert!(matches(ppu_write, 0, 0x11, [0x99, 0xFF, 0x1F]));
+        // STA ($0F,X) and STA ($FF),Y, with the pointer wrapping around
+        assert!(matches(ppu_write, 1, 0, [0x81, 0x0F, 0x00]));
+        assert!(!matches(ppu_write, 0, 0, [0x81, 0x0F, 0x00]));
+        assert!(matches(ppu_write, 0, 0x10, [0x91, 0xFF, 0x00]));
+        assert!(!matches(ppu_write, 0, 0x0F, [0x91, 0xFF, 0x00]));
+    }
+}
@@ -0,0 +1,268 @@
+// Minimal JSON reader and writer for the files of the debugger, as the core has no dependency
+// to do it. Numbers are integers only, which is all the debugger needs.
+// https://www.json.org
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order of the file
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Parses a whole document, returning the byte offset of the error if it's invalid
+    pub fn parse(source: &str) -> Result<Value, usize> {
+        let mut parser = Parser {
+            source: source.as_bytes(),
+            offset: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset == source.len() {
+            Ok(value)
+        } else {
+            Err(parser.offset)
+        }
+    }
+}
+
+/// Writes `string` as a JSON string, quotes included
+pub(crate) fn write_string(text: &mut String, string: &str) {
+    text.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                // Writing to a String can't fail
+                let _ = write!(text, "\\u{:04x}", c as u32);
+            }
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+}
+
+struct Parser<'a> {
+    source: &'a [u8],
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.offset).copied()
+    }
+
+    /// Consumes `token` if it's next
+    fn eat(&mut self, token: &[u8]) -> bool {
+        self.skip_whitespace();
+        if self.source[self.offset..].starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, usize> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.eat(b"null") => Ok(Value::Null),
+            _ if self.eat(b"true") => Ok(Value::Bool(true)),
+            _ if self.eat(b"false") => Ok(Value::Bool(false)),
+            _ => Err(self.offset),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, usize> {
+        self.offset += 1;
+        let mut members = Vec::new();
+        if self.eat(b"}") {
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            if !self.eat(b":") {
+                return Err(self.offset);
+            }
+            members.push((key, self.value()?));
+
+            if self.eat(b"}") {
+                return Ok(Value::Object(members));
+            } else if !self.eat(b",") {
+                return Err(self.offset);
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, usize> {
+        self.offset += 1;
+        let mut values = Vec::new();
+        if self.eat(b"]") {
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            if self.eat(b"]") {
+                return Ok(Value::Array(values));
+            } else if !self.eat(b",") {
+                return Err(self.offset);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, usize> {
+        if self.peek() != Some(b'"') {
+            return Err(self.offset);
+        }
+        self.offset += 1;
+
+        let mut string = String::new();
+        loop {
+            let start = self.offset;
+            // The source is valid UTF-8, and '"' and '\\' can't be in a multibyte character
+            while !matches!(self.peek(), Some(b'"' | b'\\') | None) {
+                self.offset += 1;
+            }
+            string.push_str(core::str::from_utf8(&self.source[start..self.offset]).unwrap());
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.offset += 1;
+                    return Ok(string);
+                }
+                Some(_) => {
+                    let escaped = match self.source.get(self.offset + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let code = self
+                                .source
+                                .get(self.offset + 2..self.offset + 6)
+                                .and_then(|hex| core::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or(self.offset)?;
+                            self.offset += 4;
+                            // Surrogate pairs aren't needed by the debugger
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.offset),
+                    };
+                    string.push(escaped);
+                    self.offset += 2;
+                }
+                None => return Err(self.offset),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, usize> {
+        let start = self.offset;
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.offset += 1;
+        }
+
+        core::str::from_utf8(&self.source[start..self.offset])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn parses_documents() {
+        let value = Value::parse(r#" [{"a": -12, "b": "x\"é\n"}, null, true, []] "#);
+        assert_eq!(
+            value,
+            Ok(Value::Array(vec![
+                Value::Object(vec![
+                    ("a".to_string(), Value::Number(-12)),
+                    ("b".to_string(), Value::String("x\"é\n".to_string())),
+                ]),
+                Value::Null,
+                Value::Bool(true),
+                Value::Array(Vec::new()),
+            ]))
+        );
+        assert_eq!(value.unwrap().get("a"), None);
+
+        assert_eq!(Value::parse("{\"a\" 1}"), Err(5));
+        assert_eq!(Value::parse("[1, 2"), Err(5));
+        assert_eq!(Value::parse("1.5"), Err(1));
+        assert_eq!(Value::parse("\"abc"), Err(4));
+    }
+
+    #[test]
+    fn writes_strings() {
+        let mut text = String::new();
+        write_string(&mut text, "a\"b\\c\n\u{1}é");
+        assert_eq!(text, r#""a\"b\\c\n\u0001é""#);
+        assert_eq!(
+            Value::parse(&text),
+            Ok(Value::String("a\"b\\c\n\u{1}é".to_string()))
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// Labels and comments on addresses, shown by the disassembly, the trace and the profile.
+//
+// They're keyed by PRG ROM bank like in the rest of the debugger, as the same address holds
+// different code in every bank. The core doesn't touch files, so the frontends save them as
+// JSON in a sidecar file next to the ROM, "game.nes.labels.json" for "game.nes":
+//
+// {"labels": [
+//   {"bank": 0, "address": 32768, "name": "reset", "comment": "Entry point"},
+//   {"bank": null, "address": 768, "name": "", "comment": "Sprites for the OAM DMA"}
+// ]}
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use core::convert::TryFrom;
+use core::fmt::Write as _;
+
+use super::json::{self, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelError {
+    /// The name isn't made of letters, digits and '_', or starts with a digit
+    InvalidName,
+    /// The name is given to another address
+    DuplicateName,
+    /// No address has the name
+    UnknownName,
+    /// Byte offset of the error in a file that isn't valid JSON
+    InvalidJson(usize),
+    /// Index of a label of the file with a member missing or out of range
+    InvalidLabel(usize),
+}
+
+impl core::fmt::Display for LabelError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// Name and comment of an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// PRG ROM bank of the address, `None` outside of the PRG ROM
+    pub bank: Option<u8>,
+    pub address: u16,
+    /// Empty for an address with only a comment
+    pub name: String,
+    pub comment: String,
+}
+
+type Key = (Option<u8>, u16);
+
+#[derive(Default)]
+pub(crate) struct Labels {
+    labels: BTreeMap<Key, Label>,
+}
+
+impl Labels {
+    pub fn get(&self, bank: Option<u8>, address: u16) -> Option<&Label> {
+        self.labels.get(&(bank, address))
+    }
+
+    /// Name of the address, if it has one
+    pub fn name(&self, bank: Option<u8>, address: u16) -> Option<&str> {
+        self.get(bank, address)
+            .map(|l| l.name.as_str())
+            .filter(|n| !n.is_empty())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Label> {
+        self.labels
+            .values()
+            .find(|l| !name.is_empty() && l.name == name)
+    }
+
+    /// Labels ordered by bank and address
+    pub fn iter(&self) -> impl Iterator<Item = &Label> {
+        self.labels.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.labels.clear();
+    }
+
+    /// Names the address, or removes its name with an empty one
+    pub fn set_name(
+        &mut self,
+        bank: Option<u8>,
+        address: u16,
+        name: &str,
+    ) -> Result<(), LabelError> {
+        if !is_valid_name(name) {
+            return Err(LabelError::InvalidName);
+        }
+        if self
+            .find(name)
+            .is_some_and(|l| (l.bank, l.address) != (bank, address))
+        {
+            return Err(LabelError::DuplicateName);
+        }
+
+        self.entry(bank, address).name = name.to_string();
+        self.remove_if_empty((bank, address));
+        Ok(())
+    }
+
+    /// Comments the address, or removes its comment with an empty one
+    pub fn set_comment(&mut self, bank: Option<u8>, address: u16, comment: &str) {
+        self.entry(bank, address).comment = comment.to_string();
+        self.remove_if_empty((bank, address));
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), LabelError> {
+        let (bank, address) = self
+            .find(name)
+            .map(|l| (l.bank, l.address))
+            .ok_or(LabelError::UnknownName)?;
+        self.set_name(bank, address, new_name)
+    }
+
+    pub fn remove(&mut self, bank: Option<u8>, address: u16) -> Option<Label> {
+        self.labels.remove(&(bank, address))
+    }
+
+    /// Exports the labels as JSON, with one per line
+    pub fn to_json(&self) -> String {
+        let mut text = String::from("{\"labels\": [");
+
+        for (i, label) in self.labels.values().enumerate() {
+            text.push_str(if i == 0 { "\n  " } else { ",\n  " });
+            // Writing to a String can't fail
+            match label.bank {
+                Some(bank) => {
+                    let _ = write!(text, "{{\"bank\": {}", bank);
+                }
+                None => text.push_str("{\"bank\": null"),
+            }
+            let _ = write!(text, ", \"address\": {}, \"name\": ", label.address);
+            json::write_string(&mut text, &label.name);
+            text.push_str(", \"comment\": ");
+            json::write_string(&mut text, &label.comment);
+            text.push('}');
+        }
+
+        text.push_str("\n]}\n");
+        text
+    }
+
+    /// Reads labels exported by `to_json`. The comment of a label may be missing.
+    pub fn from_json(source: &str) -> Result<Self, LabelError> {
+        let document = Value::parse(source).map_err(LabelError::InvalidJson)?;
+        let entries = match document.get("labels") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(LabelError::InvalidLabel(0)),
+        };
+
+        let mut labels = Self::default();
+        for (i, entry) in entries.iter().enumerate() {
+            let invalid = LabelError::InvalidLabel(i);
+            let bank = match entry.get("bank").ok_or(invalid)? {
+                Value::Null => None,
+                bank => Some(
+                    bank.as_i64()
+                        .and_then(|b| u8::try_from(b).ok())
+                        .ok_or(invalid)?,
+                ),
+            };
+            let address = entry
+                .get("address")
+                .and_then(Value::as_i64)
+                .and_then(|a| u16::try_from(a).ok())
+                .ok_or(invalid)?;
+            let name = entry.get("name").and_then(Value::as_str).ok_or(invalid)?;
+            let comment = match entry.get("comment") {
+                Some(comment) => comment.as_str().ok_or(invalid)?,
+                None => "",
+            };
+
+            labels.set_name(bank, address, name).map_err(|_| invalid)?;
+            labels.set_comment(bank, address, comment);
+        }
+
+        Ok(labels)
+    }
+
+    fn entry(&mut self, bank: Option<u8>, address: u16) -> &mut Label {
+        self.labels.entry((bank, address)).or_insert_with(|| Label {
+            bank,
+            address,
+            name: String::new(),
+            comment: String::new(),
+        })
+    }
+
+    fn remove_if_empty(&mut self, key: Key) {
+        if self
+            .labels
+            .get(&key)
+            .is_some_and(|l| l.name.is_empty() && l.comment.is_empty())
+        {
+            self.labels.remove(&key);
+        }
+    }
+}
+
+/// Whether the name is empty or an identifier, like the labels of assemblers
+fn is_valid_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_comments_addresses() {
+        let mut labels = Labels::default();
+        assert_eq!(labels.set_name(Some(0), 0x8000, "reset"), Ok(()));
+        assert_eq!(
+            labels.set_name(Some(1), 0x8000, "reset"),
+            Err(LabelError::DuplicateName)
+        );
+        assert_eq!(
+            labels.set_name(Some(1), 0x8000, "1st"),
+            Err(LabelError::InvalidName)
+        );
+        labels.set_comment(None, 0x0300, "Sprites");
+        assert_eq!(labels.name(None, 0x0300), None);
+        assert_eq!(labels.name(Some(0), 0x8000), Some("reset"));
+
+        assert_eq!(labels.rename("reset", "main_loop"), Ok(()));
+        assert_eq!(labels.rename("reset", "x"), Err(LabelError::UnknownName));
+        assert_eq!(labels.find("main_loop").map(|l| l.address), Some(0x8000));
+
+        labels.set_comment(None, 0x0300, "");
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels.set_name(Some(0), 0x8000, ""), Ok(()));
+        assert_eq!(labels.len(), 0);
+    }
+
+    #[test]
+    fn exports_and_imports_json() {
+        let mut labels = Labels::default();
+        labels.set_name(Some(2), 0xC000, "nmi").unwrap();
+        labels.set_comment(Some(2), 0xC000, "Says \"hi\"");
+        labels.set_comment(None, 0x0300, "OAM");
+
+        let json = labels.to_json();
+        assert_eq!(
+            json,
+            "{\"labels\": [\n  \
+             {\"bank\": null, \"address\": 768, \"name\": \"\", \"comment\": \"OAM\"},\n  \
+             {\"bank\": 2, \"address\": 49152, \"name\": \"nmi\", \"comment\": \"Says \\\"hi\\\"\"}\n\
+             ]}\n"
+        );
+        let imported = Labels::from_json(&json).unwrap();
+        assert!(imported.iter().eq(labels.iter()));
+        assert_eq!(Labels::default().to_json(), "{\"labels\": [\n]}\n");
+
+        let imported =
+            Labels::from_json(r#"{"labels": [{"bank": 1, "address": 32768, "name": "a"}]}"#);
+        assert_eq!(
+            imported.map(|l| l.name(Some(1), 0x8000).is_some()),
+            Ok(true)
+        );
+        assert_eq!(
+            Labels::from_json(r#"{"labels": [{"bank": 256, "address": 0, "name": "a"}]}"#).err(),
+            Some(LabelError::InvalidLabel(0))
+        );
+        assert_eq!(
+            Labels::from_json(r#"{"labels": [}"#).err(),
+            Some(LabelError::InvalidJson(12))
+        );
+    }
+}
@@ -0,0 +1,866 @@
+// Debugging tools of the emulator, behind the `debugger` feature.
+//
+// The emulator asks the debugger whether to break before every instruction. When it does, the
+// emulator pauses right there, queues an `Event::Break` and keeps it as the last break, so the
+// embedder learns what stopped the emulation without polling. Resuming runs the instruction the
+// emulation stopped on, without breaking on it again.
+//
+// Breakpoints and watchpoints may have a condition, an expression that must be true for them to
+// stop the emulation. The condition of a watchpoint is evaluated once the instruction that made
+// the access ran, so it sees the value written.
+//
+// The debugger can also break on the events of the mapper: the bank switches, seen by comparing
+// the memory mapped before and after the CPU writes to the cartridge, and the IRQ. The banks
+// switched otherwise, like by the latches of the MMC2, don't stop the emulation.
+//
+// Watches are expressions too, evaluated at the end of every frame and whenever the emulation
+// pauses, for the panels showing the variables of a game as it runs.
+
+mod call_stack;
+mod coverage;
+mod expression;
+mod instruction_breaks;
+mod json;
+mod labels;
+mod profiler;
+mod stack_view;
+mod timing;
+mod trace;
+mod watchpoints;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cartridge::BankMapping;
+use crate::cpu::Cpu;
+
+use self::call_stack::CallStack;
+pub use self::call_stack::{CallKind, StackFrame};
+use self::coverage::Coverage;
+pub use self::coverage::COVERAGE_BITMAP_SIZE;
+pub use self::expression::{Expression, ExpressionContext, ExpressionError, Variable};
+pub use self::instruction_breaks::InstructionClass;
+use self::labels::Labels;
+pub use self::labels::{Label, LabelError};
+pub use self::profiler::ProfileEntry;
+use self::profiler::Profiler;
+pub(crate) use self::stack_view::annotate as annotate_stack;
+pub use self::stack_view::{StackByte, StackSlot};
+use self::timing::Timing;
+pub use self::timing::{TimingEvent, TimingEventKind};
+use self::trace::Trace;
+pub use self::trace::TraceEntry;
+pub(crate) use self::watchpoints::BusAccess;
+pub use self::watchpoints::{WatchKind, Watchpoint};
+
+/// Breakpoint on the address of an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: u32,
+    pub address: u16,
+    pub enabled: bool,
+    /// Breaks only when it's true
+    pub condition: Option<Expression>,
+    /// Number of times the emulation stopped on it
+    pub hits: u64,
+}
+
+/// Expression whose value is kept up to date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub id: u32,
+    pub expression: Expression,
+    /// Value at the last evaluation, `None` until then
+    pub value: Option<i64>,
+}
+
+/// What stopped the emulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// Id of the breakpoint
+    Breakpoint(u32),
+    Watchpoint {
+        id: u32,
+        /// Address accessed
+        address: u16,
+        /// Value read or written
+        value: u8,
+        /// Address of the instruction that made the access
+        instruction: u16,
+    },
+    /// The mapper switched the PRG ROM mapped at `address`, from $6000 to $F000 by 4KB
+    PrgBankSwitch {
+        address: u16,
+        /// PRG ROM bank now mapped there
+        bank: Option<u8>,
+        /// Address of the instruction that wrote to the mapper
+        instruction: u16,
+    },
+    /// The mapper switched the CHR memory mapped at `address` of the pattern tables, by 1KB
+    ChrBankSwitch {
+        address: u16,
+        /// Offset now mapped there, in 1KB
+        bank: u16,
+        /// Address of the instruction that wrote to the mapper
+        instruction: u16,
+    },
+    /// The mapper asserted its IRQ
+    MapperIrq,
+    /// The instruction about to run belongs to the class
+    Instruction(InstructionClass),
+}
+
+/// Events of the mapper the debugger can break on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperEvent {
+    PrgBankSwitch,
+    ChrBankSwitch,
+    Irq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Break {
+    pub reason: BreakReason,
+    /// Address of the instruction about to run
+    pub pc: u16,
+    /// CPU cycles run since power-on
+    pub cycle: u64,
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    watches: Vec<Watch>,
+    /// Ids of the breakpoints, watchpoints and watches
+    next_id: u32,
+    last_break: Option<Break>,
+    /// Watchpoints triggered by the instruction running, checked before the next one
+    triggered: Vec<BreakReason>,
+    /// Address of the instruction running
+    instruction: u16,
+    /// CPU cycles run since power-on
+    cycles: u64,
+    /// Interrupts taken since power-on
+    interrupts: u64,
+    call_stack: CallStack,
+    trace: Trace,
+    timing: Timing,
+    profiler: Profiler,
+    coverage: Coverage,
+    labels: Labels,
+    /// Classes of instructions to break on
+    instruction_breaks: Vec<InstructionClass>,
+    /// Mapper events to break on, indexed by `MapperEvent`
+    mapper_breaks: [bool; 3],
+    /// Level of the IRQ of the mapper
+    mapper_irq: bool,
+}
+
+impl Debugger {
+    /// Adds an enabled breakpoint and returns its id
+    pub fn add_breakpoint(&mut self, address: u16) -> u32 {
+        let id = self.new_id();
+        self.breakpoints.push(Breakpoint {
+            id,
+            address,
+            enabled: true,
+            condition: None,
+            hits: 0,
+        });
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: u32) -> Option<Breakpoint> {
+        let index = self.breakpoints.iter().position(|b| b.id == id)?;
+        Some(self.breakpoints.remove(index))
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, id: u32, enabled: bool) {
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|b| b.id == id) {
+            breakpoint.enabled = enabled;
+        }
+    }
+
+    /// Sets the condition of a breakpoint, or removes it with `None`
+    pub fn set_breakpoint_condition(&mut self, id: u32, condition: Option<Expression>) {
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|b| b.id == id) {
+            breakpoint.condition = condition;
+        }
+    }
+
+    /// Adds an enabled watchpoint on the addresses from `start` to `end` included, and returns
+    /// its id
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, kind: WatchKind) -> u32 {
+        let id = self.new_id();
+        self.watchpoints.push(Watchpoint {
+            id,
+            start: start.min(end),
+            end: start.max(end),
+            kind,
+            enabled: true,
+            condition: None,
+            hits: 0,
+        });
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: u32) -> Option<Watchpoint> {
+        let index = self.watchpoints.iter().position(|w| w.id == id)?;
+        Some(self.watchpoints.remove(index))
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, id: u32, enabled: bool) {
+        if let Some(watchpoint) = self.watchpoints.iter_mut().find(|w| w.id == id) {
+            watchpoint.enabled = enabled;
+        }
+    }
+
+    /// Sets the condition of a watchpoint, or removes it with `None`
+    pub fn set_watchpoint_condition(&mut self, id: u32, condition: Option<Expression>) {
+        if let Some(watchpoint) = self.watchpoints.iter_mut().find(|w| w.id == id) {
+            watchpoint.condition = condition;
+        }
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Sets the hit count of every breakpoint and watchpoint back to 0
+    pub fn reset_hit_counts(&mut self) {
+        self.breakpoints.iter_mut().for_each(|b| b.hits = 0);
+        self.watchpoints.iter_mut().for_each(|w| w.hits = 0);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Breaks before running any instruction of the class. It's disabled by default.
+    pub fn set_break_on_instructions(&mut self, class: InstructionClass, enabled: bool) {
+        self.instruction_breaks.retain(|c| *c != class);
+        if enabled {
+            self.instruction_breaks.push(class);
+        }
+    }
+
+    pub fn breaks_on_instructions(&self, class: InstructionClass) -> bool {
+        self.instruction_breaks.contains(&class)
+    }
+
+    /// Breaks after the instruction that made the mapper switch a bank, or when it asserts its
+    /// IRQ. It's disabled by default.
+    pub fn set_break_on_mapper_event(&mut self, event: MapperEvent, enabled: bool) {
+        self.mapper_breaks[event as usize] = enabled;
+    }
+
+    pub fn breaks_on_mapper_event(&self, event: MapperEvent) -> bool {
+        self.mapper_breaks[event as usize]
+    }
+
+    /// Adds a watch, evaluated at the end of every frame and whenever the emulation pauses, or
+    /// right away with `Emulator::update_watches`. Returns its id.
+    pub fn add_watch(&mut self, expression: Expression) -> u32 {
+        let id = self.new_id();
+        self.watches.push(Watch {
+            id,
+            expression,
+            value: None,
+        });
+        id
+    }
+
+    pub fn remove_watch(&mut self, id: u32) -> Option<Watch> {
+        let index = self.watches.iter().position(|w| w.id == id)?;
+        Some(self.watches.remove(index))
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    /// Last time the emulation stopped, also sent as an `Event::Break`
+    pub fn last_break(&self) -> Option<Break> {
+        self.last_break
+    }
+
+    /// CPU cycles run since power-on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// NMIs and IRQs taken since power-on
+    pub fn interrupts(&self) -> u64 {
+        self.interrupts
+    }
+
+    /// Subroutines and interrupt handlers running, the outermost first. It's rebuilt from the
+    /// calls seen since power-on, so it's empty until a call is made.
+    pub fn call_stack(&self) -> &[StackFrame] {
+        self.call_stack.frames()
+    }
+
+    /// Keeps the state of the CPU before each of the last `capacity` instructions run, or stops
+    /// keeping it with 0, the default
+    pub fn set_trace_capacity(&mut self, capacity: usize) {
+        self.trace.set_capacity(capacity);
+    }
+
+    pub fn trace_capacity(&self) -> usize {
+        self.trace.capacity()
+    }
+
+    /// Last instructions run, from the oldest. After a break, the last one is the instruction the
+    /// emulation stopped on.
+    pub fn trace(&self) -> impl DoubleEndedIterator<Item = &TraceEntry> {
+        self.trace.entries()
+    }
+
+    /// Exports the last `count` instructions of the trace as text, one per line from the oldest:
+    /// `bank:pc opcode A:a X:x Y:y SP:sp P:p CYC:cycle`, in hexadecimal but the cycle, followed by
+    /// `; name` for the instructions with a label
+    pub fn export_trace(&self, count: usize) -> String {
+        self.trace.to_text(count, &self.labels)
+    }
+
+    /// Records the timing of the events of the PPU, see `timing_events`. It's disabled by default.
+    pub fn set_timing_events_enabled(&mut self, enabled: bool) {
+        self.timing.set_enabled(enabled);
+    }
+
+    /// Events of the last frame rendered, from the pre-render scanline, with the dot they
+    /// happened on
+    pub fn timing_events(&self) -> &[TimingEvent] {
+        self.timing.frame_events()
+    }
+
+    /// Counts the CPU cycles spent on every instruction and in every routine, see `profile`. It's
+    /// disabled by default.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.set_enabled(enabled);
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.is_enabled()
+    }
+
+    /// Sets the cycles counted by the profiler back to 0
+    pub fn reset_profile(&mut self) {
+        self.profiler.reset();
+    }
+
+    /// Cycles spent on every instruction run while profiling, from the one with the most
+    pub fn profile(&self) -> Vec<ProfileEntry> {
+        self.profiler.instructions()
+    }
+
+    /// Cycles spent in every routine of the call stack while profiling, from the one with the
+    /// most. The cycles of a routine don't include the ones of the routines it calls.
+    pub fn routine_profile(&self) -> Vec<ProfileEntry> {
+        self.profiler.routines()
+    }
+
+    /// CPU cycles counted by the profiler
+    pub fn profiled_cycles(&self) -> u64 {
+        self.profiler.total_cycles()
+    }
+
+    /// Exports the profile as text, the routines then the instructions from the ones with the
+    /// most cycles, with one per line: `bank:address cycles percentage`, followed by `; name` for
+    /// the addresses with a label
+    pub fn export_profile(&self) -> String {
+        self.profiler.to_text(&self.labels)
+    }
+
+    /// Marks the addresses run, see `is_covered`. It's disabled by default.
+    pub fn set_coverage_enabled(&mut self, enabled: bool) {
+        self.coverage.set_enabled(enabled);
+    }
+
+    pub fn is_coverage_enabled(&self) -> bool {
+        self.coverage.is_enabled()
+    }
+
+    /// Forgets the addresses run
+    pub fn reset_coverage(&mut self) {
+        self.coverage.clear();
+    }
+
+    /// Whether the byte at `addr` was run while the PRG ROM `bank` was mapped there, `None` being
+    /// the addresses outside of the PRG ROM. Every byte of the instructions is marked.
+    pub fn is_covered(&self, bank: Option<u8>, addr: u16) -> bool {
+        self.coverage.is_covered(bank, addr)
+    }
+
+    /// Addresses run while `bank` was mapped, with a bit per address of the CPU address space:
+    /// the bit `addr % 8` of the byte `addr / 8`
+    pub fn coverage_bitmap(&self, bank: Option<u8>) -> Option<&[u8; COVERAGE_BITMAP_SIZE]> {
+        self.coverage.bitmap(bank)
+    }
+
+    /// Banks with addresses run, see `coverage_bitmap`
+    pub fn covered_banks(&self) -> impl Iterator<Item = Option<u8>> + '_ {
+        self.coverage.banks()
+    }
+
+    /// Bytes run in the PRG ROM banks. `Emulator::code_coverage` gives it as a percentage of the
+    /// PRG ROM.
+    pub fn covered_rom_bytes(&self) -> usize {
+        self.coverage.rom_bytes()
+    }
+
+    /// Names `address` while the PRG ROM `bank` is mapped there, `None` being the addresses
+    /// outside of the PRG ROM, or removes its name with an empty one. The names are identifiers
+    /// given to a single address.
+    pub fn set_label(
+        &mut self,
+        bank: Option<u8>,
+        address: u16,
+        name: &str,
+    ) -> Result<(), LabelError> {
+        self.labels.set_name(bank, address, name)
+    }
+
+    /// Comments `address` like `set_label`, or removes its comment with an empty one
+    pub fn set_label_comment(&mut self, bank: Option<u8>, address: u16, comment: &str) {
+        self.labels.set_comment(bank, address, comment);
+    }
+
+    pub fn rename_label(&mut self, name: &str, new_name: &str) -> Result<(), LabelError> {
+        self.labels.rename(name, new_name)
+    }
+
+    /// Removes the name and the comment of an address
+    pub fn remove_label(&mut self, bank: Option<u8>, address: u16) -> Option<Label> {
+        self.labels.remove(bank, address)
+    }
+
+    pub fn clear_labels(&mut self) {
+        self.labels.clear();
+    }
+
+    pub fn label(&self, bank: Option<u8>, address: u16) -> Option<&Label> {
+        self.labels.get(bank, address)
+    }
+
+    /// Label with the name, to break on it for instance
+    pub fn find_label(&self, name: &str) -> Option<&Label> {
+        self.labels.find(name)
+    }
+
+    /// Labels ordered by bank and address
+    pub fn labels(&self) -> impl Iterator<Item = &Label> {
+        self.labels.iter()
+    }
+
+    /// Exports the labels as JSON, to save them next to the ROM
+    pub fn export_labels(&self) -> String {
+        self.labels.to_json()
+    }
+
+    /// Replaces the labels with the ones exported by `export_labels`, and returns how many there
+    /// are. The labels are kept as they were on an error.
+    pub fn import_labels(&mut self, json: &str) -> Result<usize, LabelError> {
+        self.labels = Labels::from_json(json)?;
+        Ok(self.labels.len())
+    }
+
+    /// Evaluates the watches in `context`, the emulator
+    pub(crate) fn watch_values(&self, context: &dyn ExpressionContext) -> Vec<i64> {
+        self.watches
+            .iter()
+            .map(|w| w.expression.evaluate(context))
+            .collect()
+    }
+
+    /// Called with the result of `watch_values`
+    pub(crate) fn set_watch_values(&mut self, values: Vec<i64>) {
+        for (watch, value) in self.watches.iter_mut().zip(values) {
+            watch.value = Some(value);
+        }
+    }
+
+    pub(crate) fn is_recording_timing(&self) -> bool {
+        self.timing.is_enabled()
+    }
+
+    pub(crate) fn record_timing_event(&mut self, scanline: i16, dot: u16, kind: TimingEventKind) {
+        self.timing.record(scanline, dot, kind);
+    }
+
+    /// Called after every dot while recording the timing of the events
+    pub(crate) fn update_timing(
+        &mut self,
+        scanline: i16,
+        dot: u16,
+        sprite_zero_hit: bool,
+        mapper_irq: bool,
+    ) {
+        self.timing
+            .update(scanline, dot, sprite_zero_hit, mapper_irq);
+    }
+
+    pub(crate) fn power_on(&mut self) {
+        self.cycles = 0;
+        self.interrupts = 0;
+        self.call_stack.clear();
+        self.trace.clear();
+        self.profiler.power_on();
+        self.last_break = None;
+        self.triggered.clear();
+    }
+
+    pub(crate) fn cpu_clock(&mut self) {
+        self.cycles += 1;
+    }
+
+    /// Called once the CPU took an interrupt, which came before the instruction at `caller`.
+    /// `prg_bank` gives the PRG ROM bank mapped at an address.
+    pub(crate) fn on_interrupt<F>(
+        &mut self,
+        kind: CallKind,
+        caller: u16,
+        pc: u16,
+        st: u8,
+        prg_bank: F,
+    ) where
+        F: Fn(u16) -> Option<u8>,
+    {
+        self.interrupts += 1;
+        // The instruction traced didn't run
+        self.trace.discard_last();
+        self.call_stack.on_interrupt(kind, caller, pc, st, prg_bank);
+    }
+
+    /// Called before the CPU runs `opcode`, to follow the calls, trace and profile the
+    /// instruction
+    pub(crate) fn on_instruction<F>(&mut self, cpu: &Cpu, opcode: u8, prg_bank: F)
+    where
+        F: Fn(u16) -> Option<u8>,
+    {
+        let bank = prg_bank(cpu.pc);
+        self.trace
+            .record(TraceEntry::new(cpu, bank, opcode, self.cycles));
+        self.call_stack
+            .before_instruction(cpu.pc, cpu.st, opcode, prg_bank);
+
+        self.coverage.on_instruction(bank, cpu.pc, opcode);
+
+        if self.profiler.is_enabled() {
+            let routine = self.call_stack().last().map(|f| (f.target_bank, f.target));
+            self.profiler
+                .on_instruction(self.cycles, (bank, cpu.pc), routine);
+        }
+    }
+
+    /// Why the emulation must stop before running the instruction at `pc`, if it must. The
+    /// conditions are evaluated in `context`, the emulator.
+    pub(crate) fn break_reason(
+        &self,
+        pc: u16,
+        context: &dyn ExpressionContext,
+    ) -> Option<BreakReason> {
+        let is_met =
+            |condition: &Option<Expression>| condition.as_ref().is_none_or(|c| c.is_true(context));
+
+        let watchpoint = self.triggered.iter().find(|reason| match reason {
+            BreakReason::Watchpoint { id, .. } => self
+                .watchpoints
+                .iter()
+                .any(|w| w.id == *id && is_met(&w.condition)),
+            BreakReason::Breakpoint(_) => false,
+            _ => true,
+        });
+
+        watchpoint
+            .copied()
+            .or_else(|| {
+                self.breakpoints
+                    .iter()
+                    .find(|b| b.enabled && b.address == pc && is_met(&b.condition))
+                    .map(|b| BreakReason::Breakpoint(b.id))
+            })
+            .or_else(|| {
+                self.instruction_breaks
+                    .iter()
+                    .find(|c| c.matches(pc, context))
+                    .map(|c| BreakReason::Instruction(*c))
+            })
+    }
+
+    /// Called before running the instruction at `pc`, with the result of `break_reason`. Returns
+    /// the break if the emulation must stop.
+    pub(crate) fn before_instruction(
+        &mut self,
+        pc: u16,
+        reason: Option<BreakReason>,
+    ) -> Option<Break> {
+        self.instruction = pc;
+        self.triggered.clear();
+
+        let reason = reason?;
+        match reason {
+            BreakReason::Breakpoint(id) => {
+                if let Some(breakpoint) = self.breakpoints.iter_mut().find(|b| b.id == id) {
+                    breakpoint.hits += 1;
+                }
+            }
+            BreakReason::Watchpoint { id, .. } => {
+                if let Some(watchpoint) = self.watchpoints.iter_mut().find(|w| w.id == id) {
+                    watchpoint.hits += 1;
+                }
+            }
+            _ => {}
+        }
+
+        let brk = Break {
+            reason,
+            pc,
+            cycle: self.cycles,
+        };
+        self.last_break = Some(brk);
+        Some(brk)
+    }
+
+    /// Whether an access at `addr` may trigger a watchpoint, to skip the work of reporting it
+    pub(crate) fn is_watched(&self, addr: u16) -> bool {
+        self.watchpoints.iter().any(|w| w.watches(addr))
+    }
+
+    /// Called on every access of the CPU bus
+    pub(crate) fn on_access(&mut self, addr: u16, access: BusAccess, value: u8) {
+        for watchpoint in &self.watchpoints {
+            // The first access of the instruction triggering the watchpoint is reported
+            let reported = self
+                .triggered
+                .iter()
+                .any(|r| matches!(r, BreakReason::Watchpoint { id, .. } if *id == watchpoint.id));
+            if !reported && watchpoint.is_triggered(addr, access, value) {
+                self.triggered.push(BreakReason::Watchpoint {
+                    id: watchpoint.id,
+                    address: addr,
+                    value,
+                    instruction: self.instruction,
+                });
+            }
+        }
+    }
+
+    /// Whether the memory mapped must be compared around the CPU writes to the cartridge
+    pub(crate) fn breaks_on_bank_switch(&self) -> bool {
+        self.breaks_on_mapper_event(MapperEvent::PrgBankSwitch)
+            || self.breaks_on_mapper_event(MapperEvent::ChrBankSwitch)
+    }
+
+    /// Called with the memory mapped before and after a CPU write to the cartridge, while
+    /// breaking on the bank switches. `prg_bank` gives the PRG ROM bank mapped at an address.
+    pub(crate) fn on_bank_switch<F>(
+        &mut self,
+        before: &BankMapping,
+        after: &BankMapping,
+        prg_bank: F,
+    ) where
+        F: Fn(u16) -> Option<u8>,
+    {
+        if self.breaks_on_mapper_event(MapperEvent::PrgBankSwitch) {
+            let window = (0..before.prg.len()).find(|&i| before.prg[i] != after.prg[i]);
+            if let Some(window) = window {
+                let address = 0x6000 + window as u16 * 0x1000;
+                self.triggered.push(BreakReason::PrgBankSwitch {
+                    address,
+                    bank: prg_bank(address),
+                    instruction: self.instruction,
+                });
+            }
+        }
+
+        if self.breaks_on_mapper_event(MapperEvent::ChrBankSwitch) {
+            let window = (0..before.chr.len()).find(|&i| before.chr[i] != after.chr[i]);
+            if let Some(window) = window {
+                self.triggered.push(BreakReason::ChrBankSwitch {
+                    address: window as u16 * 0x400,
+                    bank: after.chr[window].map_or(0, |offset| (offset / 0x400) as u16),
+                    instruction: self.instruction,
+                });
+            }
+        }
+    }
+
+    /// Called on every CPU cycle with the level of the IRQ of the mapper
+    pub(crate) fn on_mapper_irq(&mut self, asserted: bool) {
+        if asserted && !self.mapper_irq && self.breaks_on_mapper_event(MapperEvent::Irq) {
+            self.triggered.push(BreakReason::MapperIrq);
+        }
+        self.mapper_irq = asserted;
+    }
+
+    fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Registers {
+        a: u8,
+    }
+
+    impl ExpressionContext for Registers {
+        fn variable(&self, variable: Variable) -> i64 {
+            match variable {
+                Variable::A => i64::from(self.a),
+                _ => 0,
+            }
+        }
+
+        fn peek(&self, _addr: u16) -> u8 {
+            0
+        }
+    }
+
+    fn step(debugger: &mut Debugger, pc: u16, registers: &Registers) -> Option<Break> {
+        let reason = debugger.break_reason(pc, registers);
+        debugger.before_instruction(pc, reason)
+    }
+
+    #[test]
+    fn counts_enabled_breakpoint_hits() {
+        let mut debugger = Debugger::default();
+        let first = debugger.add_breakpoint(0x8000);
+        let second = debugger.add_breakpoint(0x8003);
+        debugger.cpu_clock();
+
+        assert_eq!(step(&mut debugger, 0x8001, &Registers::default()), None);
+        assert_eq!(
+            step(&mut debugger, 0x8000, &Registers::default()),
+            Some(Break {
+                reason: BreakReason::Breakpoint(first),
+                pc: 0x8000,
+                cycle: 1,
+            })
+        );
+
+        debugger.set_breakpoint_enabled(second, false);
+        assert_eq!(step(&mut debugger, 0x8003, &Registers::default()), None);
+        assert_eq!(debugger.breakpoints()[0].hits, 1);
+        assert_eq!(debugger.breakpoints()[1].hits, 0);
+
+        assert_eq!(
+            debugger.remove_breakpoint(first).map(|b| b.address),
+            Some(0x8000)
+        );
+        assert_eq!(step(&mut debugger, 0x8000, &Registers::default()), None);
+    }
+
+    #[test]
+    fn triggers_watchpoints_on_accesses() {
+        let mut debugger = Debugger::default();
+        let write = debugger.add_watchpoint(0x0010, 0x0000, WatchKind::Write);
+        let change = debugger.add_watchpoint(0x0300, 0x0300, WatchKind::Change);
+        assert!(debugger.is_watched(0x0008) && !debugger.is_watched(0x0011));
+
+        assert_eq!(step(&mut debugger, 0xC000, &Registers::default()), None);
+        debugger.on_access(0x0008, BusAccess::Read, 0x12);
+        debugger.on_access(0x0300, BusAccess::Write(Some(0x34)), 0x34);
+        assert_eq!(step(&mut debugger, 0xC002, &Registers::default()), None);
+
+        debugger.on_access(0x0008, BusAccess::Write(Some(0x12)), 0x13);
+        let brk = step(&mut debugger, 0xC004, &Registers::default()).unwrap();
+        assert_eq!(
+            brk.reason,
+            BreakReason::Watchpoint {
+                id: write,
+                address: 0x0008,
+                value: 0x13,
+                instruction: 0xC002,
+            }
+        );
+        assert_eq!(brk.pc, 0xC004);
+
+        debugger.on_access(0x0300, BusAccess::Write(Some(0x34)), 0x35);
+        assert!(matches!(
+            step(&mut debugger, 0xC006, &Registers::default()).map(|b| b.reason),
+            Some(BreakReason::Watchpoint { id, .. }) if id == change
+        ));
+        assert_eq!(debugger.watchpoints()[0].hits, 1);
+    }
+
+    #[test]
+    fn breaks_when_conditions_are_true() {
+        let mut debugger = Debugger::default();
+        let breakpoint = debugger.add_breakpoint(0x8000);
+        let watchpoint = debugger.add_watchpoint(0x0000, 0x0000, WatchKind::Write);
+        debugger.set_breakpoint_condition(breakpoint, Some(Expression::parse("A == 3").unwrap()));
+        debugger.set_watchpoint_condition(watchpoint, Some(Expression::parse("A > 8").unwrap()));
+
+        assert_eq!(step(&mut debugger, 0x8000, &Registers { a: 2 }), None);
+        assert!(step(&mut debugger, 0x8000, &Registers { a: 3 }).is_some());
+
+        debugger.on_access(0x0000, BusAccess::Write(None), 0x12);
+        assert_eq!(step(&mut debugger, 0xC000, &Registers { a: 8 }), None);
+        debugger.on_access(0x0000, BusAccess::Write(None), 0x12);
+        assert!(matches!(
+            step(&mut debugger, 0xC000, &Registers { a: 9 }).map(|b| b.reason),
+            Some(BreakReason::Watchpoint { id, .. }) if id == watchpoint
+        ));
+
+        assert_eq!(debugger.breakpoints()[0].hits, 1);
+        assert_eq!(debugger.watchpoints()[0].hits, 1);
+        debugger.set_breakpoint_condition(breakpoint, None);
+        assert!(step(&mut debugger, 0x8000, &Registers { a: 2 }).is_some());
+    }
+
+    #[test]
+    fn breaks_on_mapper_irq_edges() {
+        let mut debugger = Debugger::default();
+        debugger.on_mapper_irq(true);
+        assert_eq!(step(&mut debugger, 0x8000, &Registers::default()), None);
+
+        debugger.set_break_on_mapper_event(MapperEvent::Irq, true);
+        debugger.on_mapper_irq(true);
+        assert_eq!(step(&mut debugger, 0x8000, &Registers::default()), None);
+        debugger.on_mapper_irq(false);
+        debugger.on_mapper_irq(true);
+        assert_eq!(
+            step(&mut debugger, 0x8000, &Registers::default()).map(|b| b.reason),
+            Some(BreakReason::MapperIrq)
+        );
+    }
+
+    #[test]
+    fn evaluates_watches() {
+        let mut debugger = Debugger::default();
+        let double = debugger.add_watch(Expression::parse("A * 2").unwrap());
+        let constant = debugger.add_watch(Expression::parse("$10").unwrap());
+        assert_eq!(debugger.watches()[0].value, None);
+
+        let values = debugger.watch_values(&Registers { a: 21 });
+        debugger.set_watch_values(values);
+        let values: Vec<_> = debugger.watches().iter().map(|w| w.value).collect();
+        assert_eq!(values, [Some(42), Some(16)]);
+
+        assert_eq!(
+            debugger.remove_watch(double).map(|w| w.value),
+            Some(Some(42))
+        );
+        assert_eq!(debugger.watches()[0].id, constant);
+    }
+}
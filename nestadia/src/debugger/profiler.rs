@@ -0,0 +1,202 @@
+// Exact profiler, counting the CPU cycles spent on every instruction and in every routine.
+//
+// The cycles run between the starts of two instructions are given to the first one, so the
+// cycles of the DMAs and of the interrupts entries go to the instruction they came before or
+// during. The routines are the ones of the call stack, keyed by their first instruction: the
+// cycles are given to the innermost one, and the code run outside of any call isn't counted
+// there.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+
+use super::labels::Labels;
+
+/// Cycles spent on an instruction, or in a routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// PRG ROM bank of the address, when mapped from the ROM
+    pub bank: Option<u8>,
+    /// Address of the instruction, or first instruction of the routine
+    pub address: u16,
+    pub cycles: u64,
+}
+
+type Key = (Option<u8>, u16);
+
+/// Instruction running, whose cycles are counted when the next one starts
+#[derive(Debug, Clone, Copy)]
+struct Running {
+    instruction: Key,
+    routine: Option<Key>,
+    start: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Profiler {
+    enabled: bool,
+    instructions: BTreeMap<Key, u64>,
+    routines: BTreeMap<Key, u64>,
+    total: u64,
+    running: Option<Running>,
+}
+
+impl Profiler {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.running = None;
+    }
+
+    pub fn reset(&mut self) {
+        self.instructions.clear();
+        self.routines.clear();
+        self.total = 0;
+        self.running = None;
+    }
+
+    /// Forgets the instruction running, as the cycles count again from 0
+    pub fn power_on(&mut self) {
+        self.running = None;
+    }
+
+    /// CPU cycles counted since the profile was reset
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    /// Instructions from the one with the most cycles
+    pub fn instructions(&self) -> Vec<ProfileEntry> {
+        Self::sorted(&self.instructions)
+    }
+
+    /// Routines from the one with the most cycles
+    pub fn routines(&self) -> Vec<ProfileEntry> {
+        Self::sorted(&self.routines)
+    }
+
+    /// Called when an instruction starts at `cycle`, in the innermost `routine` of the call stack
+    pub fn on_instruction(&mut self, cycle: u64, instruction: Key, routine: Option<Key>) {
+        if !self.enabled {
+            return;
+        }
+
+        if let Some(running) = self.running {
+            let cycles = cycle - running.start;
+            *self.instructions.entry(running.instruction).or_default() += cycles;
+            if let Some(routine) = running.routine {
+                *self.routines.entry(routine).or_default() += cycles;
+            }
+            self.total += cycles;
+        }
+
+        self.running = Some(Running {
+            instruction,
+            routine,
+            start: cycle,
+        });
+    }
+
+    /// Exports the profile as text, the routines then the instructions from the ones with the
+    /// most cycles, with one per line: `bank:address cycles percentage`, and the names of the
+    /// addresses in `labels`
+    pub fn to_text(&self, labels: &Labels) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "# total cycles: {}", self.total);
+
+        for (title, entries) in [
+            ("routines", self.routines()),
+            ("instructions", self.instructions()),
+        ] {
+            let _ = writeln!(text, "# {}", title);
+            for entry in entries {
+                match entry.bank {
+                    Some(bank) => {
+                        let _ = write!(text, "{:02X}:", bank);
+                    }
+                    None => text.push_str("--:"),
+                }
+                // Writing to a String can't fail
+                let _ = write!(
+                    text,
+                    "{:04X} {} {:.2}%",
+                    entry.address,
+                    entry.cycles,
+                    entry.cycles as f64 * 100.0 / self.total.max(1) as f64
+                );
+                if let Some(name) = labels.name(entry.bank, entry.address) {
+                    let _ = write!(text, " ; {}", name);
+                }
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+
+    fn sorted(cycles: &BTreeMap<Key, u64>) -> Vec<ProfileEntry> {
+        let mut entries: Vec<_> = cycles
+            .iter()
+            .map(|(&(bank, address), &cycles)| ProfileEntry {
+                bank,
+                address,
+                cycles,
+            })
+            .collect();
+        entries.sort_by_key(|e| core::cmp::Reverse(e.cycles));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_cycles_per_instruction_and_routine() {
+        let mut profiler = Profiler::default();
+        profiler.on_instruction(0, (Some(0), 0x8000), None);
+        assert!(profiler.instructions().is_empty());
+
+        profiler.set_enabled(true);
+        profiler.on_instruction(10, (Some(0), 0x8000), None);
+        profiler.on_instruction(16, (Some(1), 0xC000), Some((Some(1), 0xC000)));
+        profiler.on_instruction(18, (Some(1), 0xC001), Some((Some(1), 0xC000)));
+        profiler.on_instruction(24, (Some(0), 0x8003), None);
+        profiler.on_instruction(26, (None, 0x0300), None);
+
+        assert_eq!(profiler.total_cycles(), 16);
+        assert_eq!(
+            profiler.instructions()[0],
+            ProfileEntry {
+                bank: Some(0),
+                address: 0x8000,
+                cycles: 6,
+            }
+        );
+        assert_eq!(profiler.instructions()[1].address, 0xC001);
+        assert_eq!(profiler.routines().len(), 1);
+        assert_eq!(profiler.routines()[0].cycles, 8);
+
+        let mut labels = Labels::default();
+        labels.set_name(Some(1), 0xC000, "update").unwrap();
+        assert_eq!(
+            profiler.to_text(&labels),
+            "# total cycles: 16\n\
+             # routines\n\
+             01:C000 8 50.00% ; update\n\
+             # instructions\n\
+             00:8000 6 37.50%\n\
+             01:C001 6 37.50%\n\
+             00:8003 2 12.50%\n\
+             01:C000 2 12.50% ; update\n"
+        );
+
+        profiler.reset();
+        assert_eq!(profiler.total_cycles(), 0);
+    }
+}
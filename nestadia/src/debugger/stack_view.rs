@@ -0,0 +1,214 @@
+// Stack page annotated with what its bytes likely are, for the stack panels of the debuggers.
+//
+// The frames of the call stack tell where the calls pushed their return address, and the
+// status for the interrupts. The bytes between them were pushed by the routine of the frame
+// under them, like registers saved with PHA. The return addresses of the calls made before the
+// debugger could see them are guessed: a pair of bytes pointing right after a JSR likely is one.
+
+use alloc::vec::Vec;
+
+use super::{CallKind, StackFrame};
+use crate::cpu::Opcode;
+
+/// What a byte of the stack page likely holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackSlot {
+    /// At or below the stack pointer, so not in use
+    Free,
+    /// Pushed by something the debugger didn't see
+    Unknown,
+    /// Byte of the address a call returns to. A JSR pushes it minus 1, which RTS adds back.
+    ReturnAddress {
+        return_address: u16,
+        /// Whether it's the high byte, pushed first
+        high: bool,
+        kind: CallKind,
+        /// Index of the frame in the call stack, `None` when guessed
+        frame: Option<usize>,
+    },
+    /// Status register pushed by an interrupt or a BRK
+    Status { kind: CallKind, frame: usize },
+    /// Pushed by the routine of the frame once called
+    Pushed { frame: usize },
+}
+
+/// Byte of the stack page, $0100-$01FF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackByte {
+    pub address: u16,
+    pub value: u8,
+    pub slot: StackSlot,
+}
+
+/// Annotates the stack page, with the stack pointer `st` and the frames of the call stack.
+/// `peek` reads the memory.
+pub(crate) fn annotate<F>(frames: &[StackFrame], st: u8, peek: F) -> Vec<StackByte>
+where
+    F: Fn(u16) -> u8,
+{
+    let values: Vec<u8> = (0x0100..=0x01FF).map(&peek).collect();
+    let mut slots = [StackSlot::Free; 256];
+    for slot in slots.iter_mut().skip(usize::from(st) + 1) {
+        *slot = StackSlot::Unknown;
+    }
+
+    // From the innermost frame, as the stack grows down
+    let mut top = usize::from(st);
+    for (index, frame) in frames.iter().enumerate().rev() {
+        let base = usize::from(frame.stack_pointer);
+        for slot in slots.iter_mut().take(base + 1).skip(top + 1) {
+            *slot = StackSlot::Pushed { frame: index };
+        }
+
+        let (status, pushed) = match frame.kind {
+            CallKind::Jsr => (None, frame.return_address.wrapping_sub(1)),
+            _ => (Some(base + 1), frame.return_address),
+        };
+        let low = status.map_or(base + 1, |status| status + 1);
+        // Left as unknown when the bytes were pulled, or don't match as the game overwrote them
+        if base >= usize::from(st) && low < 0xFF && word(&values, low) == pushed {
+            if let Some(status) = status {
+                slots[status] = StackSlot::Status {
+                    kind: frame.kind,
+                    frame: index,
+                };
+            }
+            for (offset, high) in [(low, false), (low + 1, true)] {
+                slots[offset] = StackSlot::ReturnAddress {
+                    return_address: frame.return_address,
+                    high,
+                    kind: frame.kind,
+                    frame: Some(index),
+                };
+            }
+        }
+        top = low + 1;
+    }
+
+    // Return addresses pushed by the JSR the debugger didn't see
+    let mut offset = usize::from(st) + 1;
+    while offset < 0xFF {
+        let is_unknown = |offset: usize| slots[offset] == StackSlot::Unknown;
+        let return_address = word(&values, offset).wrapping_add(1);
+        if is_unknown(offset)
+            && is_unknown(offset + 1)
+            && peek(return_address.wrapping_sub(3)) == Opcode::JsrAbs as u8
+        {
+            for (offset, high) in [(offset, false), (offset + 1, true)] {
+                slots[offset] = StackSlot::ReturnAddress {
+                    return_address,
+                    high,
+                    kind: CallKind::Jsr,
+                    frame: None,
+                };
+            }
+            offset += 2;
+        } else {
+            offset += 1;
+        }
+    }
+
+    values
+        .iter()
+        .zip(slots.iter())
+        .enumerate()
+        .map(|(offset, (value, slot))| StackByte {
+            address: 0x0100 + offset as u16,
+            value: *value,
+            slot: *slot,
+        })
+        .collect()
+}
+
+/// Little endian word at `offset` of the stack page
+fn word(values: &[u8], offset: usize) -> u16 {
+    u16::from(values[offset]) | u16::from(values[offset + 1]) << 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: CallKind, return_address: u16, stack_pointer: u8) -> StackFrame {
+        StackFrame {
+            kind,
+            caller: 0,
+            caller_bank: None,
+            target: 0,
+            target_bank: None,
+            return_address,
+            stack_pointer,
+        }
+    }
+
+    #[test]
+    fn annotates_frames_and_pushed_bytes() {
+        // A JSR from $8000, a PHA, then an NMI before $C000, with the status 0x24
+        let mut memory = [0u8; 0x10000];
+        memory[0x01FC..0x01FE].copy_from_slice(&[0x02, 0x80]);
+        memory[0x01FB] = 0x55;
+        memory[0x01F8..0x01FB].copy_from_slice(&[0x24, 0x00, 0xC0]);
+        let frames = [
+            frame(CallKind::Jsr, 0x8003, 0xFB),
+            frame(CallKind::Nmi, 0xC000, 0xF7),
+        ];
+
+        let stack = annotate(&frames, 0xF7, |addr| memory[usize::from(addr)]);
+        assert_eq!(stack.len(), 256);
+        assert_eq!(stack[0xF7].slot, StackSlot::Free);
+        assert_eq!(
+            stack[0xF8].slot,
+            StackSlot::Status {
+                kind: CallKind::Nmi,
+                frame: 1
+            }
+        );
+        assert_eq!(
+            stack[0xFA],
+            StackByte {
+                address: 0x01FA,
+                value: 0xC0,
+                slot: StackSlot::ReturnAddress {
+                    return_address: 0xC000,
+                    high: true,
+                    kind: CallKind::Nmi,
+                    frame: Some(1),
+                },
+            }
+        );
+        assert_eq!(stack[0xFB].slot, StackSlot::Pushed { frame: 0 });
+        assert_eq!(
+            stack[0xFC].slot,
+            StackSlot::ReturnAddress {
+                return_address: 0x8003,
+                high: false,
+                kind: CallKind::Jsr,
+                frame: Some(0),
+            }
+        );
+        assert_eq!(stack[0xFE].slot, StackSlot::Unknown);
+    }
+
+    #[test]
+    fn guesses_return_addresses() {
+        // A JSR at $9000 the debugger didn't see, and a return address it saw overwritten
+        let mut memory = [0u8; 0x10000];
+        memory[0x9000] = Opcode::JsrAbs as u8;
+        memory[0x01FE..0x0200].copy_from_slice(&[0x02, 0x90]);
+        memory[0x01FA..0x01FC].copy_from_slice(&[0x34, 0x12]);
+        let frames = [frame(CallKind::Jsr, 0x8003, 0xF9)];
+
+        let stack = annotate(&frames, 0xF9, |addr| memory[usize::from(addr)]);
+        assert_eq!(stack[0xFA].slot, StackSlot::Unknown);
+        assert_eq!(stack[0xFD].slot, StackSlot::Unknown);
+        assert_eq!(
+            stack[0xFF].slot,
+            StackSlot::ReturnAddress {
+                return_address: 0x9003,
+                high: true,
+                kind: CallKind::Jsr,
+                frame: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// Timing of the events of the PPU within the frame, for event viewers like the one of FCEUX,
+// which plot them on a 341x262 grid to debug the raster effects.
+// https://wiki.nesdev.com/w/index.php/PPU_rendering
+//
+// Once enabled, the events of the frame being rendered are collected, and kept as a whole when
+// it ends, at the start of the pre-render scanline. The scroll changes are the writes to PPUCTRL,
+// PPUSCROLL and PPUADDR, which all change the scroll registers.
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingEventKind {
+    /// Write to a register of the PPU, $2000-$2007 without the mirrors, or to OAMDMA ($4014)
+    RegisterWrite {
+        addr: u16,
+        data: u8,
+    },
+    SpriteZeroHit,
+    /// NMI taken by the CPU
+    Nmi,
+    /// The mapper asserted its IRQ
+    MapperIrq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingEvent {
+    /// From -1 (pre-render) to 260
+    pub scanline: i16,
+    /// From 0 to 340
+    pub dot: u16,
+    pub kind: TimingEventKind,
+}
+
+impl TimingEvent {
+    /// Whether it's a write to PPUCTRL, PPUSCROLL or PPUADDR
+    pub fn is_scroll_change(&self) -> bool {
+        matches!(
+            self.kind,
+            TimingEventKind::RegisterWrite {
+                addr: 0x2000 | 0x2005 | 0x2006,
+                ..
+            }
+        )
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Timing {
+    enabled: bool,
+    /// Events of the frame being rendered
+    events: Vec<TimingEvent>,
+    /// Events of the last complete frame
+    frame_events: Vec<TimingEvent>,
+    scanline: i16,
+    sprite_zero_hit: bool,
+    mapper_irq: bool,
+}
+
+impl Timing {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.events.clear();
+            self.frame_events.clear();
+        }
+    }
+
+    pub fn frame_events(&self) -> &[TimingEvent] {
+        &self.frame_events
+    }
+
+    pub fn record(&mut self, scanline: i16, dot: u16, kind: TimingEventKind) {
+        if self.enabled {
+            self.events.push(TimingEvent {
+                scanline,
+                dot,
+                kind,
+            });
+        }
+    }
+
+    /// Called on every dot, with the state of the sprite 0 hit flag and of the IRQ of the mapper,
+    /// whose rising edges are recorded
+    pub fn update(&mut self, scanline: i16, dot: u16, sprite_zero_hit: bool, mapper_irq: bool) {
+        if scanline == -1 && self.scanline != -1 {
+            core::mem::swap(&mut self.events, &mut self.frame_events);
+            self.events.clear();
+        }
+        self.scanline = scanline;
+
+        if sprite_zero_hit && !self.sprite_zero_hit {
+            self.record(scanline, dot, TimingEventKind::SpriteZeroHit);
+        }
+        if mapper_irq && !self.mapper_irq {
+            self.record(scanline, dot, TimingEventKind::MapperIrq);
+        }
+        self.sprite_zero_hit = sprite_zero_hit;
+        self.mapper_irq = mapper_irq;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_events_of_last_frame() {
+        let mut timing = Timing::default();
+        timing.record(0, 10, TimingEventKind::Nmi);
+        timing.set_enabled(true);
+
+        timing.update(-1, 0, false, false);
+        timing.update(30, 5, true, false);
+        timing.update(30, 6, true, false);
+        timing.record(
+            100,
+            20,
+            TimingEventKind::RegisterWrite {
+                addr: 0x2005,
+                data: 0,
+            },
+        );
+        timing.update(200, 260, false, true);
+        assert!(timing.frame_events().is_empty());
+
+        timing.update(-1, 0, false, true);
+        let events = timing.frame_events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            TimingEvent {
+                scanline: 30,
+                dot: 5,
+                kind: TimingEventKind::SpriteZeroHit,
+            }
+        );
+        assert!(events[1].is_scroll_change());
+        assert_eq!(events[2].kind, TimingEventKind::MapperIrq);
+    }
+}
@@ -0,0 +1,158 @@
+// Flight recorder of the last instructions run, for the issues that are hard to reproduce.
+//
+// It's a ring buffer of the state of the CPU before every instruction, filled while the emulation
+// runs normally and not only when stepping, so the path that led to a break can be exported once
+// it's hit. It's disabled until given a capacity, as it costs a bit on every instruction.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::fmt::Write as _;
+
+use super::labels::Labels;
+use crate::cpu::Cpu;
+
+/// State of the CPU before an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    /// PRG ROM bank of the instruction, when mapped from the ROM
+    pub bank: Option<u8>,
+    pub opcode: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    /// Status register
+    pub p: u8,
+    /// CPU cycles run since power-on
+    pub cycle: u64,
+}
+
+impl TraceEntry {
+    pub(crate) fn new(cpu: &Cpu, bank: Option<u8>, opcode: u8, cycle: u64) -> Self {
+        Self {
+            pc: cpu.pc,
+            bank,
+            opcode,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            sp: cpu.st,
+            p: cpu.status_register.bits(),
+            cycle,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Trace {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl Trace {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Keeps the last `capacity` entries, dropping the oldest ones above it
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+        self.entries.shrink_to_fit();
+    }
+
+    /// Entries from the oldest
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            if self.capacity == 0 {
+                return;
+            }
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Drops the last entry, for an instruction that didn't run after all
+    pub fn discard_last(&mut self) {
+        self.entries.pop_back();
+    }
+
+    /// Exports the last `count` entries as text, with one instruction per line, from the oldest,
+    /// and the names of the instructions in `labels`
+    pub fn to_text(&self, count: usize, labels: &Labels) -> String {
+        let mut text = String::from("# bank:pc opcode registers cycle\n");
+        let skipped = self.entries.len().saturating_sub(count);
+
+        for entry in self.entries.iter().skip(skipped) {
+            match entry.bank {
+                Some(bank) => {
+                    let _ = write!(text, "{:02X}:", bank);
+                }
+                None => text.push_str("--:"),
+            }
+            // Writing to a String can't fail
+            let _ = write!(
+                text,
+                "{:04X} {:02X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} CYC:{}",
+                entry.pc, entry.opcode, entry.a, entry.x, entry.y, entry.sp, entry.p, entry.cycle
+            );
+            if let Some(name) = labels.name(entry.bank, entry.pc) {
+                let _ = write!(text, " ; {}", name);
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: u16) -> TraceEntry {
+        let cpu = Cpu {
+            pc,
+            a: 0x42,
+            ..Cpu::default()
+        };
+        TraceEntry::new(&cpu, (pc >= 0x8000).then_some(1), 0xEA, u64::from(pc))
+    }
+
+    #[test]
+    fn keeps_last_entries() {
+        let mut trace = Trace::default();
+        trace.record(entry(0x8000));
+        assert_eq!(trace.entries().count(), 0);
+
+        trace.set_capacity(3);
+        for pc in 0x7FFE..0x8003 {
+            trace.record(entry(pc));
+        }
+        trace.discard_last();
+        let pcs: alloc::vec::Vec<_> = trace.entries().map(|e| e.pc).collect();
+        assert_eq!(pcs, [0x8000, 0x8001]);
+
+        let mut labels = Labels::default();
+        labels.set_name(Some(1), 0x8001, "start").unwrap();
+        assert_eq!(
+            trace.to_text(2, &labels),
+            "# bank:pc opcode registers cycle\n\
+             01:8000 EA A:42 X:00 Y:00 SP:00 P:00 CYC:32768\n\
+             01:8001 EA A:42 X:00 Y:00 SP:00 P:00 CYC:32769 ; start\n"
+        );
+        trace.set_capacity(1);
+        assert_eq!(trace.entries().next().map(|e| e.pc), Some(0x8001));
+    }
+}
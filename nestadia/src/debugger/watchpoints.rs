@@ -0,0 +1,60 @@
+// Watchpoints on ranges of the CPU address space.
+//
+// The CPU bus of the emulation reports every access to the debugger, the ones of the OAM and DMC
+// DMA included. An instruction runs all at once, so the emulation stops before the next one, the
+// break giving the address of the instruction that made the access. The accesses made through
+// `Emulator::read_memory` and `write_memory` aren't reported.
+
+use super::Expression;
+
+/// Accesses triggering a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Write of a value different from the one in memory. Writes to the registers always
+    /// change them.
+    Change,
+}
+
+/// Access of the CPU bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BusAccess {
+    Read,
+    /// Value in memory before the write, unless it's a register
+    Write(Option<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub id: u32,
+    /// First address of the range
+    pub start: u16,
+    /// Last address of the range, included
+    pub end: u16,
+    pub kind: WatchKind,
+    pub enabled: bool,
+    /// Breaks only when it's true, once the instruction that made the access ran
+    pub condition: Option<Expression>,
+    /// Number of times the emulation stopped on it
+    pub hits: u64,
+}
+
+impl Watchpoint {
+    pub(crate) fn watches(&self, addr: u16) -> bool {
+        self.enabled && (self.start..=self.end).contains(&addr)
+    }
+
+    /// Whether an access of `value` at `addr` triggers the watchpoint
+    pub(crate) fn is_triggered(&self, addr: u16, access: BusAccess, value: u8) -> bool {
+        if !self.watches(addr) {
+            return false;
+        }
+
+        match (self.kind, access) {
+            (WatchKind::Read, BusAccess::Read) | (WatchKind::Write, BusAccess::Write(_)) => true,
+            (WatchKind::Change, BusAccess::Write(previous)) => previous != Some(value),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// Emulation on a dedicated thread, controlled through a handle that never blocks.
+//
+// The thread runs the frames at the rate of the console. After a stall, the late frames are
+// emulated without being rendered to catch up, and beyond `MAX_CATCH_UP_FRAMES` they are
+// dropped. Commands are applied between frames, and every rendered frame is sent to the
+// subscribers along with its audio.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::ppu::PpuFrame;
+use crate::{Emulator, FastForward, FastForwardAudio, SaveStateError, NTSC_FRAME_RATE};
+
+/// Duration of a frame of the NTSC console
+const FRAME_TIME: Duration =
+    Duration::from_nanos(1_000_000_000 * NTSC_FRAME_RATE.1 as u64 / NTSC_FRAME_RATE.0 as u64);
+/// Most frames run at once to catch up after a stall
+const MAX_CATCH_UP_FRAMES: u32 = 30;
+
+/// Frame sent to the subscribers of an `EmulatorHandle`
+#[derive(Clone)]
+pub struct HandleFrame {
+    /// Palette indices of the pixels, see `frame_to_rgb`
+    pub video: Box<PpuFrame>,
+    /// Mono audio samples produced since the previous frame
+    pub audio: Vec<i16>,
+    /// Number of frames run since power-on, this one included
+    pub frame_number: u64,
+    /// Buttons of the 4 controllers read by the game, see `Emulator::latched_inputs`
+    pub inputs: Option<[u8; 4]>,
+}
+
+enum Command {
+    SetInput(usize, u8),
+    SaveState(Sender<Vec<u8>>),
+    LoadState(Vec<u8>, Sender<Result<(), SaveStateError>>),
+    Subscribe(Sender<HandleFrame>),
+    Pause,
+    Resume,
+    AdvanceFrame,
+    Run(Box<dyn FnOnce(&mut Emulator) + Send>),
+    Stop,
+}
+
+/// Handle of an emulator running on its own thread. Dropping it stops the thread.
+pub struct EmulatorHandle {
+    commands: Sender<Command>,
+    thread: Option<JoinHandle<Emulator>>,
+}
+
+impl EmulatorHandle {
+    /// Starts running the emulator on a new thread
+    pub fn spawn(emulator: Emulator) -> Self {
+        let (commands, receiver) = channel();
+        let thread = thread::spawn(move || run(emulator, receiver));
+
+        Self {
+            commands,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sets the buttons of a controller, from 0 to 3, for the next frames
+    pub fn set_input(&self, controller: usize, state: u8) {
+        self.send(Command::SetInput(controller, state));
+    }
+
+    /// Returns a channel receiving every frame rendered from now on
+    pub fn subscribe(&self) -> Receiver<HandleFrame> {
+        let (sender, receiver) = channel();
+        self.send(Command::Subscribe(sender));
+        receiver
+    }
+
+    /// Saves the state before the next frame. The state is received on the returned channel.
+    pub fn request_save_state(&self) -> Receiver<Vec<u8>> {
+        let (sender, receiver) = channel();
+        self.send(Command::SaveState(sender));
+        receiver
+    }
+
+    /// Loads a state before the next frame. The result is received on the returned channel.
+    pub fn load_state(&self, state: Vec<u8>) -> Receiver<Result<(), SaveStateError>> {
+        let (sender, receiver) = channel();
+        self.send(Command::LoadState(state, sender));
+        receiver
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(Command::Resume);
+    }
+
+    /// Runs a single frame while paused, which is sent to the subscribers
+    pub fn advance_frame(&self) {
+        self.send(Command::AdvanceFrame);
+    }
+
+    /// Runs `f` on the emulation thread between two frames, for anything without a command
+    pub fn run<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Emulator) + Send + 'static,
+    {
+        self.send(Command::Run(Box::new(f)));
+    }
+
+    /// Stops the thread and returns the emulator
+    pub fn stop(mut self) -> Emulator {
+        self.join().expect("The emulation thread panicked")
+    }
+
+    fn send(&self, command: Command) {
+        // The thread only stops with the handle, unless it panicked
+        let _ = self.commands.send(command);
+    }
+
+    fn join(&mut self) -> Option<Emulator> {
+        let thread = self.thread.take()?;
+        self.send(Command::Stop);
+        thread.join().ok()
+    }
+}
+
+impl Drop for EmulatorHandle {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+fn run(mut emulator: Emulator, commands: Receiver<Command>) -> Emulator {
+    let mut inputs = emulator.controller_states();
+    let mut subscribers: Vec<Sender<HandleFrame>> = Vec::new();
+    let mut next_frame_time = Instant::now() + FRAME_TIME;
+
+    loop {
+        let mut advance = false;
+
+        // While paused, waits for the commands instead of polling them
+        let mut command = if emulator.is_paused() {
+            commands.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            commands.try_recv()
+        };
+        loop {
+            match command {
+                Ok(Command::SetInput(controller, state)) => {
+                    if let Some(input) = inputs.get_mut(controller) {
+                        *input = state;
+                    }
+                }
+                Ok(Command::SaveState(sender)) => {
+                    let _ = sender.send(emulator.save_state());
+                }
+                Ok(Command::LoadState(state, sender)) => {
+                    let _ = sender.send(emulator.load_state(&state));
+                }
+                Ok(Command::Subscribe(sender)) => subscribers.push(sender),
+                Ok(Command::Pause) => emulator.pause(),
+                Ok(Command::Resume) => {
+                    if emulator.is_paused() {
+                        emulator.resume();
+                        next_frame_time = Instant::now() + FRAME_TIME;
+                    }
+                }
+                Ok(Command::AdvanceFrame) => advance = emulator.is_paused(),
+                Ok(Command::Run(f)) => f(&mut emulator),
+                Ok(Command::Stop) | Err(TryRecvError::Disconnected) => return emulator,
+                Err(TryRecvError::Empty) => break,
+            }
+            command = commands.try_recv();
+        }
+
+        let frame = if advance {
+            for (controller, state) in inputs.iter().enumerate() {
+                emulator.controllers.set_state(controller, *state);
+            }
+            emulator.advance_frame();
+            HandleFrame {
+                video: Box::new(*emulator.frame()),
+                audio: emulator.drain_audio_samples().collect(),
+                frame_number: emulator.frame_count(),
+                inputs: emulator.latched_inputs(),
+            }
+        } else if emulator.is_paused() {
+            continue;
+        } else {
+            // Frames we are late on are emulated without being rendered
+            let late_frames = (Instant::now()
+                .saturating_duration_since(next_frame_time)
+                .as_nanos()
+                / FRAME_TIME.as_nanos()) as u32;
+            let frame_skip = if late_frames > MAX_CATCH_UP_FRAMES {
+                next_frame_time = Instant::now();
+                0
+            } else {
+                late_frames
+            };
+            emulator.set_fast_forward(if frame_skip > 0 {
+                Some(FastForward::new(frame_skip, FastForwardAudio::Mute))
+            } else {
+                None
+            });
+
+            let output = emulator.run_frame(inputs);
+            let frame = HandleFrame {
+                video: Box::new(*output.video),
+                audio: output.audio.to_vec(),
+                frame_number: output.frame_number,
+                inputs: output.inputs,
+            };
+
+            let now = Instant::now();
+            if now < next_frame_time {
+                thread::sleep(next_frame_time - now);
+            }
+            next_frame_time += FRAME_TIME * (frame_skip + 1);
+            frame
+        };
+
+        subscribers.retain(|subscriber| subscriber.send(frame.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::counter_rom;
+
+    #[test]
+    fn runs_on_thread() {
+        let rom = counter_rom();
+
+        let handle = EmulatorHandle::spawn(Emulator::new(&rom, None).unwrap());
+        let frames = handle.subscribe();
+        handle.set_input(1, 0x81);
+
+        let first = frames.recv().unwrap();
+        let second = frames.recv().unwrap();
+        assert_eq!(second.frame_number, first.frame_number + 1);
+        assert!(!second.audio.is_empty());
+
+        handle.pause();
+        let state = handle.request_save_state().recv().unwrap();
+        while frames.try_recv().is_ok() {}
+        handle.advance_frame();
+        let advanced = frames.recv().unwrap();
+        assert_eq!(handle.load_state(state).recv().unwrap(), Ok(()));
+
+        let emulator = handle.stop();
+        assert_eq!(emulator.controller_states(), [0, 0x81, 0, 0]);
+        assert_eq!(emulator.frame_count(), advanced.frame_number - 1);
+    }
+}
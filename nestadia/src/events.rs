@@ -0,0 +1,11 @@
+// Events of the emulation that the frontends react to, like a server awarding points for an
+// achievement. They are queued by the emulator until the frontend drains them.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The conditions of an achievement were met, see `Emulator::add_achievement`
+    AchievementUnlocked { id: u32, points: u32 },
+    /// The debugger paused the emulation
+    #[cfg(feature = "debugger")]
+    Break(crate::Break),
+}
@@ -0,0 +1,28 @@
+/// Fast-forward settings, applied with `Emulator::set_fast_forward`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FastForward {
+    /// Number of frames emulated without being rendered after each rendered frame
+    pub frame_skip: u32,
+    pub audio: FastForwardAudio,
+}
+
+/// Audio produced while fast-forwarding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FastForwardAudio {
+    /// No samples are produced
+    Mute,
+    /// The audio of the skipped frames is squeezed into the rendered ones. It plays faster and
+    /// higher pitched, at the pace of the rendered frames.
+    Resample,
+}
+
+impl FastForward {
+    pub fn new(frame_skip: u32, audio: FastForwardAudio) -> Self {
+        Self { frame_skip, audio }
+    }
+
+    /// Number of emulated frames per rendered frame
+    pub fn speed(&self) -> u32 {
+        self.frame_skip.saturating_add(1)
+    }
+}
@@ -0,0 +1,162 @@
+// Hashes of the frames, to check a run against a known one without storing the images.
+// https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+//
+// A frame is hashed with XXH64 over its palette indices, so the hash doesn't depend on the
+// colors used to display it. The hashes are stable: they only change when the emulation does.
+
+use core::convert::TryInto as _;
+
+use crate::ppu::PpuFrame;
+
+const PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/// Hash of the palette indices of a frame
+pub fn frame_hash(frame: &PpuFrame) -> u64 {
+    xxh64(frame, 0)
+}
+
+/// Hash of a sequence of frames, which changes if any of them or their order does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameHasher {
+    hash: u64,
+    frames: u64,
+}
+
+impl FrameHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a frame to the sequence and returns its own hash
+    pub fn push(&mut self, frame: &PpuFrame) -> u64 {
+        let hash = frame_hash(frame);
+
+        let mut chained = [0u8; 16];
+        chained[..8].copy_from_slice(&self.hash.to_le_bytes());
+        chained[8..].copy_from_slice(&hash.to_le_bytes());
+        self.hash = xxh64(&chained, 0);
+        self.frames += 1;
+
+        hash
+    }
+
+    /// Hash of the frames added so far
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let read_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let read_u32 = |bytes: &[u8]| u64::from(u32::from_le_bytes(bytes[..4].try_into().unwrap()));
+
+    let mut stripes = data.chunks_exact(32);
+    let mut hash = if data.len() >= 32 {
+        let mut accumulators = [
+            seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2),
+            seed.wrapping_add(PRIME_2),
+            seed,
+            seed.wrapping_sub(PRIME_1),
+        ];
+        for stripe in &mut stripes {
+            for (accumulator, lane) in accumulators.iter_mut().zip(stripe.chunks_exact(8)) {
+                *accumulator = round(*accumulator, read_u64(lane));
+            }
+        }
+
+        let [a, b, c, d] = accumulators;
+        let hash = a
+            .rotate_left(1)
+            .wrapping_add(b.rotate_left(7))
+            .wrapping_add(c.rotate_left(12))
+            .wrapping_add(d.rotate_left(18));
+        accumulators.iter().fold(hash, |hash, accumulator| {
+            merge_accumulator(hash, *accumulator)
+        })
+    } else {
+        seed.wrapping_add(PRIME_5)
+    };
+
+    hash = hash.wrapping_add(data.len() as u64);
+
+    let mut remaining = stripes.remainder();
+    while remaining.len() >= 8 {
+        hash ^= round(0, read_u64(remaining));
+        hash = hash
+            .rotate_left(27)
+            .wrapping_mul(PRIME_1)
+            .wrapping_add(PRIME_4);
+        remaining = &remaining[8..];
+    }
+    if remaining.len() >= 4 {
+        hash ^= read_u32(remaining).wrapping_mul(PRIME_1);
+        hash = hash
+            .rotate_left(23)
+            .wrapping_mul(PRIME_2)
+            .wrapping_add(PRIME_3);
+        remaining = &remaining[4..];
+    }
+    for byte in remaining {
+        hash ^= u64::from(*byte).wrapping_mul(PRIME_5);
+        hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+    }
+
+    // Avalanche
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME_3);
+    hash ^ (hash >> 32)
+}
+
+fn round(accumulator: u64, lane: u64) -> u64 {
+    accumulator
+        .wrapping_add(lane.wrapping_mul(PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME_1)
+}
+
+fn merge_accumulator(hash: u64, accumulator: u64) -> u64 {
+    (hash ^ round(0, accumulator))
+        .wrapping_mul(PRIME_1)
+        .wrapping_add(PRIME_4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_frames() {
+        // Reference values of XXH64
+        assert_eq!(xxh64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxh64(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxh64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(
+            xxh64(b"Nobody inspects the spammish repetition", 0),
+            0xFBCE_A83C_8A37_8BF1
+        );
+
+        let mut frame = [0u8; 256 * 240];
+        let mut hasher = FrameHasher::new();
+        let first = hasher.push(&frame);
+        frame[1000] = 0x21;
+        assert_ne!(hasher.push(&frame), first);
+        assert_eq!(hasher.frames(), 2);
+
+        // The order of the frames matters
+        let mut reversed = FrameHasher::new();
+        reversed.push(&frame);
+        frame[1000] = 0;
+        reversed.push(&frame);
+        assert_ne!(reversed.hash(), hasher.hash());
+    }
+}
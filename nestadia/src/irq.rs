@@ -0,0 +1,50 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Devices that can pull the CPU's /IRQ line
+    pub struct IrqSource: u8 {
+        const APU_FRAME_COUNTER = (1 << 0);
+        const APU_DMC = (1 << 1);
+        const MAPPER = (1 << 2);
+    }
+}
+
+impl_stateful_bitflags!(IrqSource);
+
+impl Default for IrqSource {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// The CPU's /IRQ line, shared by every device that can request an interrupt.
+/// It is level-triggered: the line stays asserted until every source has been acknowledged.
+#[derive(Default, Clone, Copy)]
+pub struct IrqLine {
+    sources: IrqSource,
+}
+
+impl_stateful!(IrqLine { sources });
+
+impl IrqLine {
+    pub fn assert(&mut self, source: IrqSource) {
+        self.sources.insert(source);
+    }
+
+    pub fn acknowledge(&mut self, source: IrqSource) {
+        self.sources.remove(source);
+    }
+
+    /// Follows the level of a source driving its output continuously
+    pub fn set(&mut self, source: IrqSource, asserted: bool) {
+        self.sources.set(source, asserted);
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        !self.sources.is_empty()
+    }
+
+    pub fn is_asserted_by(&self, source: IrqSource) -> bool {
+        self.sources.intersects(source)
+    }
+}
@@ -1,178 +1,2428 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 #[macro_use]
 mod bus;
+#[macro_use]
+mod state;
 
+mod achievements;
+mod apu;
+mod audio;
+mod builder;
 mod cartridge;
+mod cheats;
+mod controllers;
 mod cpu;
+#[cfg(feature = "debugger")]
+mod debugger;
+#[cfg(feature = "thread")]
+mod emulator_handle;
+mod events;
+mod fast_forward;
+mod frame_hash;
+mod irq;
+mod movie;
+mod overlay;
+mod overscan;
+#[cfg(feature = "screenshot")]
+mod png;
+mod power_on_ram;
 mod ppu;
+mod ram_search;
+mod rewind;
 mod rgb_palette;
+mod save_data;
+mod state_diff;
+#[cfg(test)]
+mod test_roms;
+mod video_recorder;
 
 pub use rgb_palette::RGB_PALETTE;
 
-pub use cartridge::RomParserError;
+pub use achievements::{
+    Achievement, AchievementError, Comparison, Condition, ConditionKind, MemorySize, Operand,
+};
+pub use apu::{Apu, ApuRegisterLog, ApuRegisterWrite, AudioChannel};
+pub use audio::{DynamicRateControl, ResamplerKind, DEFAULT_SAMPLE_RATE};
+pub use builder::{BuildError, EmulatorBuilder, ExecutionMode, Region};
+pub use cartridge::{
+    BoardInfo, CartridgeInfo, CartridgeReadTarget, ConsoleType, HeaderOverride, LoadedImage,
+    Mapper, MapperFactory, MapperRegistry, Mirroring, NsfHeader, RomDatabase, RomFormat,
+    RomParserError, RomSection, SoundChips, DEFAULT_PRG_RAM_SIZE,
+};
+pub use cheats::{Cheat, CheatError};
+pub use controllers::{ArkanoidPaddle, PortDevice, Turbo};
 pub use cpu::Cpu;
+#[cfg(feature = "debugger")]
+pub use debugger::{
+    Break, BreakReason, Breakpoint, CallKind, Debugger, Expression, ExpressionContext,
+    ExpressionError, InstructionClass, Label, LabelError, MapperEvent, ProfileEntry, StackByte,
+    StackFrame, StackSlot, TimingEvent, TimingEventKind, TraceEntry, Variable, Watch, WatchKind,
+    Watchpoint, COVERAGE_BITMAP_SIZE,
+};
+#[cfg(feature = "thread")]
+pub use emulator_handle::{EmulatorHandle, HandleFrame};
+pub use events::Event;
+pub use fast_forward::{FastForward, FastForwardAudio};
+pub use frame_hash::{frame_hash, FrameHasher};
+pub use movie::{Fm2Error, Movie, MovieError, MovieFrame, MoviePlayer, MovieRecorder, MovieStart};
+pub use overlay::{Overlay, CHAR_HEIGHT, CHAR_WIDTH};
+pub use overscan::Overscan;
+#[cfg(feature = "screenshot")]
+pub use png::encode_png;
+pub use power_on_ram::{PowerOnRam, DEFAULT_POWER_ON_RAM_SEED};
 pub use ppu::Ppu;
+pub use ram_search::{RamSearch, SearchCondition};
+pub use rewind::{Rewind, DEFAULT_REWIND_INTERVAL, DEFAULT_REWIND_MEMORY_BUDGET};
+pub use save_data::{SaveDataError, SaveDataKind};
+pub use state::{SaveStateError, StateReader, StateWriter, Stateful};
+pub use state_diff::{ByteRangeDiff, RegisterDiff, StateComponent, StateDiff};
+pub use video_recorder::{VideoChunk, VideoFormat, VideoRecorder, NTSC_FRAME_RATE};
 
+use crate::achievements::Achievements;
+use crate::audio::AudioOutput;
 use crate::cartridge::Cartridge;
+use crate::controllers::Controllers;
+use crate::irq::IrqLine;
 use crate::ppu::PpuFrame;
 
 pub const RAM_SIZE: u16 = 0x0800;
 
+/// Frequency of the NTSC CPU, in Hz
+pub const CPU_FREQUENCY: u32 = 1_789_773;
+
+/// Number of CPU cycles the CPU is stalled for when the DMC fetches a sample byte
+const DMC_DMA_STALL_CYCLES: u8 = 4;
+
+/// Number of CPU cycles after power/reset during which the PPU ignores writes to $2000, $2001, $2005 and $2006
+pub const PPU_WARMUP_CYCLES: u32 = 29658;
+
+/// Version of the layout of the save states, increased when the state of a component changes
+const SAVE_STATE_VERSION: u8 = 10;
+
 pub struct Emulator {
     // Cartridge is shared by CPU (PRG) and PPU (CHR)
     cartridge: Cartridge,
 
     // == CPU == //
     cpu: Cpu,
-    controller1: u8,
-    controller2: u8,
-    controller_state: bool,
-    controller1_snapshot: u8,
-    controller2_snapshot: u8,
+    irq_line: IrqLine,
+    controllers: Controllers,
     ram: [u8; RAM_SIZE as usize],
 
+    // == APU == //
+    apu: Apu,
+    audio: AudioOutput,
+
     // == PPU == //
     ppu: Ppu,
     name_tables: [u8; 1024 * 4], // VRAM
 
     // Emulator internal state
     clock_count: u8,
+    frame_count: u64,
+    ppu_warmup_cycles: u32,
+    fast_forward: Option<FastForward>,
+    frames_to_skip: u32,
+    paused: bool,
+    power_on_ram: PowerOnRam,
+    palette: [[u8; 3]; 64],
+    overscan: Overscan,
+    cheats: alloc::vec::Vec<Cheat>,
+    achievements: Achievements,
+    events: alloc::vec::Vec<Event>,
+    frame_audio: alloc::vec::Vec<i16>, // Samples returned by `run_frame`
+    #[cfg(feature = "debugger")]
+    debugger: Debugger,
+    step_hook: Option<StepHook>,
+}
+
+// The audio output only holds samples, and is left as is when loading a state
+impl_stateful_sections!(Emulator {
+    cartridge,
+    cpu,
+    irq_line,
+    controllers,
+    ram,
+    apu,
+    ppu,
+    name_tables,
+    clock_count,
+    frame_count,
+    ppu_warmup_cycles,
+});
+
+/// What the emulator does once the step hook returns, see `Emulator::set_step_hook`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepAction {
+    Continue,
+    /// Pauses before the instruction, which runs once resumed
+    Pause,
+    /// Removes the hook and continues
+    Remove,
+}
+
+/// Callback of `Emulator::set_step_hook`
+pub type StepHook = alloc::boxed::Box<dyn FnMut(&mut Emulator) -> StepAction + Send>;
+
+/// How far the `run_*` functions of the emulator advanced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RunProgress {
+    pub cpu_cycles: u64,
+    /// Frames completed, skipped ones included
+    pub frames: u64,
+    /// The target was reached. False when the cycle limit ran out first, or while paused.
+    pub completed: bool,
+}
+
+impl RunProgress {
+    /// Adds the progress of a run that followed this one
+    #[cfg(feature = "debugger")]
+    fn add(&mut self, next: RunProgress) {
+        self.cpu_cycles += next.cpu_cycles;
+        self.frames += next.frames;
+        self.completed = next.completed;
+    }
+}
+
+/// Output of a frame run by `Emulator::run_frame`
+pub struct FrameOutput<'a> {
+    /// Palette indices of the pixels, see `frame_to_rgb`
+    pub video: &'a PpuFrame,
+    /// Mono audio samples produced since the previous call
+    pub audio: &'a [i16],
+    /// Number of frames run since power-on, this one included
+    pub frame_number: u64,
+    /// Buttons of the 4 controllers read by the game, see `Emulator::latched_inputs`
+    pub inputs: Option<[u8; 4]>,
 }
 
 impl Emulator {
+    /// Creates an emulator running an iNES/NES 2.0 ROM. NSF files are recognized and played
+    /// with a built-in player, see `loaded_image`.
     pub fn new(rom: &[u8], save_data: Option<&[u8]>) -> Result<Self, RomParserError> {
-        let mut emulator = Self {
-            cartridge: Cartridge::load(rom, save_data)?,
+        let mut emulator = Self::with_cartridge(Cartridge::load(rom, save_data)?);
+        emulator.power_cycle();
+
+        Ok(emulator)
+    }
+
+    /// Same as `new`, with the ROM received in chunks of any size
+    pub fn new_from_chunks<I>(chunks: I, save_data: Option<&[u8]>) -> Result<Self, RomParserError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let options = cartridge::LoadOptions::default();
+        let cartridge = Cartridge::load_from_chunks(chunks, save_data, &options)?;
+        let mut emulator = Self::with_cartridge(cartridge);
+        emulator.power_cycle();
+
+        Ok(emulator)
+    }
+
+    /// Same as `new`, with the ROM read from `reader`, like a file, a few KB at a time
+    #[cfg(feature = "std")]
+    pub fn new_from_reader<R: std::io::Read>(
+        mut reader: R,
+        save_data: Option<&[u8]>,
+    ) -> Result<Self, RomParserError> {
+        const READ_SIZE: usize = 8192;
+
+        let mut error = None;
+        let chunks = core::iter::from_fn(|| {
+            let mut chunk = alloc::vec![0u8; READ_SIZE];
+            loop {
+                match reader.read(&mut chunk) {
+                    Ok(0) => return None,
+                    Ok(len) => {
+                        chunk.truncate(len);
+                        return Some(chunk);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        error = Some(e.kind());
+                        return None;
+                    }
+                }
+            }
+        });
+        let emulator = Self::new_from_chunks(chunks, save_data);
+
+        // The loader only saw the ROM end early
+        match error {
+            Some(kind) => Err(RomParserError::Io(kind)),
+            None => emulator,
+        }
+    }
+
+    /// Configures an emulator before loading its ROM, for the settings of the ROM loader or
+    /// the ones to apply before power-on
+    pub fn builder<'a>() -> EmulatorBuilder<'a> {
+        EmulatorBuilder::new()
+    }
+
+    fn with_cartridge(cartridge: Cartridge) -> Self {
+        Self {
+            cartridge,
 
             cpu: Default::default(),
-            controller1: 0,
-            controller2: 0,
-            controller_state: false,
-            controller1_snapshot: 0,
-            controller2_snapshot: 0,
+            irq_line: Default::default(),
+            controllers: Default::default(),
             ram: [0u8; RAM_SIZE as usize],
 
+            apu: Apu::new(),
+            audio: Default::default(),
+
             ppu: Ppu::new(),
             name_tables: [0u8; 1024 * 4],
 
             clock_count: 0,
-        };
+            frame_count: 0,
+            ppu_warmup_cycles: PPU_WARMUP_CYCLES,
+            fast_forward: None,
+            frames_to_skip: 0,
+            paused: false,
+            power_on_ram: PowerOnRam::default(),
+            palette: RGB_PALETTE,
+            overscan: Overscan::NONE,
+            cheats: alloc::vec::Vec::new(),
+            achievements: Default::default(),
+            events: alloc::vec::Vec::new(),
+            frame_audio: alloc::vec::Vec::new(),
+            #[cfg(feature = "debugger")]
+            debugger: Default::default(),
+            step_hook: None,
+        }
+    }
+
+    /// Creates an emulator playing a NES Sound Format file with a built-in player.
+    /// The starting song of the file is selected.
+    pub fn new_nsf(nsf: &[u8]) -> Result<Self, RomParserError> {
+        let mut emulator = Self::with_cartridge(Cartridge::load_nsf(nsf)?);
+        emulator.power_cycle();
 
-        emulator.reset();
+        Ok(emulator)
+    }
+
+    /// Creates an emulator running a Famicom Disk System disk image (.fds), with the
+    /// first disk side inserted. `bios` is the 8KB BIOS of the RAM adapter.
+    pub fn new_fds(bios: &[u8], disk: &[u8]) -> Result<Self, RomParserError> {
+        let mut emulator = Self::with_cartridge(Cartridge::load_fds(bios, disk)?);
+        emulator.power_cycle();
 
         Ok(emulator)
     }
 
+    /// Runs a PPU cycle and returns the frame when it's complete. While paused, nothing runs and
+    /// the last frame is returned, so the frontends waiting for a frame don't hang.
     pub fn clock(&mut self) -> Option<&PpuFrame> {
+        if self.paused {
+            return Some(self.ppu.frame());
+        }
+
+        self.step()
+    }
+
+    /// Last frame returned by `clock`, or the one being rendered
+    pub fn frame(&self) -> &PpuFrame {
+        self.ppu.frame()
+    }
+
+    /// Draws on the last frame returned by `clock`, before displaying it
+    pub fn overlay(&mut self) -> Overlay<'_> {
+        Overlay::new(self.ppu.frame_mut())
+    }
+
+    /// Hash of the last frame, see `frame_hash`
+    pub fn frame_hash(&self) -> u64 {
+        frame_hash(self.ppu.frame())
+    }
+
+    /// Number of frames run since power-on, skipped ones included
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Runs `cycles` CPU cycles
+    pub fn run_cycles(&mut self, cycles: u64) -> RunProgress {
+        let mut progress = self.run_until(cycles, |_| false);
+        progress.completed = progress.cpu_cycles == cycles;
+        progress
+    }
+
+    /// Runs until `frames` frames are completed, skipped ones included. The emulator ends on a
+    /// frame boundary.
+    pub fn run_frames(&mut self, frames: u64) -> RunProgress {
+        let target = self.frame_count + frames;
+        self.run_until(u64::MAX, |emulator| emulator.frame_count >= target)
+    }
+
+    /// Runs until the PPU starts rendering `scanline`, from -1 (pre-render) to 260, or at most
+    /// `max_cycles` CPU cycles
+    pub fn run_until_scanline(&mut self, scanline: i16, max_cycles: u64) -> RunProgress {
+        let mut previous = self.ppu.scanline();
+        self.run_until(max_cycles, |emulator| {
+            let current = emulator.ppu.scanline();
+            let started = current == scanline && previous != scanline;
+            previous = current;
+            started
+        })
+    }
+
+    /// Runs until the CPU is about to execute the instruction at `pc`, or at most `max_cycles`
+    /// CPU cycles. At least one cycle is run, so the emulator moves on if it's already there.
+    pub fn run_until_pc(&mut self, pc: u16, max_cycles: u64) -> RunProgress {
+        self.run_until(max_cycles, |emulator| {
+            emulator.is_at_instruction() && emulator.cpu.pc == pc
+        })
+    }
+
+    /// Calls `hook` before every instruction, or before taking an interrupt instead, with the
+    /// emulator about to run it. It drives the emulation from an external debugger or a script
+    /// without running the clock loop itself: the hook can inspect and change the emulator, run
+    /// it further, in which case it isn't called again until it returns, or pause it. It replaces
+    /// the previous hook.
+    pub fn set_step_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut Emulator) -> StepAction + Send + 'static,
+    {
+        self.step_hook = Some(alloc::boxed::Box::new(hook));
+    }
+
+    /// Removes the step hook, which costs nothing once removed
+    pub fn remove_step_hook(&mut self) {
+        self.step_hook = None;
+    }
+
+    /// Calls the step hook, out of the emulator while it runs
+    fn call_step_hook(&mut self) {
+        if let Some(mut hook) = self.step_hook.take() {
+            let action = hook(self);
+            match action {
+                StepAction::Continue => {}
+                StepAction::Pause => self.paused = true,
+                StepAction::Remove => return,
+            }
+            // Unless the hook set another one
+            if self.step_hook.is_none() {
+                self.step_hook = Some(hook);
+            }
+        }
+    }
+
+    /// Whether the next CPU cycle starts an instruction, or takes an interrupt
+    fn is_at_instruction(&self) -> bool {
+        self.clock_count.is_multiple_of(3) && self.cpu.cycles == 0
+    }
+
+    /// Steps the emulator until `done` returns true after a step, or `max_cycles` CPU cycles
+    /// were run. Nothing runs while paused, and a break of the debugger stops it early.
+    fn run_until<F>(&mut self, max_cycles: u64, mut done: F) -> RunProgress
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        let mut progress = RunProgress::default();
+        if self.paused {
+            return progress;
+        }
+
+        let start_frame = self.frame_count;
+        while !progress.completed && !self.paused && progress.cpu_cycles < max_cycles {
+            if self.clock_count.is_multiple_of(3) {
+                progress.cpu_cycles += 1;
+            }
+            self.step();
+            progress.completed = done(self);
+        }
+
+        progress.frames = self.frame_count - start_frame;
+        progress
+    }
+
+    /// Sets the buttons of the 4 controllers, and runs until the next frame is returned. Skipped
+    /// frames are run through when fast-forwarding, and nothing runs while paused.
+    pub fn run_frame(&mut self, inputs: [u8; 4]) -> FrameOutput<'_> {
+        for (controller, state) in inputs.iter().enumerate() {
+            self.controllers.set_state(controller, *state);
+        }
+
+        while self.clock().is_none() {}
+
+        self.frame_audio.clear();
+        self.frame_audio.extend(self.audio.drain());
+        FrameOutput {
+            video: self.ppu.frame(),
+            audio: &self.frame_audio,
+            frame_number: self.frame_count,
+            inputs: self.controllers.frame_latched(),
+        }
+    }
+
+    fn step(&mut self) -> Option<&PpuFrame> {
         // Make PPU clock first
         let mut ppu_bus = borrow_ppu_bus!(self);
         self.ppu.clock(&mut ppu_bus);
 
+        let frame_skipped = if self.ppu.ready_frame().is_some() {
+            self.apu.end_frame();
+            self.apply_frozen_cheats();
+            self.evaluate_achievements();
+            self.controllers.end_frame();
+            self.frame_count += 1;
+            #[cfg(feature = "debugger")]
+            self.update_watches();
+            self.end_frame()
+        } else {
+            false
+        };
+
         // CPU clock is 3 times slower
-        if self.clock_count % 3 == 0 {
+        if self.clock_count.is_multiple_of(3) {
             self.clock_count = 0;
 
+            #[cfg(feature = "debugger")]
+            let caller = self.cpu.pc;
+
             if self.cpu.cycles == 0 && self.ppu.take_vblank_nmi_set_state() {
                 // NMI interrupt
-                let mut cpu_bus = borrow_cpu_bus!(self);
+                let mut cpu_bus = borrow_watched_cpu_bus!(self);
                 self.cpu.nmi(&mut cpu_bus);
                 self.cpu.clock(&mut cpu_bus);
-            } else if self.cpu.cycles == 0 && self.cartridge.take_irq_set_state() {
-                // IRQ interrupt
-                let mut cpu_bus = borrow_cpu_bus!(self);
+                #[cfg(feature = "debugger")]
+                self.on_interrupt(debugger::CallKind::Nmi, caller);
+            } else if self.cpu.cycles == 0 && self.irq_line.is_asserted() {
+                // IRQ interrupt, ignored while the I flag is set
+                let mut cpu_bus = borrow_watched_cpu_bus!(self);
                 self.cpu.irq(&mut cpu_bus);
+                #[cfg(feature = "debugger")]
+                let taken = self.cpu.cycles != 0;
                 self.cpu.clock(&mut cpu_bus);
+                #[cfg(feature = "debugger")]
+                if taken {
+                    self.on_interrupt(debugger::CallKind::Irq, caller);
+                }
             } else {
-                let mut cpu_bus = borrow_cpu_bus!(self);
+                let mut cpu_bus = borrow_watched_cpu_bus!(self);
                 self.cpu.clock(&mut cpu_bus);
             }
+
+            #[cfg(feature = "debugger")]
+            self.debugger.cpu_clock();
+
+            self.apu.clock(&mut self.irq_line);
+            self.cartridge.cpu_clock();
+            self.cartridge.update_irq_line(&mut self.irq_line);
+            #[cfg(feature = "debugger")]
+            self.debugger
+                .on_mapper_irq(self.irq_line.is_asserted_by(irq::IrqSource::MAPPER));
+            self.cartridge.clock_audio();
+
+            // Expansion audio is mixed after the APU
+            if !self.is_audio_skipped() {
+                self.audio
+                    .push(self.apu.output() + self.cartridge.audio_output());
+            }
+
+            // The DMC memory reader steals CPU cycles to fetch its samples
+            if let Some(addr) = self.apu.dmc_dma_address() {
+                let mut cpu_bus = borrow_watched_cpu_bus!(self);
+                let data = self.cpu.dma_read(&mut cpu_bus, addr, DMC_DMA_STALL_CYCLES);
+                self.apu.dmc_dma_fill(data, &mut self.irq_line);
+            }
         }
 
         self.clock_count = self.clock_count.wrapping_add(1);
 
+        #[cfg(feature = "debugger")]
+        if self.debugger.is_recording_timing() {
+            let sprite_zero_hit = self.ppu.peek_register(0x2002) & 0x40 != 0;
+            let mapper_irq = self.irq_line.is_asserted_by(irq::IrqSource::MAPPER);
+            self.debugger.update_timing(
+                self.ppu.scanline(),
+                self.ppu.dot(),
+                sprite_zero_hit,
+                mapper_irq,
+            );
+        }
+
+        // The next CPU cycle starts an instruction
+        #[cfg(feature = "debugger")]
+        if self.is_at_instruction() {
+            let opcode = self.peek_memory(self.cpu.pc);
+            let cartridge = &self.cartridge;
+            self.debugger
+                .on_instruction(&self.cpu, opcode, |addr| cartridge.get_prg_bank(addr));
+
+            let reason = self.debugger.break_reason(self.cpu.pc, self);
+            if let Some(brk) = self.debugger.before_instruction(self.cpu.pc, reason) {
+                self.paused = true;
+                self.events.push(Event::Break(brk));
+                self.update_watches();
+            }
+        }
+
+        if self.step_hook.is_some() && self.is_at_instruction() {
+            self.call_step_hook();
+        }
+
         // returns PPU frame if any
-        self.ppu.ready_frame()
+        if frame_skipped {
+            None
+        } else {
+            self.ppu.ready_frame()
+        }
+    }
+
+    /// Tells the debugger the CPU took an interrupt, which came before the instruction at `caller`
+    #[cfg(feature = "debugger")]
+    fn on_interrupt(&mut self, kind: debugger::CallKind, caller: u16) {
+        if kind == debugger::CallKind::Nmi {
+            let kind = debugger::TimingEventKind::Nmi;
+            self.debugger
+                .record_timing_event(self.ppu.scanline(), self.ppu.dot(), kind);
+        }
+
+        let cartridge = &self.cartridge;
+        self.debugger
+            .on_interrupt(kind, caller, self.cpu.pc, self.cpu.st, |addr| {
+                cartridge.get_prg_bank(addr)
+            });
+    }
+
+    /// Decides whether the next frame is rendered. Returns whether the frame that just ended was
+    /// skipped.
+    fn end_frame(&mut self) -> bool {
+        let skipped = self.ppu.is_output_skipped();
+        if !skipped {
+            self.frames_to_skip = self.fast_forward.map_or(0, |ff| ff.frame_skip);
+        }
+
+        let skip_next = self.frames_to_skip > 0;
+        if skip_next {
+            self.frames_to_skip -= 1;
+        }
+        self.ppu.set_output_skipped(skip_next);
+
+        skipped
+    }
+
+    fn is_audio_skipped(&self) -> bool {
+        matches!(
+            self.fast_forward,
+            Some(FastForward {
+                audio: FastForwardAudio::Mute,
+                ..
+            })
+        )
+    }
+
+    /// Runs the emulator faster than real time: only one frame out of `frame_skip + 1` is
+    /// rendered and returned by `clock`, the others are still fully emulated. The frontend
+    /// decides how fast to call `clock`. `None` goes back to normal speed after the current
+    /// frame. The settings can be changed on every frame.
+    pub fn set_fast_forward(&mut self, fast_forward: Option<FastForward>) {
+        let audio_speed = match fast_forward {
+            Some(ff) if ff.audio == FastForwardAudio::Resample => ff.speed(),
+            _ => 1,
+        };
+        self.audio.set_speed(audio_speed);
+
+        self.fast_forward = fast_forward;
+        self.frames_to_skip = self
+            .frames_to_skip
+            .min(fast_forward.map_or(0, |ff| ff.frame_skip));
+    }
+
+    pub fn fast_forward(&self) -> Option<FastForward> {
+        self.fast_forward
+    }
+
+    /// Pauses the emulation on a frame boundary, running the rest of the current frame if needed.
+    /// The inputs set while paused are seen from the first cycle of the next frame.
+    pub fn pause(&mut self) {
+        while self.ppu.ready_frame().is_none() {
+            self.step();
+        }
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Runs the emulation until the next frame boundary and pauses there. When paused, this runs
+    /// exactly one frame, which is rendered even while fast-forwarding.
+    pub fn advance_frame(&mut self) -> &PpuFrame {
+        if self.ppu.ready_frame().is_some() {
+            self.ppu.set_output_skipped(false);
+        }
+
+        self.paused = false;
+        self.step();
+        self.pause();
+        self.ppu.frame()
     }
 
     pub fn set_controller1(&mut self, state: u8) {
-        self.controller1 = state;
+        self.controllers.set_state(0, state);
     }
 
     pub fn set_controller2(&mut self, state: u8) {
-        self.controller2 = state;
+        self.controllers.set_state(1, state);
+    }
+
+    /// Sets the buttons of the 3rd controller, read through the Four Score
+    pub fn set_controller3(&mut self, state: u8) {
+        self.controllers.set_state(2, state);
+    }
+
+    /// Sets the buttons of the 4th controller, read through the Four Score
+    pub fn set_controller4(&mut self, state: u8) {
+        self.controllers.set_state(3, state);
+    }
+
+    /// Buttons of the 4 controllers
+    pub fn controller_states(&self) -> [u8; 4] {
+        [0, 1, 2, 3].map(|controller| self.controllers.state(controller))
+    }
+
+    /// Buttons of the 4 controllers as the game latched them during the last frame, turbo
+    /// applied, for the input displays. `None` if the game didn't read the controllers.
+    pub fn latched_inputs(&self) -> Option<[u8; 4]> {
+        self.controllers.frame_latched()
+    }
+
+    /// Plugs the Four Score adapter, for up to 4 players, in the controller ports
+    pub fn set_four_score(&mut self, four_score: bool) {
+        self.controllers.set_four_score(four_score);
+    }
+
+    pub fn is_four_score_plugged(&self) -> bool {
+        self.controllers.four_score()
+    }
+
+    /// Sets the turbo buttons of a controller, from 0 to 3
+    pub fn set_turbo(&mut self, controller: usize, turbo: Turbo) {
+        self.controllers.set_turbo(controller, turbo);
+    }
+
+    pub fn turbo(&self, controller: usize) -> Turbo {
+        self.controllers.turbo(controller)
+    }
+
+    /// Plugs a device in a controller port, 0 for $4016 and 1 for $4017, in place of the
+    /// controllers. Arkanoid reads its paddle on port 1, and the Power Pad games their mat.
+    pub fn set_port_device(&mut self, port: usize, device: PortDevice) {
+        self.controllers.set_device(port, device);
+    }
+
+    pub fn port_device(&self, port: usize) -> PortDevice {
+        self.controllers.device(port)
+    }
+
+    /// Sets the knob and the button of the Arkanoid paddle plugged in a port
+    pub fn set_arkanoid_paddle(&mut self, port: usize, position: u8, fire: bool) {
+        self.controllers
+            .set_paddle(port, ArkanoidPaddle::new(position, fire));
+    }
+
+    pub fn arkanoid_paddle(&self, port: usize) -> ArkanoidPaddle {
+        self.controllers.paddle(port)
+    }
+
+    /// Sets the buttons held on the Power Pad plugged in a port, bit 0 for button 1 up to bit 11
+    /// for button 12
+    pub fn set_power_pad(&mut self, port: usize, buttons: u16) {
+        self.controllers.set_power_pad(port, buttons);
+    }
+
+    pub fn power_pad(&self, port: usize) -> u16 {
+        self.controllers.power_pad(port)
+    }
+
+    /// Blows in the microphone of the second controller of the Famicom, checked by a few games
+    /// like The Legend of Zelda to defeat the Pols Voice
+    pub fn set_microphone(&mut self, active: bool) {
+        self.controllers.set_microphone(active);
+    }
+
+    pub fn is_microphone_active(&self) -> bool {
+        self.controllers.microphone()
+    }
+
+    /// Presses the reset button: the CPU jumps to its reset vector, the audio is silenced and
+    /// the PPU registers are cleared. The RAM and the rest of the state are kept, as some games
+    /// check them to tell a reset from a power-on.
+    pub fn soft_reset(&mut self) {
+        let mut cpu_bus = borrow_cpu_bus!(self);
+        self.cpu.soft_reset(&mut cpu_bus);
+        self.apu.soft_reset(&mut self.irq_line);
+        self.ppu.soft_reset();
+        self.ppu.start_warmup(self.ppu_warmup_cycles);
     }
 
-    pub fn reset(&mut self) {
+    /// Turns the console off and on: the CPU RAM is filled with the power-on content (see
+    /// `set_power_on_ram`), and the CPU, PPU, APU and mapper are reinitialized. The cartridge
+    /// only keeps its PRG RAM and CHR RAM.
+    pub fn power_cycle(&mut self) {
+        self.cartridge.power_on();
+        self.power_on_ram.fill(&mut self.ram);
+        self.name_tables.fill(0);
+        self.controllers.power_on();
+
         let mut cpu_bus = borrow_cpu_bus!(self);
         self.cpu.reset(&mut cpu_bus);
+        self.apu.reset();
+        self.irq_line = Default::default();
         self.ppu.reset();
+        self.ppu.start_warmup(self.ppu_warmup_cycles);
         self.clock_count = 0;
+        self.frame_count = 0;
+        #[cfg(feature = "debugger")]
+        self.debugger.power_on();
     }
 
-    pub fn get_save_data(&self) -> Option<&[u8]> {
-        self.cartridge.get_save_data()
+    /// Adds a cheat and returns its index. Enabled cheats are written right away.
+    pub fn add_cheat(&mut self, cheat: Cheat) -> usize {
+        if cheat.enabled {
+            self.write_cheat(cheat);
+        }
+
+        self.cheats.push(cheat);
+        self.cheats.len() - 1
     }
 
-    #[cfg(feature = "debugger")]
-    #[allow(unused_variables)] // FIXME
-    pub fn disassemble(
-        &self,
-        start: u16,
-        end: u16,
-    ) -> alloc::vec::Vec<(Option<u8>, u16, alloc::string::String)> {
-        crate::cpu::disassembler::disassemble(&self.cartridge, 0x4020)
+    /// Removes a cheat. The value it wrote stays until the game changes it.
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        if index < self.cheats.len() {
+            Some(self.cheats.remove(index))
+        } else {
+            None
+        }
     }
 
-    #[cfg(feature = "debugger")]
-    pub fn mem_dump(&mut self, start: u16, end: u16) -> alloc::vec::Vec<u8> {
-        let mut data = alloc::vec::Vec::new();
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            let newly_enabled = enabled && !cheat.enabled;
+            cheat.enabled = enabled;
 
-        for addr in start..=end {
-            let mut bus = borrow_cpu_bus!(self);
-            data.push(self.cpu.mem_dump(&mut bus, addr));
+            if newly_enabled {
+                let cheat = *cheat;
+                self.write_cheat(cheat);
+            }
         }
+    }
 
-        data
+    pub fn clear_cheats(&mut self) {
+        self.cheats.clear();
     }
 
-    #[cfg(feature = "debugger")]
-    pub fn cpu(&self) -> &Cpu {
-        &self.cpu
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
     }
-}
 
-pub fn frame_to_rgb(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 3]) {
-    for i in 0..frame.len() {
-        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
-        output[i * 3] = f[0];
-        output[i * 3 + 1] = f[1];
-        output[i * 3 + 2] = f[2];
+    fn write_cheat(&mut self, cheat: Cheat) {
+        match cheat.address {
+            0x0000..=0x1FFF => self.ram[usize::from(cheat.address & 0x07FF)] = cheat.value,
+            _ => self.cartridge.write_prg_mem(cheat.address, cheat.value),
+        }
     }
-}
 
-pub fn frame_to_rgba(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 4]) {
-    for i in 0..frame.len() {
-        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
-        output[i * 4] = f[0]; // R
-        output[i * 4 + 1] = f[1]; // G
-        output[i * 4 + 2] = f[2]; // B
+    fn apply_frozen_cheats(&mut self) {
+        for i in 0..self.cheats.len() {
+            let cheat = self.cheats[i];
+            if cheat.enabled && cheat.freeze {
+                self.write_cheat(cheat);
+            }
+        }
+    }
 
-        // Alpha is always 0xff because it's opaque
-        output[i * 4 + 3] = 0xff; // A
+    /// Adds an achievement, evaluated at the end of every frame until it's unlocked. It
+    /// replaces the achievement with the same id.
+    pub fn add_achievement(&mut self, achievement: Achievement) {
+        self.achievements.add(achievement);
     }
-}
 
-pub fn frame_to_argb(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 4]) {
-    for i in 0..frame.len() {
-        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
-        output[i * 4] = f[2]; // B
-        output[i * 4 + 1] = f[1]; // G
-        output[i * 4 + 2] = f[0]; // R
+    pub fn remove_achievement(&mut self, id: u32) -> Option<Achievement> {
+        self.achievements.remove(id)
+    }
 
-        // Alpha is always 0xff because it's opaque
-        output[i * 4 + 3] = 0xff; // A
+    pub fn clear_achievements(&mut self) {
+        self.achievements.clear();
+    }
+
+    pub fn achievements(&self) -> impl Iterator<Item = &Achievement> {
+        self.achievements.iter()
+    }
+
+    pub fn is_achievement_unlocked(&self, id: u32) -> bool {
+        self.achievements.is_unlocked(id)
+    }
+
+    /// Returns the events queued since the last call, oldest first. Frontends reacting to
+    /// events should call it every frame, as the queue isn't bounded.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain(..)
+    }
+
+    fn evaluate_achievements(&mut self) {
+        let ram = &self.ram;
+        let cartridge = &self.cartridge;
+        let read = |addr: u16| match addr {
+            0x0000..=0x1FFF => ram[usize::from(addr & (RAM_SIZE - 1))],
+            _ => cartridge.peek_prg_mem(addr),
+        };
+
+        self.achievements.evaluate(read, &mut self.events);
+    }
+
+    /// Fills the CPU RAM with `power_on_ram`, as it would be at power-on, right away and on every
+    /// `power_cycle`. Movies starting at power-on must be played with the same setting they were
+    /// recorded with.
+    pub fn set_power_on_ram(&mut self, power_on_ram: PowerOnRam) {
+        self.power_on_ram = power_on_ram;
+        power_on_ram.fill(&mut self.ram);
+    }
+
+    pub fn power_on_ram(&self) -> PowerOnRam {
+        self.power_on_ram
+    }
+
+    /// Disabling the sprite limit draws all the sprites of a scanline instead of only the 8 the
+    /// console can, which removes the flickering of some games. The sprite overflow flag is
+    /// still set for the games relying on it.
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.ppu.set_sprite_limit(enabled);
+    }
+
+    pub fn sprite_limit(&self) -> bool {
+        self.ppu.sprite_limit()
+    }
+
+    /// Sets the colors of the 64 palette indices, used by `frame_rgba` and the screenshots.
+    /// `RGB_PALETTE` is the default.
+    pub fn set_palette(&mut self, palette: [[u8; 3]; 64]) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> &[[u8; 3]; 64] {
+        &self.palette
+    }
+
+    /// Sets the edges cropped by `frame_rgba` and the screenshots. It is ignored if it crops
+    /// the whole frame.
+    pub fn set_overscan(&mut self, overscan: Overscan) {
+        if overscan.is_valid() {
+            self.overscan = overscan;
+        } else {
+            log::warn!("Ignored overscan {:?} cropping the whole frame", overscan);
+        }
+    }
+
+    pub fn overscan(&self) -> Overscan {
+        self.overscan
+    }
+
+    /// Writes the last frame as RGBA pixels in `output`, with the palette and without the
+    /// overscan of the emulator. Returns the width and height of the image.
+    pub fn frame_rgba(&self, output: &mut alloc::vec::Vec<u8>) -> (usize, usize) {
+        output.clear();
+        for line in self.overscan.visible_lines(self.ppu.frame()) {
+            for pixel in line {
+                output.extend_from_slice(&self.palette[usize::from(pixel & 0x3F)]);
+                output.push(0xFF);
+            }
+        }
+
+        self.overscan.visible_size()
+    }
+
+    /// Sets the length of the PPU warm-up period, in CPU cycles, applied on the next reset.
+    /// Use 0 to disable it.
+    pub fn set_ppu_warmup_cycles(&mut self, cycles: u32) {
+        self.ppu_warmup_cycles = cycles;
+    }
+
+    pub fn ppu_warmup_cycles(&self) -> u32 {
+        self.ppu_warmup_cycles
+    }
+
+    /// Sets the sample rate of the audio samples produced by the emulator, in Hz.
+    /// Samples that were not drained yet are discarded.
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.audio.set_sample_rate(sample_rate);
+    }
+
+    pub fn audio_sample_rate(&self) -> u32 {
+        self.audio.sample_rate()
+    }
+
+    /// Selects the interpolation used to convert the audio to the output sample rate
+    pub fn set_audio_resampler(&mut self, kind: ResamplerKind) {
+        self.audio.set_resampler_kind(kind);
+    }
+
+    pub fn audio_resampler(&self) -> ResamplerKind {
+        self.audio.resampler_kind()
+    }
+
+    /// Enables or disables the emulation of the filters between the console and the TV.
+    /// They are enabled by default.
+    pub fn set_audio_filters_enabled(&mut self, enabled: bool) {
+        self.audio.set_filters_enabled(enabled);
+    }
+
+    pub fn audio_filters_enabled(&self) -> bool {
+        self.audio.filters_enabled()
+    }
+
+    /// Enables dynamic rate control of the audio output, or disables it with `None`.
+    /// Once enabled, the frontend must report its buffer level with `report_audio_buffer_level`.
+    pub fn set_audio_rate_control(&mut self, rate_control: Option<DynamicRateControl>) {
+        self.audio.set_rate_control(rate_control);
+    }
+
+    pub fn audio_rate_control(&self) -> Option<DynamicRateControl> {
+        self.audio.rate_control()
+    }
+
+    /// Reports how many samples are queued in the frontend's audio buffer out of its `capacity`,
+    /// so the audio output rate can be nudged to keep it half full. Frontends pulling samples
+    /// from an audio callback can report `pending_audio_samples()` against their target latency.
+    /// Does nothing unless dynamic rate control is enabled.
+    pub fn report_audio_buffer_level(&mut self, fill: usize, capacity: usize) {
+        self.audio.report_buffer_level(fill, capacity);
+    }
+
+    /// Number of mono audio samples waiting to be drained
+    pub fn pending_audio_samples(&self) -> usize {
+        self.audio.len()
+    }
+
+    /// Removes all the pending mono audio samples, in the order they were produced.
+    /// The emulator keeps at most half a second of audio, older samples are dropped.
+    pub fn drain_audio_samples(&mut self) -> impl Iterator<Item = i16> + '_ {
+        self.audio.drain()
+    }
+
+    /// Fills `output` with pending mono audio samples and returns how many were written.
+    /// Useful for audio callbacks that must not allocate.
+    pub fn read_audio_samples(&mut self, output: &mut [i16]) -> usize {
+        self.audio.read(output)
+    }
+
+    /// Starts recording the mixed audio output of the APU. An ongoing recording is discarded.
+    pub fn start_audio_recording(&mut self) {
+        self.audio.start_recording();
+    }
+
+    pub fn is_recording_audio(&self) -> bool {
+        self.audio.is_recording()
+    }
+
+    /// Stops the audio recording and returns the content of a mono 16 bits WAV file,
+    /// or `None` if nothing was being recorded.
+    pub fn stop_audio_recording(&mut self) -> Option<alloc::vec::Vec<u8>> {
+        self.audio.stop_recording()
+    }
+
+    /// Starts logging the writes to the registers of the APU and of the expansion audio chip.
+    /// An ongoing log is discarded.
+    pub fn start_apu_register_log(&mut self) {
+        self.apu.start_register_log();
+    }
+
+    /// Stops logging the writes to the audio registers and returns the log, if any
+    pub fn stop_apu_register_log(&mut self) -> Option<ApuRegisterLog> {
+        self.apu.stop_register_log()
+    }
+
+    /// Mutes or unmutes a channel of the APU or of the expansion audio chip of the cartridge
+    pub fn set_audio_channel_muted(&mut self, channel: AudioChannel, muted: bool) {
+        match channel {
+            AudioChannel::Expansion(channel) => {
+                self.cartridge.set_audio_channel_muted(channel, muted)
+            }
+            _ => self.apu.set_channel_muted(channel, muted),
+        }
+    }
+
+    pub fn is_audio_channel_muted(&self, channel: AudioChannel) -> bool {
+        match channel {
+            AudioChannel::Expansion(channel) => self.cartridge.is_audio_channel_muted(channel),
+            _ => self.apu.is_channel_muted(channel),
+        }
+    }
+
+    /// Mutes every channel except `channel`
+    pub fn solo_audio_channel(&mut self, channel: AudioChannel) {
+        for other in self.audio_channels() {
+            self.set_audio_channel_muted(other, other != channel);
+        }
+    }
+
+    /// Unmutes every channel
+    pub fn unmute_audio_channels(&mut self) {
+        for channel in self.audio_channels() {
+            self.set_audio_channel_muted(channel, false);
+        }
+    }
+
+    /// Every channel of the APU and of the expansion audio chip of the cartridge, if any
+    pub fn audio_channels(&self) -> alloc::vec::Vec<AudioChannel> {
+        let mut channels = alloc::vec![
+            AudioChannel::Pulse1,
+            AudioChannel::Pulse2,
+            AudioChannel::Triangle,
+            AudioChannel::Noise,
+            AudioChannel::Dmc,
+        ];
+        channels.extend((0..self.cartridge.audio_channel_count()).map(AudioChannel::Expansion));
+        channels
+    }
+
+    /// What was loaded: a ROM, a NSF file or a FDS disk image
+    pub fn loaded_image(&self) -> LoadedImage {
+        self.cartridge.loaded_image()
+    }
+
+    /// Description of the loaded cartridge, to show it or to key per-game settings
+    pub fn cartridge_info(&self) -> &CartridgeInfo {
+        self.cartridge.info()
+    }
+
+    /// Header of the NSF file being played, if any
+    pub fn nsf_header(&self) -> Option<&NsfHeader> {
+        self.cartridge.nsf_header()
+    }
+
+    /// Starts playing another song of the NSF file. `track` is 0-based.
+    pub fn select_nsf_track(&mut self, track: u8) {
+        self.cartridge.select_nsf_track(track);
+        self.power_cycle();
+    }
+
+    /// Number of disk sides of the Famicom Disk System image, 0 for other cartridges
+    pub fn fds_disk_side_count(&self) -> u8 {
+        self.cartridge.disk_side_count()
+    }
+
+    /// Disk side currently in the drive, if any
+    pub fn fds_inserted_disk_side(&self) -> Option<u8> {
+        self.cartridge.inserted_disk_side()
+    }
+
+    /// Switches the disk side in the drive. The drive reports no disk for about a second
+    /// first, so the game notices the disk was changed.
+    pub fn insert_fds_disk_side(&mut self, side: u8) {
+        self.cartridge.insert_disk_side(Some(side));
+    }
+
+    pub fn eject_fds_disk(&mut self) {
+        self.cartridge.insert_disk_side(None);
+    }
+
+    /// Size of the PRG RAM of the cartridge, battery-backed or not
+    pub fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    /// Battery-backed memory of the cartridge, in a container identifying the ROM
+    pub fn get_save_data(&self) -> Option<alloc::vec::Vec<u8>> {
+        self.cartridge.get_save_data()
+    }
+
+    /// Whether the battery-backed memory was written since the last call to
+    /// `mark_save_data_saved`. Frontends can poll it to write the save file only when needed.
+    pub fn is_save_data_dirty(&self) -> bool {
+        self.cartridge.is_save_data_dirty()
+    }
+
+    /// Tells that the save data from `get_save_data` was written out, which clears
+    /// `is_save_data_dirty` until the game writes to its battery-backed memory again
+    pub fn mark_save_data_saved(&mut self) {
+        self.cartridge.mark_save_data_saved();
+    }
+
+    /// Checks that save data comes from the loaded ROM
+    pub fn check_save_data(&self, data: &[u8]) -> Result<(), SaveDataError> {
+        self.cartridge.check_save_data(data)
+    }
+
+    /// Snapshot of the whole machine, in a container identifying the ROM
+    pub fn save_state(&self) -> alloc::vec::Vec<u8> {
+        let mut state = StateWriter::new();
+        SAVE_STATE_VERSION.save_state(&mut state);
+        Stateful::save_state(self, &mut state);
+
+        save_data::pack(
+            SaveDataKind::SaveState,
+            self.cartridge.origin(),
+            &state.into_inner(),
+        )
+    }
+
+    /// Restores a snapshot taken by `save_state` on the same ROM. The emulator is left as is if
+    /// it fails.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let payload = self.unpack_state(data)?;
+        let mut state = StateReader::new(&payload[1..]);
+
+        let mut backup = StateWriter::new();
+        Stateful::save_state(self, &mut backup);
+
+        let result = Stateful::load_state(self, &mut state).and_then(|_| {
+            if state.remaining() == 0 {
+                Ok(())
+            } else {
+                Err(SaveStateError::InvalidValue)
+            }
+        });
+
+        if result.is_err() {
+            // Restoring the state that was just saved can't fail
+            let _ = Stateful::load_state(self, &mut StateReader::new(&backup.into_inner()));
+        }
+
+        result
+    }
+
+    /// Compares two states taken by `save_state` on the same ROM, component by component
+    pub fn diff_states(&self, first: &[u8], second: &[u8]) -> Result<StateDiff, SaveStateError> {
+        let snapshot = |data| {
+            let payload = self.unpack_state(data)?;
+            state_diff::StateSnapshot::decode(&mut StateReader::new(&payload[1..]))
+        };
+
+        Ok(StateDiff::new(&snapshot(first)?, &snapshot(second)?))
+    }
+
+    /// Payload of a save state taken on the same ROM, starting with its version, which must be
+    /// the current one
+    fn unpack_state<'a>(
+        &self,
+        data: &'a [u8],
+    ) -> Result<alloc::borrow::Cow<'a, [u8]>, SaveStateError> {
+        let payload = save_data::unpack(data, SaveDataKind::SaveState, self.cartridge.origin())
+            .map_err(SaveStateError::InvalidContainer)?;
+
+        match payload.first() {
+            Some(&SAVE_STATE_VERSION) => Ok(payload),
+            Some(&version) => Err(SaveStateError::UnsupportedVersion(version)),
+            None => Err(SaveStateError::UnexpectedEnd),
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    #[allow(unused_variables)] // FIXME
+    pub fn disassemble(
+        &self,
+        start: u16,
+        end: u16,
+    ) -> alloc::vec::Vec<(Option<u8>, u16, alloc::string::String)> {
+        let mut disassembly = crate::cpu::disassembler::disassemble(&self.cartridge, 0x4020);
+        self.label_disassembly(&mut disassembly);
+        disassembly
+    }
+
+    /// Disassembles `count` instructions from `start`, for the views showing a few of them
+    #[cfg(feature = "debugger")]
+    pub fn disassemble_instructions(
+        &self,
+        start: u16,
+        count: usize,
+    ) -> alloc::vec::Vec<(Option<u8>, u16, alloc::string::String)> {
+        let mut disassembly =
+            crate::cpu::disassembler::disassemble_instructions(&self.cartridge, start, count);
+        self.label_disassembly(&mut disassembly);
+        disassembly
+    }
+
+    /// Appends "; name: comment" to the instructions with a label
+    #[cfg(feature = "debugger")]
+    fn label_disassembly(&self, disassembly: &mut [(Option<u8>, u16, alloc::string::String)]) {
+        for (bank, addr, disas) in disassembly {
+            if let Some(label) = self.debugger.label(*bank, *addr) {
+                disas.push_str(" ; ");
+                disas.push_str(&label.name);
+                if !label.name.is_empty() && !label.comment.is_empty() {
+                    disas.push_str(": ");
+                }
+                disas.push_str(&label.comment);
+            }
+        }
+    }
+
+    /// Reads an address of the CPU address space like the CPU, with the side effects of reading
+    /// the registers
+    pub fn read_memory(&mut self, addr: u16) -> u8 {
+        let mut cpu_bus = borrow_cpu_bus!(self);
+        cpu_bus.read(addr)
+    }
+
+    /// Reads an address of the CPU address space without changing anything, like the registers
+    /// that are acknowledged or advance when read
+    pub fn peek_memory(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.ram[usize::from(addr & (RAM_SIZE - 1))],
+            0x2000..=0x3FFF => self.ppu.peek_register(addr),
+            0x4015 => self.apu.peek_status(&self.irq_line),
+            0x4016 => self.controllers.peek(0),
+            0x4017 => self.controllers.peek(1),
+            0x4000..=0x401F => 0, // Write-only or disabled
+            0x4020..=0xFFFF => self.cartridge.peek_prg_mem(addr),
+        }
+    }
+
+    /// Writes an address of the CPU address space like the CPU, registers included
+    pub fn write_memory(&mut self, addr: u16, data: u8) {
+        let mut cpu_bus = borrow_cpu_bus!(self);
+        cpu_bus.write(addr, data);
+    }
+
+    /// Fills `buffer` with the CPU address space from `start`, wrapping after $FFFF, without
+    /// changing anything like `peek_memory`. Meant for memory viewers.
+    pub fn peek_memory_range(&self, start: u16, buffer: &mut [u8]) {
+        for (i, data) in buffer.iter_mut().enumerate() {
+            *data = self.peek_memory(start.wrapping_add(i as u16));
+        }
+    }
+
+    /// Writes `data` to the CPU address space from `start`, wrapping after $FFFF, like
+    /// `write_memory`
+    pub fn write_memory_range(&mut self, start: u16, data: &[u8]) {
+        for (i, data) in data.iter().enumerate() {
+            self.write_memory(start.wrapping_add(i as u16), *data);
+        }
+    }
+
+    /// Freezes an address of the CPU RAM or the PRG RAM to `value`, written right away and at the
+    /// end of every frame. It's a frozen cheat, replacing the ones already on the address, and
+    /// its index is returned.
+    pub fn freeze_memory(&mut self, addr: u16, value: u8) -> Result<usize, CheatError> {
+        let cheat = Cheat::new(addr, value, true)?;
+        self.unfreeze_memory(addr);
+        Ok(self.add_cheat(cheat))
+    }
+
+    /// Removes the frozen cheats on an address, returning whether there was any. The value they
+    /// wrote stays until the game changes it.
+    pub fn unfreeze_memory(&mut self, addr: u16) -> bool {
+        let count = self.cheats.len();
+        self.cheats.retain(|c| !(c.freeze && c.address == addr));
+        self.cheats.len() != count
+    }
+
+    /// Value an address is frozen to, by an enabled cheat
+    pub fn frozen_value(&self, addr: u16) -> Option<u8> {
+        self.cheats
+            .iter()
+            .rev()
+            .find(|c| c.enabled && c.freeze && c.address == addr)
+            .map(|c| c.value)
+    }
+
+    /// Reads an address of the PPU address space ($0000-$3FFF). The pattern tables are read
+    /// through the mapper, which a few boards latch their banks on.
+    pub fn read_vram(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        match addr {
+            0x0000..=0x1FFF => ppu_bus.read_chr_mem(addr),
+            0x2000..=0x3EFF => ppu_bus.read_name_tables(addr),
+            _ => self.ppu.read_palette(addr),
+        }
+    }
+
+    /// Writes an address of the PPU address space ($0000-$3FFF)
+    pub fn write_vram(&mut self, addr: u16, data: u8) {
+        let addr = addr & 0x3FFF;
+        let mut ppu_bus = borrow_ppu_bus!(self);
+        match addr {
+            0x0000..=0x1FFF => ppu_bus.write_chr_mem(addr, data),
+            0x2000..=0x3EFF => ppu_bus.write_name_tables(addr, data),
+            _ => self.ppu.write_palette(addr, data),
+        }
+    }
+
+    /// Object Attribute Memory of the PPU, holding the 64 sprites
+    pub fn oam(&self) -> &[u8; 256] {
+        self.ppu.oam()
+    }
+
+    pub fn write_oam(&mut self, addr: u8, data: u8) {
+        self.ppu.write_oam(addr, data);
+    }
+
+    /// Encodes the last frame to a PNG file
+    #[cfg(feature = "screenshot")]
+    pub fn screenshot(&self) -> alloc::vec::Vec<u8> {
+        png::encode_png_with(self.ppu.frame(), &self.palette, self.overscan)
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn mem_dump(&mut self, start: u16, end: u16) -> alloc::vec::Vec<u8> {
+        let mut data = alloc::vec::Vec::new();
+
+        for addr in start..=end {
+            let mut bus = borrow_cpu_bus!(self);
+            data.push(self.cpu.mem_dump(&mut bus, addr));
+        }
+
+        data
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    #[cfg(feature = "debugger")]
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Breakpoints and the other tools of the debugger. A break pauses the emulator before the
+    /// instruction, see `resume`.
+    #[cfg(feature = "debugger")]
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// Percentage of the PRG ROM run while the code coverage of the debugger was enabled
+    #[cfg(feature = "debugger")]
+    pub fn code_coverage(&self) -> f64 {
+        let size = self.cartridge.info().prg_rom_size.max(1);
+        // Mappers with banks smaller than 16KB may count a byte twice
+        (self.debugger.covered_rom_bytes() as f64 * 100.0 / size as f64).min(100.0)
+    }
+
+    /// Evaluates the watches of the debugger, which is done at the end of every frame and whenever
+    /// the emulation pauses
+    #[cfg(feature = "debugger")]
+    pub fn update_watches(&mut self) {
+        let values = self.debugger.watch_values(self);
+        self.debugger.set_watch_values(values);
+    }
+
+    /// The stack page, $0100-$01FF, with what its bytes likely are according to the calls seen by
+    /// the debugger: return addresses, status pushed by interrupts, or bytes pushed by routines
+    #[cfg(feature = "debugger")]
+    pub fn stack_view(&self) -> alloc::vec::Vec<StackByte> {
+        debugger::annotate_stack(self.debugger.call_stack(), self.cpu.st, |addr| {
+            self.peek_memory(addr)
+        })
+    }
+
+    /// Runs the next instruction and pauses before the one after it. When an interrupt is taken
+    /// instead, this stops on the first instruction of its handler.
+    #[cfg(feature = "debugger")]
+    pub fn step_into(&mut self) -> RunProgress {
+        self.step_until(u64::MAX, |_| true)
+    }
+
+    /// Runs the next instruction and pauses before the one after it, running the subroutine it
+    /// calls with JSR to completion. An interrupt taken before it also runs to completion. Stops
+    /// early after `max_cycles` CPU cycles, or on a break of the debugger.
+    #[cfg(feature = "debugger")]
+    pub fn step_over(&mut self, max_cycles: u64) -> RunProgress {
+        let mut progress = RunProgress::default();
+
+        loop {
+            let stack = self.cpu.st;
+            let jsr = self.peek_memory(self.cpu.pc) == cpu::Opcode::JsrAbs as u8;
+            let interrupts = self.debugger.interrupts();
+            let last_break = self.debugger.last_break();
+
+            progress.add(self.step_until(max_cycles - progress.cpu_cycles, |_| true));
+            let interrupted = self.debugger.interrupts() != interrupts;
+            let broke = self.debugger.last_break() != last_break;
+            if progress.completed && !broke && (jsr || interrupted) {
+                // Until the stack pointer is back where it was
+                progress.add(
+                    self.step_until(max_cycles - progress.cpu_cycles, |emulator| {
+                        emulator.cpu.st.wrapping_sub(stack) as i8 >= 0
+                    }),
+                );
+            }
+
+            // Once the interrupt returns, the instruction is still to run, unless the debugger
+            // broke on the way
+            let broke = self.debugger.last_break() != last_break;
+            if broke || !(progress.completed && interrupted) {
+                return progress;
+            }
+        }
+    }
+
+    /// Runs until the current subroutine or interrupt handler returns, and pauses before the
+    /// instruction it returns to. Stops early after `max_cycles` CPU cycles, or on a break of the
+    /// debugger.
+    ///
+    /// The routine is considered returned once the stack pointer rises above its return
+    /// address, so this stops early on routines pulling more than they pushed.
+    #[cfg(feature = "debugger")]
+    pub fn step_out(&mut self, max_cycles: u64) -> RunProgress {
+        let stack = self.cpu.st;
+        self.step_until(max_cycles, |emulator| {
+            emulator.cpu.st.wrapping_sub(stack) as i8 >= 2
+        })
+    }
+
+    /// Runs until `done` returns true before an instruction, even while paused, and pauses there
+    #[cfg(feature = "debugger")]
+    fn step_until<F>(&mut self, max_cycles: u64, mut done: F) -> RunProgress
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        self.paused = false;
+        let progress = self.run_until(max_cycles, |emulator| {
+            emulator.is_at_instruction() && done(emulator)
+        });
+        self.paused = true;
+        self.update_watches();
+        progress
+    }
+}
+
+pub fn frame_to_rgb(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 3]) {
+    for i in 0..frame.len() {
+        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
+        output[i * 3] = f[0];
+        output[i * 3 + 1] = f[1];
+        output[i * 3 + 2] = f[2];
+    }
+}
+
+pub fn frame_to_rgba(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 4]) {
+    for i in 0..frame.len() {
+        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
+        output[i * 4] = f[0]; // R
+        output[i * 4 + 1] = f[1]; // G
+        output[i * 4 + 2] = f[2]; // B
+
+        // Alpha is always 0xff because it's opaque
+        output[i * 4 + 3] = 0xff; // A
+    }
+}
+
+pub fn frame_to_argb(frame: &PpuFrame, output: &mut [u8; 256 * 240 * 4]) {
+    for i in 0..frame.len() {
+        let f = RGB_PALETTE[(frame[i] & 0x3f) as usize];
+        output[i * 4] = f[2]; // B
+        output[i * 4 + 1] = f[1]; // G
+        output[i * 4 + 2] = f[0]; // R
+
+        // Alpha is always 0xff because it's opaque
+        output[i * 4 + 3] = 0xff; // A
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::test_roms::{counter_rom, offscreen_sprites_rom};
+
+    fn run_frames(emulator: &mut Emulator, frames: usize) {
+        for _ in 0..frames {
+            while emulator.clock().is_none() {}
+        }
+    }
+
+    #[test]
+    fn calls_step_hook_between_instructions() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let mut pcs = Vec::new();
+        emulator.set_step_hook(move |emulator| {
+            pcs.push(emulator.cpu.pc);
+            match pcs.len() {
+                4 => StepAction::Pause,
+                5 => {
+                    assert_eq!(pcs, [0x8000, 0x8002, 0x8000, 0x8002, 0x8000]);
+                    StepAction::Remove
+                }
+                _ => StepAction::Continue,
+            }
+        });
+
+        run_frames(&mut emulator, 1);
+        assert!(emulator.is_paused());
+        assert_eq!(emulator.cpu.pc, 0x8002);
+        assert_eq!(emulator.ram[0], 2);
+
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        assert!(emulator.step_hook.is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn loads_rom_from_reader() {
+        use std::io::Read;
+
+        // Reads a few bytes at a time, and is interrupted once
+        struct SlowReader<'a> {
+            data: &'a [u8],
+            interrupted: bool,
+        }
+
+        impl Read for SlowReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if !self.interrupted {
+                    self.interrupted = true;
+                    return Err(std::io::ErrorKind::Interrupted.into());
+                }
+                let len = buf.len().min(self.data.len()).min(1000);
+                buf[..len].copy_from_slice(&self.data[..len]);
+                self.data = &self.data[len..];
+                Ok(len)
+            }
+        }
+
+        let rom = counter_rom();
+        let reader = SlowReader {
+            data: &rom,
+            interrupted: false,
+        };
+        let mut emulator = Emulator::new_from_reader(reader, None).unwrap();
+        let mut expected = Emulator::new(&rom, None).unwrap();
+        run_frames(&mut emulator, 1);
+        run_frames(&mut expected, 1);
+        assert_eq!(emulator.save_state(), expected.save_state());
+
+        let short_rom = &rom[..rom.len() - 1];
+        assert!(matches!(
+            Emulator::new_from_reader(short_rom, None),
+            Err(RomParserError::TooShort { .. })
+        ));
+
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let failing = rom[..100].chain(FailingReader);
+        assert_eq!(
+            Emulator::new_from_reader(failing, None).err(),
+            Some(RomParserError::Io(std::io::ErrorKind::BrokenPipe))
+        );
+    }
+
+    #[test]
+    fn loaded_state_runs_identically() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        run_frames(&mut emulator, 2);
+        let state = emulator.save_state();
+
+        run_frames(&mut emulator, 3);
+        let expected = emulator.save_state();
+        assert_ne!(state, expected);
+
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.save_state(), state);
+        run_frames(&mut emulator, 3);
+        assert_eq!(emulator.save_state(), expected);
+    }
+
+    /// Number of PPU cycles until the next frame returned by `clock`
+    fn cycles_to_frame(emulator: &mut Emulator) -> u32 {
+        let mut cycles = 1;
+        while emulator.clock().is_none() {
+            cycles += 1;
+        }
+        cycles
+    }
+
+    #[test]
+    fn fast_forward_skips_frames() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        run_frames(&mut emulator, 1);
+        let frame_cycles = cycles_to_frame(&mut emulator);
+
+        emulator.set_fast_forward(Some(FastForward::new(2, FastForwardAudio::Mute)));
+        emulator.drain_audio_samples().for_each(drop);
+        assert_eq!(cycles_to_frame(&mut emulator), frame_cycles);
+        assert_eq!(cycles_to_frame(&mut emulator), 3 * frame_cycles);
+        assert_eq!(emulator.pending_audio_samples(), 0);
+
+        // The audio of 3 frames takes the time of one
+        emulator.set_fast_forward(Some(FastForward::new(2, FastForwardAudio::Resample)));
+        run_frames(&mut emulator, 1);
+        emulator.drain_audio_samples().for_each(drop);
+        run_frames(&mut emulator, 10);
+        let samples = emulator.pending_audio_samples() as u32;
+        let expected = DEFAULT_SAMPLE_RATE * 10 / 60;
+        assert!((expected - 100..expected + 100).contains(&samples));
+
+        emulator.set_fast_forward(None);
+        run_frames(&mut emulator, 1);
+        assert_eq!(cycles_to_frame(&mut emulator), frame_cycles);
+    }
+
+    #[test]
+    fn runs_frame_with_inputs() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        assert_eq!(emulator.run_frame([0x80, 0, 0, 0x01]).frame_number, 1);
+        assert_eq!(emulator.controller_states(), [0x80, 0, 0, 0x01]);
+
+        let output = emulator.run_frame([0; 4]);
+        assert_eq!(output.frame_number, 2);
+        // The game never reads the controllers
+        assert_eq!(output.inputs, None);
+        let expected = (DEFAULT_SAMPLE_RATE / 60) as usize;
+        assert!((expected - 10..expected + 10).contains(&output.audio.len()));
+        assert_eq!(emulator.pending_audio_samples(), 0);
+    }
+
+    #[test]
+    fn runs_to_targets() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        assert_eq!(emulator.run_frames(2).frames, 2);
+        let progress = emulator.run_frames(1);
+        assert_eq!(progress.frames, 1);
+        assert!(progress.completed);
+        assert!((29_780..=29_781).contains(&progress.cpu_cycles));
+
+        let progress = emulator.run_cycles(100);
+        assert_eq!(progress.cpu_cycles, 100);
+        assert!(progress.completed);
+
+        let progress = emulator.run_until_scanline(241, u64::MAX);
+        assert!(progress.completed);
+        assert_eq!(emulator.ppu.scanline(), 241);
+
+        // JMP $8000
+        let progress = emulator.run_until_pc(0x8002, 100);
+        assert!(progress.completed);
+        assert!(progress.cpu_cycles <= 8);
+        assert_eq!(emulator.cpu.pc, 0x8002);
+
+        let progress = emulator.run_until_pc(0x9000, 100);
+        assert_eq!(progress.cpu_cycles, 100);
+        assert!(!progress.completed);
+
+        emulator.pause();
+        assert_eq!(emulator.run_cycles(10), RunProgress::default());
+    }
+
+    #[test]
+    fn pauses_on_frame_boundary() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        run_frames(&mut emulator, 1);
+        let frame_cycles = cycles_to_frame(&mut emulator);
+        for _ in 0..1000 {
+            emulator.clock();
+        }
+
+        // The rest of the frame is run before pausing
+        emulator.pause();
+        let paused_state = emulator.save_state();
+        assert!(emulator.is_paused());
+        assert_eq!(cycles_to_frame(&mut emulator), 1);
+        assert_eq!(emulator.save_state(), paused_state);
+
+        emulator.advance_frame();
+        assert!(emulator.is_paused());
+        let advanced_state = emulator.save_state();
+
+        emulator.load_state(&paused_state).unwrap();
+        emulator.resume();
+        assert_eq!(cycles_to_frame(&mut emulator), frame_cycles);
+        assert_eq!(emulator.save_state(), advanced_state);
+    }
+
+    #[test]
+    fn edits_and_freezes_memory() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        emulator.pause();
+        emulator.write_memory_range(0x07FF, &[0x12, 0x34]);
+        let mut data = [0; 3];
+        emulator.peek_memory_range(0x0FFF, &mut data);
+        assert_eq!(data, [0x12, 0x34, 0x00]);
+
+        emulator.add_cheat(Cheat::from_code("0000:01").unwrap());
+        assert_eq!(emulator.freeze_memory(0x0000, 0x40), Ok(0));
+        assert_eq!(
+            emulator.freeze_memory(0x4000, 0),
+            Err(CheatError::UnsupportedAddress(0x4000))
+        );
+        assert_eq!(emulator.frozen_value(0x0000), Some(0x40));
+        assert_eq!(emulator.cheats().len(), 1);
+
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        // Frozen at the end of the frame, then incremented by at most one INC
+        assert!(matches!(emulator.peek_memory(0x0000), 0x40..=0x41));
+        assert!(emulator.unfreeze_memory(0x0000));
+        assert!(!emulator.unfreeze_memory(0x0000));
+        assert_eq!(emulator.frozen_value(0x0000), None);
+    }
+
+    #[test]
+    fn frozen_cheats_are_written_every_frame() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        emulator.add_cheat(Cheat::new(0x0010, 0x55, false).unwrap());
+        assert_eq!(emulator.ram[0x10], 0x55);
+
+        let frozen = emulator.add_cheat(Cheat::from_code("0000:42").unwrap());
+        for _ in 0..3 {
+            run_frames(&mut emulator, 1);
+            assert!((0x42..=0x43).contains(&emulator.ram[0]));
+        }
+
+        emulator.set_cheat_enabled(frozen, false);
+        run_frames(&mut emulator, 1);
+        assert!(!(0x42..=0x43).contains(&emulator.ram[0]));
+        assert_eq!(emulator.remove_cheat(frozen).map(|c| c.address), Some(0));
+        assert_eq!(emulator.cheats().len(), 1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn breaks_on_breakpoints() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let jmp = emulator.debugger_mut().add_breakpoint(0x8002);
+        run_frames(&mut emulator, 1);
+        assert!(emulator.is_paused());
+        assert_eq!(emulator.cpu().pc, 0x8002);
+        let first = emulator.debugger().last_break().unwrap();
+        assert_eq!(first.reason, BreakReason::Breakpoint(jmp));
+        assert_eq!(
+            emulator.drain_events().collect::<alloc::vec::Vec<_>>(),
+            [Event::Break(first)]
+        );
+
+        let counter = emulator.ram[0];
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        assert_eq!(emulator.ram[0], counter.wrapping_add(1));
+        let second = emulator.debugger().last_break().unwrap();
+        assert_eq!(second.cycle - first.cycle, 8); // JMP, then INC
+        assert_eq!(emulator.debugger().breakpoints()[0].hits, 2);
+
+        emulator.debugger_mut().set_breakpoint_enabled(jmp, false);
+        emulator.resume();
+        assert_eq!(emulator.run_frames(1).frames, 1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn measures_code_coverage() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        emulator.debugger_mut().set_coverage_enabled(true);
+        run_frames(&mut emulator, 1);
+
+        let debugger = emulator.debugger();
+        assert!((0x8000..0x8005).all(|addr| debugger.is_covered(Some(0), addr)));
+        assert!(!debugger.is_covered(Some(0), 0x8005));
+        assert_eq!(debugger.coverage_bitmap(Some(0)).unwrap()[0x1000], 0x1F);
+        assert_eq!(debugger.covered_banks().collect::<Vec<_>>(), [Some(0)]);
+        assert_eq!(emulator.code_coverage(), 5.0 * 100.0 / 16384.0);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn updates_watches() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let frame = Expression::parse("FRAME").unwrap();
+        let frame = emulator.debugger_mut().add_watch(frame);
+        let pc = Expression::parse("PC").unwrap();
+        let pc = emulator.debugger_mut().add_watch(pc);
+        emulator.update_watches();
+        assert_eq!(emulator.debugger().watches()[0].value, Some(0));
+
+        run_frames(&mut emulator, 2);
+        let value = |emulator: &Emulator, id| {
+            let watches = emulator.debugger().watches();
+            watches.iter().find(|w| w.id == id).unwrap().value
+        };
+        assert_eq!(value(&emulator, frame), Some(2));
+
+        emulator.step_into();
+        assert_eq!(value(&emulator, pc), Some(i64::from(emulator.cpu().pc)));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn labels_addresses() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let debugger = emulator.debugger_mut();
+        debugger.set_label(Some(0), 0x8000, "main").unwrap();
+        debugger.set_label_comment(Some(0), 0x8000, "Counts frames");
+        debugger.set_label_comment(Some(1), 0x8000, "Other bank");
+        debugger.set_trace_capacity(2);
+        run_frames(&mut emulator, 1);
+
+        let disassembly = emulator.disassemble(0, 0);
+        let main = disassembly.iter().find(|(_, addr, _)| *addr == 0x8000);
+        assert!(main.unwrap().2.ends_with(" ; main: Counts frames"));
+        let lines = emulator.disassemble_instructions(0x8000, 2);
+        assert_eq!(
+            lines.iter().map(|(_, addr, _)| *addr).collect::<Vec<_>>(),
+            [0x8000, 0x8002]
+        );
+        assert!(lines[0].2.ends_with(" ; main: Counts frames"));
+
+        let debugger = emulator.debugger_mut();
+        let json = debugger.export_labels();
+        debugger.rename_label("main", "count").unwrap();
+        assert!(debugger.export_trace(2).contains(" ; count\n"));
+        assert_eq!(debugger.import_labels(&json), Ok(2));
+        assert_eq!(debugger.find_label("main").map(|l| l.address), Some(0x8000));
+        assert_eq!(
+            debugger.remove_label(Some(1), 0x8000).map(|l| l.comment),
+            Some("Other bank".into())
+        );
+        assert_eq!(debugger.labels().count(), 1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn profiles_cycles() {
+        let mut rom = counter_rom();
+        // JSR $8010; JMP $8000, then INC $00; RTS
+        rom[16..22].copy_from_slice(&[0x20, 0x10, 0x80, 0x4C, 0x00, 0x80]);
+        rom[16 + 0x10..16 + 0x13].copy_from_slice(&[0xE6, 0x00, 0x60]);
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator.debugger_mut().set_profiling(true);
+        run_frames(&mut emulator, 2);
+
+        let debugger = emulator.debugger();
+        let profile = debugger.profile();
+        let total = debugger.profiled_cycles();
+        // JSR, INC and RTS take 6, 5 and 6 cycles of the 20 of a loop
+        let addresses: Vec<_> = profile.iter().map(|e| e.address).collect();
+        assert_eq!(addresses, [0x8000, 0x8012, 0x8010, 0x8003]);
+        assert!(profile[0].cycles * 20 / total == 6);
+        let routines = debugger.routine_profile();
+        assert_eq!((routines.len(), routines[0].address), (1, 0x8010));
+        assert_eq!(routines[0].cycles, profile[1].cycles + profile[2].cycles);
+        assert!(debugger.export_profile().contains("\n00:8010 "));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn records_timing_events() {
+        let mut rom = counter_rom();
+        // STA $3FFD; JMP $8000
+        rom[16..22].copy_from_slice(&[0x8D, 0xFD, 0x3F, 0x4C, 0x00, 0x80]);
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator.debugger_mut().set_timing_events_enabled(true);
+        run_frames(&mut emulator, 2);
+
+        let events = emulator.debugger().timing_events();
+        // Every 7 CPU cycles, over the 262 scanlines of 341 dots
+        assert!((4250..4260).contains(&events.len()));
+        assert!(events.iter().all(|e| e.is_scroll_change()));
+        assert_eq!(
+            events[0].kind,
+            TimingEventKind::RegisterWrite {
+                addr: 0x2005,
+                data: 0
+            }
+        );
+        assert!(events.windows(2).all(|e| e[0].scanline <= e[1].scanline));
+        assert_eq!(events[0].scanline, -1);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn traces_last_instructions() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        emulator.debugger_mut().set_trace_capacity(4);
+        emulator.debugger_mut().add_breakpoint(0x8002);
+        run_frames(&mut emulator, 1);
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+
+        let trace: Vec<_> = emulator.debugger().trace().copied().collect();
+        assert_eq!(trace.len(), 4);
+        let pcs: Vec<_> = trace.iter().map(|e| e.pc).collect();
+        assert_eq!(pcs, [0x8000, 0x8002, 0x8000, 0x8002]);
+        assert_eq!(trace[3].cycle - trace[2].cycle, 5); // INC
+        assert_eq!((trace[3].opcode, trace[3].bank), (0x4C, Some(0)));
+
+        let export = emulator.debugger().export_trace(1);
+        assert_eq!(export.lines().count(), 2);
+        assert!(export.starts_with("# bank:pc opcode registers cycle\n00:8002 4C A:00"));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn breaks_on_watchpoints() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let counter = emulator
+            .debugger_mut()
+            .add_watchpoint(0, 0, WatchKind::Write);
+        run_frames(&mut emulator, 1);
+        let brk = emulator.debugger().last_break().unwrap();
+        assert_eq!(
+            brk.reason,
+            BreakReason::Watchpoint {
+                id: counter,
+                address: 0x0000,
+                value: emulator.ram[0],
+                instruction: 0x8000,
+            }
+        );
+        assert_eq!(brk.pc, 0x8002);
+
+        // The OAM DMA reads $0200-$02FF
+        let mut rom = counter_rom();
+        #[rustfmt::skip]
+        rom[16..24].copy_from_slice(&[
+            0xA9, 0x02, 0x8D, 0x14, 0x40, // LDA #$02; STA $4014
+            0x4C, 0x05, 0x80, // JMP $8005
+        ]);
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator
+            .debugger_mut()
+            .add_watchpoint(0x0210, 0x0210, WatchKind::Read);
+        run_frames(&mut emulator, 1);
+        assert!(matches!(
+            emulator.debugger().last_break().map(|brk| brk.reason),
+            Some(BreakReason::Watchpoint {
+                address: 0x0210,
+                instruction: 0x8002,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn annotates_stack() {
+        let mut rom = counter_rom();
+        let prg = &mut rom[16..16 + 0x4000];
+        prg[..6].copy_from_slice(&[0x20, 0x10, 0x80, 0x4C, 0x00, 0x80]); // JSR $8010; JMP $8000
+                                                                         // PHA; JSR $8020; PLA; RTS
+        prg[0x10..0x16].copy_from_slice(&[0x48, 0x20, 0x20, 0x80, 0x68, 0x60]);
+        prg[0x20..0x22].copy_from_slice(&[0xEA, 0x60]); // NOP; RTS
+
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator.debugger_mut().add_breakpoint(0x8021);
+        run_frames(&mut emulator, 1);
+        let st = usize::from(emulator.cpu().st);
+        let stack = emulator.stack_view();
+
+        assert_eq!(stack[st].slot, StackSlot::Free);
+        assert_eq!(
+            stack[st + 2],
+            StackByte {
+                address: 0x0100 + st as u16 + 2,
+                value: 0x80,
+                slot: StackSlot::ReturnAddress {
+                    return_address: 0x8014,
+                    high: true,
+                    kind: CallKind::Jsr,
+                    frame: Some(1),
+                },
+            }
+        );
+        assert_eq!(stack[st + 3].slot, StackSlot::Pushed { frame: 0 });
+        assert_eq!(
+            stack[st + 4].slot,
+            StackSlot::ReturnAddress {
+                return_address: 0x8003,
+                high: false,
+                kind: CallKind::Jsr,
+                frame: Some(0),
+            }
+        );
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn steps_over_and_out_of_routines() {
+        let mut rom = counter_rom();
+        let prg = &mut rom[16..16 + 0x4000];
+        // LDA #$80; STA $2000; JSR $8010; INC $01; JMP $8000
+        prg[..0x0D].copy_from_slice(&[
+            0xA9, 0x80, 0x8D, 0x00, 0x20, 0x20, 0x10, 0x80, 0xE6, 0x01, 0x4C, 0x00, 0x80,
+        ]);
+        // INC $00; JSR $8020; RTS
+        prg[0x10..0x16].copy_from_slice(&[0xE6, 0x00, 0x20, 0x20, 0x80, 0x60]);
+        prg[0x20..0x23].copy_from_slice(&[0xE6, 0x02, 0x60]); // INC $02; RTS
+        prg[0x40..0x43].copy_from_slice(&[0xE6, 0x03, 0x40]); // INC $03; RTI
+        prg[0x3FFA..0x3FFC].copy_from_slice(&[0x40, 0x80]); // NMI vector
+
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        let jsr = emulator.debugger_mut().add_breakpoint(0x8005);
+        run_frames(&mut emulator, 1);
+        emulator.debugger_mut().remove_breakpoint(jsr);
+        let stack = emulator.cpu().st;
+
+        assert!(emulator.step_into().completed);
+        assert_eq!((emulator.cpu().pc, emulator.cpu().st), (0x8010, stack - 2));
+        let call = emulator.debugger().call_stack().last().copied().unwrap();
+        assert_eq!((call.kind, call.caller), (CallKind::Jsr, 0x8005));
+        assert_eq!((call.return_address, call.target_bank), (0x8008, Some(0)));
+        emulator.step_over(100);
+        let called = emulator.ram[2];
+        assert!(emulator.step_over(100).completed);
+        assert_eq!(emulator.cpu().pc, 0x8015);
+        assert_eq!(emulator.ram[2], called.wrapping_add(1));
+        assert!(emulator.step_out(100).completed);
+        assert_eq!((emulator.cpu().pc, emulator.cpu().st), (0x8008, stack));
+        assert!(emulator.is_paused());
+
+        // The NMI handler is stepped over, and into
+        let interrupts = emulator.debugger().interrupts();
+        while emulator.debugger().interrupts() == interrupts {
+            assert!(emulator.step_over(100).completed);
+            assert_ne!(emulator.cpu().pc, 0x8040);
+        }
+        while emulator.debugger().interrupts() == interrupts + 1 {
+            emulator.step_into();
+        }
+        assert_eq!(emulator.cpu().pc, 0x8040);
+        let nmi = emulator.debugger().call_stack().last().unwrap();
+        assert_eq!((nmi.kind, nmi.target), (CallKind::Nmi, 0x8040));
+        let interrupted = emulator.peek_memory(0x0100 + u16::from(emulator.cpu().st) + 2);
+        assert!(emulator.step_out(100).completed);
+        assert_eq!(emulator.cpu().pc & 0xFF, u16::from(interrupted));
+
+        let progress = emulator.step_out(1000);
+        assert_eq!((progress.completed, progress.cpu_cycles), (false, 1000));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn breaks_on_instruction_classes() {
+        let mut rom = counter_rom();
+        // LDA #0; STA $2001; JMP $8000
+        rom[16..24].copy_from_slice(&[0xA9, 0x00, 0x8D, 0x01, 0x20, 0x4C, 0x00, 0x80]);
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        let debugger = emulator.debugger_mut();
+        debugger.set_break_on_instructions(InstructionClass::PpuRegisterWrite, true);
+        debugger.set_break_on_instructions(InstructionClass::Opcode(0x4C), true);
+        run_frames(&mut emulator, 1);
+        let brk = emulator.debugger().last_break().unwrap();
+        assert_eq!(
+            (brk.reason, brk.pc),
+            (
+                BreakReason::Instruction(InstructionClass::PpuRegisterWrite),
+                0x8002
+            )
+        );
+
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        let brk = emulator.debugger().last_break().unwrap();
+        assert_eq!(
+            (brk.reason, brk.pc),
+            (
+                BreakReason::Instruction(InstructionClass::Opcode(0x4C)),
+                0x8005
+            )
+        );
+
+        let debugger = emulator.debugger_mut();
+        debugger.set_break_on_instructions(InstructionClass::PpuRegisterWrite, false);
+        assert!(!debugger.breaks_on_instructions(InstructionClass::PpuRegisterWrite));
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn breaks_on_bank_switches() {
+        // LDA #1; STA $8000; JMP $xx05, on UxROM at $C000 and on CNROM at $8000
+        let code = |rom: &mut Vec<u8>, offset: usize, high: u8| {
+            let code = [0xA9, 0x01, 0x8D, 0x00, 0x80, 0x4C, 0x05, high];
+            rom[16 + offset..16 + offset + 8].copy_from_slice(&code);
+            let vector = rom.len() - 4 - 0x2000 * usize::from(rom[5]);
+            rom[vector..vector + 2].copy_from_slice(&[0x00, high]);
+        };
+        let mut uxrom = vec![0u8; 16 + 0x8000];
+        uxrom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x20, 0x00]);
+        code(&mut uxrom, 0x4000, 0xC0);
+        let mut cnrom = counter_rom();
+        cnrom[6] = 0x30;
+        code(&mut cnrom, 0, 0x80);
+
+        let mut emulator = Emulator::new(&uxrom, None).unwrap();
+        let debugger = emulator.debugger_mut();
+        debugger.set_break_on_mapper_event(MapperEvent::ChrBankSwitch, true);
+        debugger.set_break_on_mapper_event(MapperEvent::PrgBankSwitch, true);
+        run_frames(&mut emulator, 1);
+        let brk = emulator.debugger().last_break().unwrap();
+        assert_eq!(
+            brk.reason,
+            BreakReason::PrgBankSwitch {
+                address: 0x8000,
+                bank: Some(1),
+                instruction: 0xC002,
+            }
+        );
+        assert_eq!(brk.pc, 0xC005);
+
+        // Writing the same bank again doesn't switch it
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        assert_eq!(emulator.debugger().last_break(), Some(brk));
+
+        let mut emulator = Emulator::new(&cnrom, None).unwrap();
+        let debugger = emulator.debugger_mut();
+        debugger.set_break_on_mapper_event(MapperEvent::ChrBankSwitch, true);
+        run_frames(&mut emulator, 1);
+        assert_eq!(
+            emulator.debugger().last_break().map(|b| b.reason),
+            Some(BreakReason::ChrBankSwitch {
+                address: 0x0000,
+                bank: 8,
+                instruction: 0x8002,
+            })
+        );
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn breaks_on_conditions() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let jmp = emulator.debugger_mut().add_breakpoint(0x8002);
+        let condition = Expression::parse("[$00] == 5 && frame == 0").unwrap();
+        emulator
+            .debugger_mut()
+            .set_breakpoint_condition(jmp, Some(condition));
+        run_frames(&mut emulator, 1);
+        assert_eq!((emulator.cpu().pc, emulator.ram[0]), (0x8002, 5));
+        let pc = Expression::parse("pc - 2").unwrap();
+        assert_eq!(pc.evaluate(&emulator), 0x8000);
+
+        emulator.debugger_mut().clear_breakpoints();
+        let counter = emulator
+            .debugger_mut()
+            .add_watchpoint(0, 0, WatchKind::Change);
+        let condition = Expression::parse("[0] == $40").unwrap();
+        emulator
+            .debugger_mut()
+            .set_watchpoint_condition(counter, Some(condition));
+        emulator.resume();
+        run_frames(&mut emulator, 1);
+        assert!(matches!(
+            emulator.debugger().last_break().map(|brk| brk.reason),
+            Some(BreakReason::Watchpoint { value: 0x40, .. })
+        ));
+        assert_eq!(emulator.debugger().watchpoints()[0].hits, 1);
+    }
+
+    #[test]
+    fn unlocks_achievements_through_events() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let achievement = Achievement::parse(3, "Cheater".into(), 25, "0xH0010=h55").unwrap();
+        emulator.add_achievement(achievement);
+        run_frames(&mut emulator, 1);
+        assert_eq!(emulator.drain_events().count(), 0);
+
+        emulator.add_cheat(Cheat::new(0x0010, 0x55, false).unwrap());
+        run_frames(&mut emulator, 1);
+        assert!(emulator.is_achievement_unlocked(3));
+        assert_eq!(
+            emulator.drain_events().collect::<alloc::vec::Vec<_>>(),
+            [Event::AchievementUnlocked { id: 3, points: 25 }]
+        );
+        assert_eq!(emulator.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn peeks_without_side_effects() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        run_frames(&mut emulator, 1);
+        while emulator.peek_memory(0x2002) & 0x80 == 0 {
+            emulator.clock();
+        }
+
+        // Peeking leaves the vblank flag set, reading clears it
+        assert_eq!(emulator.peek_memory(0x2002) & 0x80, 0x80);
+        assert_eq!(emulator.read_memory(0x2002) & 0x80, 0x80);
+        assert_eq!(emulator.peek_memory(0x2002) & 0x80, 0);
+
+        emulator.write_memory(0x0801, 0x12);
+        assert_eq!(emulator.peek_memory(0x0001), 0x12);
+        assert_eq!(emulator.peek_memory(0x8002), 0x4C);
+
+        // Nametables mirrored horizontally, and palette mirrors
+        emulator.write_vram(0x2005, 0x34);
+        assert_eq!(emulator.read_vram(0x2405), 0x34);
+        emulator.write_vram(0x3F10, 0x0F);
+        assert_eq!(emulator.read_vram(0x3F00), 0x0F);
+
+        emulator.write_oam(4, 0x56);
+        assert_eq!(emulator.oam()[4], 0x56);
+    }
+
+    #[test]
+    fn soft_reset_keeps_ram() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        emulator.set_power_on_ram(PowerOnRam::Pattern);
+        emulator.write_memory(0x0010, 0x12);
+        run_frames(&mut emulator, 1);
+        let counter = emulator.ram[0];
+        let stack_pointer = emulator.cpu.st;
+
+        emulator.soft_reset();
+        assert_eq!(emulator.cpu.st, stack_pointer.wrapping_sub(3));
+        assert_eq!(emulator.ram[0], counter);
+        assert_eq!(emulator.ram[0x10], 0x12);
+
+        emulator.power_cycle();
+        assert_eq!(emulator.cpu.st, 0xFD);
+        assert_eq!(emulator.ram[..8], [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(emulator.ram[0x10], 0);
+    }
+
+    #[test]
+    fn renders_sprites_off_screen() {
+        // The PPU fetches patterns past $1FFF for the sprites out of range, which the mappers
+        // must not see
+        for (mapper_id, prg_banks) in [
+            (16, 2),
+            (68, 2),
+            (21, 2),
+            (21, 1),
+            (22, 2),
+            (23, 2),
+            (25, 2),
+            (19, 2),
+            (24, 2),
+            (26, 2),
+            (34, 2),
+            (69, 2),
+            (85, 2),
+            (210, 2),
+            (9, 2),
+            (9, 1),
+        ] {
+            let rom = offscreen_sprites_rom(mapper_id, prg_banks);
+            let mut emulator = Emulator::new(&rom, None).unwrap();
+            run_frames(&mut emulator, 4);
+            // Showing the sprites
+            assert_eq!(emulator.ppu.named_registers()[1], ("PPUMASK", 0x14));
+        }
+    }
+
+    #[test]
+    fn frame_rgba_uses_palette_and_overscan() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let mut palette = RGB_PALETTE;
+        palette[0x0F] = [1, 2, 3];
+        emulator.set_palette(palette);
+        emulator.set_overscan(Overscan::NTSC);
+        emulator.set_overscan(Overscan::new(200, 100, 0, 0));
+        assert_eq!(emulator.overscan(), Overscan::NTSC);
+
+        emulator.ppu.frame_mut()[8 * 256] = 0x0F;
+        emulator.ppu.frame_mut()[8 * 256 + 1] = 0x30;
+        let mut rgba = Vec::new();
+        assert_eq!(emulator.frame_rgba(&mut rgba), (256, 224));
+        assert_eq!(rgba.len(), 256 * 224 * 4);
+        assert_eq!(rgba[..4], [1, 2, 3, 0xFF]);
+        assert_eq!(rgba[4..7], RGB_PALETTE[0x30]);
+    }
+
+    #[test]
+    fn diffs_save_states() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        // Past the warm-up of the PPU, which ignores the writes to PPUCTRL
+        run_frames(&mut emulator, 2);
+        let state = emulator.save_state();
+        assert_eq!(
+            emulator.diff_states(&state, &state),
+            Ok(StateDiff::default())
+        );
+
+        emulator.write_memory(0x0300, 1);
+        emulator.write_memory(0x0301, 2);
+        emulator.write_memory(0x2000, 0x80);
+        let edited = emulator.save_state();
+        run_frames(&mut emulator, 1);
+        let current = emulator.save_state();
+
+        let diff = emulator.diff_states(&state, &edited).unwrap();
+        assert_eq!(emulator.save_state(), current);
+        assert_eq!(
+            diff.components(),
+            [StateComponent::Ram, StateComponent::Ppu]
+        );
+        assert!(diff.ranges.contains(&ByteRangeDiff {
+            component: StateComponent::Ram,
+            start: 0x0300,
+            end: 0x0302,
+        }));
+        assert_eq!(
+            diff.registers,
+            [RegisterDiff {
+                component: StateComponent::Ppu,
+                name: "PPUCTRL",
+                first: 0,
+                second: 0x80,
+            }]
+        );
+
+        let diff = emulator.diff_states(&state, &current).unwrap();
+        assert!(diff.differs(StateComponent::Cpu));
+        assert!(diff.differs(StateComponent::Timing));
+        assert!(!diff.differs(StateComponent::Cartridge));
+
+        let mut truncated = current.clone();
+        truncated.pop();
+        assert!(emulator.diff_states(&state, &truncated).is_err());
+
+        // A section cut short, in a valid container
+        let origin = emulator.cartridge.origin();
+        let payload = save_data::unpack(&current, SaveDataKind::SaveState, origin).unwrap();
+        let truncated = save_data::pack(
+            SaveDataKind::SaveState,
+            origin,
+            &payload[..payload.len() - 1],
+        );
+        assert_eq!(
+            emulator.diff_states(&state, &truncated),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn invalid_state_is_rejected() {
+        let mut emulator = Emulator::new(&counter_rom(), None).unwrap();
+        let state = emulator.save_state();
+        run_frames(&mut emulator, 1);
+        let current = emulator.save_state();
+
+        let mut other_rom = counter_rom();
+        other_rom[16 + 1] = 0x01;
+        let other_state = Emulator::new(&other_rom, None).unwrap().save_state();
+        assert_eq!(
+            emulator.load_state(&other_state),
+            Err(SaveStateError::InvalidContainer(SaveDataError::RomMismatch))
+        );
+
+        // A state cut short, in a valid container
+        let origin = emulator.cartridge.origin();
+        let payload = save_data::unpack(&state, SaveDataKind::SaveState, origin).unwrap();
+        let truncated = save_data::pack(
+            SaveDataKind::SaveState,
+            origin,
+            &payload[..payload.len() - 1],
+        );
+        assert_eq!(
+            emulator.load_state(&truncated),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+        assert_eq!(emulator.save_state(), current);
     }
 }
@@ -0,0 +1,311 @@
+// FCEUX text movies (.fm2): a header of "key value" lines, then one input record per frame.
+// http://fceux.com/web/help/fm2.html
+//
+// An input record is "|commands|port0|port1|port2|", or "|commands|1|2|3|4|port2|" with a Four
+// Score. The commands are flags, 1 being a soft reset. The standard controllers are written as
+// "RLDUTSBA", with a '.' or a space for the buttons that aren't pressed. Only the movies starting
+// at power-on with standard controllers are supported.
+
+use alloc::string::String;
+use core::fmt::Write as _;
+
+use super::{Movie, MovieFrame, MovieStart};
+use crate::cartridge::Md5;
+use crate::{Emulator, PowerOnRam};
+
+/// Version of the format written by FCEUX 2.x
+const FM2_VERSION: u32 = 3;
+
+/// Buttons of the standard controller, from the lowest bit
+const BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+const COMMAND_SOFT_RESET: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fm2Error {
+    /// The binary variant of the input records isn't supported
+    BinaryFormat,
+    UnsupportedVersion(u32),
+    /// The movie starts from a save state instead of power-on
+    SaveStateStart,
+    /// The movie uses other devices than the standard controllers
+    UnsupportedDevice,
+    /// The input record of `line` has commands other than a soft reset
+    UnsupportedCommand {
+        line: usize,
+        commands: u8,
+    },
+    InvalidLine(usize),
+}
+
+impl core::fmt::Display for Fm2Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+impl Movie {
+    /// Imports an FCEUX movie, to be played on the ROM loaded in `emulator`
+    pub fn from_fm2(emulator: &Emulator, fm2: &str) -> Result<Self, Fm2Error> {
+        let mut frames = alloc::vec::Vec::new();
+        let mut four_score = false;
+
+        for (index, line) in fm2.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim_end_matches('\r');
+
+            if line.starts_with('|') {
+                frames.push(parse_input_record(line, line_number, four_score)?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = || Fm2Error::InvalidLine(line_number);
+            match key {
+                "version" => {
+                    let version = value.parse().map_err(|_| invalid())?;
+                    if version != FM2_VERSION {
+                        return Err(Fm2Error::UnsupportedVersion(version));
+                    }
+                }
+                "binary" if value != "0" => return Err(Fm2Error::BinaryFormat),
+                "savestate" if !value.is_empty() => return Err(Fm2Error::SaveStateStart),
+                "fourscore" => four_score = value == "1",
+                "port2" if value != "0" => return Err(Fm2Error::UnsupportedDevice),
+                // 0 is no device and 1 a standard controller
+                "port0" | "port1" if value != "0" && value != "1" => {
+                    return Err(Fm2Error::UnsupportedDevice)
+                }
+                "palFlag" if value != "0" => {
+                    log::warn!("Movie was recorded with PAL timing, but only NTSC is emulated")
+                }
+                "romChecksum" => {
+                    let expected = encode_md5(&emulator.cartridge.rom_md5());
+                    if value != expected {
+                        log::warn!(
+                            "Movie was recorded on another ROM, with checksum {} instead of {}",
+                            value,
+                            expected
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // FCEUX fills the RAM with its pattern at power-on
+        let start = MovieStart::PowerOn {
+            power_on_ram: PowerOnRam::Pattern,
+            ppu_warmup_cycles: emulator.ppu_warmup_cycles(),
+        };
+        let mut movie = Self::new(emulator, start, frames);
+        movie.four_score = four_score;
+        Ok(movie)
+    }
+
+    /// Exports the movie for FCEUX. `rom_filename` is only informative.
+    pub fn to_fm2(&self, emulator: &Emulator, rom_filename: &str) -> Result<String, Fm2Error> {
+        if !matches!(self.start, MovieStart::PowerOn { .. }) {
+            return Err(Fm2Error::SaveStateStart);
+        }
+
+        // The movie is identified by a GUID, which is derived from its content
+        let mut md5 = Md5::new();
+        md5.update(&self.save());
+        let id = md5.finish();
+
+        let mut fm2 = String::new();
+        let _ = writeln!(fm2, "version {}", FM2_VERSION);
+        let _ = writeln!(fm2, "emuVersion 22020");
+        let _ = writeln!(fm2, "rerecordCount 0");
+        let _ = writeln!(fm2, "palFlag 0");
+        let _ = writeln!(fm2, "romFilename {}", rom_filename);
+        let _ = writeln!(
+            fm2,
+            "romChecksum {}",
+            encode_md5(&emulator.cartridge.rom_md5())
+        );
+        let _ = write!(fm2, "guid ");
+        for (i, byte) in id.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                fm2.push('-');
+            }
+            let _ = write!(fm2, "{:02X}", byte);
+        }
+        fm2.push('\n');
+        let _ = writeln!(fm2, "fourscore {}", u8::from(self.four_score));
+        let _ = writeln!(fm2, "microphone 0");
+        let _ = writeln!(fm2, "port0 1");
+        let _ = writeln!(fm2, "port1 1");
+        let _ = writeln!(fm2, "port2 0");
+        let _ = writeln!(fm2, "FDS 0");
+        let _ = writeln!(fm2, "NewPPU 0");
+
+        for frame in &self.frames {
+            let commands = if frame.reset { COMMAND_SOFT_RESET } else { 0 };
+            let _ = write!(fm2, "|{}|", commands);
+            write_controller(&mut fm2, frame.controller1);
+            fm2.push('|');
+            write_controller(&mut fm2, frame.controller2);
+            if self.four_score {
+                fm2.push('|');
+                write_controller(&mut fm2, frame.controller3);
+                fm2.push('|');
+                write_controller(&mut fm2, frame.controller4);
+            }
+            fm2.push_str("||\n");
+        }
+
+        Ok(fm2)
+    }
+}
+
+fn parse_input_record(
+    line: &str,
+    line_number: usize,
+    four_score: bool,
+) -> Result<MovieFrame, Fm2Error> {
+    let invalid = || Fm2Error::InvalidLine(line_number);
+
+    let mut fields = line.split('|').skip(1);
+    let commands: u8 = fields
+        .next()
+        .and_then(|commands| commands.parse().ok())
+        .ok_or_else(invalid)?;
+    if commands & !COMMAND_SOFT_RESET != 0 {
+        return Err(Fm2Error::UnsupportedCommand {
+            line: line_number,
+            commands,
+        });
+    }
+
+    let mut controller = || -> Result<u8, Fm2Error> {
+        let field = fields.next().ok_or_else(invalid)?;
+        if field.is_empty() {
+            // No device on the port
+            return Ok(0);
+        }
+
+        if field.len() != BUTTONS.len() {
+            return Err(invalid());
+        }
+        Ok(field
+            .bytes()
+            .enumerate()
+            .filter(|(_, c)| *c != b'.' && *c != b' ')
+            .fold(0, |state, (i, _)| state | (1 << i)))
+    };
+
+    let mut frame = MovieFrame {
+        controller1: controller()?,
+        controller2: controller()?,
+        reset: commands & COMMAND_SOFT_RESET != 0,
+        ..MovieFrame::default()
+    };
+    if four_score {
+        frame.controller3 = controller()?;
+        frame.controller4 = controller()?;
+    }
+    Ok(frame)
+}
+
+fn write_controller(fm2: &mut String, state: u8) {
+    for (i, button) in BUTTONS.iter().enumerate() {
+        fm2.push(if state & (1 << i) != 0 {
+            char::from(*button)
+        } else {
+            '.'
+        });
+    }
+}
+
+/// Writes the MD5 of the ROM like FCEUX: base64, with a prefix
+fn encode_md5(md5: &[u8; 16]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::from("base64:");
+    for chunk in md5.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3F;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::idle_rom;
+
+    #[test]
+    fn imports_exported_movie() {
+        let rom = idle_rom();
+        let emulator = Emulator::new(&rom, None).unwrap();
+
+        let fm2 = "version 3\nemuVersion 22020\nport0 1\nport1 0\nport2 0\n\
+                   |0|........|||\n|1|R..U...A|||\r\n|0|RLDUTSBA|||\n";
+        let movie = Movie::from_fm2(&emulator, fm2).unwrap();
+        assert_eq!(
+            movie.frames(),
+            [
+                MovieFrame::default(),
+                MovieFrame {
+                    controller1: 0x89,
+                    reset: true,
+                    ..MovieFrame::default()
+                },
+                MovieFrame {
+                    controller1: 0xFF,
+                    ..MovieFrame::default()
+                },
+            ]
+        );
+
+        let exported = movie.to_fm2(&emulator, "test.nes").unwrap();
+        assert!(exported.contains("\n|1|R..U...A|........||\n"));
+        assert_eq!(Movie::from_fm2(&emulator, &exported), Ok(movie));
+
+        assert_eq!(encode_md5(&[0; 16]), "base64:AAAAAAAAAAAAAAAAAAAAAA==");
+        let four_score = Movie::from_fm2(
+            &emulator,
+            "version 3\nfourscore 1\n|0|R.......||..D.....|.......A||\n",
+        )
+        .unwrap();
+        assert!(four_score.four_score());
+        assert_eq!(
+            four_score.frames(),
+            [MovieFrame {
+                controller1: 0x01,
+                controller3: 0x04,
+                controller4: 0x80,
+                ..MovieFrame::default()
+            }]
+        );
+        let exported = four_score.to_fm2(&emulator, "test.nes").unwrap();
+        assert!(exported.contains("\n|0|R.......|........|..D.....|.......A||\n"));
+        assert_eq!(
+            Movie::from_fm2(&emulator, "version 3\nport2 1\n"),
+            Err(Fm2Error::UnsupportedDevice)
+        );
+        assert_eq!(
+            Movie::from_fm2(&emulator, "version 3\n|2|........|||\n"),
+            Err(Fm2Error::UnsupportedCommand {
+                line: 2,
+                commands: 2
+            })
+        );
+        assert_eq!(
+            Movie::from_fm2(&emulator, "version 3\n|0|...|||\n"),
+            Err(Fm2Error::InvalidLine(2))
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// Recording and playback of the inputs of every frame, replaying a run exactly.
+//
+// A movie starts either at power-on, with the settings of the power-on, or from a save state,
+// and holds the controller states of every frame after it, along with whether a Four Score was
+// plugged. It is saved in a save data container, tying it to the ROM it was recorded on. The
+// payload is the start, followed by the frames, written like a save state.
+
+mod fm2;
+
+use alloc::vec::Vec;
+
+use crate::save_data::{self, SaveDataError, SaveDataKind, SaveDataOrigin};
+use crate::state::{SaveStateError, StateReader, StateWriter, Stateful};
+use crate::{Emulator, PowerOnRam, PPU_WARMUP_CYCLES};
+
+pub use fm2::Fm2Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieError {
+    InvalidContainer(SaveDataError),
+    /// The movie data can't be decoded
+    Corrupted,
+    /// The save state the movie starts from can't be loaded
+    InvalidSaveState(SaveStateError),
+}
+
+impl core::fmt::Display for MovieError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// Point a movie starts from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    /// Power-on of the emulator, with the settings it was recorded with
+    PowerOn {
+        power_on_ram: PowerOnRam,
+        ppu_warmup_cycles: u32,
+    },
+    /// Save state taken on a frame boundary
+    SaveState(Vec<u8>),
+}
+
+impl MovieStart {
+    /// Power-on with the current settings of `emulator`
+    pub fn power_on(emulator: &Emulator) -> Self {
+        Self::PowerOn {
+            power_on_ram: emulator.power_on_ram(),
+            ppu_warmup_cycles: emulator.ppu_warmup_cycles(),
+        }
+    }
+}
+
+impl Stateful for MovieStart {
+    fn save_state(&self, state: &mut StateWriter) {
+        match self {
+            Self::PowerOn {
+                power_on_ram,
+                ppu_warmup_cycles,
+            } => {
+                2u8.save_state(state);
+                power_on_ram.save_state(state);
+                ppu_warmup_cycles.save_state(state);
+            }
+            Self::SaveState(data) => {
+                1u8.save_state(state);
+                data.save_state(state);
+            }
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut tag = 0u8;
+        tag.load_state(state)?;
+
+        *self = match tag {
+            // Saved before the settings of the power-on were, which were the default ones
+            0 => Self::PowerOn {
+                power_on_ram: PowerOnRam::default(),
+                ppu_warmup_cycles: PPU_WARMUP_CYCLES,
+            },
+            1 => {
+                let mut data = Vec::new();
+                data.load_state(state)?;
+                Self::SaveState(data)
+            }
+            2 => {
+                let mut power_on_ram = PowerOnRam::default();
+                let mut ppu_warmup_cycles = 0u32;
+                power_on_ram.load_state(state)?;
+                ppu_warmup_cycles.load_state(state)?;
+                Self::PowerOn {
+                    power_on_ram,
+                    ppu_warmup_cycles,
+                }
+            }
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+/// Inputs of a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub controller1: u8,
+    pub controller2: u8,
+    /// Only read with a Four Score
+    pub controller3: u8,
+    /// Only read with a Four Score
+    pub controller4: u8,
+    /// The console is soft reset before the frame runs
+    pub reset: bool,
+}
+
+impl_stateful!(MovieFrame {
+    controller1,
+    controller2,
+    controller3,
+    controller4,
+    reset
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    origin: SaveDataOrigin,
+    start: MovieStart,
+    four_score: bool,
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    /// Creates a movie for the ROM loaded in `emulator`, with its Four Score setting
+    pub fn new(emulator: &Emulator, start: MovieStart, frames: Vec<MovieFrame>) -> Self {
+        Self {
+            origin: emulator.cartridge.origin(),
+            start,
+            four_score: emulator.is_four_score_plugged(),
+            frames,
+        }
+    }
+
+    pub fn start(&self) -> &MovieStart {
+        &self.start
+    }
+
+    /// The movie is played with a Four Score
+    pub fn four_score(&self) -> bool {
+        self.four_score
+    }
+
+    pub fn frames(&self) -> &[MovieFrame] {
+        &self.frames
+    }
+
+    /// Drops the frames after `len`, to record again from there
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+    }
+
+    /// Serializes the movie, to be loaded with `Movie::load`
+    pub fn save(&self) -> Vec<u8> {
+        let mut payload = StateWriter::new();
+        self.start.save_state(&mut payload);
+        self.four_score.save_state(&mut payload);
+        self.frames.save_state(&mut payload);
+
+        save_data::pack(SaveDataKind::Movie, self.origin, &payload.into_inner())
+    }
+
+    /// Loads a movie saved by `Movie::save` from the ROM loaded in `emulator`
+    pub fn load(emulator: &Emulator, data: &[u8]) -> Result<Self, MovieError> {
+        let origin = emulator.cartridge.origin();
+        let payload = save_data::unpack(data, SaveDataKind::Movie, origin)
+            .map_err(MovieError::InvalidContainer)?;
+
+        let mut payload = StateReader::new(&payload);
+        let mut movie = Self {
+            origin,
+            start: MovieStart::SaveState(Vec::new()),
+            four_score: false,
+            frames: Vec::new(),
+        };
+        movie
+            .start
+            .load_state(&mut payload)
+            .and_then(|_| movie.four_score.load_state(&mut payload))
+            .and_then(|_| movie.frames.load_state(&mut payload))
+            .map_err(|_| MovieError::Corrupted)?;
+
+        if payload.remaining() != 0 {
+            return Err(MovieError::Corrupted);
+        }
+
+        Ok(movie)
+    }
+}
+
+/// Records the inputs given to an emulator. The emulator must be on a frame boundary whenever
+/// the recorder is used, that is right after `clock` returned a frame, or while paused.
+pub struct MovieRecorder {
+    movie: Movie,
+    reset_pending: bool,
+}
+
+impl MovieRecorder {
+    /// Starts a movie on an emulator that was just created from the ROM, or power cycled
+    pub fn from_power_on(emulator: &Emulator) -> Self {
+        Self {
+            movie: Movie::new(emulator, MovieStart::power_on(emulator), Vec::new()),
+            reset_pending: false,
+        }
+    }
+
+    /// Starts a movie from the current state of the emulator
+    pub fn from_current_state(emulator: &Emulator) -> Self {
+        let start = MovieStart::SaveState(emulator.save_state());
+        Self {
+            movie: Movie::new(emulator, start, Vec::new()),
+            reset_pending: false,
+        }
+    }
+
+    /// Soft resets the emulator. The reset is recorded with the next frame.
+    pub fn reset(&mut self, emulator: &mut Emulator) {
+        emulator.soft_reset();
+        self.reset_pending = true;
+    }
+
+    /// Records the current inputs of the emulator, before running the next frame
+    pub fn record_frame(&mut self, emulator: &Emulator) {
+        let [controller1, controller2, controller3, controller4] = emulator.controller_states();
+        self.movie.frames.push(MovieFrame {
+            controller1,
+            controller2,
+            controller3,
+            controller4,
+            reset: self.reset_pending,
+        });
+        self.reset_pending = false;
+    }
+
+    pub fn recorded_frames(&self) -> usize {
+        self.movie.frames.len()
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Feeds the inputs of a movie to an emulator. The emulator must be on a frame boundary whenever
+/// the player is used, that is right after `clock` returned a frame, or while paused.
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+}
+
+impl MoviePlayer {
+    /// Brings the emulator to the start of the movie, power cycling it with the settings of the
+    /// movie for the ones starting at power-on
+    pub fn new(movie: Movie, emulator: &mut Emulator) -> Result<Self, MovieError> {
+        if movie.origin != emulator.cartridge.origin() {
+            return Err(MovieError::InvalidContainer(SaveDataError::RomMismatch));
+        }
+
+        match &movie.start {
+            MovieStart::PowerOn {
+                power_on_ram,
+                ppu_warmup_cycles,
+            } => {
+                emulator.set_power_on_ram(*power_on_ram);
+                emulator.set_ppu_warmup_cycles(*ppu_warmup_cycles);
+                emulator.power_cycle();
+            }
+            MovieStart::SaveState(state) => emulator
+                .load_state(state)
+                .map_err(MovieError::InvalidSaveState)?,
+        }
+        emulator.set_four_score(movie.four_score);
+
+        Ok(Self { movie, frame: 0 })
+    }
+
+    /// Gives the inputs of the next frame to the emulator, before running it. Returns false once
+    /// the movie is over.
+    pub fn play_frame(&mut self, emulator: &mut Emulator) -> bool {
+        let frame = match self.movie.frames.get(self.frame) {
+            Some(frame) => *frame,
+            None => return false,
+        };
+        self.frame += 1;
+
+        if frame.reset {
+            emulator.soft_reset();
+        }
+        emulator.set_controller1(frame.controller1);
+        emulator.set_controller2(frame.controller2);
+        emulator.set_controller3(frame.controller3);
+        emulator.set_controller4(frame.controller4);
+        true
+    }
+
+    /// Number of frames played so far
+    pub fn position(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Stops the playback and continues recording from the current frame, dropping the rest of
+    /// the movie
+    pub fn into_recorder(mut self) -> MovieRecorder {
+        self.movie.truncate(self.frame);
+        MovieRecorder {
+            movie: self.movie,
+            reset_pending: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::counter_rom;
+
+    fn run_frame(emulator: &mut Emulator) {
+        while emulator.clock().is_none() {}
+    }
+
+    #[test]
+    fn playback_matches_recording() {
+        let rom = counter_rom();
+
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator.set_four_score(true);
+        run_frame(&mut emulator);
+
+        let mut recorder = MovieRecorder::from_current_state(&emulator);
+        for i in 0..6 {
+            if i == 3 {
+                recorder.reset(&mut emulator);
+            }
+            emulator.set_controller1(i);
+            emulator.set_controller2(0x80 | i);
+            emulator.set_controller4(0x40 | i);
+            recorder.record_frame(&emulator);
+            run_frame(&mut emulator);
+        }
+        let expected = emulator.save_state();
+
+        let movie = recorder.finish();
+        let loaded = Movie::load(&emulator, &movie.save()).unwrap();
+        assert_eq!(loaded, movie);
+        assert!(loaded.frames()[3].reset);
+        assert!(loaded.four_score());
+        assert_eq!(loaded.frames()[5].controller4, 0x45);
+
+        let mut player = MoviePlayer::new(loaded, &mut emulator).unwrap();
+        while player.play_frame(&mut emulator) {
+            run_frame(&mut emulator);
+        }
+        assert!(player.is_finished());
+        assert_eq!(emulator.save_state(), expected);
+
+        let mut data = movie.save();
+        data.truncate(data.len() - 1);
+        assert_eq!(
+            Movie::load(&emulator, &data),
+            Err(MovieError::InvalidContainer(SaveDataError::TooShort))
+        );
+    }
+
+    #[test]
+    fn playback_from_power_on_uses_its_settings() {
+        let rom = counter_rom();
+
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        emulator.set_power_on_ram(PowerOnRam::Random(5));
+        emulator.set_ppu_warmup_cycles(0);
+        emulator.power_cycle();
+
+        let mut recorder = MovieRecorder::from_power_on(&emulator);
+        for i in 0..3 {
+            emulator.set_controller1(i);
+            recorder.record_frame(&emulator);
+            run_frame(&mut emulator);
+        }
+        let expected = emulator.save_state();
+        let movie = Movie::load(&emulator, &recorder.finish().save()).unwrap();
+        assert_eq!(
+            movie.start(),
+            &MovieStart::PowerOn {
+                power_on_ram: PowerOnRam::Random(5),
+                ppu_warmup_cycles: 0,
+            }
+        );
+
+        // Played on an emulator with other settings, which already ran
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        run_frame(&mut emulator);
+        let mut player = MoviePlayer::new(movie, &mut emulator).unwrap();
+        while player.play_frame(&mut emulator) {
+            run_frame(&mut emulator);
+        }
+        assert_eq!(emulator.save_state(), expected);
+    }
+}
@@ -0,0 +1,148 @@
+// Drawing on the frames, for the overlays of scripts and tools.
+//
+// The colors are indices of the NES palette, like the pixels of the PPU. Shapes are clipped to
+// the frame, so they can be partly outside of it. The next frame rendered by the PPU replaces
+// everything drawn.
+
+use crate::ppu::{PpuFrame, FRAME_HEIGHT, FRAME_WIDTH};
+
+/// Width of the characters of `Overlay::text`, spacing included
+pub const CHAR_WIDTH: i32 = 4;
+/// Height of the characters of `Overlay::text`, spacing included
+pub const CHAR_HEIGHT: i32 = 6;
+
+pub struct Overlay<'a> {
+    frame: &'a mut PpuFrame,
+}
+
+impl<'a> Overlay<'a> {
+    pub fn new(frame: &'a mut PpuFrame) -> Self {
+        Self { frame }
+    }
+
+    pub fn pixel(&mut self, x: i32, y: i32, color: u8) {
+        if (0..FRAME_WIDTH as i32).contains(&x) && (0..FRAME_HEIGHT as i32).contains(&y) {
+            self.frame[y as usize * FRAME_WIDTH + x as usize] = color & 0x3F;
+        }
+    }
+
+    /// Draws a line from (x0, y0) to (x1, y1), both ends included
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        // Bresenham's algorithm, for every octant
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+
+        loop {
+            self.pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle
+    pub fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        self.line(x, y, right, y, color);
+        self.line(x, bottom, right, bottom, color);
+        self.line(x, y, x, bottom, color);
+        self.line(right, y, right, bottom, color);
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        for row in y.max(0)..(y + height).min(FRAME_HEIGHT as i32) {
+            for column in x.max(0)..(x + width).min(FRAME_WIDTH as i32) {
+                self.pixel(column, row, color);
+            }
+        }
+    }
+
+    /// Writes text with a 3x5 pixels font, (x, y) being its top left corner. Only digits, letters
+    /// and a few symbols are drawn, lowercase letters as uppercase ones.
+    pub fn text(&mut self, x: i32, y: i32, text: &str, color: u8) {
+        for (index, c) in text.chars().enumerate() {
+            let glyph = glyph(c.to_ascii_uppercase());
+            let left = x + index as i32 * CHAR_WIDTH;
+
+            for bit in 0..15 {
+                if glyph & (0x4000 >> bit) != 0 {
+                    self.pixel(left + bit % 3, y + bit / 3, color);
+                }
+            }
+        }
+    }
+}
+
+/// Pixels of a character, 3 bits per row from the top, the leftmost pixel in the high bit
+#[rustfmt::skip]
+fn glyph(c: char) -> u16 {
+    match c {
+        '0' => 0b111_101_101_101_111, '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111, '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001, '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111, '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111, '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101, 'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011, 'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111, 'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011, 'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111, 'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101, 'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101, 'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010, 'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011, 'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110, 'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111, 'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101, 'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010, 'Z' => 0b111_001_010_100_111,
+        ':' => 0b000_010_000_010_000, '-' => 0b000_000_111_000_000,
+        '.' => 0b000_000_000_000_010, '/' => 0b001_001_010_100_100,
+        '!' => 0b010_010_010_000_010, '$' => 0b011_110_010_011_110,
+        '=' => 0b000_111_000_111_000, '+' => 0b000_010_111_010_000,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_clipped_shapes() {
+        let mut frame = [0u8; FRAME_WIDTH * FRAME_HEIGHT];
+        let mut overlay = Overlay::new(&mut frame);
+        overlay.fill_rect(-2, -2, 4, 4, 0x16);
+        overlay.line(10, 10, 13, 12, 0x2A);
+        overlay.rect(250, 230, 20, 20, 0x30);
+        overlay.text(100, 100, "1a", 0x20);
+
+        let at = |x: usize, y: usize| frame[y * FRAME_WIDTH + x];
+        assert_eq!([at(0, 0), at(1, 1), at(2, 2)], [0x16, 0x16, 0]);
+        assert_eq!([at(10, 10), at(11, 11), at(12, 11), at(13, 12)], [0x2A; 4]);
+        assert_eq!(
+            [at(250, 230), at(255, 230), at(250, 239), at(251, 231)],
+            [0x30, 0x30, 0x30, 0]
+        );
+
+        // The top rows of "1" and "A"
+        let row: alloc::vec::Vec<_> = (100..107).map(|x| at(x, 100)).collect();
+        assert_eq!(row, [0, 0x20, 0, 0, 0, 0x20, 0]);
+        assert_eq!(frame.iter().filter(|&&pixel| pixel == 0x20).count(), 8 + 10);
+    }
+}
@@ -0,0 +1,85 @@
+// Cropping of the edges of the frames, which the bezel of a TV hides.
+// https://wiki.nesdev.com/w/index.php/Overscan
+//
+// Games often leave garbage there, like the tiles updated while scrolling, so frontends usually
+// crop a few lines. The frames of the PPU are always complete: the overscan only applies to the
+// images made from them, like `Emulator::frame_rgba` and the screenshots.
+
+use crate::ppu::{PpuFrame, FRAME_HEIGHT, FRAME_WIDTH};
+
+/// Number of pixels cropped on each side of the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overscan {
+    pub top: u8,
+    pub bottom: u8,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl Overscan {
+    /// The whole frame is shown
+    pub const NONE: Self = Self::new(0, 0, 0, 0);
+    /// The 8 lines at the top and bottom, hidden by most NTSC TVs
+    pub const NTSC: Self = Self::new(8, 8, 0, 0);
+
+    pub const fn new(top: u8, bottom: u8, left: u8, right: u8) -> Self {
+        Self {
+            top,
+            bottom,
+            left,
+            right,
+        }
+    }
+
+    /// Whether some of the frame is left once cropped
+    pub fn is_valid(&self) -> bool {
+        usize::from(self.top) + usize::from(self.bottom) < FRAME_HEIGHT
+            && usize::from(self.left) + usize::from(self.right) < FRAME_WIDTH
+    }
+
+    /// Width and height of the cropped frames
+    pub fn visible_size(&self) -> (usize, usize) {
+        (
+            FRAME_WIDTH.saturating_sub(usize::from(self.left) + usize::from(self.right)),
+            FRAME_HEIGHT.saturating_sub(usize::from(self.top) + usize::from(self.bottom)),
+        )
+    }
+
+    /// Visible part of the lines of a frame, from the top
+    pub fn visible_lines<'a>(&self, frame: &'a PpuFrame) -> impl Iterator<Item = &'a [u8]> {
+        let (width, height) = self.visible_size();
+        let left = usize::from(self.left);
+
+        frame
+            .chunks_exact(FRAME_WIDTH)
+            .skip(usize::from(self.top))
+            .take(height)
+            .map(move |line| &line[left..left + width])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crops_frame() {
+        let mut frame = [0u8; FRAME_WIDTH * FRAME_HEIGHT];
+        frame[8 * FRAME_WIDTH + 2] = 0x16;
+        frame[FRAME_WIDTH * FRAME_HEIGHT - 1] = 0x30;
+
+        let overscan = Overscan::new(8, 4, 2, 0);
+        assert_eq!(overscan.visible_size(), (254, 228));
+        let lines: alloc::vec::Vec<_> = overscan.visible_lines(&frame).collect();
+        assert_eq!(lines.len(), 228);
+        assert_eq!(lines[0][0], 0x16);
+        assert!(lines.iter().all(|line| line.len() == 254));
+
+        assert_eq!(
+            Overscan::NONE.visible_lines(&frame).last().unwrap()[255],
+            0x30
+        );
+        assert!(Overscan::NTSC.is_valid());
+        assert!(!Overscan::new(120, 120, 0, 0).is_valid());
+    }
+}
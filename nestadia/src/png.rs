@@ -0,0 +1,150 @@
+// PNG encoding of the frames, for screenshots.
+// https://www.w3.org/TR/png/
+//
+// The frames are written as indexed images: the palette of the PNG is `RGB_PALETTE`, and the
+// pixels are the palette indices of the PPU. The image data is stored in a zlib stream without
+// compression, as the frames are small and this keeps the encoder short.
+
+use alloc::vec::Vec;
+
+use crate::ppu::PpuFrame;
+use crate::{Overscan, RGB_PALETTE};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Largest payload of a stored deflate block
+const MAX_STORED_BLOCK_LEN: usize = 0xFFFF;
+
+/// Encodes a frame to a PNG file
+pub fn encode_png(frame: &PpuFrame) -> Vec<u8> {
+    encode_png_with(frame, &RGB_PALETTE, Overscan::NONE)
+}
+
+/// Same as `encode_png`, with the colors of `palette` and without the `overscan`
+pub(crate) fn encode_png_with(
+    frame: &PpuFrame,
+    palette: &[[u8; 3]; 64],
+    overscan: Overscan,
+) -> Vec<u8> {
+    let (width, height) = overscan.visible_size();
+    let mut png = Vec::from(SIGNATURE);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per pixel, indexed color, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let palette: Vec<u8> = palette.iter().flatten().copied().collect();
+    write_chunk(&mut png, b"PLTE", &palette);
+
+    // Every line starts with its filter type, none here
+    let mut pixels = Vec::with_capacity(height * (width + 1));
+    for line in overscan.visible_lines(frame) {
+        pixels.push(0);
+        pixels.extend(line.iter().map(|index| index & 0x3F));
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32KB window, no dictionary, fastest compression
+    let mut stream = Vec::from([0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        stream.push(u8::from(is_final));
+
+        let len = block.len() as u16;
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFF_FFFF, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + u32::from(*byte)) % MODULO;
+        (a, (b + a) % MODULO)
+    });
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::TryInto as _;
+
+    use super::*;
+    use crate::ppu::{FRAME_HEIGHT, FRAME_WIDTH};
+
+    #[test]
+    fn encodes_frame() {
+        let mut frame = [0x0Fu8; FRAME_WIDTH * FRAME_HEIGHT];
+        frame[FRAME_WIDTH + 2] = 0x30;
+        let png = encode_png(&frame);
+
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(png[12..16], *b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 1, 0, 0, 0, 0, 240]);
+        // The IEND chunk always has the same CRC
+        assert_eq!(
+            png[png.len() - 8..],
+            [b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+
+        // Unwraps the stored blocks
+        let idat = png.windows(4).position(|w| w == b"IDAT").unwrap();
+        let len = u32::from_be_bytes(png[idat - 4..idat].try_into().unwrap()) as usize;
+        let mut stream = &png[idat + 4 + 2..idat + 4 + len - 4];
+        let mut pixels = Vec::new();
+        while !stream.is_empty() {
+            let block_len = usize::from(u16::from_le_bytes([stream[1], stream[2]]));
+            pixels.extend_from_slice(&stream[5..5 + block_len]);
+            stream = &stream[5 + block_len..];
+        }
+
+        assert_eq!(pixels.len(), FRAME_HEIGHT * (FRAME_WIDTH + 1));
+        assert_eq!(pixels[..3], [0, 0x0F, 0x0F]);
+        assert_eq!(
+            pixels[FRAME_WIDTH + 1..FRAME_WIDTH + 5],
+            [0, 0x0F, 0x0F, 0x30]
+        );
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let cropped = encode_png_with(&frame, &RGB_PALETTE, Overscan::NTSC);
+        assert_eq!(cropped[16..24], [0, 0, 1, 0, 0, 0, 0, 224]);
+    }
+}
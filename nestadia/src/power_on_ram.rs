@@ -0,0 +1,103 @@
+// Content of the CPU RAM at power-on.
+//
+// The RAM of a real console holds an unpredictable pattern at power-on, which some games use as
+// a source of randomness. Every option here is reproducible: the random one comes from a seeded
+// generator, so the same seed always gives the same RAM. Along with the rest of the emulation,
+// which never looks at the wall clock, this makes a run entirely determined by its inputs.
+
+use crate::state::{SaveStateError, StateReader, StateWriter, Stateful};
+
+/// Suggested seed of `PowerOnRam::Random`, also used in place of 0
+pub const DEFAULT_POWER_ON_RAM_SEED: u64 = 0x6E65_7374_6164_6961;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerOnRam {
+    /// Every byte is $00
+    #[default]
+    Zeros,
+    /// Every byte is $FF
+    Ones,
+    /// Blocks of 4 $00 bytes and 4 $FF bytes, like FCEUX. Its movies expect this pattern.
+    Pattern,
+    /// Bytes from a xorshift64* generator starting from the seed
+    Random(u64),
+}
+
+impl PowerOnRam {
+    pub fn fill(&self, ram: &mut [u8]) {
+        match *self {
+            Self::Zeros => ram.fill(0x00),
+            Self::Ones => ram.fill(0xFF),
+            Self::Pattern => ram
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, byte)| *byte = if i & 0x04 == 0 { 0x00 } else { 0xFF }),
+            Self::Random(seed) => {
+                // xorshift64* can't leave the zero state
+                let mut state = if seed == 0 {
+                    DEFAULT_POWER_ON_RAM_SEED
+                } else {
+                    seed
+                };
+
+                for byte in ram {
+                    state ^= state >> 12;
+                    state ^= state << 25;
+                    state ^= state >> 27;
+                    *byte = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8;
+                }
+            }
+        }
+    }
+}
+
+impl Stateful for PowerOnRam {
+    fn save_state(&self, state: &mut StateWriter) {
+        match *self {
+            Self::Zeros => 0u8.save_state(state),
+            Self::Ones => 1u8.save_state(state),
+            Self::Pattern => 2u8.save_state(state),
+            Self::Random(seed) => {
+                3u8.save_state(state);
+                seed.save_state(state);
+            }
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut tag = 0u8;
+        tag.load_state(state)?;
+
+        *self = match tag {
+            0 => Self::Zeros,
+            1 => Self::Ones,
+            2 => Self::Pattern,
+            3 => {
+                let mut seed = 0u64;
+                seed.load_state(state)?;
+                Self::Random(seed)
+            }
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_ram() {
+        let mut ram = [0x12u8; 16];
+        PowerOnRam::Pattern.fill(&mut ram);
+        assert_eq!(ram[..10], [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+
+        let mut other = [0u8; 16];
+        PowerOnRam::Random(1).fill(&mut ram);
+        PowerOnRam::Random(1).fill(&mut other);
+        assert_eq!(ram, other);
+        PowerOnRam::Random(2).fill(&mut other);
+        assert_ne!(ram, other);
+    }
+}
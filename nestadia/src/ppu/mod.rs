@@ -10,6 +10,9 @@ pub const FRAME_HEIGHT: usize = 240;
 
 pub type PpuFrame = [u8; FRAME_WIDTH * FRAME_HEIGHT];
 
+/// Number of sprites in the OAM
+const MAX_SPRITES: usize = 64;
+
 pub struct Ppu {
     // Internal memory
     palette_table: [u8; 32],    // For color stuff
@@ -19,9 +22,11 @@ pub struct Ppu {
     // Rendering pipeline memory
     pattern_pipeline: [u16; 2], // Shift registers that contains the next 8 pixels
     palette_pipeline: [u16; 2], // Contains the palette attributes for the next 8 pixels
-    sprites_pipeline: [u8; 8 * 2], // Contains the pattern info for the currently loaded sprites
-    sprites_attributes: [u8; 8], // Attribute bytes for the currently loaded sprites
-    sprites_x_counter: [SpriteXCounter; 8], // X counter for the currently loaded sprites
+    // The 8 first sprites are the ones of the hardware, the others are only loaded without the sprite limit
+    sprites_pipeline: [u8; MAX_SPRITES * 2], // Contains the pattern info for the currently loaded sprites
+    sprites_attributes: [u8; MAX_SPRITES],   // Attribute bytes for the currently loaded sprites
+    sprites_x_counter: [SpriteXCounter; MAX_SPRITES], // X counter for the currently loaded sprites
+    extra_sprites: u8, // Number of sprites loaded beyond the 8 of the hardware
     sprite_evaluation_state: SpriteEvalutationState, // State machine for the sprite evaluation process
     oam_pointer: u8, // Pointer to a primary OAM entry during the sprite evaluation phase. Known as `n` on the wiki.
     secondary_oam_pointer: u8, // Pointer to the secondary OAM entry during the sprite evaluation phase.
@@ -43,10 +48,13 @@ pub struct Ppu {
     cycle_count: u16,
     scanline: i16,
     frame: PpuFrame,
+    skip_output: bool, // The pixels aren't written to the frame while fast-forwarding
+    sprite_limit: bool, // Setting, only 8 sprites are drawn per scanline when enabled
     vblank_nmi_set: bool,
     last_data_on_bus: u8,
     sprite_zero_hit_state: SpriteZeroHitState,
     is_odd_frame: bool,
+    warmup_dots: u32, // Writes to some registers are ignored until this reaches 0
 
     // Buffers for cycle-accurate reads
     nt_buffer: u8,
@@ -55,6 +63,43 @@ pub struct Ppu {
     bg_hi_buffer: u8,
 }
 
+impl_stateful!(Ppu {
+    palette_table,
+    oam_data,
+    secondary_oam,
+    pattern_pipeline,
+    palette_pipeline,
+    sprites_pipeline,
+    sprites_attributes,
+    sprites_x_counter,
+    extra_sprites,
+    sprite_evaluation_state,
+    oam_pointer,
+    secondary_oam_pointer,
+    oam_latch,
+    oam_temp_y_buffer,
+    oam_temp_tile_buffer,
+    ctrl_reg,
+    mask_reg,
+    status_reg,
+    oam_addr_reg,
+    vram_addr,
+    temp_vram_addr,
+    fine_x,
+    write_latch,
+    cycle_count,
+    scanline,
+    vblank_nmi_set,
+    last_data_on_bus,
+    sprite_zero_hit_state,
+    is_odd_frame,
+    warmup_dots,
+    nt_buffer,
+    at_buffer,
+    bg_lo_buffer,
+    bg_hi_buffer
+});
+
 impl Default for Ppu {
     fn default() -> Self {
         Self::new()
@@ -70,9 +115,10 @@ impl Ppu {
 
             pattern_pipeline: [0u16; 2],
             palette_pipeline: [0u16; 2],
-            sprites_pipeline: [0u8; 8 * 2],
-            sprites_attributes: [0u8; 8],
-            sprites_x_counter: Default::default(),
+            sprites_pipeline: [0u8; MAX_SPRITES * 2],
+            sprites_attributes: [0u8; MAX_SPRITES],
+            sprites_x_counter: [SpriteXCounter::WontRender; MAX_SPRITES],
+            extra_sprites: 0,
             sprite_evaluation_state: Default::default(),
             oam_pointer: 0,
             secondary_oam_pointer: 0,
@@ -92,10 +138,13 @@ impl Ppu {
             cycle_count: 0,
             scanline: -1,
             frame: [0u8; 256 * 240],
+            skip_output: false,
+            sprite_limit: true,
             vblank_nmi_set: false,
             last_data_on_bus: 0,
             sprite_zero_hit_state: Default::default(),
             is_odd_frame: false,
+            warmup_dots: 0,
 
             nt_buffer: 0,
             at_buffer: 0,
@@ -105,7 +154,76 @@ impl Ppu {
     }
 
     pub fn reset(&mut self) {
-        *self = Default::default()
+        *self = Self {
+            sprite_limit: self.sprite_limit,
+            ..Default::default()
+        }
+    }
+
+    /// Reset signal while running: PPUCTRL, PPUMASK, the scroll and the write latch are
+    /// cleared. The memories and PPUADDR are kept.
+    /// http://wiki.nesdev.com/w/index.php/PPU_power_up_state
+    pub fn soft_reset(&mut self) {
+        self.ctrl_reg = Default::default();
+        self.mask_reg = Default::default();
+        self.temp_vram_addr = Default::default();
+        self.fine_x = 0;
+        self.write_latch = false;
+        self.is_odd_frame = false;
+    }
+
+    /// Scanline being rendered, from -1 (pre-render) to 260
+    pub fn scanline(&self) -> i16 {
+        self.scanline
+    }
+
+    /// Dot of the scanline being rendered, from 0 to 340
+    pub fn dot(&self) -> u16 {
+        self.cycle_count
+    }
+
+    /// Registers by their name on the wiki, including the internal ones and the position of the
+    /// rendering, for the comparison of the save states
+    pub(crate) fn named_registers(&self) -> [(&'static str, i64); 10] {
+        [
+            ("PPUCTRL", i64::from(self.ctrl_reg.bits())),
+            ("PPUMASK", i64::from(self.mask_reg.bits())),
+            ("PPUSTATUS", i64::from(self.status_reg.bits())),
+            ("OAMADDR", i64::from(self.oam_addr_reg)),
+            ("v", i64::from(self.vram_addr.get())),
+            ("t", i64::from(self.temp_vram_addr.get())),
+            ("x", i64::from(self.fine_x)),
+            ("w", i64::from(self.write_latch)),
+            ("scanline", i64::from(self.scanline)),
+            ("dot", i64::from(self.cycle_count)),
+        ]
+    }
+
+    /// Starts the warm-up period during which writes to PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR are ignored.
+    /// http://wiki.nesdev.com/w/index.php/PPU_power_up_state
+    pub fn start_warmup(&mut self, cpu_cycles: u32) {
+        // The PPU is clocked 3 times per CPU cycle
+        self.warmup_dots = cpu_cycles.saturating_mul(3);
+    }
+
+    /// Stops writing the pixels to the frame. Everything else is still emulated, including the
+    /// sprite 0 hits.
+    pub fn set_output_skipped(&mut self, skipped: bool) {
+        self.skip_output = skipped;
+    }
+
+    pub fn is_output_skipped(&self) -> bool {
+        self.skip_output
+    }
+
+    /// Disabling the sprite limit draws all the sprites of a scanline instead of only 8, which
+    /// removes the flickering of some games. The sprite overflow flag is still set.
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+    }
+
+    pub fn sprite_limit(&self) -> bool {
+        self.sprite_limit
     }
 
     pub fn take_vblank_nmi_set_state(&mut self) -> bool {
@@ -117,6 +235,14 @@ impl Ppu {
     pub fn write(&mut self, bus: &mut PpuBus<'_>, addr: u16, data: u8) {
         let addr = addr & 0x07; // mirror
 
+        if self.warmup_dots > 0 && matches!(addr, 0 | 1 | 5 | 6) {
+            log::debug!(
+                "Ignored write to PPU register {:#X} during the warm-up period",
+                addr
+            );
+            return;
+        }
+
         match addr {
             0 => {
                 // Write Control register
@@ -203,14 +329,7 @@ impl Ppu {
                     0x3000..=0x3EFF => log::warn!("address space 0x3000..0x3EFF is not expected to be used, but it was attempted to write at 0x{:#X}", write_addr),
 
                     // Palette table:
-                    0x3F00..=0x3FFF => {
-                        if write_addr & 0b11 == 0 {
-                            // Mirror to the universal background color
-                            self.palette_table[usize::from(write_addr & 0x0f)] = data;
-                        } else {
-                            self.palette_table[usize::from(write_addr & 0x1f)] = data;
-                        }
-                    },
+                    0x3F00..=0x3FFF => self.write_palette(write_addr, data),
 
                     _ => unreachable!("unexpected write to mirrored space {:#X}", write_addr),
                 }
@@ -228,6 +347,37 @@ impl Ppu {
         }
     }
 
+    /// Reads a register like `read`, but without changing anything
+    pub fn peek_register(&self, addr: u16) -> u8 {
+        match addr & 0x07 {
+            2 => self.status_reg.read() | self.last_data_on_bus & 0x1F,
+            4 => self.oam_data[self.oam_addr_reg as usize],
+            7 => match self.vram_addr.get() & 0x3fff {
+                0x3F00..=0x3FFF => self.read_palette(self.vram_addr.get()),
+                // The data read comes from the buffer
+                _ => self.last_data_on_bus,
+            },
+            _ => 0,
+        }
+    }
+
+    /// Reads the palette RAM, mapped at $3F00-$3FFF
+    pub fn read_palette(&self, addr: u16) -> u8 {
+        self.palette_table[palette_index(addr)]
+    }
+
+    pub fn write_palette(&mut self, addr: u16, data: u8) {
+        self.palette_table[palette_index(addr)] = data;
+    }
+
+    pub fn oam(&self) -> &[u8; 64 * 4] {
+        &self.oam_data
+    }
+
+    pub fn write_oam(&mut self, addr: u8, data: u8) {
+        self.oam_data[usize::from(addr)] = data;
+    }
+
     pub fn read(&mut self, bus: &mut PpuBus<'_>, addr: u16) -> u8 {
         let addr = addr & 0x07; // mirror
 
@@ -291,14 +441,7 @@ impl Ppu {
                     }
 
                     // Palette table:
-                    0x3F00..=0x3FFF => {
-                        if read_addr & 0b11 == 0 {
-                            // Mirror to the universal background color
-                            self.palette_table[usize::from(read_addr & 0x0f)]
-                        } else {
-                            self.palette_table[usize::from(read_addr & 0x1f)]
-                        }
-                    }
+                    0x3F00..=0x3FFF => self.read_palette(read_addr),
 
                     _ => unreachable!("unexpected access to mirrored space {:#X}", read_addr),
                 }
@@ -310,6 +453,15 @@ impl Ppu {
         }
     }
 
+    /// Last frame produced, complete or not
+    pub fn frame(&self) -> &PpuFrame {
+        &self.frame
+    }
+
+    pub fn frame_mut(&mut self) -> &mut PpuFrame {
+        &mut self.frame
+    }
+
     pub fn ready_frame(&mut self) -> Option<&PpuFrame> {
         if self.cycle_count == 256 && self.scanline == 239 {
             // Yeah! We got a frame ready
@@ -321,9 +473,12 @@ impl Ppu {
 
     /// Returns frame when it's ready
     pub fn clock(&mut self, bus: &mut PpuBus) {
+        self.warmup_dots = self.warmup_dots.saturating_sub(1);
         self.cycle_count += 1;
 
         if self.cycle_count >= 341 {
+            bus.end_of_scanline();
+
             self.cycle_count = 0;
             self.scanline += 1;
 
@@ -395,7 +550,8 @@ impl Ppu {
             if self.scanline < 240 {
                 if self.scanline == -1 {
                     // Sprites are not loaded during the pre-render scanline
-                    self.sprites_x_counter = Default::default();
+                    self.sprites_x_counter = [SpriteXCounter::WontRender; MAX_SPRITES];
+                    self.extra_sprites = 0;
                 } else {
                     self.sprites_load_cycle(bus);
                 };
@@ -413,6 +569,10 @@ impl Ppu {
                     self.bg_load_cycle(bus);
                 } else if self.cycle_count == 257 {
                     self.vram_addr.reset_x(&self.temp_vram_addr);
+                } else if self.cycle_count == 338 || self.cycle_count == 340 {
+                    // Unused NT fetches, which the MMC5 relies on to detect scanlines
+                    let address = (self.vram_addr.get() & 0xfff) | 0x2000;
+                    bus.read_name_tables(address);
                 };
             }
         }
@@ -496,7 +656,7 @@ impl Ppu {
 
     fn set_pixel(&mut self, x: u16, y: u16, color: u8) {
         let idx = y as usize * FRAME_WIDTH + x as usize;
-        if idx < self.frame.len() {
+        if !self.skip_output && idx < self.frame.len() {
             self.frame[idx] = color;
         }
     }
@@ -536,7 +696,7 @@ impl Ppu {
         // Here I use this pattern to  make sure that all counter gets decremented even if the right pixel has been found
         let mut pixel = None;
 
-        for sprite_idx in 0..self.sprites_x_counter.len() {
+        for sprite_idx in 0..8 + usize::from(self.extra_sprites) {
             match self.sprites_x_counter[sprite_idx] {
                 SpriteXCounter::NotRendered(mut x) => {
                     x -= 1;
@@ -564,8 +724,8 @@ impl Ppu {
                         let lo = self.sprites_pipeline[sprite_idx] & 0b1;
                         self.sprites_pipeline[sprite_idx] >>= 1;
 
-                        let hi = self.sprites_pipeline[8 | sprite_idx] & 0b1;
-                        self.sprites_pipeline[8 | sprite_idx] >>= 1;
+                        let hi = self.sprites_pipeline[MAX_SPRITES + sprite_idx] & 0b1;
+                        self.sprites_pipeline[MAX_SPRITES + sprite_idx] >>= 1;
 
                         let sprite_pat = (hi << 1) | lo;
 
@@ -805,137 +965,128 @@ impl Ppu {
                         };
                     }
                     5 => {
-                        let y = (self.scanline as u16).wrapping_sub(self.oam_temp_y_buffer as u16);
                         let attributes = self.sprites_attributes[sprite_idx as usize];
-
-                        if self.ctrl_reg.sprite_size() == 8 {
-                            // 8x8 sprites
-                            let bank: u16 = self.ctrl_reg.sprite_pattern_base_addr();
-
-                            let flipped_y = if attributes >> 7 & 1 == 1 {
-                                // Y flipped
-                                7u16.wrapping_sub(y)
-                            } else {
-                                y
-                            };
-
-                            let lo = bus.read_chr_mem(
-                                bank | ((self.oam_temp_tile_buffer as u16) << 4)
-                                    | (flipped_y as u16),
-                            );
-
-                            let lo = if attributes >> 6 & 1 == 1 {
-                                // X flipped
-                                lo
-                            } else {
-                                lo.reverse_bits()
-                            };
-
-                            self.sprites_pipeline[sprite_idx as usize] = lo;
-                        } else {
-                            // 8x16 sprites
-                            let bank = if self.oam_temp_tile_buffer & 0b1 == 1 {
-                                0x1000
-                            } else {
-                                0x0000
-                            };
-                            let tile_idx = self.oam_temp_tile_buffer as u16 & 0xfffe;
-
-                            let flipped_y = if attributes >> 7 & 1 == 1 {
-                                // It's flipped vertically
-                                15u16.wrapping_sub(y)
-                            } else {
-                                y
-                            };
-
-                            // This is because of the hi/lo parts of the pattern memory
-                            let flipped_y = if flipped_y >= 8 {
-                                flipped_y.wrapping_add(8)
-                            } else {
-                                flipped_y
-                            };
-
-                            let lo = bus.read_chr_mem(bank | (tile_idx << 4) | (flipped_y as u16));
-
-                            let lo = if attributes >> 6 & 1 == 1 {
-                                // X flipped
-                                lo
-                            } else {
-                                lo.reverse_bits()
-                            };
-
-                            self.sprites_pipeline[sprite_idx as usize] = lo;
-                        }
+                        self.sprites_pipeline[sprite_idx as usize] = self.fetch_sprite_pattern(
+                            bus,
+                            self.oam_temp_y_buffer,
+                            self.oam_temp_tile_buffer,
+                            attributes,
+                            0,
+                        );
                     }
                     7 => {
-                        let y = (self.scanline as u16).wrapping_sub(self.oam_temp_y_buffer as u16);
                         let attributes = self.sprites_attributes[sprite_idx as usize];
+                        self.sprites_pipeline[MAX_SPRITES + sprite_idx as usize] = self
+                            .fetch_sprite_pattern(
+                                bus,
+                                self.oam_temp_y_buffer,
+                                self.oam_temp_tile_buffer,
+                                attributes,
+                                8,
+                            );
 
-                        if self.ctrl_reg.sprite_size() == 8 {
-                            // 8x8 sprites
-                            let bank: u16 = self.ctrl_reg.sprite_pattern_base_addr();
+                        if sprite_idx == 7 {
+                            self.load_extra_sprites(bus);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
 
-                            let flipped_y = if attributes >> 7 & 1 == 1 {
-                                // Y flipped
-                                7u16.wrapping_sub(y)
-                            } else {
-                                y
-                            };
+    /// Reads a plane of the pattern of a sprite on the next scanline, `plane` being 0 for the
+    /// low bits and 8 for the high bits. The bits are returned in rendering order.
+    fn fetch_sprite_pattern(
+        &self,
+        bus: &mut PpuBus,
+        sprite_y: u8,
+        tile: u8,
+        attributes: u8,
+        plane: u16,
+    ) -> u8 {
+        let y = (self.scanline as u16).wrapping_sub(sprite_y as u16);
+
+        let address = if self.ctrl_reg.sprite_size() == 8 {
+            // 8x8 sprites
+            let bank: u16 = self.ctrl_reg.sprite_pattern_base_addr();
+
+            let flipped_y = if attributes >> 7 & 1 == 1 {
+                // Y flipped
+                7u16.wrapping_sub(y)
+            } else {
+                y
+            };
 
-                            let hi = bus.read_chr_mem(
-                                bank | 8
-                                    | ((self.oam_temp_tile_buffer as u16) << 4)
-                                    | (flipped_y as u16),
-                            );
+            bank | ((tile as u16) << 4) | flipped_y
+        } else {
+            // 8x16 sprites
+            let bank = if tile & 0b1 == 1 { 0x1000 } else { 0x0000 };
+            let tile_idx = tile as u16 & 0xfffe;
 
-                            let hi = if attributes >> 6 & 1 == 1 {
-                                // X flipped
-                                hi
-                            } else {
-                                hi.reverse_bits()
-                            };
+            let flipped_y = if attributes >> 7 & 1 == 1 {
+                // It's flipped vertically
+                15u16.wrapping_sub(y)
+            } else {
+                y
+            };
 
-                            self.sprites_pipeline[8 | sprite_idx as usize] = hi;
-                        } else {
-                            // 8x16 sprites
-                            let bank = if self.oam_temp_tile_buffer & 0b1 == 1 {
-                                0x1000
-                            } else {
-                                0x0000
-                            };
-                            let tile_idx = self.oam_temp_tile_buffer as u16 & 0xfffe;
+            // This is because of the hi/lo parts of the pattern memory
+            let flipped_y = if flipped_y >= 8 {
+                flipped_y.wrapping_add(8)
+            } else {
+                flipped_y
+            };
 
-                            let flipped_y = if attributes >> 7 & 1 == 1 {
-                                // It's flipped vertically
-                                15u16.wrapping_sub(y)
-                            } else {
-                                y
-                            };
+            bank | (tile_idx << 4) | flipped_y
+        };
 
-                            // This is because of the hi/lo parts of the pattern memory
-                            let flipped_y = if flipped_y >= 8 {
-                                flipped_y.wrapping_add(8)
-                            } else {
-                                flipped_y
-                            };
+        let pattern = bus.read_chr_mem(address | plane);
+        if attributes >> 6 & 1 == 1 {
+            // X flipped
+            pattern
+        } else {
+            pattern.reverse_bits()
+        }
+    }
 
-                            let hi =
-                                bus.read_chr_mem(bank | 8 | (tile_idx << 4) | (flipped_y as u16));
+    /// Without the sprite limit, loads the sprites of the next scanline that the hardware
+    /// skipped, after the 8 first ones. They are fetched all at once after the regular fetches.
+    fn load_extra_sprites(&mut self, bus: &mut PpuBus) {
+        self.extra_sprites = 0;
+        if self.sprite_limit {
+            return;
+        }
 
-                            let hi = if attributes >> 6 & 1 == 1 {
-                                // X flipped
-                                hi
-                            } else {
-                                hi.reverse_bits()
-                            };
+        let mut sprites_in_range = 0;
+        for sprite in 0..MAX_SPRITES {
+            let y = self.oam_data[sprite << 2];
+            if (self.scanline as u8).wrapping_sub(y) >= self.ctrl_reg.sprite_size() {
+                continue;
+            }
 
-                            self.sprites_pipeline[8 | sprite_idx as usize] = hi;
-                        }
-                    }
-                    _ => {}
-                }
+            sprites_in_range += 1;
+            if sprites_in_range <= 8 {
+                // Already loaded by the hardware
+                continue;
             }
-            _ => {}
+
+            let slot = 8 + usize::from(self.extra_sprites);
+            let tile = self.oam_data[sprite << 2 | 1];
+            let attributes = self.oam_data[sprite << 2 | 2];
+            let x = self.oam_data[sprite << 2 | 3];
+
+            self.sprites_attributes[slot] = attributes;
+            self.sprites_x_counter[slot] = if x == 0 {
+                SpriteXCounter::Rendering(0)
+            } else {
+                SpriteXCounter::NotRendered(x)
+            };
+            self.sprites_pipeline[slot] = self.fetch_sprite_pattern(bus, y, tile, attributes, 0);
+            self.sprites_pipeline[MAX_SPRITES + slot] =
+                self.fetch_sprite_pattern(bus, y, tile, attributes, 8);
+            self.extra_sprites += 1;
         }
     }
 
@@ -945,6 +1096,15 @@ impl Ppu {
     }
 }
 
+fn palette_index(addr: u16) -> usize {
+    if addr & 0b11 == 0 {
+        // Mirror to the universal background color
+        usize::from(addr & 0x0f)
+    } else {
+        usize::from(addr & 0x1f)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -1154,6 +1314,29 @@ pub mod test {
         assert_eq!(emu.ppu.status_reg.read() >> 7, 0);
     }
 
+    #[test]
+    fn writes_ignored_during_warmup() {
+        let mut emu = mock_emu_horizontal();
+        emu.ppu.start_warmup(1);
+        let mut bus = borrow_ppu_bus!(emu);
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(!emu
+            .ppu
+            .ctrl_reg
+            .contains(registers::ControlReg::GENERATE_NMI));
+
+        for _ in 0..3 {
+            emu.ppu.clock(&mut bus);
+        }
+
+        emu.ppu.write(&mut bus, 0x2000, 0x80);
+        assert!(emu
+            .ppu
+            .ctrl_reg
+            .contains(registers::ControlReg::GENERATE_NMI));
+    }
+
     #[test]
     fn oam_read_write() {
         let mut emu = mock_emu_horizontal();
@@ -1170,6 +1353,48 @@ pub mod test {
         assert_eq!(emu.ppu.read(&mut bus, 0x2004), 0x77);
     }
 
+    #[test]
+    fn sprite_limit_can_be_disabled() {
+        // NROM with CHR RAM
+        let mut rom = alloc::vec![0u8; 16 + 0x4000];
+        rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 1, 0, 0x00, 0x00]);
+
+        // 9 sprites on the scanlines 21 to 28, 10 pixels apart, with a solid tile
+        let mut oam = [0xFF; 256];
+        for sprite in 0..9 {
+            oam[sprite * 4..sprite * 4 + 4].copy_from_slice(&[20, 1, 0, sprite as u8 * 10]);
+        }
+
+        for sprite_limit in [true, false] {
+            let mut emu = mock_emu(&rom);
+            emu.ppu.set_sprite_limit(sprite_limit);
+            let mut bus = borrow_ppu_bus!(emu);
+
+            emu.ppu.write(&mut bus, 0x2006, 0x00);
+            emu.ppu.write(&mut bus, 0x2006, 0x10);
+            for _ in 0..8 {
+                emu.ppu.write(&mut bus, 0x2007, 0xFF);
+            }
+            emu.ppu.write(&mut bus, 0x2006, 0x3F);
+            emu.ppu.write(&mut bus, 0x2006, 0x11);
+            emu.ppu.write(&mut bus, 0x2007, 0x16);
+            emu.ppu.write_oam_dma(&oam);
+            emu.ppu.write(&mut bus, 0x2001, 0x14); // Sprites only
+
+            while emu.ppu.scanline != 30 {
+                emu.ppu.clock(&mut bus);
+            }
+
+            let line = &emu.ppu.frame()[21 * FRAME_WIDTH..22 * FRAME_WIDTH];
+            assert_eq!(line[75], 0x16);
+            assert_eq!(line[85] == 0x16, !sprite_limit);
+            assert!(emu
+                .ppu
+                .status_reg
+                .contains(registers::StatusReg::SPRITE_OVERFLOW));
+        }
+    }
+
     #[test]
     fn oam_dma() {
         let mut emu = mock_emu_horizontal();
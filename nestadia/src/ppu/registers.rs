@@ -1,6 +1,8 @@
 use bitfield::bitfield;
 use bitflags::bitflags;
 
+use crate::state::{SaveStateError, StateReader, StateWriter, Stateful};
+
 bitfield! {
     /// A Vram address. Used to read and write on the PPU bus and during rendering
     #[derive(Clone, Copy)]
@@ -25,6 +27,16 @@ impl Default for VramAddr {
     }
 }
 
+impl Stateful for VramAddr {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.0.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0.load_state(state)
+    }
+}
+
 impl VramAddr {
     pub fn get(&self) -> u16 {
         self.0
@@ -258,3 +270,5 @@ impl MaskReg {
         self.bits = data;
     }
 }
+
+impl_stateful_bitflags!(ControlReg, StatusReg, MaskReg);
@@ -1,3 +1,5 @@
+use crate::state::{SaveStateError, StateReader, StateWriter, Stateful};
+
 /// State machine for the sprite evaluation phase.
 #[derive(Clone, Copy)]
 pub enum SpriteEvalutationState {
@@ -67,3 +69,83 @@ impl Default for SpriteZeroHitState {
         Self::Idle
     }
 }
+
+// The enums are saved as a tag, followed by their inner value
+
+impl Stateful for SpriteEvalutationState {
+    fn save_state(&self, state: &mut StateWriter) {
+        let (tag, value) = match *self {
+            Self::Idle => (0u8, 0),
+            Self::CheckY => (1, 0),
+            Self::CopyOam(index) => (2, index),
+            Self::EvaluateOverflow(m) => (3, m),
+        };
+        (tag, value).save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut tag_value = (0u8, 0u8);
+        tag_value.load_state(state)?;
+
+        *self = match tag_value {
+            (0, _) => Self::Idle,
+            (1, _) => Self::CheckY,
+            (2, index) => Self::CopyOam(index),
+            (3, m) => Self::EvaluateOverflow(m),
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl Stateful for SpriteXCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        let (tag, value) = match *self {
+            Self::WontRender => (0u8, 0),
+            Self::NotRendered(x) => (1, x),
+            Self::Rendering(pixels) => (2, pixels),
+            Self::Rendered => (3, 0),
+        };
+        (tag, value).save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut tag_value = (0u8, 0u8);
+        tag_value.load_state(state)?;
+
+        *self = match tag_value {
+            (0, _) => Self::WontRender,
+            (1, x) => Self::NotRendered(x),
+            (2, pixels) => Self::Rendering(pixels),
+            (3, _) => Self::Rendered,
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl Stateful for SpriteZeroHitState {
+    fn save_state(&self, state: &mut StateWriter) {
+        let (tag, value) = match *self {
+            Self::Idle => (0u8, 0),
+            Self::IsInOam => (1, 0),
+            Self::OnCurrentScanline(in_next_oam) => (2, u8::from(in_next_oam)),
+            Self::Delay(cycles) => (3, cycles),
+        };
+        (tag, value).save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut tag_value = (0u8, 0u8);
+        tag_value.load_state(state)?;
+
+        *self = match tag_value {
+            (0, _) => Self::Idle,
+            (1, _) => Self::IsInOam,
+            (2, in_next_oam) => Self::OnCurrentScanline(in_next_oam != 0),
+            (3, cycles) => Self::Delay(cycles),
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+// Search of the CPU RAM for the address of a value, to make cheats.
+//
+// The search starts with every address as a candidate. Each filter compares the RAM with the
+// snapshot taken at the previous step, keeps the addresses matching the condition, and takes a
+// new snapshot. A few steps usually leave the address of the value.
+
+use alloc::vec::Vec;
+
+use crate::{Emulator, RAM_SIZE};
+
+/// Condition on the value at an address, compared to the previous snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchCondition {
+    EqualTo(u8),
+    NotEqualTo(u8),
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    /// Increased by exactly this amount, wrapping around
+    IncreasedBy(u8),
+    /// Decreased by exactly this amount, wrapping around
+    DecreasedBy(u8),
+}
+
+impl SearchCondition {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Self::EqualTo(value) => current == value,
+            Self::NotEqualTo(value) => current != value,
+            Self::Unchanged => current == previous,
+            Self::Changed => current != previous,
+            Self::Increased => current > previous,
+            Self::Decreased => current < previous,
+            Self::IncreasedBy(delta) => current == previous.wrapping_add(delta),
+            Self::DecreasedBy(delta) => current == previous.wrapping_sub(delta),
+        }
+    }
+}
+
+pub struct RamSearch {
+    snapshot: [u8; RAM_SIZE as usize],
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// Starts a search with every address as a candidate
+    pub fn new(emulator: &Emulator) -> Self {
+        Self {
+            snapshot: emulator.ram,
+            candidates: (0..RAM_SIZE).collect(),
+        }
+    }
+
+    /// Keeps the candidates matching `condition`, and takes a new snapshot. Returns the number of
+    /// candidates left.
+    pub fn filter(&mut self, emulator: &Emulator, condition: SearchCondition) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&addr| {
+            let addr = usize::from(addr);
+            condition.matches(snapshot[addr], emulator.ram[addr])
+        });
+
+        self.snapshot = emulator.ram;
+        self.candidates.len()
+    }
+
+    /// Starts over with every address as a candidate
+    pub fn restart(&mut self, emulator: &Emulator) {
+        *self = Self::new(emulator);
+    }
+
+    /// Addresses left, with their value in the last snapshot
+    pub fn candidates(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.candidates
+            .iter()
+            .map(move |&addr| (addr, self.snapshot[usize::from(addr)]))
+    }
+
+    pub fn candidate_count(&self) -> usize {
+        self.candidates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_roms::idle_rom;
+    use crate::Cheat;
+
+    #[test]
+    fn finds_changed_value() {
+        let rom = idle_rom();
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+
+        let mut search = RamSearch::new(&emulator);
+        assert_eq!(search.filter(&emulator, SearchCondition::Unchanged), 0x800);
+
+        // The "game" gains 3 lives
+        emulator.add_cheat(Cheat::new(0x0123, 3, false).unwrap());
+        emulator.add_cheat(Cheat::new(0x0456, 5, false).unwrap());
+        assert_eq!(search.filter(&emulator, SearchCondition::Increased), 2);
+        assert_eq!(search.filter(&emulator, SearchCondition::EqualTo(3)), 1);
+
+        emulator.add_cheat(Cheat::new(0x0123, 2, false).unwrap());
+        assert_eq!(search.filter(&emulator, SearchCondition::DecreasedBy(1)), 1);
+        assert_eq!(search.candidates().collect::<Vec<_>>(), [(0x0123, 2)]);
+
+        search.restart(&emulator);
+        assert_eq!(search.candidate_count(), 0x800);
+    }
+}
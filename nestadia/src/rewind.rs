@@ -0,0 +1,255 @@
+// Rewind buffer, keeping the save states of the last frames.
+//
+// Only the newest state is kept whole. Each older state is stored as its difference with the
+// state following it: the XOR of both states, where the runs of zeros are replaced by their
+// length. Consecutive states are mostly identical, so this takes a fraction of their size.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::Emulator;
+
+/// Default number of frames between two states
+pub const DEFAULT_REWIND_INTERVAL: u32 = 4;
+
+/// Default memory taken by the states, in bytes
+pub const DEFAULT_REWIND_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+/// Keeps a state of the emulator every few frames, to go back in time
+pub struct Rewind {
+    interval: u32,
+    memory_budget: usize,
+
+    /// Frames captured since the rewind buffer was created
+    frame: u64,
+
+    /// Newest state, with the frame it was taken at
+    newest: Option<(u64, Vec<u8>)>,
+    /// Older states, as the difference with the state following them. The newest is at the back.
+    deltas: VecDeque<(u64, Vec<u8>)>,
+    deltas_size: usize,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_REWIND_INTERVAL, DEFAULT_REWIND_MEMORY_BUDGET)
+    }
+}
+
+impl Rewind {
+    /// Keeps a state every `interval` frames, dropping the oldest ones when the states take more
+    /// than `memory_budget` bytes
+    pub fn new(interval: u32, memory_budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            memory_budget,
+            frame: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+        }
+    }
+
+    /// To be called after every frame. It saves the state of the emulator every `interval`
+    /// frames.
+    pub fn capture(&mut self, emulator: &Emulator) {
+        let frame = self.frame;
+        self.frame += 1;
+
+        if !frame.is_multiple_of(u64::from(self.interval)) {
+            return;
+        }
+
+        let state = emulator.save_state();
+        // After a rewind, the newest state is replaced
+        if let Some((newest_frame, newest)) = self.newest.take() {
+            if newest_frame != frame {
+                let delta = encode_delta(&newest, &state);
+                self.deltas_size += delta.len();
+                self.deltas.push_back((newest_frame, delta));
+            }
+        }
+        self.newest = Some((frame, state));
+
+        while self.memory_used() > self.memory_budget {
+            match self.deltas.pop_front() {
+                Some((_, delta)) => self.deltas_size -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Goes back at least `frames` frames, to the nearest state kept, or to the oldest one.
+    /// Returns the number of frames actually rewound.
+    pub fn rewind(&mut self, emulator: &mut Emulator, frames: u32) -> u32 {
+        let (mut newest_frame, mut newest) = match self.newest.take() {
+            Some(newest) => newest,
+            None => return 0,
+        };
+
+        let target = self.frame.saturating_sub(u64::from(frames).max(1));
+        while newest_frame > target {
+            match self.deltas.pop_back() {
+                Some((frame, delta)) => {
+                    self.deltas_size -= delta.len();
+                    newest = decode_delta(&delta, &newest);
+                    newest_frame = frame;
+                }
+                None => break,
+            }
+        }
+
+        // The states are saved by the same emulator, so they can be loaded
+        let _ = emulator.load_state(&newest);
+
+        let rewound = self.frame - newest_frame;
+        self.frame = newest_frame;
+        self.newest = Some((newest_frame, newest));
+        rewound as u32
+    }
+
+    /// Drops all the states, after loading another ROM or a save state
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.deltas_size = 0;
+    }
+
+    /// Number of frames that can be rewound
+    pub fn available_frames(&self) -> u64 {
+        let oldest = self
+            .deltas
+            .front()
+            .or(self.newest.as_ref())
+            .map_or(self.frame, |(frame, _)| *frame);
+        self.frame - oldest
+    }
+
+    /// Memory taken by the states, in bytes
+    pub fn memory_used(&self) -> usize {
+        self.newest.as_ref().map_or(0, |(_, state)| state.len()) + self.deltas_size
+    }
+}
+
+fn write_length(data: &mut Vec<u8>, mut len: usize) {
+    // 7 bits per byte, the high bit set on all bytes but the last
+    while len >= 0x80 {
+        data.push((len as u8) | 0x80);
+        len >>= 7;
+    }
+    data.push(len as u8);
+}
+
+fn read_length(data: &mut &[u8]) -> usize {
+    let mut len = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = data.split_first() {
+        *data = rest;
+        len |= usize::from(byte & 0x7F) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    len
+}
+
+/// Difference between a state and the next one, from which the state can be decoded
+fn encode_delta(state: &[u8], next: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| state[i] ^ next.get(i).copied().unwrap_or(0);
+
+    let mut delta = Vec::new();
+    write_length(&mut delta, state.len());
+
+    // Alternating runs of identical and different bytes
+    let mut i = 0;
+    while i < state.len() {
+        let start = i;
+        while i < state.len() && xor(i) == 0 {
+            i += 1;
+        }
+        write_length(&mut delta, i - start);
+
+        let start = i;
+        while i < state.len() && xor(i) != 0 {
+            i += 1;
+        }
+        write_length(&mut delta, i - start);
+        delta.extend((start..i).map(xor));
+    }
+
+    delta
+}
+
+fn decode_delta(mut delta: &[u8], next: &[u8]) -> Vec<u8> {
+    let len = read_length(&mut delta);
+    let mut state: Vec<u8> = (0..len)
+        .map(|i| next.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let mut i = 0;
+    while i < len && !delta.is_empty() {
+        i += read_length(&mut delta);
+
+        let different = read_length(&mut delta).min(delta.len());
+        let (xor, rest) = delta.split_at(different);
+        state[i..i + different]
+            .iter_mut()
+            .zip(xor)
+            .for_each(|(byte, xor)| *byte ^= xor);
+        delta = rest;
+        i += different;
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::test_roms::counter_rom;
+
+    #[test]
+    fn decodes_delta() {
+        let state = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        for next in [vec![1, 2, 0, 4, 5, 6, 0, 0, 9], vec![1, 2], vec![]] {
+            assert_eq!(decode_delta(&encode_delta(&state, &next), &next), state);
+        }
+
+        let state = vec![0u8; 1000];
+        assert_eq!(encode_delta(&state, &state).len(), 5);
+    }
+
+    #[test]
+    fn rewinds_to_older_state() {
+        let rom = counter_rom();
+
+        let mut emulator = Emulator::new(&rom, None).unwrap();
+        let mut rewind = Rewind::new(2, usize::MAX);
+        let mut states = Vec::new();
+        for _ in 0..10 {
+            states.push(emulator.save_state());
+            rewind.capture(&emulator);
+            while emulator.clock().is_none() {}
+        }
+        assert_eq!(rewind.available_frames(), 10);
+
+        // Frames 0, 2, 4, 6 and 8 were kept
+        assert_eq!(rewind.rewind(&mut emulator, 3), 4);
+        assert_eq!(emulator.save_state(), states[6]);
+        assert_eq!(rewind.rewind(&mut emulator, 1), 2);
+        assert_eq!(emulator.save_state(), states[4]);
+        assert_eq!(rewind.rewind(&mut emulator, 100), 4);
+        assert_eq!(emulator.save_state(), states[0]);
+        assert_eq!(rewind.rewind(&mut emulator, 1), 0);
+
+        // The budget only leaves room for the newest state
+        let mut rewind = Rewind::new(1, states[0].len());
+        for _ in 0..3 {
+            rewind.capture(&emulator);
+        }
+        assert_eq!(rewind.available_frames(), 1);
+    }
+}
@@ -0,0 +1,219 @@
+// Container of the data saved by the emulator, tying it to the ROM it was saved from.
+//
+// Layout, in little endian:
+// - 0-3: magic bytes, "NSAV"
+// - 4: format version
+// - 5: kind of payload
+// - 6-7: mapper of the ROM
+// - 8-11: CRC32 of the ROM
+// - 12-15: length of the payload
+// - 16-19: CRC32 of the payload
+// - 20-: payload
+//
+// Data without the magic bytes is a raw payload saved before the container existed.
+//
+// The payloads of the older versions are migrated to the current one by `migrate` when
+// unpacked, so a new version only needs a step converting from the previous one.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::convert::TryInto as _;
+
+use crate::cartridge::crc32;
+
+const MAGIC: [u8; 4] = *b"NSAV";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 20;
+
+/// Kind of data in a save data container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveDataKind {
+    /// Battery-backed memory of the cartridge
+    BatteryRam,
+    /// Snapshot of the whole machine
+    SaveState,
+    /// Inputs recorded frame by frame
+    Movie,
+}
+
+impl SaveDataKind {
+    fn id(self) -> u8 {
+        match self {
+            Self::BatteryRam => 0,
+            Self::SaveState => 1,
+            Self::Movie => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveDataError {
+    TooShort,
+    /// The data isn't in a container, which only the battery RAM accepts
+    MissingHeader,
+    UnsupportedVersion(u8),
+    WrongKind,
+    /// The data was saved from another ROM
+    RomMismatch,
+    InvalidChecksum,
+}
+
+impl core::fmt::Display for SaveDataError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// ROM the data is saved from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveDataOrigin {
+    pub mapper_id: u16,
+    pub rom_crc: u32,
+}
+
+/// Wraps `payload` in a container
+pub fn pack(kind: SaveDataKind, origin: SaveDataOrigin, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_SIZE + payload.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    data.push(kind.id());
+    data.extend_from_slice(&origin.mapper_id.to_le_bytes());
+    data.extend_from_slice(&origin.rom_crc.to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(&crc32(payload).to_le_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Extracts the payload of a container after checking it comes from the expected ROM, migrated
+/// to the current version. Raw battery RAM is returned as is, since it can't be checked.
+pub fn unpack(
+    data: &[u8],
+    kind: SaveDataKind,
+    origin: SaveDataOrigin,
+) -> Result<Cow<'_, [u8]>, SaveDataError> {
+    if !data.starts_with(&MAGIC) {
+        return match kind {
+            SaveDataKind::BatteryRam => Ok(Cow::Borrowed(data)),
+            SaveDataKind::SaveState | SaveDataKind::Movie => Err(SaveDataError::MissingHeader),
+        };
+    }
+
+    if data.len() < HEADER_SIZE {
+        return Err(SaveDataError::TooShort);
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes(data[i..i + 2].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+
+    // The older versions are migrated once the payload is checked
+    if !(1..=VERSION).contains(&data[4]) {
+        return Err(SaveDataError::UnsupportedVersion(data[4]));
+    }
+
+    if data[5] != kind.id() {
+        return Err(SaveDataError::WrongKind);
+    }
+
+    if u16_at(6) != origin.mapper_id || u32_at(8) != origin.rom_crc {
+        return Err(SaveDataError::RomMismatch);
+    }
+
+    let payload = data
+        .get(HEADER_SIZE..HEADER_SIZE + u32_at(12) as usize)
+        .ok_or(SaveDataError::TooShort)?;
+    if crc32(payload) != u32_at(16) {
+        return Err(SaveDataError::InvalidChecksum);
+    }
+
+    migrate(data[4], payload)
+}
+
+/// Converts a payload saved by the container `version` to the current one. Each version
+/// converts from the previous one, then lets it convert the rest of the way.
+fn migrate(version: u8, payload: &[u8]) -> Result<Cow<'_, [u8]>, SaveDataError> {
+    match version {
+        1 => Ok(Cow::Borrowed(payload)),
+        _ => Err(SaveDataError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: SaveDataOrigin = SaveDataOrigin {
+        mapper_id: 4,
+        rom_crc: 0x1234_5678,
+    };
+
+    #[test]
+    fn unpacks_packed_data() {
+        let data = pack(SaveDataKind::BatteryRam, ORIGIN, &[1, 2, 3]);
+        assert_eq!(
+            unpack(&data, SaveDataKind::BatteryRam, ORIGIN),
+            Ok(Cow::Borrowed(&[1, 2, 3][..]))
+        );
+
+        // Raw battery RAM is accepted as is
+        assert_eq!(
+            unpack(&[1, 2], SaveDataKind::BatteryRam, ORIGIN),
+            Ok(Cow::Borrowed(&[1, 2][..]))
+        );
+        assert_eq!(
+            unpack(&[1, 2], SaveDataKind::SaveState, ORIGIN),
+            Err(SaveDataError::MissingHeader)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let mut data = pack(SaveDataKind::BatteryRam, ORIGIN, &[1, 2, 3]);
+
+        let other_rom = SaveDataOrigin {
+            rom_crc: 0,
+            ..ORIGIN
+        };
+        assert_eq!(
+            unpack(&data, SaveDataKind::BatteryRam, other_rom),
+            Err(SaveDataError::RomMismatch)
+        );
+        assert_eq!(
+            unpack(&data, SaveDataKind::SaveState, ORIGIN),
+            Err(SaveDataError::WrongKind)
+        );
+
+        data[HEADER_SIZE] ^= 0xFF;
+        assert_eq!(
+            unpack(&data, SaveDataKind::BatteryRam, ORIGIN),
+            Err(SaveDataError::InvalidChecksum)
+        );
+
+        data[HEADER_SIZE] ^= 0xFF;
+        for version in [0, VERSION + 1] {
+            data[4] = version;
+            assert_eq!(
+                unpack(&data, SaveDataKind::BatteryRam, ORIGIN),
+                Err(SaveDataError::UnsupportedVersion(version))
+            );
+        }
+        data[4] = VERSION;
+
+        data.truncate(HEADER_SIZE + 1);
+        assert_eq!(
+            unpack(&data, SaveDataKind::BatteryRam, ORIGIN),
+            Err(SaveDataError::TooShort)
+        );
+    }
+
+    #[test]
+    fn migrates_older_versions() {
+        // Version 1 is the current one, kept as is
+        let payload = [1, 2, 3];
+        assert!(matches!(migrate(1, &payload), Ok(Cow::Borrowed(p)) if p == payload));
+        assert_eq!(
+            migrate(VERSION + 1, &payload),
+            Err(SaveDataError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,414 @@
+// Serialization of the state of the machine, for the save states.
+//
+// Every component writes its fields in a fixed order, and reads them back in the same order.
+// Integers are in little endian, and the length of the vectors is written before their
+// elements. Anything that can be recomputed from the ROM, like lookup tables or the PRG ROM,
+// is left out.
+//
+// The components of the emulator are written in sections, prefixed by their length, so a save
+// state can be split into its components without loading it.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryInto as _;
+
+use crate::save_data::SaveDataError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateError {
+    InvalidContainer(SaveDataError),
+    UnsupportedVersion(u8),
+    /// The state ends before all the components are read
+    UnexpectedEnd,
+    InvalidValue,
+}
+
+impl core::fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+/// Destination of the state of the components
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Source of the state of the components
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Reads the next `len` bytes
+    pub fn read(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.data.len() < len {
+            return Err(SaveStateError::UnexpectedEnd);
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Reads the bytes of the next section written by `save_section`
+    pub fn read_section(&mut self) -> Result<&'a [u8], SaveStateError> {
+        let mut len = 0u32;
+        len.load_state(self)?;
+        self.read(len as usize)
+    }
+}
+
+/// Writes the state of `component` in a section, prefixed by its length
+pub fn save_section(component: &dyn Stateful, state: &mut StateWriter) {
+    let mut section = StateWriter::new();
+    component.save_state(&mut section);
+    (section.data.len() as u32).save_state(state);
+    state.write(&section.data);
+}
+
+/// Reads the state of `component` from a section written by `save_section`, which it must read
+/// whole
+pub fn load_section(
+    component: &mut dyn Stateful,
+    state: &mut StateReader,
+) -> Result<(), SaveStateError> {
+    let mut section = StateReader::new(state.read_section()?);
+    component.load_state(&mut section)?;
+    if section.remaining() == 0 {
+        Ok(())
+    } else {
+        Err(SaveStateError::InvalidValue)
+    }
+}
+
+/// Component whose state is saved in the save states. Mappers implemented outside of the crate
+/// must save all of their registers and RAM.
+pub trait Stateful {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+/// Implements `Stateful` for a struct by saving the listed fields, in order
+macro_rules! impl_stateful {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::state::Stateful for $type {
+            fn save_state(&self, state: &mut $crate::state::StateWriter) {
+                $($crate::state::Stateful::save_state(&self.$field, state);)*
+            }
+
+            fn load_state(
+                &mut self,
+                state: &mut $crate::state::StateReader,
+            ) -> Result<(), $crate::state::SaveStateError> {
+                $($crate::state::Stateful::load_state(&mut self.$field, state)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Same as `impl_stateful`, with every field in its own section, see `save_section`
+macro_rules! impl_stateful_sections {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::state::Stateful for $type {
+            fn save_state(&self, state: &mut $crate::state::StateWriter) {
+                $($crate::state::save_section(&self.$field, state);)*
+            }
+
+            fn load_state(
+                &mut self,
+                state: &mut $crate::state::StateReader,
+            ) -> Result<(), $crate::state::SaveStateError> {
+                $($crate::state::load_section(&mut self.$field, state)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Implements `Stateful` for bitflags, saved as their bits
+macro_rules! impl_stateful_bitflags {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl $crate::state::Stateful for $type {
+                fn save_state(&self, state: &mut $crate::state::StateWriter) {
+                    $crate::state::Stateful::save_state(&self.bits(), state);
+                }
+
+                fn load_state(
+                    &mut self,
+                    state: &mut $crate::state::StateReader,
+                ) -> Result<(), $crate::state::SaveStateError> {
+                    let mut bits = self.bits();
+                    $crate::state::Stateful::load_state(&mut bits, state)?;
+                    *self = Self::from_bits_truncate(bits);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+/// Implements `Stateful` for a fieldless enum, saved as the index of its variant in the list
+macro_rules! impl_stateful_enum {
+    ($type:ident { $($variant:ident),* $(,)? }) => {
+        impl $crate::state::Stateful for $type {
+            fn save_state(&self, state: &mut $crate::state::StateWriter) {
+                let variants = [$($type::$variant),*];
+                let index = variants.iter().position(|v| v == self).unwrap() as u8;
+                $crate::state::Stateful::save_state(&index, state);
+            }
+
+            fn load_state(
+                &mut self,
+                state: &mut $crate::state::StateReader,
+            ) -> Result<(), $crate::state::SaveStateError> {
+                let variants = [$($type::$variant),*];
+                let mut index = 0u8;
+                $crate::state::Stateful::load_state(&mut index, state)?;
+                *self = *variants
+                    .get(usize::from(index))
+                    .ok_or($crate::state::SaveStateError::InvalidValue)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+macro_rules! impl_stateful_int {
+    ($($type:ty),*) => {
+        $(
+            impl Stateful for $type {
+                fn save_state(&self, state: &mut StateWriter) {
+                    state.write(&self.to_le_bytes());
+                }
+
+                fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+                    let bytes = state.read(core::mem::size_of::<$type>())?;
+                    *self = <$type>::from_le_bytes(bytes.try_into().unwrap());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_stateful_int!(u8, u16, u32, u64, i8, i16, i32);
+
+impl Stateful for usize {
+    fn save_state(&self, state: &mut StateWriter) {
+        (*self as u64).save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut value = 0u64;
+        value.load_state(state)?;
+        *self = value.try_into().map_err(|_| SaveStateError::InvalidValue)?;
+        Ok(())
+    }
+}
+
+impl Stateful for bool {
+    fn save_state(&self, state: &mut StateWriter) {
+        u8::from(*self).save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        *self = match state.read(1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(SaveStateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl<T: Stateful, const N: usize> Stateful for [T; N] {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.iter().for_each(|element| element.save_state(state));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.iter_mut()
+            .try_for_each(|element| element.load_state(state))
+    }
+}
+
+impl<T: Stateful + Default> Stateful for Vec<T> {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.len().save_state(state);
+        self.iter().for_each(|element| element.save_state(state));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut len = 0usize;
+        len.load_state(state)?;
+
+        // Every element takes at least a byte, which avoids allocating for an invalid length
+        if len > state.remaining() {
+            return Err(SaveStateError::UnexpectedEnd);
+        }
+
+        self.clear();
+        for _ in 0..len {
+            let mut element = T::default();
+            element.load_state(state)?;
+            self.push(element);
+        }
+        Ok(())
+    }
+}
+
+impl<T: Stateful + Default> Stateful for Option<T> {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.is_some().save_state(state);
+        if let Some(value) = self {
+            value.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut is_some = false;
+        is_some.load_state(state)?;
+
+        *self = if is_some {
+            let mut value = T::default();
+            value.load_state(state)?;
+            Some(value)
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+impl<T: Stateful + ?Sized> Stateful for Box<T> {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.as_ref().save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.as_mut().load_state(state)
+    }
+}
+
+impl<A: Stateful, B: Stateful> Stateful for (A, B) {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.0.save_state(state);
+        self.1.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.0.load_state(state)?;
+        self.1.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Component {
+        a: u8,
+        b: u16,
+        c: bool,
+        d: Option<[u8; 2]>,
+        e: Vec<u32>,
+    }
+
+    impl_stateful!(Component { a, b, c, d, e });
+
+    #[test]
+    fn loads_saved_state() {
+        let component = Component {
+            a: 1,
+            b: 0x0302,
+            c: true,
+            d: Some([4, 5]),
+            e: alloc::vec![6, 7],
+        };
+
+        let mut state = StateWriter::new();
+        component.save_state(&mut state);
+        let data = state.into_inner();
+
+        let mut loaded = Component::default();
+        assert_eq!(loaded.load_state(&mut StateReader::new(&data)), Ok(()));
+        assert_eq!(loaded.a, 1);
+        assert_eq!(loaded.b, 0x0302);
+        assert!(loaded.c);
+        assert_eq!(loaded.d, Some([4, 5]));
+        assert_eq!(loaded.e, [6, 7]);
+
+        assert_eq!(
+            loaded.load_state(&mut StateReader::new(&data[..data.len() - 1])),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+    }
+
+    #[derive(Default)]
+    struct Sections {
+        a: u8,
+        e: Vec<u32>,
+    }
+
+    impl_stateful_sections!(Sections { a, e });
+
+    #[test]
+    fn splits_sections() {
+        let sections = Sections {
+            a: 1,
+            e: alloc::vec![2],
+        };
+
+        let mut state = StateWriter::new();
+        sections.save_state(&mut state);
+        let data = state.into_inner();
+
+        let mut reader = StateReader::new(&data);
+        assert_eq!(reader.read_section(), Ok(&[1][..]));
+        assert_eq!(
+            reader.read_section(),
+            Ok(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0][..])
+        );
+        assert_eq!(reader.remaining(), 0);
+
+        let mut loaded = Sections::default();
+        assert_eq!(loaded.load_state(&mut StateReader::new(&data)), Ok(()));
+        assert_eq!((loaded.a, &loaded.e), (1, &alloc::vec![2]));
+
+        // A section longer than its component
+        let mut data = data;
+        data[..5].copy_from_slice(&[2, 0, 0, 0, 1]);
+        data.insert(5, 0);
+        assert_eq!(
+            loaded.load_state(&mut StateReader::new(&data)),
+            Err(SaveStateError::InvalidValue)
+        );
+    }
+}
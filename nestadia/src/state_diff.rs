@@ -0,0 +1,209 @@
+// Comparison of two save states, to hunt the nondeterminism making two runs go out of sync,
+// and to check that a mapper saves all of its registers.
+//
+// The components of the emulator are in their own section of the save states, so both states
+// are split into their components without being loaded, and compared byte by byte. The
+// registers of the CPU and the PPU are also compared by name, decoded from their sections.
+
+use alloc::vec::Vec;
+
+use crate::cpu::Cpu;
+use crate::ppu::Ppu;
+use crate::state::{SaveStateError, StateReader, Stateful};
+
+/// Component of the machine in a save state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateComponent {
+    Cpu,
+    IrqLine,
+    Controllers,
+    /// The 2KB of RAM of the CPU
+    Ram,
+    Apu,
+    Ppu,
+    /// The 4KB of VRAM holding the name tables
+    NameTables,
+    /// The CHR RAM, and the registers and memory of the mapper
+    Cartridge,
+    /// Counters of the emulator, like the frame count
+    Timing,
+}
+
+/// Register with a different value in the two states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDiff {
+    pub component: StateComponent,
+    /// Name of the register, like "PC" or "PPUCTRL"
+    pub name: &'static str,
+    pub first: i64,
+    pub second: i64,
+}
+
+/// Bytes differing in the state of a component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRangeDiff {
+    pub component: StateComponent,
+    /// Offset of the first byte in the state of the component, which is its address for the
+    /// RAM and the name tables
+    pub start: usize,
+    /// Offset after the last byte
+    pub end: usize,
+}
+
+/// Differences between two save states, see `Emulator::diff_states`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub registers: Vec<RegisterDiff>,
+    /// Ranges in the order of the components, then of the offsets
+    pub ranges: Vec<ByteRangeDiff>,
+}
+
+impl StateDiff {
+    pub(crate) fn new(first: &StateSnapshot, second: &StateSnapshot) -> Self {
+        let registers = first
+            .registers
+            .iter()
+            .zip(&second.registers)
+            .filter(|(first, second)| first.2 != second.2)
+            .map(|(first, second)| RegisterDiff {
+                component: first.0,
+                name: first.1,
+                first: first.2,
+                second: second.2,
+            })
+            .collect();
+
+        let mut ranges = Vec::new();
+        for ((component, first), (_, second)) in first.components.iter().zip(&second.components) {
+            ranges.extend(
+                differing_ranges(first, second)
+                    .into_iter()
+                    .map(|(start, end)| ByteRangeDiff {
+                        component: *component,
+                        start,
+                        end,
+                    }),
+            );
+        }
+
+        Self { registers, ranges }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.ranges.is_empty()
+    }
+
+    /// Whether the state of `component` differs
+    pub fn differs(&self, component: StateComponent) -> bool {
+        self.ranges.iter().any(|range| range.component == component)
+    }
+
+    /// Components whose state differs, in the order of the save states
+    pub fn components(&self) -> Vec<StateComponent> {
+        let mut components: Vec<StateComponent> =
+            self.ranges.iter().map(|range| range.component).collect();
+        components.dedup();
+        components
+    }
+}
+
+/// State of every component of an emulator, saved apart
+pub(crate) struct StateSnapshot {
+    components: Vec<(StateComponent, Vec<u8>)>,
+    registers: Vec<(StateComponent, &'static str, i64)>,
+}
+
+impl StateSnapshot {
+    /// Splits the sections of the state of an emulator, read after the version of the state
+    pub fn decode(state: &mut StateReader) -> Result<Self, SaveStateError> {
+        // The fields of the emulator, in the order of their sections
+        let mut sections: [&[u8]; 11] = Default::default();
+        for section in &mut sections {
+            *section = state.read_section()?;
+        }
+        if state.remaining() != 0 {
+            return Err(SaveStateError::InvalidValue);
+        }
+        let [cartridge, cpu, irq_line, controllers, ram, apu, ppu, name_tables, clock_count, frame_count, ppu_warmup_cycles] =
+            sections;
+
+        let components = alloc::vec![
+            (StateComponent::Cpu, cpu.to_vec()),
+            (StateComponent::IrqLine, irq_line.to_vec()),
+            (StateComponent::Controllers, controllers.to_vec()),
+            (StateComponent::Ram, ram.to_vec()),
+            (StateComponent::Apu, apu.to_vec()),
+            (StateComponent::Ppu, ppu.to_vec()),
+            (StateComponent::NameTables, name_tables.to_vec()),
+            (StateComponent::Cartridge, cartridge.to_vec()),
+            (
+                StateComponent::Timing,
+                [clock_count, frame_count, ppu_warmup_cycles].concat(),
+            ),
+        ];
+
+        let mut cpu_state = Cpu::default();
+        cpu_state.load_state(&mut StateReader::new(cpu))?;
+        let mut ppu_state = Ppu::default();
+        ppu_state.load_state(&mut StateReader::new(ppu))?;
+
+        let cpu = &cpu_state;
+        let mut registers = alloc::vec![
+            (StateComponent::Cpu, "A", i64::from(cpu.a)),
+            (StateComponent::Cpu, "X", i64::from(cpu.x)),
+            (StateComponent::Cpu, "Y", i64::from(cpu.y)),
+            (StateComponent::Cpu, "S", i64::from(cpu.st)),
+            (StateComponent::Cpu, "PC", i64::from(cpu.pc)),
+            (
+                StateComponent::Cpu,
+                "P",
+                i64::from(cpu.status_register.bits())
+            ),
+        ];
+        registers.extend(
+            ppu_state
+                .named_registers()
+                .iter()
+                .map(|(name, value)| (StateComponent::Ppu, *name, *value)),
+        );
+
+        Ok(Self {
+            components,
+            registers,
+        })
+    }
+}
+
+/// Ranges of offsets, end excluded, where the bytes differ. The bytes past the end of the
+/// shorter one differ.
+fn differing_ranges(first: &[u8], second: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for offset in 0..first.len().max(second.len()) {
+        if first.get(offset) == second.get(offset) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if *end == offset => *end += 1,
+            _ => ranges.push((offset, offset + 1)),
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_differing_ranges() {
+        assert_eq!(differing_ranges(&[1, 2, 3], &[1, 2, 3]), []);
+        assert_eq!(
+            differing_ranges(&[1, 2, 3, 4, 5], &[0, 0, 3, 0, 5]),
+            [(0, 2), (3, 4)]
+        );
+        assert_eq!(differing_ranges(&[1, 2], &[1, 2, 3, 4]), [(2, 4)]);
+        assert_eq!(differing_ranges(&[1, 2, 3], &[1, 0]), [(1, 3)]);
+    }
+}
@@ -0,0 +1,72 @@
+// ROMs built by the tests, to run the emulator without any ROM file.
+//
+// They are NROM boards with a 16KB PRG ROM mirrored at $8000 and $C000, and an 8KB CHR ROM, all
+// zeros but for the program starting at $8000 and the reset vector pointing to it.
+
+use alloc::vec::Vec;
+
+/// Offset of the PRG ROM in the ROM files, after the iNES header
+pub(crate) const PRG_ROM_OFFSET: usize = 16;
+
+/// Offset of the CHR ROM in the ROMs of `nrom`
+pub(crate) const CHR_ROM_OFFSET: usize = PRG_ROM_OFFSET + 0x4000;
+
+/// NROM running `program` from $8000
+pub(crate) fn nrom(program: &[u8]) -> Vec<u8> {
+    let mut rom = alloc::vec![0u8; CHR_ROM_OFFSET + 0x2000];
+    rom[..8].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A, 1, 1, 0x00, 0x00]);
+    rom[PRG_ROM_OFFSET..PRG_ROM_OFFSET + program.len()].copy_from_slice(program);
+    // Reset vector
+    rom[PRG_ROM_OFFSET + 0x3FFC..PRG_ROM_OFFSET + 0x3FFE].copy_from_slice(&[0x00, 0x80]);
+    rom
+}
+
+/// NROM incrementing $00 in a loop
+pub(crate) fn counter_rom() -> Vec<u8> {
+    nrom(&[0xE6, 0x00, 0x4C, 0x00, 0x80]) // INC $00; JMP $8000
+}
+
+/// ROM of `mapper_id` with `prg_banks` 16KB banks of PRG ROM, rendering 8x16 sprites that are
+/// all off screen at Y $FF, which the PPU still fetches on the first scanlines. Every 8KB of PRG ROM holds the program and the vectors, so it runs
+/// whichever banks the mapper selects on power-on.
+pub(crate) fn offscreen_sprites_rom(mapper_id: u8, prg_banks: u8) -> Vec<u8> {
+    #[rustfmt::skip]
+    let program = [
+        // Waits for the PPU to warm up
+        0x2C, 0x02, 0x20, 0x10, 0xFB, // BIT $2002; BPL $8000
+        0x2C, 0x02, 0x20, 0x10, 0xFB, // BIT $2002; BPL $8005
+        0xA9, 0x20, 0x8D, 0x00, 0x20, // 8x16 sprites
+        0xA9, 0x00, 0x8D, 0x03, 0x20, // LDA #$00; STA $2003
+        0xA2, 0x40, // LDX #$40
+        0xA9, 0xFF, 0x8D, 0x04, 0x20, // Y = $FF
+        0xA9, 0x00, 0x8D, 0x04, 0x20, // Tile 0
+        0x8D, 0x04, 0x20, 0x8D, 0x04, 0x20, // No attributes, X = 0
+        0xCA, 0xD0, 0xED, // DEX; BNE $8016
+        0xA9, 0x14, 0x8D, 0x01, 0x20, // Show all the sprites
+        0x4C, 0x2E, 0x80, // JMP $802E
+    ];
+
+    let prg_rom_size = usize::from(prg_banks) * 0x4000;
+    let mut rom = alloc::vec![0u8; PRG_ROM_OFFSET + prg_rom_size + 0x2000];
+    rom[..8].copy_from_slice(&[
+        0x4E,
+        0x45,
+        0x53,
+        0x1A,
+        prg_banks,
+        1,
+        mapper_id << 4,
+        mapper_id & 0xF0,
+    ]);
+    for bank in rom[PRG_ROM_OFFSET..PRG_ROM_OFFSET + prg_rom_size].chunks_mut(0x2000) {
+        bank[..program.len()].copy_from_slice(&program);
+        // Reset vector
+        bank[0x1FFC..0x1FFE].copy_from_slice(&[0x00, 0x80]);
+    }
+    rom
+}
+
+/// NROM running an infinite loop, leaving the memory alone
+pub(crate) fn idle_rom() -> Vec<u8> {
+    nrom(&[0x4C, 0x00, 0x80]) // JMP $8000
+}
@@ -0,0 +1,183 @@
+// Recording of the frames, to capture gameplay footage.
+// https://wiki.multimedia.cx/index.php/YUV4MPEG2
+//
+// The frames are encoded either as a YUV4MPEG2 (.y4m) stream, which can be piped into ffmpeg,
+// or as a sequence of images. The output always has the frame rate of the console: when frames
+// are skipped, like while fast-forwarding, the previous frame is repeated in their place.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::ppu::{PpuFrame, FRAME_HEIGHT, FRAME_WIDTH};
+use crate::RGB_PALETTE;
+
+/// Frame rate of the NTSC console, about 60.0988 frames per second, as a fraction
+pub const NTSC_FRAME_RATE: (u32, u32) = (39_375_000, 655_171);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 stream, with full resolution chroma
+    Y4m,
+    /// Binary PPM images
+    PpmSequence,
+    /// PNG images
+    #[cfg(feature = "screenshot")]
+    PngSequence,
+}
+
+/// Encoded output of the recorder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VideoChunk {
+    /// Bytes to append to the Y4M stream
+    Stream(Vec<u8>),
+    /// Next image of the sequence, with its file name
+    Image { name: String, data: Vec<u8> },
+}
+
+pub struct VideoRecorder {
+    format: VideoFormat,
+    last_frame: Option<(u64, PpuFrame)>,
+    recorded_frames: u64,
+}
+
+impl VideoRecorder {
+    pub fn new(format: VideoFormat) -> Self {
+        Self {
+            format,
+            last_frame: None,
+            recorded_frames: 0,
+        }
+    }
+
+    /// Encodes a frame returned by the emulator, along with its number (see
+    /// `Emulator::frame_count`). The frames skipped since the previous one are filled with it, a
+    /// frame with the same number is ignored, and an earlier number, after loading a state, is
+    /// recorded as the next frame.
+    pub fn record_frame(&mut self, frame: &PpuFrame, frame_number: u64) -> Vec<VideoChunk> {
+        let mut chunks = Vec::new();
+
+        if let Some((last_number, last_frame)) = &self.last_frame {
+            if frame_number == *last_number {
+                return chunks;
+            }
+
+            let skipped = frame_number.saturating_sub(*last_number + 1);
+            let last_frame = *last_frame;
+            for _ in 0..skipped {
+                self.encode(&last_frame, &mut chunks);
+            }
+        }
+
+        self.encode(frame, &mut chunks);
+        self.last_frame = Some((frame_number, *frame));
+        chunks
+    }
+
+    /// Number of frames in the output so far, repeated ones included
+    pub fn recorded_frames(&self) -> u64 {
+        self.recorded_frames
+    }
+
+    fn encode(&mut self, frame: &PpuFrame, chunks: &mut Vec<VideoChunk>) {
+        let index = self.recorded_frames;
+        self.recorded_frames += 1;
+
+        let colors = frame
+            .iter()
+            .map(|pixel| RGB_PALETTE[usize::from(pixel & 0x3F)]);
+        match self.format {
+            VideoFormat::Y4m => {
+                // The frames of a stream are returned in a single chunk
+                if chunks.is_empty() {
+                    chunks.push(VideoChunk::Stream(Vec::new()));
+                }
+                if let Some(VideoChunk::Stream(data)) = chunks.last_mut() {
+                    if index == 0 {
+                        data.extend_from_slice(y4m_header().as_bytes());
+                    }
+                    data.extend_from_slice(b"FRAME\n");
+
+                    let ycbcr: Vec<[u8; 3]> =
+                        colors.map(|[r, g, b]| rgb_to_ycbcr(r, g, b)).collect();
+                    for plane in 0..3 {
+                        data.extend(ycbcr.iter().map(|pixel| pixel[plane]));
+                    }
+                }
+            }
+            VideoFormat::PpmSequence => {
+                let mut data = format!("P6\n{} {}\n255\n", FRAME_WIDTH, FRAME_HEIGHT).into_bytes();
+                data.extend(colors.flatten());
+                chunks.push(VideoChunk::Image {
+                    name: format!("frame_{:06}.ppm", index),
+                    data,
+                });
+            }
+            #[cfg(feature = "screenshot")]
+            VideoFormat::PngSequence => chunks.push(VideoChunk::Image {
+                name: format!("frame_{:06}.png", index),
+                data: crate::encode_png(frame),
+            }),
+        }
+    }
+}
+
+/// The pixels of the NES are about 8:7 on a TV
+fn y4m_header() -> String {
+    format!(
+        "YUV4MPEG2 W{} H{} F{}:{} Ip A8:7 C444\n",
+        FRAME_WIDTH, FRAME_HEIGHT, NTSC_FRAME_RATE.0, NTSC_FRAME_RATE.1
+    )
+}
+
+/// BT.601 conversion, in the limited range expected by the video tools
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let cb = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let cr = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    [y as u8, cb as u8, cr as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_len(chunks: &[VideoChunk]) -> usize {
+        match chunks {
+            [VideoChunk::Stream(data)] => data.len(),
+            _ => panic!("Expected a single stream chunk"),
+        }
+    }
+
+    #[test]
+    fn repeats_skipped_frames() {
+        let frame = [0x20u8; FRAME_WIDTH * FRAME_HEIGHT];
+        let frame_size = 6 + 3 * frame.len();
+
+        let mut recorder = VideoRecorder::new(VideoFormat::Y4m);
+        let first = recorder.record_frame(&frame, 1);
+        assert_eq!(stream_len(&first), y4m_header().len() + frame_size);
+        assert_eq!(
+            stream_len(&recorder.record_frame(&frame, 4)),
+            3 * frame_size
+        );
+        assert!(recorder.record_frame(&frame, 4).is_empty());
+        assert_eq!(recorder.recorded_frames(), 4);
+
+        // White is the brightest luma, without chroma
+        assert_eq!(rgb_to_ycbcr(0xFF, 0xFF, 0xFF), [235, 128, 128]);
+
+        let mut recorder = VideoRecorder::new(VideoFormat::PpmSequence);
+        recorder.record_frame(&frame, 10);
+        let chunks = recorder.record_frame(&frame, 12);
+        let names: Vec<_> = chunks
+            .iter()
+            .map(|chunk| match chunk {
+                VideoChunk::Image { name, .. } => name.as_str(),
+                VideoChunk::Stream(_) => "",
+            })
+            .collect();
+        assert_eq!(names, ["frame_000001.ppm", "frame_000002.ppm"]);
+    }
+}